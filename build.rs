@@ -1,8 +1,6 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use slint_build;
-
 fn main() {
     let ui_root = PathBuf::from("ui/");
     compile_main_ui(&ui_root);
@@ -10,7 +8,7 @@ fn main() {
 }
 
 /// Compile the entry slint UI file 
-fn compile_main_ui(ui_root: &PathBuf) {
+fn compile_main_ui(ui_root: &Path) {
     let config = slint_build::CompilerConfiguration::new()
         .with_style("native".into());
 
@@ -21,7 +19,7 @@ fn compile_main_ui(ui_root: &PathBuf) {
 }
 
 /// Sets up cargo to recompile when any .slint file in the given directory changes
-fn setup_cargo_recompile_triggers(ui_root: &PathBuf) {
+fn setup_cargo_recompile_triggers(ui_root: &Path) {
     for path in find_slint_files_iterative(ui_root) {
         if let Some(path_str) = path.to_str() {
             println!("cargo:rerun-if-changed={}", path_str);
@@ -55,9 +53,9 @@ fn _find_slint_files(directory: &PathBuf) -> Vec<PathBuf> {
 }
 
 /// Iteratively find all files with '.slint' extension in the given directory
-fn find_slint_files_iterative(directory: &PathBuf) -> Vec<PathBuf> {
+fn find_slint_files_iterative(directory: &Path) -> Vec<PathBuf> {
     let mut slint_files = Vec::new();
-    let mut dirs_to_visit = vec![directory.clone()];
+    let mut dirs_to_visit = vec![directory.to_path_buf()];
 
     while let Some(dir) = dirs_to_visit.pop() {
         let entries = match fs::read_dir(&dir) {
@@ -82,5 +80,5 @@ fn find_slint_files_iterative(directory: &PathBuf) -> Vec<PathBuf> {
 fn is_slint_file(path: &Path) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
-        .map_or(false, |ext| ext == "slint")
+        .is_some_and(|ext| ext == "slint")
 }
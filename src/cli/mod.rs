@@ -0,0 +1,423 @@
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::errors::app_errors::AppError;
+use crate::errors::file_errors::OpenVaultError;
+use crate::models::vault::{sort_items, Item, SortOrder, Vault};
+use crate::utils::file::{self, VaultMetadata};
+use crate::utils::vault_lock::VaultLock;
+use crate::utils::zero_byte::ZeroByte;
+use crate::utils;
+
+
+const USAGE: &str = "\
+Usage: nopass --vault <FILE> --password-file <FILE> [--json] <COMMAND>
+
+Commands:
+  list           Print the name of every item, one per line
+  get <NAME>     Print the password of the item named NAME
+  add <NAME>     Add an item named NAME, its password read from the first line of stdin
+  delete <NAME>  Move the item named NAME to the trash
+
+Options:
+  --vault <FILE>          Vault file to open
+  --password-file <FILE>  File holding the master password, so it stays out of shell history
+  --json                  Print JSON instead of plain text";
+
+const COMMANDS: [&str; 4] = ["list", "get", "add", "delete"];
+
+/// Arguments of a headless run, see `USAGE`
+#[derive(Debug, PartialEq)]
+pub(crate) struct CliArgs {
+    pub(crate) vault: PathBuf,
+    pub(crate) password_file: PathBuf,
+    pub(crate) json: bool,
+    pub(crate) command: CliCommand,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum CliCommand {
+    List,
+    Get { name: String },
+    Add { name: String },
+    Delete { name: String },
+}
+
+/// An item as `list --json` prints it
+#[derive(Serialize)]
+struct ListedItem<'a> {
+    id: i32,
+    name: &'a str,
+    username: &'a str,
+    url: &'a str,
+}
+
+/// An item as `get --json` prints it
+#[derive(Serialize)]
+struct SecretItem<'a> {
+    id: i32,
+    name: &'a str,
+    username: &'a str,
+    password: &'a str,
+}
+
+/// Item `add` and `delete` report with `--json`
+#[derive(Serialize)]
+struct ChangedItem<'a> {
+    id: i32,
+    name: &'a str,
+}
+
+/// Whether the process was started for a headless command rather than the window. The GUI
+/// takes no arguments besides `--portable`, so any command or CLI option means the CLI.
+pub(crate) fn is_cli_invocation(args: &[String]) -> bool {
+    args.iter().any(|arg| COMMANDS.contains(&arg.as_str()) || matches!(arg.as_str(), "--vault" | "--password-file" | "--help"))
+}
+
+impl CliArgs {
+    /// Parses the arguments after the program name
+    pub(crate) fn parse(args: &[String]) -> Result<Self, AppError> {
+        let (mut vault, mut password_file, mut json, mut positional) = (None, None, false, Vec::new());
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--vault" => vault = Some(PathBuf::from(args.next().ok_or_else(|| usage("--vault needs a file"))?)),
+                "--password-file" => password_file = Some(PathBuf::from(args.next().ok_or_else(|| usage("--password-file needs a file"))?)),
+                "--json" => json = true,
+                "--help" => return Err(AppError::Generic(USAGE.into())),
+                option if option.starts_with("--") => return Err(usage(&format!("Unknown option {}", option))),
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let command = match (positional.next().as_deref(), positional.next()) {
+            (Some("list"), None) => CliCommand::List,
+            (Some("get"), Some(name)) => CliCommand::Get { name },
+            (Some("add"), Some(name)) => CliCommand::Add { name },
+            (Some("delete"), Some(name)) => CliCommand::Delete { name },
+            (Some(command @ ("get" | "add" | "delete")), None) => return Err(usage(&format!("{} needs an item name", command))),
+            (Some(command), _) if !COMMANDS.contains(&command) => return Err(usage(&format!("Unknown command {}", command))),
+            (None, _) => return Err(usage("No command given")),
+            _ => return Err(usage("Too many arguments")),
+        };
+        if positional.next().is_some() {
+            return Err(usage("Too many arguments"));
+        }
+
+        Ok(Self {
+            vault: vault.ok_or_else(|| usage("--vault is required"))?,
+            password_file: password_file.ok_or_else(|| usage("--password-file is required"))?,
+            json,
+            command,
+        })
+    }
+}
+
+/// Unlocks the vault, runs the command and locks the vault again. Output goes to stdout, the
+/// password for `add` is read from stdin.
+pub(crate) fn run_cli(args: CliArgs) -> Result<(), AppError> {
+    let stdin = io::stdin();
+    if matches!(args.command, CliCommand::Add { .. }) && stdin.is_terminal() {
+        // Without a terminal library the typed password is echoed, piping it in avoids that
+        eprint!("Password (shown as typed, pipe it in to hide it): ");
+    }
+
+    run_with_io(args, &mut stdin.lock(), &mut io::stdout().lock())
+}
+
+fn run_with_io(args: CliArgs, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), AppError> {
+    let password = read_first_line(&mut fs::File::open(&args.password_file)?)?;
+    let password = std::str::from_utf8(password.as_ref()).map_err(|_| AppError::Generic("The password file isn't UTF-8 text".into()))?;
+
+    // Changes take the same lock as an unlocked window, so they can't overwrite each other
+    let _lock = match args.command {
+        CliCommand::List | CliCommand::Get { .. } => None,
+        CliCommand::Add { .. } | CliCommand::Delete { .. } => {
+            Some(VaultLock::acquire(&args.vault).map_err(|e| AppError::Generic(e.to_string()))?)
+        },
+    };
+
+    let mut vault = open(&args.vault, password)?;
+    let result = run_command(&args, &mut vault, input, output);
+
+    // Lock the vault again whether or not the command succeeded
+    if let Some(key) = vault.key.as_mut() {
+        key.wipe();
+    }
+    result
+}
+
+fn run_command(args: &CliArgs, vault: &mut Vault, mut input: &mut dyn Read, output: &mut dyn Write) -> Result<(), AppError> {
+    match &args.command {
+        CliCommand::List => {
+            let mut items = vault.active_items();
+            sort_items(&mut items, SortOrder::ByNameAsc);
+
+            if args.json {
+                let listed: Vec<ListedItem> = items.iter()
+                    .map(|item| ListedItem { id: item.id, name: &item.name, username: &item.username, url: &item.url })
+                    .collect();
+                write_json(output, &listed)?;
+            } else {
+                for item in items {
+                    writeln!(output, "{}", item.name)?;
+                }
+            }
+        },
+        CliCommand::Get { name } => {
+            let item = find_by_name(vault, name)?;
+
+            // The password is put together in a buffer that is zeroized once it's written
+            let mut secret = ZeroByte::default();
+            if args.json {
+                serde_json::to_writer(&mut secret, &SecretItem { id: item.id, name: &item.name, username: &item.username, password: &item.password })
+                    .map_err(|e| AppError::Generic(e.to_string()))?;
+            } else {
+                secret.extend_from_slice(item.password.as_bytes());
+            }
+            secret.extend_from_slice(b"\n");
+            output.write_all(secret.as_ref())?;
+            output.flush()?;
+        },
+        CliCommand::Add { name } => {
+            if find_by_name(vault, name).is_ok() {
+                return Err(AppError::Generic(format!("An item named '{}' already exists", name)));
+            }
+
+            let password = read_first_line(&mut input)?;
+            let password = std::str::from_utf8(password.as_ref()).map_err(|_| AppError::Generic("The password isn't UTF-8 text".into()))?;
+
+            let id = vault.next_id()?;
+            let now = utils::unix_timestamp();
+            vault.items.push(Item {
+                id,
+                name: name.clone(),
+                password: password.to_string(),
+                modified_at: now,
+                password_changed_at: now,
+                created_at: now,
+                ..Default::default()
+            });
+            vault.mark_changed();
+
+            save(&args.vault, vault)?;
+            if args.json {
+                write_json(output, &ChangedItem { id, name })?;
+            }
+        },
+        CliCommand::Delete { name } => {
+            let id = find_by_name(vault, name)?.id;
+            vault.soft_delete_item(id, utils::unix_timestamp());
+
+            save(&args.vault, vault)?;
+            if args.json {
+                write_json(output, &ChangedItem { id, name })?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Opens and decodes the vault at `path`, with its key set for saving
+fn open(path: &Path, password: &str) -> Result<Vault, AppError> {
+    let (bytes, key) = file::open_vault(path, password).map_err(|e| match e {
+        OpenVaultError::Header(e) => AppError::from(e),
+        OpenVaultError::Payload(_) => AppError::Generic("Failed to open vault file. Check password.".into()),
+    })?;
+
+    let decoded = Vault::decode(bytes.as_ref());
+    file::recycle_buffer(bytes);
+
+    let mut vault = decoded.map_err(|e| AppError::Generic(format!("Failed to decode vault data: {}", e)))?;
+    vault.key = Some(key);
+    vault.metadata = file::read_vault_metadata(path).ok().flatten().unwrap_or_else(|| VaultMetadata::for_path(path));
+    Ok(vault)
+}
+
+/// Encrypts and writes the vault back to `path`, keeping backups like a save from the window
+fn save(path: &Path, vault: &mut Vault) -> Result<(), AppError> {
+    let encoded = vault.encode().map_err(|e| AppError::Generic(e.to_string()))?;
+    let key = vault.key.as_ref().ok_or_else(|| AppError::Generic("Open vault has no key".into()))?;
+
+    Ok(file::write_encrypted_file(encoded.as_ref(), path, key, &vault.metadata, file::DEFAULT_BACKUP_DEPTH)?)
+}
+
+/// The one active item named `name`. Names aren't unique, so several matches are an error
+/// rather than a guess.
+fn find_by_name<'a>(vault: &'a Vault, name: &str) -> Result<&'a Item, AppError> {
+    let matches: Vec<&Item> = vault.active_items().into_iter().filter(|item| item.name == name).collect();
+    match matches.as_slice() {
+        [item] => Ok(item),
+        [] => Err(AppError::Generic(format!("No item named '{}'", name))),
+        _ => Err(AppError::Generic(format!("{} items are named '{}', rename them in the app first", matches.len(), name))),
+    }
+}
+
+/// Everything up to the first line break, read into a `ZeroByte`
+fn read_first_line<R: Read>(reader: &mut R) -> Result<ZeroByte, AppError> {
+    let mut contents = ZeroByte::default();
+    contents.extend_from_reader(reader)?;
+
+    let len = contents.as_ref().iter().position(|&byte| byte == b'\n').unwrap_or(contents.len());
+    let len = if len > 0 && contents.as_ref()[len - 1] == b'\r' { len - 1 } else { len };
+    contents.truncate(len);
+    Ok(contents)
+}
+
+fn write_json<T: Serialize>(output: &mut dyn Write, value: &T) -> Result<(), AppError> {
+    serde_json::to_writer(&mut *output, value).map_err(|e| AppError::Generic(e.to_string()))?;
+    writeln!(output)?;
+    Ok(())
+}
+
+fn usage(message: &str) -> AppError {
+    AppError::Generic(format!("{}\n\n{}", message, USAGE))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::crypto::{ArgonParams, Crypto, KdfAlgorithm};
+
+    const PASSWORD: &str = "correct horse";
+    const FAST_PARAMS: ArgonParams = ArgonParams { algorithm: KdfAlgorithm::Argon2id, memory_cost: 64, time_cost: 1, parallelism: 1 };
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// A vault with a "Mail" and a "Bank" item and the password file for it
+    fn vault_in(dir: &Path) -> (PathBuf, PathBuf) {
+        let (path, password_file) = (dir.join("test.vault"), dir.join("password.txt"));
+        fs::write(&password_file, format!("{}\n", PASSWORD)).expect("Failed to write");
+
+        let mut vault = Vault::new();
+        vault.items.clear();
+        for (id, (name, password)) in [("Mail", "hunter2"), ("Bank", "pä$$wörd")].into_iter().enumerate() {
+            vault.items.push(Item {
+                id: id as i32,
+                name: name.into(),
+                username: "alice".into(),
+                password: password.into(),
+                ..Default::default()
+            });
+        }
+        vault.nonce = 2;
+        vault.key = Some(Crypto::derive_argon_key(PASSWORD.as_bytes(), None, FAST_PARAMS).expect("Key derivation failed"));
+        vault.metadata = VaultMetadata::for_path(&path);
+        save(&path, &mut vault).expect("Save failed");
+
+        (path, password_file)
+    }
+
+    /// Runs `command` on the vault, returning what it printed
+    fn run(vault: &Path, password_file: &Path, command: &[&str], stdin: &str) -> Result<String, AppError> {
+        let mut list = vec!["--vault", vault.to_str().unwrap(), "--password-file", password_file.to_str().unwrap()];
+        list.extend_from_slice(command);
+        let args = CliArgs::parse(&args(&list))?;
+
+        let mut output = Vec::new();
+        run_with_io(args, &mut stdin.as_bytes(), &mut output)?;
+        Ok(String::from_utf8(output).expect("Output is not UTF-8"))
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let parsed = CliArgs::parse(&args(&["get", "Mail", "--vault", "a.vault", "--password-file", "pw", "--json"])).expect("Parse failed");
+        assert_eq!(parsed, CliArgs {
+            vault: PathBuf::from("a.vault"),
+            password_file: PathBuf::from("pw"),
+            json: true,
+            command: CliCommand::Get { name: "Mail".into() },
+        });
+
+        for invalid in [
+            &["list"][..], &["--vault", "a.vault", "list"], &["--vault", "a", "--password-file", "pw"],
+            &["--vault", "a", "--password-file", "pw", "get"], &["--vault", "a", "--password-file", "pw", "list", "extra"],
+            &["--vault", "a", "--password-file", "pw", "copy", "Mail"], &["--vault", "a", "--password-file", "pw", "--force", "list"],
+        ] {
+            assert!(CliArgs::parse(&args(invalid)).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_cli_invocation_is_detected() {
+        assert!(is_cli_invocation(&args(&["--vault", "a.vault", "list"])));
+        assert!(!is_cli_invocation(&args(&[])));
+        assert!(!is_cli_invocation(&args(&["--portable"])));
+    }
+
+    #[test]
+    fn test_list_prints_names() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let (vault, password_file) = vault_in(dir.path());
+
+        assert_eq!(run(&vault, &password_file, &["list"], "").expect("List failed"), "Bank\nMail\n");
+
+        let json: serde_json::Value = serde_json::from_str(&run(&vault, &password_file, &["--json", "list"], "").expect("List failed"))
+            .expect("Output is not JSON");
+        assert_eq!(json[0]["name"], "Bank");
+        assert_eq!(json[1]["id"], 0);
+        assert!(json[0].get("password").is_none(), "list must not print passwords");
+    }
+
+    #[test]
+    fn test_get_prints_password() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let (vault, password_file) = vault_in(dir.path());
+
+        assert_eq!(run(&vault, &password_file, &["get", "Bank"], "").expect("Get failed"), "pä$$wörd\n");
+
+        let json: serde_json::Value = serde_json::from_str(&run(&vault, &password_file, &["get", "Mail", "--json"], "").expect("Get failed"))
+            .expect("Output is not JSON");
+        assert_eq!((json["password"].as_str(), json["username"].as_str()), (Some("hunter2"), Some("alice")));
+
+        assert!(run(&vault, &password_file, &["get", "Missing"], "").is_err());
+    }
+
+    #[test]
+    fn test_add_saves_new_item() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let (vault, password_file) = vault_in(dir.path());
+
+        let output = run(&vault, &password_file, &["--json", "add", "Router"], "s3cret\r\nignored\n").expect("Add failed");
+        assert_eq!(output, "{\"id\":2,\"name\":\"Router\"}\n");
+        assert_eq!(run(&vault, &password_file, &["get", "Router"], "").expect("Get failed"), "s3cret\n");
+
+        assert!(run(&vault, &password_file, &["add", "Router"], "again\n").is_err(), "Names must stay unique");
+    }
+
+    #[test]
+    fn test_delete_moves_item_to_trash() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let (vault, password_file) = vault_in(dir.path());
+
+        assert_eq!(run(&vault, &password_file, &["delete", "Mail"], "").expect("Delete failed"), "");
+        assert_eq!(run(&vault, &password_file, &["list"], "").expect("List failed"), "Bank\n");
+
+        let (bytes, _) = file::open_vault(&vault, PASSWORD).expect("Open failed");
+        let reopened = Vault::decode(bytes.as_ref()).expect("Decode failed");
+        assert_eq!(reopened.trash().len(), 1, "Deleted items go to the trash");
+    }
+
+    #[test]
+    fn test_wrong_password_and_held_lock_are_errors() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let (vault, password_file) = vault_in(dir.path());
+
+        let wrong = dir.path().join("wrong.txt");
+        fs::write(&wrong, "wrong horse").expect("Failed to write");
+        assert!(run(&vault, &wrong, &["list"], "").is_err());
+
+        let _window = VaultLock::acquire(&vault).expect("Lock failed");
+        assert!(run(&vault, &password_file, &["delete", "Mail"], "").is_err(), "The vault is open in a window");
+        assert!(run(&vault, &password_file, &["list"], "").is_ok(), "Reading doesn't need the lock");
+    }
+}
@@ -1,32 +1,73 @@
-/////////////////////////////////////////////////////////////////
-///  NOTE:
-///  - Custom error handling (AppError) is defined
-///    but not fully integrated throughout the code yet.
-///  - Future improvements will focus on robust error handling,
-///    graceful recovery, and better logging.
-/// 
-/// Please treat this as a work-in-progress
-/////////////////////////////////////////////////////////////////
+use std::fmt;
+use std::io;
+use std::sync::PoisonError;
 
-use log::error;
+use crate::errors::clipboard_errors::ClipboardError;
+use crate::errors::file_errors::FileError;
+use crate::errors::vault_errors::VaultError;
 
 
+/// Errors from vault operations started by the UI, reported to the user through
+/// `MainWindowHandler::handle_app_error`
 #[derive(Debug)]
-enum _AppError {
-    Other {
-        message: String,
-        file: &'static str,
-        line: u32,
-    },
-}
-
-impl _AppError {
-    fn _generate(&self) {
-        let _user_msg = match self {
-            Self::Other { message, file, line } => {
-                error!("Error at {}:{} - {}", file, line, message);
-                message.clone()
-            },
-        };
+pub(crate) enum AppError {
+    /// Reading or writing the vault file failed
+    IoError(String),
+    /// The vault file was changed by another program since it was read
+    FileConflict,
+    /// A thread panicked while holding shared vault state
+    PoisedState,
+    /// Copying to the clipboard failed, reported without interrupting the user
+    Clipboard(ClipboardError),
+    Generic(String),
+}
+
+impl std::error::Error for AppError { }
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IoError(msg) => write!(f, "I/O error: {}", msg),
+            Self::FileConflict => write!(f, "Vault file was modified by another program"),
+            Self::PoisedState => write!(f, "Shared state was poisoned by a panicked thread"),
+            Self::Clipboard(e) => write!(f, "{}", e),
+            Self::Generic(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<String> for AppError {
+    fn from(msg: String) -> Self {
+        Self::Generic(msg)
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(e: io::Error) -> Self {
+        Self::IoError(e.to_string())
+    }
+}
+
+impl From<FileError> for AppError {
+    fn from(e: FileError) -> Self {
+        Self::IoError(e.to_string())
+    }
+}
+
+impl From<ClipboardError> for AppError {
+    fn from(e: ClipboardError) -> Self {
+        Self::Clipboard(e)
     }
-}
\ No newline at end of file
+}
+
+impl From<VaultError> for AppError {
+    fn from(e: VaultError) -> Self {
+        Self::Generic(e.to_string())
+    }
+}
+
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(_: PoisonError<T>) -> Self {
+        Self::PoisedState
+    }
+}
@@ -0,0 +1,23 @@
+use std::fmt;
+
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum AppearanceError {
+    /// Not a `#rrggbb` color
+    InvalidColor(String),
+    /// A valid color that isn't part of the accent palette
+    NotInPalette(String),
+    UnknownIcon(String),
+}
+
+impl std::error::Error for AppearanceError { }
+
+impl fmt::Display for AppearanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidColor(color) => write!(f, "'{}' is not a #rrggbb color", color),
+            Self::NotInPalette(color) => write!(f, "Accent color {} is not in the palette", color),
+            Self::UnknownIcon(id) => write!(f, "Unknown vault icon '{}'", id),
+        }
+    }
+}
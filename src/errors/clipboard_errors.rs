@@ -0,0 +1,22 @@
+use std::fmt;
+
+
+/// Errors from `utils::clipboard`. copypasta only reports its errors as text.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ClipboardError {
+    /// No clipboard to talk to, e.g. a headless session or a compositor that went away
+    Unavailable(String),
+    /// The clipboard was reached but didn't take the text
+    WriteFailed(String),
+}
+
+impl std::error::Error for ClipboardError { }
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unavailable(e) => write!(f, "Clipboard is not available: {}", e),
+            Self::WriteFailed(e) => write!(f, "Failed to copy to the clipboard: {}", e),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::fmt;
+
+
+/// Errors from compressing or decompressing vault data in `utils::compression`
+#[derive(Debug)]
+pub(crate) enum CompressionError {
+    /// Decompressing would produce more than the allowed output, e.g. a crafted file
+    OutputTooLarge { max_output_len: usize },
+    /// The data isn't a valid compressed stream, or the encoder failed
+    Codec(String),
+}
+
+impl std::error::Error for CompressionError { }
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutputTooLarge { max_output_len } => write!(f, "Vault data is larger than {} bytes when decompressed", max_output_len),
+            Self::Codec(e) => write!(f, "Compression failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+use std::io;
+
+
+#[derive(Debug)]
+pub(crate) enum ConfigError {
+    /// No config directory could be determined for this platform
+    NoConfigDir,
+    Io(io::Error),
+    EncodingFailed(String),
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoConfigDir => write!(f, "No config directory found"),
+            Self::Io(e) => write!(f, "Settings error: {}", e),
+            Self::EncodingFailed(e) => write!(f, "Failed to encode settings: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::errors::zero_byte_errors::ZeroByteError;
+
+
+/// Errors from `utils::crypto`.
+///
+/// `AesGcm` from decryption is security relevant: the data failed to authenticate, so either
+/// the key is wrong or the data was modified. So is `Padding`, which is how a wrong key shows up
+/// with unauthenticated AES-CBC. The other variants point at bad input or a bug and say nothing
+/// about the key.
+#[derive(Debug, PartialEq)]
+pub(crate) enum CryptoError {
+    /// Key derivation failed, e.g. the parameters were rejected by Argon2
+    Argon2(argon2::Error),
+    /// Encryption failed, or decryption failed to authenticate
+    AesGcm(aes_gcm::Error),
+    /// Key derivation parameters outside the bounds NoPass accepts
+    InvalidParams(String),
+    /// Too short to hold a nonce and tag, e.g. a truncated file
+    Nonce { min: usize, actual: usize },
+    /// AES-CBC plaintext without valid PKCS#7 padding, or ciphertext that isn't whole blocks
+    Padding,
+    /// A key of the wrong length for the cipher
+    KeyLength(ZeroByteError),
+}
+
+impl CryptoError {
+    /// Whether the error is an authentication failure. Its details must not be logged,
+    /// they would tell an attacker which guesses got further than others.
+    pub(crate) fn is_security_sensitive(&self) -> bool {
+        matches!(self, Self::AesGcm(_) | Self::Padding)
+    }
+}
+
+impl std::error::Error for CryptoError { }
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Argon2(e) => write!(f, "{}", e),
+            Self::AesGcm(_) => write!(f, "Decryption failed"),
+            Self::InvalidParams(e) => write!(f, "{}", e),
+            Self::Nonce { min, actual } => write!(f, "Encrypted data is too short: {} bytes, expected at least {}", actual, min),
+            Self::Padding => write!(f, "Decryption failed"),
+            Self::KeyLength(e) => write!(f, "Invalid key: {}", e),
+        }
+    }
+}
+
+impl From<argon2::Error> for CryptoError {
+    fn from(e: argon2::Error) -> Self {
+        Self::Argon2(e)
+    }
+}
+
+impl From<aes_gcm::Error> for CryptoError {
+    fn from(e: aes_gcm::Error) -> Self {
+        Self::AesGcm(e)
+    }
+}
+
+impl From<ZeroByteError> for CryptoError {
+    fn from(e: ZeroByteError) -> Self {
+        Self::KeyLength(e)
+    }
+}
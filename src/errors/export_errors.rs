@@ -0,0 +1,41 @@
+use std::fmt;
+use std::io;
+
+
+/// Errors from writing the vault's items to a plaintext export in `utils::export`
+#[derive(Debug)]
+pub(crate) enum ExportError {
+    Io(io::Error),
+    EncodingFailed(String),
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Failed to write export file: {}", e),
+            Self::EncodingFailed(e) => write!(f, "Failed to encode export: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// serde_json only fails on the writer or on data it can't represent, never with the item contents
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::EncodingFailed(e.to_string())
+    }
+}
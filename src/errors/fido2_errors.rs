@@ -0,0 +1,25 @@
+use std::fmt;
+
+
+#[derive(Debug, PartialEq)]
+#[allow(dead_code)]  // Raised by device backends
+pub(crate) enum Fido2Error {
+    /// No FIDO2 device is plugged in
+    DeviceNotFound,
+    /// The device refused or failed the `getAssertion` call
+    AssertionFailed(String),
+    /// The user didn't touch the device, or cancelled on it
+    Cancelled,
+}
+
+impl std::error::Error for Fido2Error { }
+
+impl fmt::Display for Fido2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeviceNotFound => write!(f, "No hardware security key was found"),
+            Self::AssertionFailed(e) => write!(f, "The hardware security key failed: {}", e),
+            Self::Cancelled => write!(f, "The hardware security key request was cancelled"),
+        }
+    }
+}
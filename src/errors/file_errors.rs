@@ -0,0 +1,164 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::errors::compression_errors::CompressionError;
+use crate::errors::crypto_errors::CryptoError;
+use crate::errors::vault_lock_errors::VaultLockError;
+
+
+/// Errors from reading and writing vault files in `utils::file`
+#[derive(Debug)]
+pub(crate) enum FileError {
+    Io(io::Error),
+    /// The file ends before its header or encrypted data is complete
+    Truncated,
+    /// The file's header doesn't have the layout the operation expects, e.g. migrating a
+    /// file that isn't in the legacy format
+    BadMagic,
+    /// Written by a newer version of NoPass
+    UnsupportedVersion(u16),
+    /// The header is present but unreadable
+    CorruptHeader(&'static str),
+    /// An attachment chunk failed to authenticate, or was swapped with another one
+    TamperingDetected,
+    /// The payload didn't decrypt: wrong password, or the data was modified
+    DecryptionFailed(String),
+    KeyDerivation(String),
+    /// Serializing, compressing or encrypting for a write failed, or decoding after a read
+    EncodingFailed(String),
+    PathNotFound(PathBuf),
+    /// The folder a vault is to be written to doesn't exist
+    ParentNotFound(PathBuf),
+    /// A vault can't be written to this path, e.g. it is a folder or its parent is a file
+    InvalidTarget(PathBuf),
+    AttachmentNotFound(u32),
+    /// The attachment index lists more entries or data than the file has left
+    CorruptAttachments,
+    /// Writing or syncing the vault file at `path` failed, the previous file is left in place
+    WriteFailed { path: PathBuf, source: io::Error },
+    /// Flushing buffered vault data to the file failed, e.g. the disk is full
+    FlushFailed(io::Error),
+    /// The OS couldn't sync the written file to the disk
+    SyncFailed(io::Error),
+    /// Fewer bytes ended up in the file than were written to it
+    IncompleteWrite { expected: u64, written: u64 },
+    /// The vault file written to `path` didn't read back and decrypt with the key it was
+    /// written with, so it wasn't put in place of the previous file
+    VerificationFailed { path: PathBuf, source: Box<FileError> },
+    /// Another window or process holds the vault's lock
+    AlreadyLocked,
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) | Self::WriteFailed { source: e, .. } | Self::FlushFailed(e) | Self::SyncFailed(e) => Some(e),
+            Self::VerificationFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Truncated => write!(f, "The vault file is incomplete or damaged."),
+            Self::BadMagic => write!(f, "Vault file is not in the expected format"),
+            Self::UnsupportedVersion(version) => write!(
+                f, "This vault was created by a newer version of NoPass (file format {}). Please update NoPass to open it.", version
+            ),
+            Self::CorruptHeader(e) => write!(f, "{}", e),
+            Self::TamperingDetected => write!(f, "Vault data was modified or damaged"),
+            Self::DecryptionFailed(e) => write!(f, "Decryption failed: {}", e),
+            Self::KeyDerivation(e) => write!(f, "Key derivation failed: {}", e),
+            Self::EncodingFailed(e) => write!(f, "Encoding failed: {}", e),
+            Self::PathNotFound(path) => write!(f, "File not found: {}", path.display()),
+            Self::ParentNotFound(path) => write!(f, "The folder {} doesn't exist", path.display()),
+            Self::InvalidTarget(path) => write!(f, "{} isn't a file path in an existing folder", path.display()),
+            Self::AttachmentNotFound(id) => write!(f, "No attachment with id {}", id),
+            Self::CorruptAttachments => write!(f, "The attachments in the vault file are damaged"),
+            Self::WriteFailed { path, source } => write!(f, "Couldn't write {}: {}", path.display(), source),
+            Self::FlushFailed(e) => write!(f, "Couldn't finish writing the vault file: {}", e),
+            Self::SyncFailed(e) => write!(f, "Couldn't save the vault file to disk: {}", e),
+            Self::IncompleteWrite { expected, written } => write!(
+                f, "The vault file is incomplete, {} of {} bytes were written", written, expected
+            ),
+            Self::VerificationFailed { path, source } => write!(
+                f, "The vault written to {} didn't read back correctly, the previous file was kept: {}", path.display(), source
+            ),
+            Self::AlreadyLocked => write!(f, "Vault is open in another window"),
+        }
+    }
+}
+
+impl FileError {
+    /// The OS error behind a failed write, flush or sync
+    pub(crate) fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Self::Io(e) | Self::WriteFailed { source: e, .. } | Self::FlushFailed(e) | Self::SyncFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Running out of file is reported as a truncated vault rather than an IO error
+impl From<io::Error> for FileError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof { Self::Truncated }
+        else { Self::Io(e) }
+    }
+}
+
+/// Errors from reading: a failed key derivation, a payload that didn't decrypt, or one too
+/// short to be encrypted data. Only the variant of an authentication failure is logged.
+impl From<CryptoError> for FileError {
+    fn from(e: CryptoError) -> Self {
+        if e.is_security_sensitive() {
+            log::debug!("Crypto error: authentication failed");
+        } else {
+            log::debug!("Crypto error: {:?}", e);
+        }
+
+        match e {
+            CryptoError::Argon2(_) | CryptoError::InvalidParams(_) | CryptoError::KeyLength(_) => Self::KeyDerivation(e.to_string()),
+            CryptoError::AesGcm(_) | CryptoError::Padding => Self::DecryptionFailed(e.to_string()),
+            CryptoError::Nonce { .. } => Self::Truncated,
+        }
+    }
+}
+
+impl From<CompressionError> for FileError {
+    fn from(e: CompressionError) -> Self {
+        Self::EncodingFailed(e.to_string())
+    }
+}
+
+impl From<VaultLockError> for FileError {
+    fn from(e: VaultLockError) -> Self {
+        match e {
+            VaultLockError::Held(_) => Self::AlreadyLocked,
+            VaultLockError::Io(e) => Self::Io(e),
+        }
+    }
+}
+
+/// Why `file::open_vault` failed. Only a payload failure can mean a wrong password.
+#[derive(Debug)]
+pub(crate) enum OpenVaultError {
+    /// The file or its header couldn't be read, or no key could be derived from it
+    Header(FileError),
+    /// The header is fine but the payload didn't decrypt or decode
+    Payload(FileError),
+}
+
+impl std::error::Error for OpenVaultError { }
+
+impl fmt::Display for OpenVaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header(e) | Self::Payload(e) => write!(f, "{}", e),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::io;
+
+
+#[derive(Debug)]
+pub(crate) enum ImportError {
+    Io(io::Error),
+    /// The file isn't in the expected export format
+    Parse(String),
+    /// The file isn't well-formed CSV, e.g. a quoted field is never closed
+    CsvParse(String),
+    /// An export that is a zip archive couldn't be read, or holds unsafe paths
+    Archive(String),
+    /// The file is larger than the importer reads
+    TooLarge { max_len: u64 },
+    /// Password protected or account restricted exports can't be read without the account
+    Encrypted,
+    /// A KeePass database didn't open with the password given
+    WrongPassword,
+    /// A KeePass database encrypted with a cipher NoPass doesn't implement
+    UnsupportedCipher(String),
+    /// A KeePass database using a version or feature NoPass can't read
+    Unsupported(String),
+    /// The unencrypted header of a KeePass database is malformed or fails its checksum
+    CorruptHeader(String),
+    /// The encrypted part of a KeePass database fails its integrity check or doesn't decode
+    Damaged(String),
+    /// A previewed import no longer fits the vault, e.g. an item it overwrites was deleted
+    InvalidPlan(String),
+}
+
+impl std::error::Error for ImportError { }
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Failed to read export file: {}", e),
+            Self::Parse(msg) => write!(f, "Not a valid export file: {}", msg),
+            Self::CsvParse(msg) => write!(f, "Not a valid CSV file: {}", msg),
+            Self::Archive(msg) => write!(f, "Not a valid export archive: {}", msg),
+            Self::TooLarge { max_len } => write!(f, "The export file is larger than {} MiB", max_len / (1024 * 1024)),
+            Self::Encrypted => write!(f, "Encrypted exports are not supported, export as unencrypted JSON instead"),
+            Self::WrongPassword => write!(f, "Wrong master password for this database, or it also needs a key file"),
+            Self::UnsupportedCipher(cipher) => write!(f, "The database is encrypted with an unsupported cipher ({})", cipher),
+            Self::Unsupported(msg) => write!(f, "Unsupported KeePass database: {}", msg),
+            Self::CorruptHeader(msg) => write!(f, "The database header is damaged: {}", msg),
+            Self::Damaged(msg) => write!(f, "The database is damaged: {}", msg),
+            Self::InvalidPlan(msg) => write!(f, "The import can't be applied: {}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Parse(e.to_string())
+    }
+}
@@ -0,0 +1,33 @@
+use std::fmt;
+
+use bincode::error::DecodeError;
+
+
+/// Errors from reading a vault payload saved with an older or newer schema, see `Vault::migrate`
+#[derive(Debug)]
+pub(crate) enum MigrationError {
+    /// A schema version this version of NoPass can't read, e.g. one saved by a newer release
+    UnsupportedVersion(u16),
+    /// The payload doesn't decode as the schema it was read as
+    Decode(DecodeError),
+    /// The payload decoded, but bytes were left over, so it isn't the schema it was read as
+    TrailingBytes { version: u16 },
+}
+
+impl std::error::Error for MigrationError { }
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion(version) => write!(f, "The vault uses schema version {}, which this version of NoPass can't read", version),
+            Self::Decode(e) => write!(f, "{}", e),
+            Self::TrailingBytes { version } => write!(f, "Vault data has unexpected bytes after schema version {} contents", version),
+        }
+    }
+}
+
+impl From<DecodeError> for MigrationError {
+    fn from(e: DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
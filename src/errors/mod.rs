@@ -1,2 +1,18 @@
-mod app_errors;
-pub(super) mod ui_errors;
\ No newline at end of file
+pub(super) mod app_errors;
+pub(super) mod appearance_errors;
+pub(super) mod clipboard_errors;
+pub(super) mod compression_errors;
+pub(super) mod config_errors;
+pub(super) mod crypto_errors;
+pub(super) mod export_errors;
+#[cfg(feature = "fido2")]
+pub(super) mod fido2_errors;
+pub(super) mod file_errors;
+pub(super) mod import_errors;
+pub(super) mod migration_errors;
+pub(super) mod password_errors;
+pub(super) mod tempsec_errors;
+pub(super) mod ui_errors;
+pub(super) mod vault_errors;
+pub(super) mod vault_lock_errors;
+pub(super) mod zero_byte_errors;
\ No newline at end of file
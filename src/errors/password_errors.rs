@@ -0,0 +1,29 @@
+use std::fmt;
+
+
+/// Reasons a new vault password or export passphrase is rejected before anything is written,
+/// or a password can't be generated with the options given.
+#[derive(Debug, PartialEq)]
+pub(crate) enum PasswordError {
+    TooShort { min: usize },
+    TooWeak,
+    Mismatch,
+    /// Generator length outside `min..=max`
+    LengthOutOfRange { min: usize, max: usize },
+    /// Generator options with every character class turned off
+    NoCharacterClasses,
+}
+
+impl std::error::Error for PasswordError { }
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort { min } => write!(f, "Password must be at least {} characters", min),
+            Self::TooWeak => write!(f, "Password is too easy to guess"),
+            Self::Mismatch => write!(f, "Passwords do not match"),
+            Self::LengthOutOfRange { min, max } => write!(f, "Password length must be between {} and {}", min, max),
+            Self::NoCharacterClasses => write!(f, "Choose at least one kind of character"),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+
+#[derive(Debug)]
+pub(crate) enum TempSecError {
+    /// Only a location other users can write to is available
+    WorldWritable(PathBuf),
+    /// No per-user temp location is configured (e.g. XDG_RUNTIME_DIR is unset)
+    NoPrivateLocation,
+    Io(io::Error),
+}
+
+impl std::error::Error for TempSecError { }
+
+impl fmt::Display for TempSecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WorldWritable(path) => write!(f, "Refusing to write decrypted data to world-writable location {}", path.display()),
+            Self::NoPrivateLocation => write!(f, "No private temp directory available, refusing to write decrypted data"),
+            Self::Io(e) => write!(f, "Temp file error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for TempSecError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -1,11 +1,11 @@
 /////////////////////////////////////////////////////////////////
-///  NOTE:
-///  - Custom error handling (UiError and UiResult) is defined
-///    but not fully integrated throughout the code yet.
-///  - Future improvements will focus on robust error handling,
-///    graceful recovery, and better logging.
-/// 
-/// Please treat this as a work-in-progress
+//   NOTE:
+//   - Custom error handling (UiError and UiResult) is defined
+//     but not fully integrated throughout the code yet.
+//   - Future improvements will focus on robust error handling,
+//     graceful recovery, and better logging.
+//  
+//  Please treat this as a work-in-progress
 /////////////////////////////////////////////////////////////////
 
 use std::fmt;
@@ -13,10 +13,13 @@ use std::fmt;
 use slint::PlatformError;
 
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub(crate) enum UiError {
-    _WindowCreation(String),
+    WindowCreation(String),
     _WindowOperation(String),
+    /// The window's weak handle no longer upgrades, the window was dropped
+    InvalidHandle,
     Platform(PlatformError),
     _Internal(String),
 }
@@ -26,8 +29,9 @@ impl std::error::Error for UiError { }
 impl fmt::Display for UiError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::_WindowCreation(msg) => write!(f, "Window creation error: {}", msg),
+            Self::WindowCreation(msg) => write!(f, "Window creation error: {}", msg),
             Self::_WindowOperation(msg) => write!(f, "Window operation error: {}", msg),
+            Self::InvalidHandle => write!(f, "Window handle is no longer valid"),
             Self::Platform(e) => write!(f, "Platform error: {}", e),
             Self::_Internal(msg) => write!(f, "Internal error: {}", msg),
         }
@@ -40,4 +44,4 @@ impl From<PlatformError> for UiError {
     }
 }
 
-type _UiResult<T> = Result<T, UiError>;
\ No newline at end of file
+pub(crate) type UiResult<T> = Result<T, UiError>;
\ No newline at end of file
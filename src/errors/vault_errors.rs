@@ -0,0 +1,18 @@
+use std::fmt;
+
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum VaultError {
+    /// Every item ID up to `i32::MAX` has been handed out
+    IdExhausted,
+}
+
+impl std::error::Error for VaultError { }
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IdExhausted => write!(f, "The vault has run out of item IDs"),
+        }
+    }
+}
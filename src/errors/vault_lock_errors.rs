@@ -0,0 +1,28 @@
+use std::fmt;
+use std::io;
+
+
+#[derive(Debug)]
+pub(crate) enum VaultLockError {
+    /// Another process holds the lock. The pid is read from the lock file when available.
+    Held(Option<u32>),
+    Io(io::Error),
+}
+
+impl std::error::Error for VaultLockError { }
+
+impl fmt::Display for VaultLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Held(Some(pid)) => write!(f, "Vault is open in another window (process {})", pid),
+            Self::Held(None) => write!(f, "Vault is open in another window"),
+            Self::Io(e) => write!(f, "Vault lock error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for VaultLockError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -0,0 +1,26 @@
+use std::fmt;
+
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum ZeroByteError {
+    LengthMismatch { left: usize, right: usize },
+    /// A `%` not followed by two hex digits, at this byte offset of the input
+    InvalidPercentEncoding { position: usize },
+    /// PKCS#7 padding that is missing or malformed
+    InvalidPadding,
+    /// An index past the end of the buffer
+    IndexOutOfBounds { index: usize, len: usize },
+}
+
+impl std::error::Error for ZeroByteError { }
+
+impl fmt::Display for ZeroByteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LengthMismatch { left, right } => write!(f, "Buffer length mismatch: {} != {}", left, right),
+            Self::InvalidPercentEncoding { position } => write!(f, "Invalid percent-encoding at byte {}", position),
+            Self::InvalidPadding => write!(f, "Invalid padding"),
+            Self::IndexOutOfBounds { index, len } => write!(f, "Index {} out of bounds for buffer of length {}", index, len),
+        }
+    }
+}
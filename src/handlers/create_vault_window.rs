@@ -1,23 +1,54 @@
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use bincode::config::standard;
-use bincode::serde::encode_to_vec;
-use slint::{ComponentHandle, SharedString, Weak};
+use slint::{ComponentHandle, SharedString, Timer, TimerMode, Weak};
 
 use crate::CreateVaultWindow;
-use crate::handlers::WindowHandler;
+use crate::errors::file_errors::FileError;
+use crate::errors::password_errors::PasswordError;
+use crate::handlers::dialog_window::{DialogButtons, DialogResult, DialogWindowHandler};
+use crate::handlers::file_dialog::{show_file_dialog, DialogAction};
+use crate::handlers::main_window::{MainWindowHandler, SETTINGS};
+use crate::handlers::{ModalSlot, WindowHandler};
+use crate::models::settings::DialogContext;
 use crate::models::vault::Vault;
-use crate::utils::crypto::Crypto;
-use crate::utils::file;
+use crate::utils::config;
+use crate::utils::crypto::{ArgonParams, Crypto, KdfAlgorithm};
+use crate::utils::file::{self, VaultMetadata};
+use crate::utils::password_strength;
+use crate::utils::zero_byte::ZeroByte;
+use crate::utils;
 
 
+/// Extension added to a vault file name typed without one
+const VAULT_EXTENSION: &str = "vault";
+
+/// How long deriving the key of a new vault should take on this machine
+const CALIBRATION_TARGET_MS: u64 = 500;
+
+/// Set while key derivation is being calibrated, new vaults wait for the result
+static CALIBRATING: AtomicBool = AtomicBool::new(false);
+
+/// What creating a vault at a path would replace
+#[derive(Debug, PartialEq)]
+enum ExistingTarget {
+    Nothing,
+    Vault,
+    OtherFile,
+}
+
 /// Coordinates the MainWindow lifecycle and UI behavior.
 /// Holds ownership to prevent premature drop and supports weak upgrade for event binding.
 pub(crate) struct CreateVaultWindowHandler {
     _window_strong: CreateVaultWindow,
     window: Weak<CreateVaultWindow>,
     visible: Arc<Mutex<bool>>,
+    modal: ModalSlot,
+    calibration_timer: Rc<Timer>,
 }
 
 impl CreateVaultWindowHandler {
@@ -30,88 +61,267 @@ impl CreateVaultWindowHandler {
             _window_strong: window,
             window: weak,
             visible: Arc::new(Mutex::new(false)),
+            modal: ModalSlot::default(),
+            calibration_timer: Rc::new(Timer::default()),
         };
 
+        // Only ever used from the UI thread; Arc<Mutex> mirrors the other handlers' visibility state
+        #[allow(clippy::arc_with_non_send_sync)]
         let handler = Arc::new(Mutex::new(handler));
         Self::setup(&handler).await;
         
         handler
     }
 
+    /// Tunes key derivation to this machine on its own thread and stores the result in the
+    /// settings once the UI is running. The create vault window shows progress meanwhile.
+    pub(crate) fn calibrate_in_background() {
+        CALIBRATING.store(true, Ordering::SeqCst);
+
+        std::thread::spawn(|| {
+            let params = Crypto::calibrate_argon2_params(CALIBRATION_TARGET_MS);
+
+            let result = slint::invoke_from_event_loop(move || {
+                if let Ok(mut settings) = SETTINGS.lock() {
+                    settings.argon_params = Some(params);
+                    settings.auto_calibrate_on_first_run = false;
+                    if let Err(e) = config::save(&settings) {
+                        log::warn!("Failed to save settings: {}", e);
+                    }
+                }
+                CALIBRATING.store(false, Ordering::SeqCst);
+            });
+            if let Err(e) = result {
+                log::warn!("Failed to store calibrated key derivation parameters: {}", e);
+                CALIBRATING.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+
+    /// Shows calibration progress until `calibrate_in_background` is done
+    fn follow_calibration(&self, window: &CreateVaultWindow) {
+        if !CALIBRATING.load(Ordering::SeqCst) {
+            return;
+        }
+
+        window.set_calibrating(true);
+        let window_weak = window.as_weak();
+        let timer = Rc::downgrade(&self.calibration_timer);
+        self.calibration_timer.start(TimerMode::Repeated, Duration::from_millis(250), move || {
+            if CALIBRATING.load(Ordering::SeqCst) {
+                return;
+            }
+            if let Some(window) = window_weak.upgrade() {
+                window.set_calibrating(false);
+            }
+            if let Some(timer) = timer.upgrade() {
+                timer.stop();
+            }
+        });
+    }
+
     async fn setup(handler_arc: &Arc<Mutex<Self>>) {
         let handler_arc_clone = Arc::clone(handler_arc);
         let window = handler_arc_clone.lock().unwrap().get_window().upgrade().unwrap();
+        handler_arc_clone.lock().unwrap().follow_calibration(&window);
         //let window_weak = window.as_weak();
 
+        let window_weak_validate = window.as_weak();
+        window.on_validate_passwords(move |password: SharedString, confirm: SharedString| {
+            let message = Self::password_error_message(&password, &confirm);
+            if let Some(window) = window_weak_validate.upgrade() {
+                window.set_password_error(message.clone());
+            }
+            message.is_empty()
+        });
+
         let handler_arc_clone_done = Arc::clone(handler_arc);
-        window.on_create_database_done(move |password: SharedString| {
-            if let Some(vault_path) = Self::save_file_dialog() {
-                let handler_arc_for_task = Arc::clone(&handler_arc_clone_done);
-
-                slint::spawn_local(async move {
-                    Self::create_vault_file(&vault_path, password.into()).await;
-                    
-                    if let Ok(mut handler) = handler_arc_for_task.lock() {
-                        handler.hide();
-                    }
-                }).ok();
+        let window_weak_done = window.as_weak();
+        window.on_create_database_done(move |password: SharedString, kdf_algorithm: i32| {
+            let mut password_bytes = ZeroByte::default();
+            password_bytes.extend_from_slice(password.as_bytes());
+            drop(password);
+
+            let handler_arc_for_task = Arc::clone(&handler_arc_clone_done);
+            let algorithm = u8::try_from(kdf_algorithm).ok()
+                .and_then(KdfAlgorithm::from_id)
+                .unwrap_or_default();
+
+            let window_weak = window_weak_done.clone();
+            if let Some(window) = window_weak.upgrade() {
+                window.set_validating(true);
             }
-        }); 
+
+            slint::spawn_local(async move {
+                // Scoring is CPU bound, keep it off the UI thread. The password comes back
+                // for creating the vault and is wiped wherever it is dropped.
+                let checked = file::run_blocking(move || {
+                    let result = Self::check_new_password(&password_bytes);
+                    result.map(|()| password_bytes)
+                }).await;
+
+                if let Some(window) = window_weak.upgrade() {
+                    window.set_validating(false);
+                }
+                let password = match checked {
+                    Ok(Ok(password)) => password,
+                    Ok(Err(e)) => {
+                        if let Some(window) = window_weak.upgrade() {
+                            window.set_password_error(e.to_string().into());
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        log::error!("Password check failed: {}", e);
+                        return;
+                    }
+                };
+
+                let Some(vault_path) = Self::save_file_dialog() else {
+                    return;
+                };
+                let Some(vault_path) = Self::confirm_target(vault_path).await else {
+                    return;
+                };
+                if let Some(window) = window_weak.upgrade() {
+                    window.set_creating(true);
+                }
+
+                let created = Self::create_vault_file(&vault_path, password, algorithm).await;
+
+                if let Some(window) = window_weak.upgrade() {
+                    window.set_creating(false);
+                    if created {
+                        window.set_password(SharedString::new());
+                        window.set_confirm_password(SharedString::new());
+                    }
+                }
+                if let Ok(mut handler) = handler_arc_for_task.lock()
+                    && let Err(e) = handler.hide() {
+                    log::error!("Failed to hide window: {}", e);
+                }
+            }).ok();
+        });
 
         let handler_arc_clone_cancel = Arc::clone(handler_arc);
         window.on_create_database_cancel(move || {
-            if let Ok(mut handler) = handler_arc_clone_cancel.lock() {
-                handler.hide();
+            if let Ok(mut handler) = handler_arc_clone_cancel.lock()
+                && let Err(e) = handler.hide() {
+                log::error!("Failed to hide window: {}", e);
             }
         });
     }
 
-    /// Create a new encrypted vault file at the specified path.
-    /// Shows a confirmation or error dialog depending on success.
-    async fn create_vault_file(path: &PathBuf, password: String) {
-        fn show_dialog(title: String, message: String) {
-            slint::spawn_local(async move {
-                rfd::MessageDialog::new()
-                    .set_title(title)
-                    .set_description(message)
-                    .set_buttons(rfd::MessageButtons::Ok)
-                    .show();
-            }).ok();
+    /// Value for the window's `password_error` property; empty when the passwords match.
+    /// Strength is checked after Done, see `check_new_password`.
+    fn password_error_message(password: &str, confirm: &str) -> SharedString {
+        match password_strength::validate_confirmation(password, confirm) {
+            Ok(()) => SharedString::new(),
+            Err(e) => e.to_string().into(),
         }
+    }
 
-        let vault = Vault::new();
-        let encoded_vault = encode_to_vec(&vault, standard()).unwrap();
-        let key = Crypto::derive_argon_key(password.as_bytes(), None).unwrap();
-        let path_clone = path.clone();
+    /// Whether `password` is long and strong enough to protect a new vault
+    fn check_new_password(password: &ZeroByte) -> Result<(), PasswordError> {
+        // The window only hands over valid UTF-8, anything else can't be scored
+        let password = std::str::from_utf8(password.as_ref()).map_err(|_| PasswordError::TooWeak)?;
+        password_strength::validate_strength(password)
+    }
+
+    /// Create a new encrypted vault file at the specified path using the chosen Argon2 variant.
+    /// Shows a confirmation or error dialog depending on success and returns whether the vault was created.
+    async fn create_vault_file(path: &Path, password: ZeroByte, algorithm: KdfAlgorithm) -> bool {
+        let mut vault = Vault::new();
+        vault.metadata = VaultMetadata::for_path(path);
+        vault.metadata.created_at = utils::unix_timestamp();
+        vault.metadata.modified_at = vault.metadata.created_at;
+        vault.metadata.item_count_hint = vault.items.len() as u32;
+
+        let calibrated = SETTINGS.lock().ok().and_then(|settings| settings.argon_params);
+        let params = ArgonParams { algorithm, ..calibrated.unwrap_or_default() };
+        let path_clone = path.to_path_buf();
 
-        let result = tokio::task::spawn_blocking(move || {
-            file::write_encrypted_file(&encoded_vault, &path_clone, &key)
-        }).await.unwrap();
+        // Key derivation is as slow as the write, keep both off the UI thread
+        let result = file::run_blocking(move || {
+            let encoded_vault = vault.encode().map_err(|e| FileError::EncodingFailed(e.to_string()))?;
+            let key = Crypto::derive_argon_key(password.as_ref(), None, params)?;
+            file::write_encrypted_file(encoded_vault.as_ref(), &path_clone, &key, &vault.metadata, file::DEFAULT_BACKUP_DEPTH)
+        }).await.unwrap_or_else(|e| Err(FileError::Io(io::Error::other(e))));
 
+        let created = result.is_ok();
         match result {
-            Ok(()) => show_dialog(
-                "Vault Created".into(),
+            Ok(()) => MainWindowHandler::show_message(
+                "Vault Created",
                 format!("Vault has been saved at {}", path.display())
             ),
-            Err(e) => show_dialog(
-                "Error".into(),
-                if cfg!(debug_assertions) { e.into() }
-                else { "Failed to create vault file.".into() }
-            )
+            Err(e) => MainWindowHandler::show_message("Error", Self::create_error_message(&e)),
+        };
+
+        created
+    }
+
+    /// Message for a vault file that couldn't be created. Details are only shown where they
+    /// help the user, or in debug builds.
+    fn create_error_message(e: &FileError) -> String {
+        match (e, e.io_error().map(io::Error::kind)) {
+            (_, Some(io::ErrorKind::PermissionDenied)) =>
+                "You don't have permission to save a vault in this folder.".into(),
+            (_, Some(io::ErrorKind::NotFound)) => "The folder for the vault file doesn't exist.".into(),
+            (_, Some(io::ErrorKind::StorageFull)) => "There isn't enough disk space to save the vault.".into(),
+            (FileError::VerificationFailed { .. }, _) =>
+                "The vault file couldn't be read back after saving. The disk may be failing.".into(),
+            (FileError::ParentNotFound(folder), _) => format!("The folder {} doesn't exist.", folder.display()),
+            _ if cfg!(debug_assertions) => e.to_string(),
+            _ => "Failed to create vault file.".into(),
+        }
+    }
+
+    /// Completes the chosen path with the vault extension and, if a file is already there, asks
+    /// before replacing it. Returns the path to create the vault at, or None if the user declined.
+    async fn confirm_target(path: PathBuf) -> Option<PathBuf> {
+        let path = Self::with_vault_extension(path);
+
+        let (title, description) = match Self::existing_target(&path) {
+            ExistingTarget::Nothing => return Some(path),
+            ExistingTarget::Vault => ("Replace Vault", format!(
+                "{} is an existing NoPass vault.\n\n\
+                Creating a new vault here replaces it with an empty one, and every item in it will be lost. \
+                This can't be undone. Replace the vault?",
+                path.display()
+            )),
+            ExistingTarget::OtherFile => ("Replace File", format!("{} already exists. Replace it?", path.display())),
         };
+
+        let answer = DialogWindowHandler::show_message(title, &description, DialogButtons::YesNo).await;
+        (answer == DialogResult::Yes).then_some(path)
+    }
+
+    /// `path` with the vault extension added if the user typed a bare name
+    fn with_vault_extension(mut path: PathBuf) -> PathBuf {
+        if path.extension().is_none() {
+            path.set_extension(VAULT_EXTENSION);
+        }
+        path
+    }
+
+    fn existing_target(path: &Path) -> ExistingTarget {
+        if !path.exists() {
+            ExistingTarget::Nothing
+        } else if file::has_vault_magic(path) {
+            ExistingTarget::Vault
+        } else {
+            ExistingTarget::OtherFile
+        }
     }
 
     /// Opens a save file dialog and returns the user-selected path (if any).
     fn save_file_dialog() -> Option<PathBuf> {
-        let handle = std::thread::spawn(move || {
-            rfd::FileDialog::new()
-                .set_title("Select Vault Location")
-                .add_filter("Vault Files", &["vault"])
-                .set_file_name("passwords.vault")
-                .save_file()
-        });
+        let dialog = rfd::FileDialog::new()
+            .set_title("Select Vault Location")
+            .add_filter("Vault Files", &["vault"])
+            .set_file_name("passwords.vault");
 
-        handle.join().ok()?
+        show_file_dialog(dialog, DialogAction::Save, DialogContext::SaveVault)
     }
 }
 
@@ -139,4 +349,63 @@ impl WindowHandler for CreateVaultWindowHandler {
             *visible = value;
         }
     }
-}
\ No newline at end of file
+
+    fn modal_slot(&self) -> Option<ModalSlot> {
+        Some(self.modal.clone())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_error_message_clears_on_retry() {
+        let first = CreateVaultWindowHandler::password_error_message("kitten12&Co", "kitten12");
+        assert_eq!(first, PasswordError::Mismatch.to_string());
+
+        let retry = CreateVaultWindowHandler::password_error_message("kitten12&Co", "kitten12&Co");
+        assert!(retry.is_empty());
+    }
+
+    #[test]
+    fn test_new_vault_needs_strong_password() {
+        let check = |password: &[u8]| {
+            let mut bytes = ZeroByte::default();
+            bytes.extend_from_slice(password);
+            CreateVaultWindowHandler::check_new_password(&bytes)
+        };
+
+        assert_eq!(check(b""), Err(PasswordError::TooShort { min: password_strength::MIN_PASSWORD_LEN }));
+        assert_eq!(check(b"k1t&Co"), Err(PasswordError::TooShort { min: password_strength::MIN_PASSWORD_LEN }));
+        assert_eq!(check(b"password"), Err(PasswordError::TooWeak));
+        assert_eq!(check(b"kitten12&Co"), Ok(()));
+        assert_eq!(check(&[0xFF; 12]), Err(PasswordError::TooWeak), "Bytes that aren't text can't be scored");
+    }
+
+    #[test]
+    fn test_bare_name_gets_vault_extension() {
+        let with_extension = |path: &str| CreateVaultWindowHandler::with_vault_extension(PathBuf::from(path));
+
+        assert_eq!(with_extension("/home/me/passwords"), PathBuf::from("/home/me/passwords.vault"));
+        assert_eq!(with_extension("/home/me/passwords.vault"), PathBuf::from("/home/me/passwords.vault"));
+        assert_eq!(with_extension("/home/me/passwords.kdbx"), PathBuf::from("/home/me/passwords.kdbx"), "A chosen extension is kept");
+    }
+
+    #[test]
+    fn test_existing_vault_is_told_apart_from_other_files() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let (vault, other) = (dir.path().join("work.vault"), dir.path().join("notes.vault"));
+
+        let params = ArgonParams { memory_cost: 64, time_cost: 1, parallelism: 1, ..ArgonParams::default() };
+        let key = Crypto::derive_argon_key(b"password", None, params).expect("Key derivation failed");
+        file::write_encrypted_file(b"vault", &vault, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        fs::write(&other, b"shopping list").expect("Failed to write");
+
+        assert_eq!(CreateVaultWindowHandler::existing_target(&vault), ExistingTarget::Vault);
+        assert_eq!(CreateVaultWindowHandler::existing_target(&other), ExistingTarget::OtherFile);
+        assert_eq!(CreateVaultWindowHandler::existing_target(&dir.path().join("new.vault")), ExistingTarget::Nothing);
+    }
+}
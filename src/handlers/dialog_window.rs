@@ -1,39 +1,163 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use slint::{ComponentHandle, Weak};
+use tokio::sync::oneshot;
 
 use crate::DialogWindow;
-use crate::errors::ui_errors::UiError;
-use crate::handlers::WindowHandler;
+use crate::errors::ui_errors::{UiError, UiResult};
+use crate::handlers::{follow_focus, WindowHandler};
 
 
-pub(crate) struct _DialogWindowHandler {
+/// Buttons offered by a dialog
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DialogButtons {
+    Ok,
+    YesNo,
+    #[allow(dead_code)]  // No dialog asks to confirm with OK yet
+    OkCancel,
+    /// Save, Cancel, and Discard set apart from the other two
+    SaveDiscardCancel,
+    /// Buttons with their own labels: primary, secondary, and one set apart from the other two.
+    /// Answered with `DialogResult::Custom` of the label pressed.
+    Custom(&'static str, &'static str, &'static str),
+}
+
+/// Button the user answered a dialog with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DialogResult {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+    Save,
+    Discard,
+    /// Label of the `DialogButtons::Custom` button pressed
+    Custom(&'static str),
+}
+
+impl DialogButtons {
+    /// Label and answer of the primary button, and of the secondary one if there is one
+    fn choices(self) -> ((&'static str, DialogResult), Option<(&'static str, DialogResult)>) {
+        match self {
+            Self::Ok => (("OK", DialogResult::Ok), None),
+            Self::YesNo => (("Yes", DialogResult::Yes), Some(("No", DialogResult::No))),
+            Self::OkCancel => (("OK", DialogResult::Ok), Some(("Cancel", DialogResult::Cancel))),
+            Self::SaveDiscardCancel => (("Save", DialogResult::Save), Some(("Cancel", DialogResult::Cancel))),
+            Self::Custom(primary, secondary, _) => ((primary, DialogResult::Custom(primary)), Some((secondary, DialogResult::Custom(secondary)))),
+        }
+    }
+
+    /// Label and answer of a third button, shown apart from the other two
+    fn extra_choice(self) -> Option<(&'static str, DialogResult)> {
+        match self {
+            Self::SaveDiscardCancel => Some(("Discard", DialogResult::Discard)),
+            Self::Custom(_, _, extra) => Some((extra, DialogResult::Custom(extra))),
+            Self::Ok | Self::YesNo | Self::OkCancel => None,
+        }
+    }
+
+    /// Answer when the dialog is closed without pressing a button, never the one that goes ahead
+    fn dismissed(self) -> DialogResult {
+        match self {
+            Self::Ok => DialogResult::Ok,
+            Self::YesNo => DialogResult::No,
+            Self::OkCancel | Self::SaveDiscardCancel | Self::Custom(..) => DialogResult::Cancel,
+        }
+    }
+}
+
+/// Hands the user's answer to the `show_message` waiting for it. Only the first answer counts,
+/// e.g. a button pressed while the window is being closed.
+#[derive(Clone)]
+struct DialogReply(Rc<RefCell<Option<oneshot::Sender<DialogResult>>>>);
+
+impl DialogReply {
+    fn new(sender: oneshot::Sender<DialogResult>) -> Self {
+        Self(Rc::new(RefCell::new(Some(sender))))
+    }
+
+    fn send(&self, result: DialogResult) {
+        if let Some(sender) = self.0.borrow_mut().take() {
+            // The receiver is only gone if show_message was dropped, then nobody wants the answer
+            let _ = sender.send(result);
+        }
+    }
+}
+
+/// Coordinates a DialogWindow showing a message with a set of buttons.
+/// Holds ownership to prevent premature drop and supports weak upgrade for event binding.
+pub(crate) struct DialogWindowHandler {
     _window_strong: DialogWindow,
     window: Weak<DialogWindow>,
     visible: Arc<Mutex<bool>>,
+    buttons: DialogButtons,
+    reply: DialogReply,
 }
 
-impl _DialogWindowHandler {
-    pub(crate) fn _new() -> Result<Self, UiError> {
-        let window = DialogWindow::new().map_err(|_| UiError::_WindowCreation("Failed to create dialog window".into()))?;
-        let weak = window.as_weak();
-        let handler = Self {
-            _window_strong: window,
-            window: weak,
-            visible: Arc::new(Mutex::new(false)),
+impl DialogWindowHandler {
+    /// Shows a dialog and waits for the user to answer it, without blocking the event loop.
+    /// A dialog that can't be shown is logged and answered as if it was closed.
+    pub(crate) async fn show_message(title: &str, body: &str, buttons: DialogButtons) -> DialogResult {
+        let (sender, receiver) = oneshot::channel();
+
+        let mut handler = match Self::new(title, body, buttons, DialogReply::new(sender)) {
+            Ok(handler) => handler,
+            Err(e) => {
+                log::error!("Failed to create dialog '{}': {}", title, e);
+                return buttons.dismissed();
+            },
         };
+        if let Err(e) = handler.show() {
+            log::error!("Failed to show dialog '{}': {}", title, e);
+            return buttons.dismissed();
+        }
 
-        //Self::setup(&handler);
-        Ok(handler)
+        let result = receiver.await.unwrap_or(buttons.dismissed());
+        if let Err(e) = handler.hide() {
+            log::error!("Failed to hide dialog: {}", e);
+        }
+
+        result
     }
 
-    // TODO: Setup for dialog box
-    fn _setup(&self) {
-        todo!()
+    fn new(title: &str, body: &str, buttons: DialogButtons, reply: DialogReply) -> UiResult<Self> {
+        let window = DialogWindow::new().map_err(|e| UiError::WindowCreation(e.to_string()))?;
+        window.set_win_title(title.into());
+        window.set_message(body.into());
+
+        let ((primary_text, primary), secondary) = buttons.choices();
+        window.set_primary_text(primary_text.into());
+        window.set_secondary_text(secondary.map(|(text, _)| text).unwrap_or_default().into());
+
+        let reply_primary = reply.clone();
+        window.on_primary_clicked(move || reply_primary.send(primary));
+
+        if let Some((_, secondary)) = secondary {
+            let reply_secondary = reply.clone();
+            window.on_secondary_clicked(move || reply_secondary.send(secondary));
+        }
+
+        let extra = buttons.extra_choice();
+        window.set_tertiary_text(extra.map(|(text, _)| text).unwrap_or_default().into());
+        if let Some((_, extra)) = extra {
+            let reply_tertiary = reply.clone();
+            window.on_tertiary_clicked(move || reply_tertiary.send(extra));
+        }
+
+        let weak = window.as_weak();
+        Ok(Self {
+            _window_strong: window,
+            window: weak,
+            visible: Arc::new(Mutex::new(false)),
+            buttons,
+            reply,
+        })
     }
 }
 
-impl WindowHandler for _DialogWindowHandler {
+impl WindowHandler for DialogWindowHandler {
     type Component = DialogWindow;
 
     fn get_window(&self) -> Weak<Self::Component> {
@@ -57,4 +181,75 @@ impl WindowHandler for _DialogWindowHandler {
             *visible = value;
         }
     }
-}
\ No newline at end of file
+
+    /// Closing the window answers the dialog as dismissed
+    fn initialize(&mut self) -> UiResult<()> {
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        let visible = self.get_visible_arc();
+        let reply = self.reply.clone();
+        let dismissed = self.buttons.dismissed();
+        follow_focus(window.window());
+
+        window.window().on_close_requested(move || {
+            if let Ok(mut visible) = visible.lock() {
+                *visible = false;
+            }
+            reply.send(dismissed);
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buttons_map_to_their_answers() {
+        assert_eq!(DialogButtons::Ok.choices(), (("OK", DialogResult::Ok), None));
+        assert_eq!(DialogButtons::YesNo.choices(), (("Yes", DialogResult::Yes), Some(("No", DialogResult::No))));
+        assert_eq!(DialogButtons::OkCancel.choices(), (("OK", DialogResult::Ok), Some(("Cancel", DialogResult::Cancel))));
+        assert_eq!(DialogButtons::SaveDiscardCancel.choices(), (("Save", DialogResult::Save), Some(("Cancel", DialogResult::Cancel))));
+
+        assert_eq!(DialogButtons::SaveDiscardCancel.extra_choice(), Some(("Discard", DialogResult::Discard)));
+        assert_eq!(DialogButtons::YesNo.extra_choice(), None);
+
+        let custom = DialogButtons::Custom("Overwrite", "Save as copy", "Reload");
+        assert_eq!(custom.choices(), (("Overwrite", DialogResult::Custom("Overwrite")), Some(("Save as copy", DialogResult::Custom("Save as copy")))));
+        assert_eq!(custom.extra_choice(), Some(("Reload", DialogResult::Custom("Reload"))));
+    }
+
+    #[test]
+    fn test_closing_never_answers_yes() {
+        assert_eq!(DialogButtons::Ok.dismissed(), DialogResult::Ok);
+        assert_eq!(DialogButtons::YesNo.dismissed(), DialogResult::No);
+        assert_eq!(DialogButtons::OkCancel.dismissed(), DialogResult::Cancel);
+        assert_eq!(DialogButtons::SaveDiscardCancel.dismissed(), DialogResult::Cancel, "Closing must keep unsaved edits");
+        assert_eq!(DialogButtons::Custom("A", "B", "C").dismissed(), DialogResult::Cancel);
+    }
+
+    #[test]
+    fn test_only_the_first_answer_is_sent() {
+        let (sender, mut receiver) = oneshot::channel();
+        let reply = DialogReply::new(sender);
+
+        reply.clone().send(DialogResult::Yes);
+        reply.send(DialogResult::No);
+
+        assert_eq!(receiver.try_recv(), Ok(DialogResult::Yes));
+    }
+
+    #[test]
+    fn test_dropped_reply_reads_as_closed() {
+        let (sender, receiver) = oneshot::channel::<DialogResult>();
+        drop(DialogReply::new(sender));
+
+        let result = tokio::runtime::Builder::new_current_thread().build().expect("Failed to build runtime")
+            .block_on(receiver)
+            .unwrap_or(DialogButtons::YesNo.dismissed());
+        assert_eq!(result, DialogResult::No);
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use crate::handlers::main_window::SETTINGS;
+use crate::models::settings::{DialogContext, Settings};
+use crate::utils::config;
+
+
+/// Whether the file picker chooses an existing file or a file to write
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DialogAction {
+    Open,
+    Save,
+}
+
+/// Shows `dialog` on its own thread, starting in the directory last used for `context`, and
+/// returns the chosen path. The directory of a chosen file is remembered for the next picker
+/// of the same context; a failed settings save only costs that, so it is logged.
+pub(crate) fn show_file_dialog(dialog: rfd::FileDialog, action: DialogAction, context: DialogContext) -> Option<PathBuf> {
+    let start_dir = SETTINGS.lock().ok().and_then(|settings| start_directory(&settings, context));
+    let dialog = match start_dir {
+        Some(dir) => dialog.set_directory(dir),
+        None => dialog,
+    };
+
+    let handle = std::thread::spawn(move || match action {
+        DialogAction::Open => dialog.pick_file(),
+        DialogAction::Save => dialog.save_file(),
+    });
+    let path = handle.join().ok().flatten()?;
+
+    if let Ok(mut settings) = SETTINGS.lock()
+        && settings.remember_directory(context, &path)
+        && let Err(e) = config::save(&settings) {
+        log::warn!("Failed to save settings: {}", e);
+    }
+
+    Some(path)
+}
+
+/// Remembered directory for `context`, unless it has since been removed
+fn start_directory(settings: &Settings, context: DialogContext) -> Option<PathBuf> {
+    settings.last_directory(context).filter(|dir| dir.is_dir()).map(PathBuf::from)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removed_directory_is_not_used() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let mut settings = Settings::default();
+        settings.remember_directory(DialogContext::OpenVault, &dir.path().join("work.vault"));
+        assert_eq!(start_directory(&settings, DialogContext::OpenVault), Some(dir.path().to_path_buf()));
+
+        let removed = dir.path().to_path_buf();
+        drop(dir);
+        assert!(!removed.exists());
+        assert_eq!(start_directory(&settings, DialogContext::OpenVault), None);
+    }
+}
@@ -1,24 +1,183 @@
-use std::path::PathBuf;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::str::FromStr;
-use std::sync::{Arc};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
 
-use bincode::config::standard;
-use bincode::serde::{encode_to_vec, decode_from_slice};
 use slint::{ComponentHandle, SharedString, Weak};
-use slint::{VecModel, ModelRc};
+use slint::{Color, Image, Model, ModelRc, Timer, TimerMode, VecModel};
+use slint::winit_030::{winit::event::WindowEvent, WinitWindowAccessor, WinitWindowEventResult};
+use zeroize::Zeroize;
 
+use crate::errors::app_errors::AppError;
+use crate::errors::export_errors::ExportError;
+use crate::errors::file_errors::{FileError, OpenVaultError};
+use crate::errors::ui_errors::{UiError, UiResult};
+use crate::errors::vault_lock_errors::VaultLockError;
 use crate::handlers::create_vault_window::CreateVaultWindowHandler;
+use crate::handlers::password_generator_window::PasswordGeneratorWindowHandler;
+use crate::handlers::dialog_window::{DialogButtons, DialogResult, DialogWindowHandler};
+use crate::handlers::file_dialog::{show_file_dialog, DialogAction};
+use crate::handlers::vault_state::VaultState;
+use crate::handlers::verify_vault_window::VerifyVaultWindowHandler;
+use crate::handlers::view_state::ViewState;
 use crate::handlers::WindowHandler;
-use crate::models::vault::{Item, Vault};
-use crate::utils::file::{self, read_encrypted_file};
-use crate::{utils, MainWindow, MainWindowItem, VaultItem};
+use crate::models::appearance::{self, VaultIcon, ACCENT_PALETTE};
+use crate::models::settings::{DialogContext, Settings, WindowGeometry};
+use crate::models::vault::{sort_items, Item, SortOrder, Vault, VaultHealthReport};
+use crate::utils::auto_lock::{self, AutoLock, LockReason};
+use crate::utils::clipboard::{self, ClearCountdown};
+use crate::utils::config;
+use crate::utils::export::PlaintextFormat;
+use crate::utils::crypto::ArgonKey;
+use crate::utils::file_watch::{FileWatcher, WATCH_INTERVAL};
+use crate::utils::file::{self, read_encrypted_file, FileFingerprint, GuardedWrite, VaultFormat, VaultMetadata};
+use crate::utils::password_strength;
+use crate::utils::query::Query;
+use crate::utils::snapshot;
+use crate::utils::tempsec;
+use crate::utils::vault_lock::VaultLock;
+use crate::utils::zero_byte::ZeroByte;
+use crate::{utils, BackupEntry, MainWindow, MainWindowItem, Page, VaultItem};
+use crate::VaultHealthReport as UiHealthReport;
 
 
-/// Global static vault data, shared between handlers.
-static GLOBAL_VAULT: Lazy<Mutex<Option<Vault>>> = Lazy::new(|| Mutex::new(None));
+/// Page the main window is showing and the vault session it belongs to
+static VIEW_STATE: Lazy<Mutex<ViewState>> = Lazy::new(|| Mutex::new(ViewState::default()));
+
+/// Set while an unlock is deriving the key and decrypting the vault
+static UNLOCK_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Set while a save is being written in the background
+static SAVE_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Set when a save was requested, cleared once the background save picks it up
+static SAVE_QUEUED: AtomicBool = AtomicBool::new(false);
+
+/// File lock on the open vault. None while locked, or when the vault was opened
+/// read-only because another window holds the lock.
+static VAULT_LOCK: Mutex<Option<VaultLock>> = Mutex::new(None);
+
+/// Watches the open vault's file for changes made by other programs, None while locked
+static VAULT_WATCHER: Mutex<Option<FileWatcher>> = Mutex::new(None);
+
+/// Counts down to locking the open vault while there is no user input
+static AUTO_LOCK: Mutex<AutoLock> = Mutex::new(AutoLock::new());
+
+thread_local! {
+    /// Polls `AUTO_LOCK` while a vault is unlocked. Slint timers live on the UI thread.
+    static AUTO_LOCK_TIMER: Timer = Timer::default();
+
+    /// Counts down until the last copied secret is cleared from the clipboard
+    static CLIPBOARD_COUNTDOWN: RefCell<ClearCountdown> = const { RefCell::new(ClearCountdown::new()) };
+    static CLIPBOARD_TIMER: Timer = Timer::default();
+
+    /// Child windows currently holding a `ModalGuard` on the main window
+    static MODAL_WINDOWS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// App settings, loaded by `main` and handed over in `MainWindowHandler::new`
+pub(super) static SETTINGS: Lazy<Mutex<Settings>> = Lazy::new(|| Mutex::new(Settings::default()));
+
+/// Failed unlock attempts and the resulting lockout, shared by all unlock requests
+static UNLOCK_THROTTLER: Mutex<UnlockThrottler> = Mutex::new(UnlockThrottler::new());
+
+/// Blocking vault write, `file::write_if_unchanged` outside of tests
+type VaultWriter = fn(&[u8], &Path, &ArgonKey, &VaultMetadata, usize, Option<&FileFingerprint>) -> Result<GuardedWrite, FileError>;
+
+/// How long a failed copy is pointed out for
+const CLIPBOARD_FAILED_SHOWN_FOR: Duration = Duration::from_secs(4);
+
+const UNLOCK_BASE_DELAY: Duration = Duration::from_millis(500);
+const UNLOCK_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Exponential backoff for failed unlock attempts: after the nth failure further
+/// attempts are refused for `min(2^n * 500ms, 5min)`.
+#[derive(Debug)]
+struct UnlockThrottler {
+    attempts: u32,
+    locked_until: Option<Instant>,
+}
+
+impl UnlockThrottler {
+    const fn new() -> Self {
+        Self { attempts: 0, locked_until: None }
+    }
+
+    /// Restores a lockout saved by a previous session as a Unix timestamp
+    #[allow(dead_code)]  // Persisted through the settings file once it exists
+    fn restore(attempts: u32, locked_until_unix: Option<u64>, now: Instant, now_unix: u64) -> Self {
+        let locked_until = locked_until_unix
+            .filter(|&until| until > now_unix)
+            .map(|until| now + Duration::from_secs(until - now_unix));
+
+        Self { attempts, locked_until }
+    }
+
+    /// Lockout end as a Unix timestamp for persisting across sessions
+    #[allow(dead_code)]  // Persisted through the settings file once it exists
+    fn locked_until_unix(&self, now: Instant, now_unix: u64) -> Option<u64> {
+        self.remaining(now).map(|remaining| now_unix + remaining.as_secs_f64().ceil() as u64)
+    }
+
+    /// Time left before the next attempt is allowed
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.locked_until
+            .filter(|&until| until > now)
+            .map(|until| until - now)
+    }
+
+    /// Counts a failed attempt and returns the new lockout delay
+    fn record_failure(&mut self, now: Instant) -> Duration {
+        self.attempts = self.attempts.saturating_add(1);
+
+        let delay = 2u32.checked_pow(self.attempts)
+            .and_then(|factor| UNLOCK_BASE_DELAY.checked_mul(factor))
+            .map_or(UNLOCK_MAX_DELAY, |delay| delay.min(UNLOCK_MAX_DELAY));
+
+        self.locked_until = Some(now + delay);
+        delay
+    }
+
+    fn record_success(&mut self) {
+        self.attempts = 0;
+        self.locked_until = None;
+    }
+}
+
+/// Marks an operation as in flight until dropped, so a second request can't start alongside it
+struct InProgressGuard<'a> {
+    flag: &'a AtomicBool,
+}
+
+impl<'a> InProgressGuard<'a> {
+    fn try_acquire(flag: &'a AtomicBool) -> Option<Self> {
+        flag.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| Self { flag })
+    }
+}
+
+impl Drop for InProgressGuard<'_> {
+    fn drop(&mut self) {
+        self.flag.store(false, Ordering::Release);
+    }
+}
+
+/// What to do with edits to the selected item that weren't saved before moving on
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UnsavedChoice {
+    Save,
+    Discard,
+    Cancel,  // Keep editing
+}
 
 /// Coordinates the MainWindow lifecycle and UI behavior.
 /// Holds ownership to prevent premature drop and supports weak upgrade for event binding.
@@ -26,18 +185,32 @@ pub(crate) struct MainWindowHandler {
     _window_strong: MainWindow,  // Keeps the actual window alive with struct
     window: Weak<MainWindow>,
     visible: Arc<Mutex<bool>>,
+    state: VaultState,
 }
 
 impl MainWindowHandler {
-    /// Creates a new `MainWindowHandler` and sets up window behavior.
+    /// Creates a new `MainWindowHandler` with no vault open and sets up window behavior.
     /// Panics on window creation failure (app can't continue without it).
-    pub(crate) async fn new() -> Self {
+    pub(crate) async fn new(settings: Settings) -> Self {
+        Self::new_with_state(VaultState::default(), settings).await
+    }
+
+    /// Creates a new `MainWindowHandler` working on the given vault state
+    pub(crate) async fn new_with_state(state: VaultState, settings: Settings) -> Self {
         let window = MainWindow::new().expect("Failed to create new MainWindow");
+        if let Some(geometry) = settings.window_geometry {
+            Self::restore_geometry(&window, geometry);
+        }
+        file::set_compression_enabled(settings.compression_enabled);
+        window.set_sort_order(settings.sort_order.id());
+        *SETTINGS.lock().unwrap() = settings;
+
         let weak = window.as_weak();
         let handler = Self {
             _window_strong: window,
             window: weak,
             visible: Arc::new(Mutex::new(false)),
+            state,
         };
 
         Self::setup(&handler).await;
@@ -48,6 +221,17 @@ impl MainWindowHandler {
         let window = handler.get_window().upgrade().unwrap();  
         let window_weak = window.as_weak();
         
+        // Any keyboard or pointer input counts as activity for the auto-lock
+        window.window().on_winit_window_event(|_, event| {
+            if Self::is_user_input(event) {
+                Self::note_activity();
+            }
+            if let WindowEvent::Focused(focused) = event {
+                Self::note_focus(*focused);
+            }
+            WinitWindowEventResult::Propagate
+        });
+
         // This must be declared outside of the event handler to prevent creating a new window handler each time
         let create_vault_window_handler = CreateVaultWindowHandler::new().await;
 
@@ -57,6 +241,32 @@ impl MainWindowHandler {
             Self::open_create_vault_window(&window_weak_create, &create_vault_window_handler);
         });
 
+        // Verify a vault file without opening it
+        let verify_vault_window_handler = VerifyVaultWindowHandler::new().await;
+        window.on_open_verify_vault(move || {
+            if let Ok(mut handler) = verify_vault_window_handler.lock()
+                && !handler.get_visible()
+                && let Err(e) = handler.show() {
+                log::error!("Failed to show window: {}", e);
+            }
+        });
+
+        // Generate a password, optionally for the selected item
+        let window_weak_generated = window_weak.clone();
+        let state_generated = handler.state.clone();
+        let password_generator_window_handler = PasswordGeneratorWindowHandler::new(Box::new(move |password: &ZeroByte| {
+            let result = Self::use_generated_password(&window_weak_generated, &state_generated, password);
+            Self::report_error(&window_weak_generated, result);
+        })).await;
+        let window_weak_generator = window_weak.clone();
+        let state_generator = handler.state.clone();
+        window.on_open_password_generator(move || {
+            let can_use = Self::selected_item_is_editable(&window_weak_generator, &state_generator);
+            if let Ok(mut handler) = password_generator_window_handler.lock() {
+                handler.open(can_use);
+            }
+        });
+
         // Open unlock vault
         let window_weak_open = window_weak.clone();
         window.on_open_unlock_vault(move || {
@@ -65,281 +275,2565 @@ impl MainWindowHandler {
 
         // Unlock vault
         let window_weak_unlock = window_weak.clone();
+        let state_unlock = handler.state.clone();
         window.on_unlock_vault(move |location: SharedString, password: SharedString| {
-            Self::unlock_vault(&window_weak_unlock, location.to_string(), password.to_string());
+            let result = Self::unlock_vault(&window_weak_unlock, &state_unlock, location.to_string(), password.to_string());
+            Self::report_error(&window_weak_unlock, result);
+        });
+
+        // Cancel unlock, or pick a different file while locked
+        let window_weak_cancel = window_weak.clone();
+        window.on_cancel_unlock(move || {
+            Self::set_view_state(&window_weak_cancel.upgrade().unwrap(), ViewState::Setup);
+        });
+
+        // Lock vault
+        let window_weak_lock = window_weak.clone();
+        let state_lock = handler.state.clone();
+        window.on_lock_vault(move || {
+            let window = window_weak_lock.upgrade().unwrap();
+            let state = state_lock.clone();
+
+            slint::spawn_local(async move {
+                let result = Self::request_lock(&window, &state).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
         });
 
         // Load item
         let window_weak_load = window_weak.clone();
+        let state_load = handler.state.clone();
         window.on_load_selected_item(move |item_id: i32| {
-            Self::load_selected_item(&window_weak_load, item_id);
+            let window = window_weak_load.upgrade().unwrap();
+            let state = state_load.clone();
+
+            slint::spawn_local(async move {
+                let result = Self::select_item(&window, &state, item_id).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
+        });
+
+        // Days since an item was last edited
+        let state_age = handler.state.clone();
+        window.on_get_item_age_days(move |item_id: i32| {
+            Self::item_age_days(&state_age, item_id).unwrap_or_else(|e| {
+                log::error!("{}", e);
+                -1
+            })
         });
 
         // Save item
         let window_weak_save = window_weak.clone();
+        let state_save = handler.state.clone();
         window.on_save_selected_item(move |new_item: VaultItem| {
-            Self::save_selected_item(&window_weak_save, new_item);
+            let result = Self::save_selected_item(&window_weak_save, &state_save, new_item);
+            Self::report_error(&window_weak_save, result);
         });
 
         // Add item
         let window_weak_add = window_weak.clone();
+        let state_add = handler.state.clone();
         window.on_add_vault_item(move || {
-            Self::add_vault_item(&window_weak_add);
+            let window = window_weak_add.upgrade().unwrap();
+            let state = state_add.clone();
+
+            slint::spawn_local(async move {
+                let result = Self::add_vault_item(&window, &state).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
         });
 
         // Delete item
         let window_weak_delete = window_weak.clone();
+        let state_delete = handler.state.clone();
         window.on_delete_vault_item(move |item_id: i32| {
-            if item_id >= 0 {
-                Self::delete_vault_item(&window_weak_delete, item_id);
-            } 
+            if item_id < 0 {
+                return;
+            }
+            let window = window_weak_delete.upgrade().unwrap();
+            let state = state_delete.clone();
+
+            slint::spawn_local(async move {
+                let result = Self::delete_vault_item(&window, &state, item_id).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
+        });
+
+        // Delete all shown items
+        let window_weak_bulk_delete = window_weak.clone();
+        let state_bulk_delete = handler.state.clone();
+        window.on_delete_vault_items(move |ids: ModelRc<i32>| {
+            let window = window_weak_bulk_delete.upgrade().unwrap();
+            let state = state_bulk_delete.clone();
+            let ids = ids.iter().collect();
+
+            slint::spawn_local(async move {
+                let result = Self::delete_vault_items(&window, &state, ids).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
+        });
+
+        // Open trash
+        let window_weak_trash = window_weak.clone();
+        let state_trash = handler.state.clone();
+        window.on_open_trash(move || {
+            let result = Self::update_trash_items(&window_weak_trash.upgrade().unwrap(), &state_trash);
+            Self::report_error(&window_weak_trash, result);
+        });
+
+        // Restore item from trash
+        let window_weak_restore = window_weak.clone();
+        let state_restore = handler.state.clone();
+        window.on_restore_vault_item(move |item_id: i32| {
+            let result = Self::restore_vault_item(&window_weak_restore, &state_restore, item_id);
+            Self::report_error(&window_weak_restore, result);
+        });
+
+        // Permanently delete item from trash
+        let window_weak_purge = window_weak.clone();
+        let state_purge = handler.state.clone();
+        window.on_permanently_delete_vault_item(move |item_id: i32| {
+            let result = Self::permanently_delete_vault_item(&window_weak_purge, &state_purge, item_id);
+            Self::report_error(&window_weak_purge, result);
+        });
+
+        // Empty trash
+        let window_weak_empty = window_weak.clone();
+        let state_empty = handler.state.clone();
+        window.on_empty_trash(move || {
+            let result = Self::empty_trash(&window_weak_empty, &state_empty);
+            Self::report_error(&window_weak_empty, result);
+        });
+
+        // Toggle favorite
+        let window_weak_favorite = window_weak.clone();
+        let state_favorite = handler.state.clone();
+        window.on_toggle_favorite(move |item_id: i32| {
+            let result = Self::toggle_favorite(&window_weak_favorite, &state_favorite, item_id);
+            Self::report_error(&window_weak_favorite, result);
+        });
+
+        // Show favorites only
+        let window_weak_favorites = window_weak.clone();
+        let state_favorites = handler.state.clone();
+        window.on_show_favorites_only(move |enabled: bool| {
+            let result = Self::show_favorites_only(&window_weak_favorites, &state_favorites, enabled);
+            Self::report_error(&window_weak_favorites, result);
+        });
+
+        let window_weak_sort = window_weak.clone();
+        let state_sort = handler.state.clone();
+        window.on_set_sort_order(move |order: i32| {
+            let result = Self::set_sort_order(&window_weak_sort, &state_sort, order);
+            Self::report_error(&window_weak_sort, result);
+        });
+
+        let window_weak_health = window_weak.clone();
+        let state_health = handler.state.clone();
+        window.on_get_health_report(move || {
+            let result = Self::show_health_report(&window_weak_health, &state_health);
+            Self::report_error(&window_weak_health, result);
+        });
+
+        // Browse and open daily snapshots
+        let window_weak_backups = window_weak.clone();
+        window.on_browse_backups(move || {
+            let result = Self::browse_backups(&window_weak_backups);
+            Self::report_error(&window_weak_backups, result);
+        });
+
+        let window_weak_open_backup = window_weak.clone();
+        window.on_open_backup(move |path: SharedString| {
+            Self::show_unlock_page(&window_weak_open_backup, PathBuf::from(path.as_str()));
+        });
+
+        let window_weak_close_backups = window_weak.clone();
+        window.on_close_backups(move || {
+            let view_state = VIEW_STATE.lock().unwrap().clone();
+            Self::set_view_state(&window_weak_close_backups.upgrade().unwrap(), view_state);
+        });
+
+        // Export an encrypted copy under its own passphrase
+        let window_weak_validate_export = window_weak.clone();
+        window.on_validate_export_passphrase(move |passphrase: SharedString, confirm: SharedString| {
+            let message: SharedString = match password_strength::validate_new_password(&passphrase, &confirm) {
+                Ok(()) => SharedString::new(),
+                Err(e) => e.to_string().into(),
+            };
+            if let Some(window) = window_weak_validate_export.upgrade() {
+                window.set_export_error(message.clone());
+            }
+            message.is_empty()
+        });
+
+        let window_weak_export = window_weak.clone();
+        let state_export = handler.state.clone();
+        window.on_export_vault(move |passphrase: SharedString| {
+            let window = window_weak_export.upgrade().unwrap();
+            let state = state_export.clone();
+            let mut passphrase_bytes = ZeroByte::default();
+            passphrase_bytes.extend_from_slice(passphrase.as_bytes());
+
+            slint::spawn_local(async move {
+                let result = Self::export_vault(&window, &state, passphrase_bytes).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
+        });
+
+        let window_weak_export_plaintext = window_weak.clone();
+        let state_export_plaintext = handler.state.clone();
+        window.on_export_plaintext(move |format: SharedString, password: SharedString| {
+            let window = window_weak_export_plaintext.upgrade().unwrap();
+            let state = state_export_plaintext.clone();
+            let Some(format) = PlaintextFormat::from_id(&format) else {
+                log::error!("Unknown export format '{}'", format);
+                return;
+            };
+            let mut password_bytes = ZeroByte::default();
+            password_bytes.extend_from_slice(password.as_bytes());
+
+            slint::spawn_local(async move {
+                let result = Self::export_plaintext(&window, &state, format, password_bytes).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
+        });
+
+        // Search items
+        let window_weak_search = window_weak.clone();
+        let state_search = handler.state.clone();
+        window.on_search_changed(move || {
+            let result = Self::update_vault_items(&window_weak_search.upgrade().unwrap(), &state_search);
+            Self::report_error(&window_weak_search, result);
+        });
+
+        // Vault appearance choices
+        let palette: Vec<Color> = ACCENT_PALETTE.iter().map(|accent| Self::accent_color(accent)).collect();
+        let icons: Vec<Image> = VaultIcon::ALL.iter().map(|&icon| Self::icon_image(icon)).collect();
+        window.set_accent_palette(ModelRc::new(VecModel::from(palette)));
+        window.set_vault_icons(ModelRc::new(VecModel::from(icons)));
+
+        // Change vault accent color
+        let window_weak_accent = window_weak.clone();
+        let state_accent = handler.state.clone();
+        window.on_set_vault_accent(move |index: i32| {
+            let result = Self::set_vault_accent(&window_weak_accent, &state_accent, index);
+            Self::report_error(&window_weak_accent, result);
+        });
+
+        // Change vault icon
+        let window_weak_icon = window_weak.clone();
+        let state_icon = handler.state.clone();
+        window.on_set_vault_icon(move |index: i32| {
+            let result = Self::set_vault_icon(&window_weak_icon, &state_icon, index);
+            Self::report_error(&window_weak_icon, result);
+        });
+
+        // Reload the vault after it changed on disk
+        let window_weak_reload = window_weak.clone();
+        let state_reload = handler.state.clone();
+        window.on_reload_vault(move || {
+            let window = window_weak_reload.upgrade().unwrap();
+            let state = state_reload.clone();
+
+            slint::spawn_local(async move {
+                let path = PathBuf::from(window.get_vault_location().as_str());
+                let result = Self::reload_vault(&window, &state, &path).await;
+                Self::report_error(&window.as_weak(), result);
+            }).ok();
+        });
+
+        // Keep the open vault despite the change on disk
+        let window_weak_dismiss = window_weak.clone();
+        window.on_dismiss_vault_change(move || {
+            Self::dismiss_vault_change(&window_weak_dismiss);
         });
 
         // Copy to clipboard
+        let window_weak_copy = window.as_weak();
         window.on_copy_to_clipboard(move |text: SharedString| {
-            utils::copy_text_to_clipboard(text.to_string());
+            let result = Self::copy_to_clipboard(&window_weak_copy, &text);
+            Self::report_error(&window_weak_copy, result);
         });
     }
 
-    /// Removed a vault item by ID and updates UI and state
-    fn delete_vault_item(window: &Weak<MainWindow>, item_id: i32) {
-        {
-            let mut vault_guard = GLOBAL_VAULT.lock().unwrap();
-            if let Some(vault) = &mut *vault_guard {
-                if let Some(pos) = vault.items.iter().position(|item| item.id == item_id) {
-                    vault.items.remove(pos);
-                }
-            }
+    /// Logs an error from a vault operation and tells the user about it
+    fn handle_app_error(window: &MainWindow, e: AppError) {
+        log::error!("{}", e);
+
+        let message = match &e {
+            AppError::Clipboard(_) => {
+                // Not worth a dialog, the user can just try again
+                Self::show_clipboard_failed(window);
+                return;
+            },
+            AppError::IoError(_) => "Failed to save vault.".to_string(),
+            AppError::FileConflict => "The vault file was changed by another program. Your changes were not saved.".to_string(),
+            AppError::PoisedState => {
+                // Vault state may be half updated, don't allow further edits on top of it
+                window.set_disable_input(true);
+                "Internal error, please restart NoPass.".to_string()
+            },
+            AppError::Generic(msg) =>
+                if cfg!(debug_assertions) { msg.clone() }
+                else { "Something went wrong.".to_string() },
+        };
+
+        let message =
+            if cfg!(debug_assertions) && !matches!(e, AppError::Generic(_)) { format!("{}\n\n{}", message, e) }
+            else { message };
+
+        Self::show_message("Error", message);
+    }
+
+    /// Reports the error of a vault operation started from a UI callback, if any
+    fn report_error(window: &Weak<MainWindow>, result: Result<(), AppError>) {
+        if let Err(e) = result
+            && let Some(window) = window.upgrade() {
+            Self::handle_app_error(&window, e);
+        }
+    }
+
+    /// Moves a vault item to the trash by ID once the user confirms, then clears the selection
+    /// and updates UI and state. Nothing is written when the user declines.
+    async fn delete_vault_item(window: &MainWindow, state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        let ask = SETTINGS.lock()?.confirm_delete;
+        let confirm = async |name: &str| !ask || Self::confirm_delete(name).await;
+        if !Self::trash_if_confirmed(state, item_id, utils::unix_timestamp(), confirm).await? {
+            return Ok(());
         }
 
-        Self::update_vault_items(&window.upgrade().unwrap());
-        Self::save_vault_state(window);
+        window.set_selected_vault_item(VaultItem { id: -1, ..VaultItem::default() });
+        window.set_item_dirty(false);
+        Self::update_vault_items(window, state)?;
+        Self::save_vault_state(&window.as_weak(), state)
     }
 
-    /// Adds a new blank vault item with incremented ID and focuses on it
-    fn add_vault_item(window: &Weak<MainWindow>) {
-        let new_id: i32;
-        {
-            let mut vault_guard = GLOBAL_VAULT.lock().unwrap();
-            if let Some(vault) = &mut *vault_guard {
-                new_id = vault.nonce;
-                vault.items.push(
-                    Item { 
-                        id: new_id,
-                        name: "New Item".into(),
-                        username: String::new(),
-                        password: String::new(),
-                        url: String::new(),
-                        notes: String::new(),
-                    }
-                ); 
+    /// Trashes the item if `confirm`, given the item's name, agrees. Returns whether it was
+    /// trashed; an item that doesn't exist isn't asked about. The vault isn't locked while
+    /// waiting for the answer.
+    async fn trash_if_confirmed(
+        state: &VaultState, item_id: i32, now: u64, confirm: impl AsyncFnOnce(&str) -> bool
+    ) -> Result<bool, AppError> {
+        let name = state.lock()?.as_ref()
+            .and_then(|vault| vault.item_by_id(item_id))
+            .map(|item| item.name.clone());
+        let Some(name) = name else {
+            return Ok(false);
+        };
 
-                vault.nonce += 1;
-            } else { return; }
+        if !confirm(&name).await {
+            return Ok(false);
         }
+        Self::trash_item(state, item_id, now)?;
+        Ok(true)
+    }
 
-        Self::update_vault_items(&window.upgrade().unwrap());
-        Self::load_selected_item(window, new_id);
-        Self::save_vault_state(window);
+    /// Asks before an item goes to the trash. Skipped when `confirm_delete` is off in the settings.
+    async fn confirm_delete(name: &str) -> bool {
+        let description = format!("Delete '{}'? It is moved to the trash and can be restored from there.", name);
+        DialogWindowHandler::show_message("Delete Item", &description, DialogButtons::YesNo).await == DialogResult::Yes
     }
 
-    /// Encrypts and writes the vault to file
-    fn save_vault_state(window: &Weak<MainWindow>) {
-        let window = window.upgrade().unwrap();
-        let mut vault_guard = GLOBAL_VAULT.lock().unwrap();
-
-        if let Some(vault) = &mut *vault_guard {
-            // Remove key from Vault struct before saving to file
-            let mut vault_without_key = vault.clone();
-            vault_without_key.key = None;
-
-            let encoded_vault = encode_to_vec(&vault_without_key, standard()).unwrap();
-            let vault_location = PathBuf::from(window.get_vault_location().to_string());
-            let key = vault.key.as_ref().unwrap();
-
-            if let Err(e) = file::write_encrypted_file(&encoded_vault, &vault_location, key) {
-                let message = 
-                    if cfg!(debug_assertions) { e.as_str().to_string() } 
-                    else { "Failed to save vault.".to_string() };
-
-                std::thread::spawn(move || {
-                    rfd::MessageDialog::new()
-                        .set_title("Error")
-                        .set_description(message)
-                        .set_buttons(rfd::MessageButtons::Ok)
-                        .show();
-                });
-            }
+    /// Removes the given items for good (bypassing the trash) once the user confirms, then
+    /// clears the selection. Nothing changes when the user declines.
+    async fn delete_vault_items(window: &MainWindow, state: &VaultState, ids: Vec<i32>) -> Result<(), AppError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let description = match ids.len() {
+            1 => "Delete 1 item? It is removed for good and can't be restored from the trash.".to_string(),
+            count => format!("Delete {} items? They are removed for good and can't be restored from the trash.", count),
+        };
+        if DialogWindowHandler::show_message("Delete Items", &description, DialogButtons::YesNo).await != DialogResult::Yes {
+            return Ok(());
+        }
+
+        window.set_selected_vault_item(VaultItem { id: -1, ..VaultItem::default() });
+        window.set_item_dirty(false);
+
+        if Self::remove_items(state, &ids)? == 0 {
+            return Ok(());
         }
+        Self::update_vault_items(window, state)?;
+        Self::save_vault_state(&window.as_weak(), state)
     }
 
-    /// Saves changes to an edited vault item and refreshes display
-    fn save_selected_item(window: &Weak<MainWindow>, new_item: VaultItem) {
-        {
-            let mut vault_guard = GLOBAL_VAULT.lock().unwrap();
-            if let Some(vault) = &mut *vault_guard {
-                if let Some(item) = vault.items.iter_mut().find(|item| item.id == new_item.id) {
-                    item.name = new_item.name.to_string();
-                    item.username = new_item.username.to_string();
-                    item.password = new_item.password.to_string();
-                    item.url = new_item.url.to_string();
-                    item.notes = new_item.notes.to_string();
-                }
-            }
+    /// Returns how many items were removed
+    fn remove_items(state: &VaultState, ids: &[i32]) -> Result<usize, AppError> {
+        Ok(match &mut *state.lock()? {
+            Some(vault) => vault.delete_items(ids),
+            None => 0,
+        })
+    }
+
+    fn trash_item(state: &VaultState, item_id: i32, now: u64) -> Result<(), AppError> {
+        if let Some(vault) = &mut *state.lock()? {
+            vault.soft_delete_item(item_id, now);
         }
+        Ok(())
+    }
 
-        let window = window.upgrade().unwrap();
-        Self::save_vault_state(&window.as_weak());
-        Self::load_selected_item(&window.as_weak(), new_item.id);
-        Self::update_vault_items(&window);
+    /// Restores a trashed item back into the item list
+    fn restore_vault_item(window: &Weak<MainWindow>, state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        Self::restore_item(state, item_id)?;
+        Self::update_vault_items(&window.upgrade().unwrap(), state)?;
+        Self::save_vault_state(window, state)
     }
 
-    /// Loads selected item into the UI for viewing/editing
-    fn load_selected_item(window: &Weak<MainWindow>, item_id: i32) {
-        let window = window.upgrade().unwrap();
-        let vault_guard = GLOBAL_VAULT.lock().unwrap();
-        
-        if let Some(vault) = &*vault_guard {
-            if let Some(item) = vault.items.iter().find(|item| item.id == item_id) {
-                let selected_item = VaultItem {
-                    id: item.id,
-                    name: item.name.clone().into(),
-                    username: item.username.clone().into(),
-                    password: item.password.clone().into(),
-                    url: item.url.clone().into(),
-                    notes: item.notes.clone().into(),
-                };
+    fn restore_item(state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        if let Some(vault) = &mut *state.lock()? {
+            vault.restore_item(item_id);
+        }
+        Ok(())
+    }
 
-                window.set_selected_vault_item(selected_item);
-            }
-        }      
+    /// Removes a trashed item from the vault for good
+    fn permanently_delete_vault_item(window: &Weak<MainWindow>, state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        Self::purge_item(state, item_id)?;
+        Self::update_vault_items(&window.upgrade().unwrap(), state)?;
+        Self::save_vault_state(window, state)
     }
 
-    /// Updates the list of vault items in the UI
-    fn update_vault_items(window: &MainWindow) {
-        let vault_guard = GLOBAL_VAULT.lock().unwrap();
+    fn purge_item(state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        if let Some(vault) = &mut *state.lock()? {
+            vault.permanently_delete_item(item_id);
+        }
+        Ok(())
+    }
 
-        if let Some(vault) = &*vault_guard {
-            let items: Vec<MainWindowItem> = vault.items
-                .iter()
-                .map(|item| MainWindowItem {
-                    id: item.id,
-                    name: item.name.clone().into(),
-                })
-                .collect();
+    /// Removes every trashed item from the vault for good
+    fn empty_trash(window: &Weak<MainWindow>, state: &VaultState) -> Result<(), AppError> {
+        Self::purge_trash(state)?;
+        Self::update_vault_items(&window.upgrade().unwrap(), state)?;
+        Self::save_vault_state(window, state)
+    }
 
-            window.set_vault_items(ModelRc::new(VecModel::from(items)));
+    fn purge_trash(state: &VaultState) -> Result<(), AppError> {
+        if let Some(vault) = &mut *state.lock()? {
+            vault.empty_trash();
         }
+        Ok(())
     }
 
-    /// Opens a file dialog for selecting an existing vault
-    fn open_unlock_vault(window: &Weak<MainWindow>) {
-        if let Some(path) = Self::open_existing_vault() {
-            let window = window.upgrade().unwrap();
-            let path = SharedString::from(path.display().to_string());
+    /// Flips the favorite flag of a vault item and saves the vault
+    fn toggle_favorite(window: &Weak<MainWindow>, state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        if !Self::flip_favorite(state, item_id)? {
+            return Ok(());
+        }
+
+        Self::update_vault_items(&window.upgrade().unwrap(), state)?;
+        Self::save_vault_state(window, state)
+    }
 
-            window.set_vault_location(path);
+    /// Returns false if there is no open vault or no such item, so nothing needs saving
+    fn flip_favorite(state: &VaultState, item_id: i32) -> Result<bool, AppError> {
+        Ok(state.lock()?.as_mut().and_then(|vault| vault.toggle_favorite(item_id)).is_some())
+    }
+
+    /// Changes the order of the item list. The order is saved in the settings, a failed save
+    /// only costs it on the next start, so it is logged rather than shown.
+    fn set_sort_order(window: &Weak<MainWindow>, state: &VaultState, order: i32) -> Result<(), AppError> {
+        let window = window.upgrade().unwrap();
+        let order = SortOrder::from_id(order).ok_or_else(|| AppError::Generic(format!("Unknown sort order {}", order)))?;
+        window.set_sort_order(order.id());
+
+        let mut settings = SETTINGS.lock()?;
+        if settings.sort_order != order {
+            settings.sort_order = order;
+            if let Err(e) = config::save(&settings) {
+                log::warn!("Failed to save settings: {}", e);
+            }
         }
+        drop(settings);
+
+        Self::update_vault_items(&window, state)
     }
 
-    /// Attempts to open and decrypt an existing vault file
-    fn unlock_vault(window: &Weak<MainWindow>, location: String, password: String) {
+    /// Limits the item list to favorites. The filter is kept until the vault is unlocked again.
+    fn show_favorites_only(window: &Weak<MainWindow>, state: &VaultState, enabled: bool) -> Result<(), AppError> {
         let window = window.upgrade().unwrap();
-        let path = PathBuf::from_str(location.as_str()).unwrap();
-        let key = file::derive_file_key(&path, &password).unwrap();
+        window.set_favorites_only(enabled);
+        Self::update_vault_items(&window, state)
+    }
 
-        if let Ok(bytes) = read_encrypted_file(&path, &key) {
-            match decode_from_slice(&bytes, standard()) {
-                Ok((decoded_bytes, _bytes_read)) => {
-                    let mut vault_guard = GLOBAL_VAULT.lock().unwrap();
+    /// Adds a new blank vault item with incremented ID and focuses on it
+    async fn add_vault_item(window: &MainWindow, state: &VaultState) -> Result<(), AppError> {
+        // The new item doesn't have an ID yet, it's never the one being edited
+        if !Self::leave_draft(window, state, None).await? {
+            return Ok(());
+        }
 
-                    let mut vault: Vault = decoded_bytes;
-                    vault.key = Some(key);
+        let Some(new_id) = Self::insert_blank_item(state)? else {
+            return Ok(());
+        };
 
-                    *vault_guard = Some(vault);
-                    window.set_vault_open(true);
-                },
-                Err(e) => {
-                    std::thread::spawn(move || {
-                        rfd::MessageDialog::new()
-                            .set_title("Decode Error")
-                            .set_description(&format!("Failed to decode vault data: {}", e))
-                            .set_buttons(rfd::MessageButtons::Ok)
-                            .show();
-                    });
-                    return;
-                }
+        Self::update_vault_items(window, state)?;
+        Self::load_selected_item(&window.as_weak(), state, new_id)?;
+        Self::save_vault_state(&window.as_weak(), state)
+    }
+
+    /// Returns the new item's ID, or None if no vault is open
+    fn insert_blank_item(state: &VaultState) -> Result<Option<i32>, AppError> {
+        let mut vault_guard = state.lock()?;
+        let Some(vault) = &mut *vault_guard else {
+            return Ok(None);
+        };
+
+        let new_id = vault.next_id()?;
+        let now = utils::unix_timestamp();
+        vault.items.push(
+            Item {
+                id: new_id,
+                name: "New Item".into(),
+                modified_at: now,
+                password_changed_at: now,
+                created_at: now,
+                ..Default::default()
             }
-        } else {
-            std::thread::spawn(move || {
-                rfd::MessageDialog::new()
-                    .set_title("Error")
-                    .set_description("Failed to open vault file. Check password.")
-                    .set_buttons(rfd::MessageButtons::Ok)
-                    .show();
-            });
-        }
+        ); 
 
-        Self::update_vault_items(&window);
+        vault.mark_changed();
+        Ok(Some(new_id))
     }
 
-    /// Opens a system file picker to select a vault file
-    fn open_existing_vault() -> Option<PathBuf> {
-        let handle = std::thread::spawn(move || {
-            rfd::FileDialog::new()
-                .set_title("Select Vault File")
-                .add_filter("Vault Files", &["vault"])
-                .pick_file()
-        });
+    /// Sets the vault's accent color to the palette entry at `index`
+    fn set_vault_accent(window: &Weak<MainWindow>, state: &VaultState, index: i32) -> Result<(), AppError> {
+        if !Self::store_accent(state, index)? {
+            return Ok(());
+        }
 
-        handle.join().ok()?
+        Self::apply_vault_appearance(&window.upgrade().unwrap(), state)?;
+        Self::save_vault_state(window, state)
     }
 
-    /// Opens the CreateVaultWindow if it's not already visible and
-    /// disables input on the main window while open.
-    fn open_create_vault_window(_window_weak: &Weak<MainWindow>, create_vault_window_handler: &Arc<Mutex<CreateVaultWindowHandler>>) {
-        // TODO: Disable window input when another window is open
+    /// Returns false if nothing changed, e.g. for an index outside the palette
+    fn store_accent(state: &VaultState, index: i32) -> Result<bool, AppError> {
+        let Some(accent) = usize::try_from(index).ok().and_then(|index| ACCENT_PALETTE.get(index)) else {
+            return Ok(false);
+        };
+
+        let mut vault_guard = state.lock()?;
+        let Some(vault) = &mut *vault_guard else {
+            return Ok(false);
+        };
 
-        if let Ok(mut handler) = create_vault_window_handler.lock() {
-            if !handler.get_visible() {
-                //window_weak.upgrade().unwrap().set_disable_input(true);
-                handler.show();
+        match appearance::validate_accent(accent) {
+            Ok(accent) => {
+                vault.metadata.accent_color = accent.to_string();
+                Ok(true)
+            },
+            Err(e) => {
+                log::warn!("{}", e);
+                Ok(false)
             }
         }
     }
-}
 
-impl WindowHandler for MainWindowHandler {
-    type Component = MainWindow;
+    /// Sets the vault's icon to the bundled icon at `index`, or removes it for -1
+    fn set_vault_icon(window: &Weak<MainWindow>, state: &VaultState, index: i32) -> Result<(), AppError> {
+        if !Self::store_icon(state, index)? {
+            return Ok(());
+        }
 
-    fn get_window(&self) -> Weak<Self::Component> {
-        self.window.clone()
+        Self::apply_vault_appearance(&window.upgrade().unwrap(), state)?;
+        Self::save_vault_state(window, state)
     }
 
-    fn get_visible(&self) -> bool {
-        if let Ok(visible) = self.visible.lock() {
-            return *visible;
+    /// Returns false if nothing changed, e.g. for an unknown icon index
+    fn store_icon(state: &VaultState, index: i32) -> Result<bool, AppError> {
+        let icon = match usize::try_from(index) {
+            Ok(index) => match VaultIcon::ALL.get(index) {
+                Some(icon) => Some(icon.id().to_string()),
+                None => return Ok(false),
+            },
+            Err(_) => None,
+        };
+
+        let mut vault_guard = state.lock()?;
+        let Some(vault) = &mut *vault_guard else {
+            return Ok(false);
+        };
+        vault.metadata.icon = icon;
+        Ok(true)
+    }
+
+    /// Pushes the open vault's accent color and icon to the window, or resets them when no vault is open
+    fn apply_vault_appearance(window: &MainWindow, state: &VaultState) -> Result<(), AppError> {
+        let vault_guard = state.lock()?;
+        let metadata = vault_guard.as_ref().map(|vault| &vault.metadata);
+
+        let accent = metadata.map_or(appearance::DEFAULT_ACCENT, |metadata| appearance::accent_or_default(&metadata.accent_color));
+        let icon = metadata
+            .and_then(|metadata| metadata.icon.as_deref())
+            .and_then(|id| VaultIcon::from_id(id).ok());
+
+        window.set_vault_accent(Self::accent_color(accent));
+        window.set_selected_accent(ACCENT_PALETTE.iter().position(|&color| color == accent).unwrap_or(0) as i32);
+        window.set_vault_icon(icon.map(Self::icon_image).unwrap_or_default());
+        window.set_selected_icon(icon.and_then(|icon| VaultIcon::ALL.iter().position(|&i| i == icon)).map_or(-1, |i| i as i32));
+
+        let name = metadata.map(|metadata| metadata.name()).unwrap_or_default();
+        let title = if name.is_empty() { "NoPass".to_string() } else { format!("{} - NoPass", name) };
+        window.set_win_title(title.into());
+        window.set_vault_name(name.into());
+        Ok(())
+    }
+
+    fn accent_color(accent: &str) -> Color {
+        let (r, g, b) = appearance::parse_hex(accent).unwrap_or_default();
+        Color::from_rgb_u8(r, g, b)
+    }
+
+    fn icon_image(icon: VaultIcon) -> Image {
+        Image::load_from_svg_data(icon.svg()).unwrap_or_default()
+    }
+
+    /// Encrypts and writes the vault to file in the background. Saves requested while one is
+    /// running are folded into a single follow-up write, so writes never race each other.
+    fn save_vault_state(window: &Weak<MainWindow>, state: &VaultState) -> Result<(), AppError> {
+        let window = window.upgrade().unwrap();
+
+        if window.get_vault_read_only() {
+            let message = if Self::is_snapshot(Path::new(window.get_vault_location().as_str())) {
+                "This is a backup and is opened read-only. Changes made here are not saved."
+            } else {
+                "This vault is open in another window. Changes made here are not saved."
+            };
+
+            Self::show_message("Read-only", message.into());
+            return Ok(());
         }
 
-        false
+        SAVE_QUEUED.store(true, Ordering::SeqCst);
+        let Some(in_progress) = InProgressGuard::try_acquire(&SAVE_IN_PROGRESS) else {
+            // The running save picks up the queued one when it's done
+            return Ok(());
+        };
+
+        let state = state.clone();
+        slint::spawn_local(async move {
+            let _in_progress = in_progress;
+            window.set_saving(true);
+
+            while SAVE_QUEUED.swap(false, Ordering::SeqCst) {
+                let path = PathBuf::from(window.get_vault_location().as_str());
+
+                let result = match Self::write_vault(&state, &path, false).await {
+                    Err(AppError::FileConflict) => Self::resolve_file_conflict(&window, &state, &path).await,
+                    result => result.and_then(|_| Self::schedule_snapshot(&path)),
+                };
+                Self::report_error(&window.as_weak(), result);
+            }
+
+            window.set_saving(false);
+        }).map_err(|e| AppError::Generic(e.to_string()))?;
+
+        Ok(())
     }
 
-    fn get_visible_arc(&self) -> Arc<Mutex<bool>> {
-        self.visible.clone()
+    /// Writes the open vault to `path`, refreshing its metadata. Fails with `AppError::FileConflict`
+    /// if another program changed the file since this session read it, unless `overwrite` is set.
+    async fn write_vault(state: &VaultState, path: &Path, overwrite: bool) -> Result<(), AppError> {
+        Self::write_vault_with(state, path, overwrite, file::write_if_unchanged).await
     }
 
-    fn set_visible(&mut self, value: bool) {
-        if let Ok(mut visible) = self.visible.lock() {
-            *visible = value;
+    /// Snapshots the vault and writes it with `writer` on the blocking pool. The vault isn't
+    /// locked during the write, so the UI can keep reading it while the file is slow to write.
+    async fn write_vault_with(state: &VaultState, path: &Path, overwrite: bool, writer: VaultWriter) -> Result<(), AppError> {
+        let (encoded_vault, key, metadata, expected, restored) = {
+            let mut vault_guard = state.lock()?;
+            let Some(vault) = &mut *vault_guard else {
+                return Ok(());
+            };
+
+            if vault.changed_on_disk && !overwrite {
+                return Err(AppError::FileConflict);
+            }
+
+            vault.metadata.modified_at = utils::unix_timestamp();
+            vault.metadata.item_count_hint = vault.active_items().len() as u32;
+
+            let key = vault.key.clone().ok_or_else(|| AppError::Generic("Open vault has no key".into()))?;
+            // A damaged vault file is replaced as is, it's set aside rather than compared
+            let expected = if overwrite || vault.restored_from_backup { None } else { vault.file_fingerprint.clone() };
+            (Self::encode_vault(vault)?, key, vault.metadata.clone(), expected, vault.restored_from_backup)
+        };
+
+        let path = path.to_path_buf();
+        let written = file::run_blocking(move || {
+            if restored {
+                file::set_aside_corrupt(&path)?;
+            }
+            writer(encoded_vault.as_ref(), &path, &key, &metadata, file::DEFAULT_BACKUP_DEPTH, expected.as_ref())
+        }).await??;
+
+        match written {
+            GuardedWrite::Written(fingerprint) => {
+                if let Some(vault) = &mut *state.lock()? {
+                    vault.file_fingerprint = Some(fingerprint);
+                    vault.changed_on_disk = false;
+                    vault.restored_from_backup = false;
+                }
+                Ok(())
+            },
+            GuardedWrite::Conflict => Err(AppError::FileConflict),
         }
     }
 
-    fn initialize(&mut self) {
-        if let Some(window) = self.get_window().upgrade() {
-            window.window().on_close_requested(move || {
-                // Exit the entire program if main window is closed
-                std::process::exit(0);
-            });
+    /// Asks what to do when the vault file was changed by another program (e.g. a sync client)
+    /// since it was read: overwrite it, save this version elsewhere, or reload it.
+    async fn resolve_file_conflict(window: &MainWindow, state: &VaultState, path: &Path) -> Result<(), AppError> {
+        const OVERWRITE: &str = "Overwrite";
+        const SAVE_COPY: &str = "Save as copy";
+        const RELOAD: &str = "Reload";
+
+        let choice = DialogWindowHandler::show_message(
+            "Vault Changed",
+            "The vault file was changed by another program since it was opened, \
+            for example by a sync client.\n\nOverwrite those changes, save your version as a copy, \
+            or reload the file and discard your unsaved changes?",
+            DialogButtons::Custom(OVERWRITE, SAVE_COPY, RELOAD),
+        ).await;
+
+        // Dismissed, the changes stay in memory and the next save asks again
+        match choice {
+            DialogResult::Custom(OVERWRITE) => Self::write_vault(state, path, true).await,
+            DialogResult::Custom(SAVE_COPY) => Self::save_vault_copy(state, path).await,
+            DialogResult::Custom(RELOAD) => Self::reload_vault(window, state, path).await,
+            _ => Ok(()),
         }
     }
-}
\ No newline at end of file
+
+    /// Writes the open vault to a new file picked by the user, leaving the session on `original`
+    async fn save_vault_copy(state: &VaultState, original: &Path) -> Result<(), AppError> {
+        let name = original.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let dialog = rfd::FileDialog::new()
+            .set_title("Save Vault Copy")
+            .add_filter("Vault Files", &["vault"])
+            .set_file_name(format!("{} (conflicted copy).vault", name));
+
+        let Some(path) = show_file_dialog(dialog, DialogAction::Save, DialogContext::SaveVault) else {
+            return Ok(());
+        };
+
+        let (encoded_vault, key, metadata) = match &mut *state.lock()? {
+            Some(vault) => {
+                let key = vault.key.clone().ok_or_else(|| AppError::Generic("Open vault has no key".into()))?;
+                (Self::encode_vault(vault)?, key, vault.metadata.clone())
+            },
+            None => return Ok(()),
+        };
+
+        let changes = file::AttachmentChanges { source: Some(original.to_path_buf()), ..Default::default() };
+        Ok(file::run_blocking(move || file::write_vault_file(encoded_vault.as_ref(), &path, &key, &metadata, 0, &changes)).await??)
+    }
+
+    /// Writes a copy of the open vault encrypted under `passphrase` instead of the master password
+    /// to a file picked by the user. The copy gets its own salt and opens like any other vault.
+    async fn export_vault(window: &MainWindow, state: &VaultState, passphrase: ZeroByte) -> Result<(), AppError> {
+        let source = PathBuf::from(window.get_vault_location().as_str());
+        let Some(path) = Self::pick_export_path(&source, "Export Encrypted Copy".into(), "(copy).vault".into(), ("Vault Files".into(), "vault")).await else {
+            return Ok(());
+        };
+
+        let (encoded_vault, key, metadata) = {
+            let mut vault_guard = state.lock()?;
+            let Some(vault) = &mut *vault_guard else {
+                return Ok(());
+            };
+
+            let key = vault.key.clone().ok_or_else(|| AppError::Generic("Open vault has no key".into()))?;
+            (Self::encode_vault(vault)?, key, vault.metadata.clone())
+        };
+
+        window.set_exporting(true);
+        let task_path = path.clone();
+        let result = file::run_blocking(move || {
+            file::export_vault_file(encoded_vault.as_ref(), &source, &key, &task_path, passphrase.as_ref(), &metadata)
+        }).await?;
+        window.set_exporting(false);
+
+        match result {
+            Ok(()) => {
+                window.set_export_passphrase(SharedString::new());
+                window.set_export_confirm_passphrase(SharedString::new());
+                window.set_active_page(Page::Vault);
+                Self::show_message("Vault Exported", format!("An encrypted copy of the vault has been saved at {}", path.display()));
+            },
+            Err(e) => Self::export_failed(&e),
+        }
+
+        Ok(())
+    }
+
+    /// Writes the vault's items to a plaintext file in `format` picked by the user, once the
+    /// master password has been entered again and the user has confirmed the risk. Nothing
+    /// about the items is logged.
+    async fn export_plaintext(window: &MainWindow, state: &VaultState, format: PlaintextFormat, password: ZeroByte) -> Result<(), AppError> {
+        let key = match &*state.lock()? {
+            Some(vault) => vault.key.clone().ok_or_else(|| AppError::Generic("Open vault has no key".into()))?,
+            None => return Ok(()),
+        };
+
+        window.set_exporting(true);
+        let matches = file::run_blocking(move || key.matches_password(password.as_ref())).await;
+        window.set_exporting(false);
+
+        if !matches?.unwrap_or(false) {
+            window.set_plaintext_export_error("Wrong master password".into());
+            return Ok(());
+        }
+        window.set_plaintext_export_password(SharedString::new());
+
+        let confirmed = DialogWindowHandler::show_message(
+            "Unencrypted Export",
+            "The export is NOT encrypted. Every username, password and note of this vault will be \
+            readable by anyone and any program that can read the file, including backup and sync tools.\n\n\
+            Delete the file as soon as you no longer need it. Export anyway?",
+            DialogButtons::YesNo,
+        ).await;
+        if confirmed != DialogResult::Yes {
+            return Ok(());
+        }
+
+        let source = PathBuf::from(window.get_vault_location().as_str());
+        let title = format!("Export Unencrypted {}", format.name());
+        let suffix = format!("(unencrypted).{}", format.extension());
+        let filter = (format!("{} Files", format.name()), format.extension());
+        let Some(path) = Self::pick_export_path(&source, title, suffix, filter).await else {
+            return Ok(());
+        };
+
+        let contents = {
+            let vault_guard = state.lock()?;
+            let Some(vault) = &*vault_guard else {
+                return Ok(());
+            };
+            format.encode(&vault.active_items(), utils::unix_timestamp())
+        };
+
+        window.set_exporting(true);
+        let task_path = path.clone();
+        let result = file::run_blocking(move || file::write_private(&task_path, contents?.as_ref()).map_err(ExportError::from)).await?;
+        window.set_exporting(false);
+
+        match result {
+            Ok(()) => {
+                window.set_active_page(Page::Vault);
+                Self::show_message("Vault Exported", format!("The unencrypted export has been saved at {}", path.display()));
+            },
+            Err(e) => Self::export_failed(&e),
+        }
+
+        Ok(())
+    }
+
+    /// Asks where to export the open vault at `source` to, suggesting its name followed by
+    /// `suffix`. An existing file is only replaced once the user confirms, and never the open
+    /// vault's own file. None if the user cancelled.
+    async fn pick_export_path(source: &Path, title: String, suffix: String, filter: (String, &'static str)) -> Option<PathBuf> {
+        let name = source.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let dialog = rfd::FileDialog::new()
+            .set_title(title)
+            .add_filter(&filter.0, &[filter.1])
+            .set_file_name(format!("{} {}", name, suffix));
+
+        let path = show_file_dialog(dialog, DialogAction::Save, DialogContext::ImportExport)?;
+        if !path.exists() {
+            return Some(path);
+        }
+
+        if fs::canonicalize(&path).ok() == fs::canonicalize(source).ok() {
+            Self::show_message("Error", "The export can't replace the open vault. Choose another file.".into());
+            return None;
+        }
+
+        let description = format!("{} already exists. Replace it?", path.display());
+        let replace = DialogWindowHandler::show_message("Replace File", &description, DialogButtons::YesNo).await;
+
+        (replace == DialogResult::Yes).then_some(path)
+    }
+
+    /// Tells the user an export failed. Details are only shown in debug builds.
+    fn export_failed(e: &dyn std::error::Error) {
+        log::error!("Failed to export vault: {}", e);
+        let message =
+            if cfg!(debug_assertions) { e.to_string() }
+            else { "Failed to export the vault.".to_string() };
+        Self::show_message("Error", message);
+    }
+
+    /// Shows a message with an OK button without waiting for it to be dismissed
+    pub(crate) fn show_message(title: &'static str, message: String) {
+        slint::spawn_local(async move {
+            DialogWindowHandler::show_message(title, &message, DialogButtons::Ok).await;
+        }).ok();
+    }
+
+    /// Replaces the open vault with the current contents of its file, discarding unsaved changes
+    async fn reload_vault(window: &MainWindow, state: &VaultState, path: &Path) -> Result<(), AppError> {
+        let key = match &*state.lock()? {
+            Some(vault) => vault.key.clone().ok_or_else(|| AppError::Generic("Open vault has no key".into()))?,
+            None => return Ok(()),
+        };
+
+        let task_path = path.to_path_buf();
+        let read = file::run_blocking(move || {
+            let bytes = read_encrypted_file(&task_path, &key)?;
+            let decoded = Vault::decode(bytes.as_ref());
+            file::recycle_buffer(bytes);
+
+            let fresh = decoded.map_err(|e| FileError::EncodingFailed(e.to_string()))?;
+            Ok::<_, FileError>((fresh, file::read_vault_metadata(&task_path).ok().flatten(), FileFingerprint::of(&task_path).ok()))
+        }).await?;
+
+        let reloaded = {
+            let mut vault_guard = state.lock()?;
+            let Some(vault) = &mut *vault_guard else {
+                return Ok(());  // Locked while the file was being read
+            };
+
+            match read {
+                Ok((mut fresh, metadata, fingerprint)) => {
+                    fresh.key = vault.key.take();
+                    fresh.metadata = metadata.unwrap_or_else(|| vault.metadata.clone());
+                    fresh.file_fingerprint = fingerprint;
+                    *vault = fresh;
+                    true
+                },
+                Err(_) => false,
+            }
+        };
+
+        if !reloaded {
+            // Most likely the password was changed on the other machine
+            Self::show_message("Error", "The changed vault file could not be opened with the current password. Lock the vault and unlock it again to load it.".into());
+            return Ok(());
+        }
+
+        window.set_vault_changed_on_disk(false);
+        Self::update_vault_items(window, state)?;
+        Self::apply_vault_appearance(window, state)?;
+        Self::load_selected_item(&window.as_weak(), state, window.get_selected_vault_item().id)
+    }
+
+    /// Starts watching the open vault's file. Changes are handled on the event loop.
+    fn watch_vault_file(window: &MainWindow, state: &VaultState, path: &Path) -> Result<(), AppError> {
+        let window_weak = window.as_weak();
+        let state = state.clone();
+        let watcher = FileWatcher::start(path, WATCH_INTERVAL, move || {
+            let window_weak = window_weak.clone();
+            let state = state.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                let result = Self::vault_file_changed(&window_weak, &state);
+                Self::report_error(&window_weak, result);
+            });
+        });
+
+        *VAULT_WATCHER.lock()? = Some(watcher);
+        Ok(())
+    }
+
+    /// Locks the vault once it has gone `Settings::auto_lock_secs` without user input, or
+    /// `Settings::lock_on_focus_loss_secs` without any of the app's windows having focus.
+    /// The timer only runs while the vault is unlocked. Input and focus are only seen through
+    /// winit, with another backend the vault would lock mid-use, so it is left to the user there.
+    fn start_auto_lock(window: &MainWindow, state: &VaultState) -> Result<(), AppError> {
+        let (mut timeout, mut focus_grace) = {
+            let settings = SETTINGS.lock()?;
+            (settings.auto_lock_secs.map(Duration::from_secs), settings.lock_on_focus_loss_secs.map(Duration::from_secs))
+        };
+        if (timeout.is_some() || focus_grace.is_some()) && !window.window().has_winit_window() {
+            log::warn!("Auto-lock needs the winit backend, the vault stays unlocked until locked by hand");
+            timeout = None;
+            focus_grace = None;
+        }
+
+        let mut auto_lock = AUTO_LOCK.lock()?;
+        auto_lock.arm(Instant::now(), timeout, focus_grace);
+        if !auto_lock.is_armed() {
+            return Ok(());
+        }
+        drop(auto_lock);
+
+        let window_weak = window.as_weak();
+        let state = state.clone();
+        AUTO_LOCK_TIMER.with(|timer| timer.start(TimerMode::Repeated, auto_lock::CHECK_INTERVAL, move || {
+            let reason = AUTO_LOCK.lock().ok().and_then(|mut auto_lock| auto_lock.poll(Instant::now()));
+            if let Some(reason) = reason {
+                match reason {
+                    LockReason::Idle => log::info!("Locking the vault after {:?} without input", timeout.unwrap_or_default()),
+                    LockReason::FocusLost => log::info!("Locking the vault after {:?} in the background", focus_grace.unwrap_or_default()),
+                }
+                let result = Self::lock_vault(&window_weak, &state);
+                Self::report_error(&window_weak, result);
+            }
+        }));
+        Ok(())
+    }
+
+    fn stop_auto_lock() {
+        AUTO_LOCK.lock().unwrap_or_else(|e| e.into_inner()).disarm();
+        AUTO_LOCK_TIMER.with(Timer::stop);
+    }
+
+    /// Copies `text` and starts the countdown to clear it again
+    fn copy_to_clipboard(window_weak: &Weak<MainWindow>, text: &str) -> Result<(), AppError> {
+        if let Some(window) = window_weak.upgrade() {
+            super::attach_clipboard(window.window());
+        }
+        clipboard::copy_text(text)?;
+        if let Some(window) = window_weak.upgrade() {
+            window.set_clipboard_failed(false);
+        }
+        Self::start_clipboard_clear(window_weak);
+        Ok(())
+    }
+
+    /// Clears the clipboard `Settings::clipboard_clear_secs` after a copy, unless it has since
+    /// been replaced by something else. Shows the seconds left in the main window meanwhile.
+    fn start_clipboard_clear(window_weak: &Weak<MainWindow>) {
+        let Some(after) = SETTINGS.lock().ok().and_then(|settings| settings.clipboard_clear_secs) else {
+            return;
+        };
+
+        CLIPBOARD_COUNTDOWN.with_borrow_mut(|countdown| countdown.start(Instant::now(), Duration::from_secs(after)));
+        Self::show_clipboard_countdown(window_weak);
+
+        let window_weak = window_weak.clone();
+        CLIPBOARD_TIMER.with(|timer| timer.start(TimerMode::Repeated, Duration::from_secs(1), move || {
+            if CLIPBOARD_COUNTDOWN.with_borrow_mut(|countdown| countdown.poll(Instant::now())) {
+                clipboard::clear_if_owned();
+                CLIPBOARD_TIMER.with(Timer::stop);
+            }
+            Self::show_clipboard_countdown(&window_weak);
+        }));
+    }
+
+    /// Stops the countdown, e.g. once the clipboard was scrubbed on lock
+    fn stop_clipboard_clear(window: &MainWindow) {
+        CLIPBOARD_COUNTDOWN.with_borrow_mut(ClearCountdown::cancel);
+        CLIPBOARD_TIMER.with(Timer::stop);
+        window.set_clipboard_seconds_left(0);
+    }
+
+    /// Shows that a copy failed for a few seconds
+    fn show_clipboard_failed(window: &MainWindow) {
+        window.set_clipboard_failed(true);
+        let window_weak = window.as_weak();
+        Timer::single_shot(CLIPBOARD_FAILED_SHOWN_FOR, move || {
+            if let Some(window) = window_weak.upgrade() {
+                window.set_clipboard_failed(false);
+            }
+        });
+    }
+
+    fn show_clipboard_countdown(window_weak: &Weak<MainWindow>) {
+        let seconds_left = CLIPBOARD_COUNTDOWN.with_borrow(|countdown| countdown.seconds_left(Instant::now())).unwrap_or(0);
+        if let Some(window) = window_weak.upgrade() {
+            window.set_clipboard_seconds_left(i32::try_from(seconds_left).unwrap_or(i32::MAX));
+        }
+    }
+
+    /// Restarts the auto-lock countdown
+    fn note_activity() {
+        if let Ok(mut auto_lock) = AUTO_LOCK.lock() {
+            auto_lock.touch(Instant::now());
+        }
+    }
+
+    /// Follows focus moving between the app's windows and other programs. Focus moving from
+    /// the main window to a child window is lost by one and gained by the other in the same
+    /// event loop pass, so it never reaches the grace period.
+    pub(super) fn note_focus(focused: bool) {
+        if let Ok(mut auto_lock) = AUTO_LOCK.lock() {
+            if focused {
+                auto_lock.focus_gained();
+            } else {
+                auto_lock.focus_lost(Instant::now());
+            }
+        }
+    }
+
+    /// Keyboard, mouse and touch events. Focus changes and redraws don't keep the vault open.
+    fn is_user_input(event: &WindowEvent) -> bool {
+        matches!(event,
+            WindowEvent::KeyboardInput { .. } | WindowEvent::MouseInput { .. } | WindowEvent::MouseWheel { .. }
+            | WindowEvent::CursorMoved { .. } | WindowEvent::Touch(_) | WindowEvent::Ime(_)
+        )
+    }
+
+    /// Shows the reload banner if the vault file no longer matches what this session last
+    /// read or wrote. The session's own saves update the fingerprint and are ignored here.
+    fn vault_file_changed(window: &Weak<MainWindow>, state: &VaultState) -> Result<(), AppError> {
+        let Some(window) = window.upgrade() else {
+            return Ok(());
+        };
+        if SAVE_IN_PROGRESS.load(Ordering::SeqCst) {
+            // Most likely our own write, whose fingerprint isn't recorded yet. A real external
+            // change is still caught by the save's own conflict check.
+            return Ok(());
+        }
+        let path = PathBuf::from(window.get_vault_location().as_str());
+
+        let mut vault_guard = state.lock()?;
+        let Some(vault) = &mut *vault_guard else {
+            return Ok(());
+        };
+
+        let changed = match &vault.file_fingerprint {
+            Some(fingerprint) => fingerprint.changed(&path)?,
+            None => true,
+        };
+
+        if changed {
+            vault.changed_on_disk = true;
+            window.set_vault_changed_on_disk(true);
+        }
+
+        Ok(())
+    }
+
+    /// Hides the reload banner. The vault stays marked as changed on disk, so the next
+    /// save asks how to resolve the conflict instead of overwriting.
+    fn dismiss_vault_change(window: &Weak<MainWindow>) {
+        if let Some(window) = window.upgrade() {
+            window.set_vault_changed_on_disk(false);
+        }
+    }
+
+    /// Serializes the vault for writing to file, without its key, see `Vault::encode`
+    fn encode_vault(vault: &mut Vault) -> Result<ZeroByte, AppError> {
+        vault.encode().map_err(|e| AppError::Generic(e.to_string()))
+    }
+
+    /// Asks whether a legacy vault file should be upgraded to the current format now,
+    /// keeping the original as a backup
+    async fn offer_legacy_migration(window: &MainWindow, state: &VaultState, path: &Path) -> Result<(), AppError> {
+        let backup = file::legacy_backup_path(path);
+        let description = format!(
+            "This vault uses an old file format. Upgrade it now?\n\nThe original file will be kept as {}. \
+            Otherwise it is upgraded without a backup the next time the vault is saved.",
+            backup.display()
+        );
+
+        if DialogWindowHandler::show_message("Upgrade Vault", &description, DialogButtons::YesNo).await != DialogResult::Yes {
+            return Ok(());
+        }
+
+        let result = {
+            let mut vault_guard = state.lock()?;
+            match &mut *vault_guard {
+                Some(vault) => {
+                    let encoded_vault = Self::encode_vault(vault)?;
+                    let key = vault.key.as_ref().ok_or_else(|| AppError::Generic("Open vault has no key".into()))?;
+                    file::migrate_legacy_file(encoded_vault.as_ref(), path, key, &vault.metadata)
+                },
+                None => return Ok(()),
+            }
+        };
+
+        match result.and_then(|_| file::read_header(path)) {
+            Ok(header) => {
+                // Keep the session header and fingerprint in sync with the rewritten file
+                Self::set_view_state(window, ViewState::Vault { path: path.to_path_buf(), header });
+                if let Some(vault) = &mut *state.lock()? {
+                    vault.file_fingerprint = FileFingerprint::of(path).ok();
+                }
+            },
+            Err(e) => {
+                let message =
+                    if cfg!(debug_assertions) { e.to_string() }
+                    else { "Failed to upgrade vault file. The original file was not changed.".to_string() };
+
+                Self::show_message("Error", message);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves changes to an edited vault item and refreshes display
+    fn save_selected_item(window: &Weak<MainWindow>, state: &VaultState, new_item: VaultItem) -> Result<(), AppError> {
+        Self::store_item(state, &new_item)?;
+
+        let window = window.upgrade().unwrap();
+        Self::save_vault_state(&window.as_weak(), state)?;
+        Self::load_selected_item(&window.as_weak(), state, new_item.id)?;
+        Self::update_vault_items(&window, state)
+    }
+
+    /// Whether the item selected in the window exists in a vault that can be written to
+    fn selected_item_is_editable(window: &Weak<MainWindow>, state: &VaultState) -> bool {
+        let Some(window) = window.upgrade() else { return false; };
+        let item_id = window.get_selected_vault_item().id;
+
+        !window.get_vault_read_only() && state.lock().is_ok_and(|vault_guard| {
+            vault_guard.as_ref().is_some_and(|vault| vault.item_by_id(item_id).is_some())
+        })
+    }
+
+    /// Sets the password of the selected item to one from the password generator and saves
+    fn use_generated_password(window: &Weak<MainWindow>, state: &VaultState, password: &ZeroByte) -> Result<(), AppError> {
+        let item_id = window.upgrade().unwrap().get_selected_vault_item().id;
+        Self::store_password(state, item_id, password)?;
+
+        let window = window.upgrade().unwrap();
+        Self::save_vault_state(&window.as_weak(), state)?;
+        Self::load_selected_item(&window.as_weak(), state, item_id)?;
+        Self::update_vault_items(&window, state)
+    }
+
+    /// Replaces the password of an item, wiping the old one. Items hold their password as a
+    /// `String`, so this is where the generated bytes become one.
+    fn store_password(state: &VaultState, item_id: i32, password: &ZeroByte) -> Result<(), AppError> {
+        let password = std::str::from_utf8(password.as_ref()).map_err(|e| AppError::Generic(e.to_string()))?;
+
+        let mut vault_guard = state.lock()?;
+        if let Some(vault) = &mut *vault_guard
+            && let Some(item) = vault.item_by_id_mut(item_id) {
+            let now = utils::unix_timestamp();
+            item.password.zeroize();
+            item.password.push_str(password);
+            item.password_changed_at = now;
+            item.modified_at = now;
+            vault.mark_changed();
+        }
+        Ok(())
+    }
+
+    fn store_item(state: &VaultState, new_item: &VaultItem) -> Result<(), AppError> {
+        let mut vault_guard = state.lock()?;
+        if let Some(vault) = &mut *vault_guard
+            && let Some(item) = vault.item_by_id_mut(new_item.id) {
+            let now = utils::unix_timestamp();
+            if item.password != new_item.password.as_str() {
+                item.password_changed_at = now;
+            }
+            item.modified_at = now;
+
+            item.name = new_item.name.to_string();
+            item.username = new_item.username.to_string();
+            item.password = new_item.password.to_string();
+            item.url = new_item.url.to_string();
+            item.notes = new_item.notes.to_string();
+            vault.mark_changed();
+        }
+        Ok(())
+    }
+
+    /// Shows the vault's health report on the health page
+    fn show_health_report(window: &Weak<MainWindow>, state: &VaultState) -> Result<(), AppError> {
+        let window = window.upgrade().unwrap();
+        let state = state.clone();
+
+        slint::spawn_local(async move {
+            window.set_health_report_busy(true);
+            let result = Self::compute_health_report(&state).await;
+            window.set_health_report_busy(false);
+
+            match result {
+                Ok(Some(report)) => window.set_health_report(Self::health_report_model(&report)),
+                Ok(None) => {},
+                Err(e) => Self::report_error(&window.as_weak(), Err(e)),
+            }
+        }).map_err(|e| AppError::Generic(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Audits a copy of the active items on a blocking thread, or returns the cached report if
+    /// the items haven't changed since it was computed. Returns None if no vault is open.
+    async fn compute_health_report(state: &VaultState) -> Result<Option<VaultHealthReport>, AppError> {
+        let (version, mut snapshot) = {
+            let vault_guard = state.lock()?;
+            let Some(vault) = &*vault_guard else {
+                return Ok(None);
+            };
+            if let Some(report) = vault.cached_health_report() {
+                return Ok(Some(report.clone()));
+            }
+
+            let mut snapshot = Vault::new();
+            snapshot.items = vault.active_items().into_iter().cloned().collect();
+            (vault.version, snapshot)
+        };
+
+        let report = file::run_blocking(move || {
+            let report = snapshot.health_report();
+            snapshot.items.zeroize();
+            report
+        }).await.map_err(AppError::Generic)?;
+
+        // Dropped by the vault if it was edited, locked or reloaded in the meantime
+        if let Some(vault) = &mut *state.lock()? {
+            vault.cache_health_report(version, report.clone());
+        }
+
+        Ok(Some(report))
+    }
+
+    fn health_report_model(report: &VaultHealthReport) -> UiHealthReport {
+        let ids = |ids: &[i32]| ModelRc::new(VecModel::from(ids.to_vec()));
+        let groups: Vec<ModelRc<i32>> = report.duplicate_passwords.iter().map(|group| ids(group)).collect();
+
+        UiHealthReport {
+            total_items: report.total_items as i32,
+            weak_passwords: ids(&report.weak_passwords),
+            duplicate_passwords: ModelRc::new(VecModel::from(groups)),
+            expired_items: ids(&report.expired_items),
+            items_without_url: ids(&report.items_without_url),
+            average_password_strength: report.average_password_strength as f32,
+        }
+    }
+
+    /// Switches the selection to `item_id`, asking first what to do with unsaved edits to
+    /// another item
+    async fn select_item(window: &MainWindow, state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        if !Self::leave_draft(window, state, Some(item_id)).await? {
+            return Ok(());
+        }
+        Self::load_selected_item(&window.as_weak(), state, item_id)
+    }
+
+    /// Settles unsaved edits before the selection moves to `target`, None for an item that
+    /// doesn't exist yet. Returns false if the user chose to keep editing.
+    async fn leave_draft(window: &MainWindow, state: &VaultState, target: Option<i32>) -> Result<bool, AppError> {
+        let Some(draft) = Self::unsaved_draft(window, state)? else {
+            return Ok(true);
+        };
+        if target == Some(draft.id) {
+            return Ok(true);  // Reloading the item being edited, e.g. Cancel
+        }
+
+        let choice = Self::ask_unsaved_changes(&draft.name).await;
+        if !Self::settle_draft(state, &draft, choice)? {
+            return Ok(false);
+        }
+
+        window.set_item_dirty(false);
+        if choice == UnsavedChoice::Save {
+            Self::save_vault_state(&window.as_weak(), state)?;
+            Self::update_vault_items(window, state)?;
+        }
+        Ok(true)
+    }
+
+    /// Edits to the selected item that haven't been saved, None if there are none or the item
+    /// is gone
+    fn unsaved_draft(window: &MainWindow, state: &VaultState) -> Result<Option<VaultItem>, AppError> {
+        if !window.get_item_dirty() || window.get_vault_read_only() {
+            return Ok(None);
+        }
+        Self::pending_draft(state, window.get_draft_item())
+    }
+
+    /// `draft` if its item is still in the vault. A draft of an item that was never stored
+    /// (the view's -1 for "nothing selected") or was removed since has nowhere to go.
+    fn pending_draft(state: &VaultState, draft: VaultItem) -> Result<Option<VaultItem>, AppError> {
+        let vault_guard = state.lock()?;
+        let exists = vault_guard.as_ref().is_some_and(|vault| vault.item_by_id(draft.id).is_some());
+        Ok(exists.then_some(draft))
+    }
+
+    /// Asks whether to save the edits to the item named `name` before moving on
+    async fn ask_unsaved_changes(name: &str) -> UnsavedChoice {
+        let description = format!("Save changes to {}?", name);
+        match DialogWindowHandler::show_message("Unsaved Changes", &description, DialogButtons::SaveDiscardCancel).await {
+            DialogResult::Save => UnsavedChoice::Save,
+            DialogResult::Discard => UnsavedChoice::Discard,
+            _ => UnsavedChoice::Cancel,  // Dismissing the dialog keeps the edits
+        }
+    }
+
+    /// Applies the user's choice for `draft` to the vault. Only stores it, writing the vault
+    /// is up to the caller. Returns false if the user chose to keep editing.
+    fn settle_draft(state: &VaultState, draft: &VaultItem, choice: UnsavedChoice) -> Result<bool, AppError> {
+        match choice {
+            UnsavedChoice::Save => Self::store_item(state, draft)?,
+            UnsavedChoice::Discard => {},
+            UnsavedChoice::Cancel => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Loads selected item into the UI for viewing/editing. Edits still in the inputs are
+    /// dropped.
+    fn load_selected_item(window: &Weak<MainWindow>, state: &VaultState, item_id: i32) -> Result<(), AppError> {
+        let window = window.upgrade().unwrap();
+        window.set_item_dirty(false);
+        let vault_guard = state.lock()?;
+        
+        if let Some(vault) = &*vault_guard
+            && let Some(item) = vault.item_by_id(item_id) {
+            let selected_item = VaultItem {
+                id: item.id,
+                name: item.name.clone().into(),
+                username: item.username.clone().into(),
+                password: item.password.clone().into(),
+                url: item.url.clone().into(),
+                notes: item.notes.clone().into(),
+                modified: if item.modified_at > 0 { utils::format_date(item.modified_at).into() } else { SharedString::new() },
+            };
+
+            window.set_selected_vault_item(selected_item);
+        }
+
+        Ok(())
+    }
+
+    /// Days since the item was last edited, -1 if there is no such item
+    fn item_age_days(state: &VaultState, item_id: i32) -> Result<i32, AppError> {
+        let vault_guard = state.lock()?;
+        let age = vault_guard.as_ref()
+            .and_then(|vault| vault.item_by_id(item_id))
+            .map_or(-1, |item| i32::try_from(item.age_days()).unwrap_or(i32::MAX));
+
+        Ok(age)
+    }
+
+    /// Updates the list of vault items in the UI, filtered by the current search query.
+    /// Malformed queries still filter (as plain text) and show a hint under the search box.
+    fn update_vault_items(window: &MainWindow, state: &VaultState) -> Result<(), AppError> {
+        let (query, query_error) = Query::parse_lenient(window.get_search_text().as_str());
+        let hint = query_error.map(|e| e.to_string()).unwrap_or_default();
+        window.set_search_hint(hint.into());
+
+        let vault_guard = state.lock()?;
+
+        if let Some(vault) = &*vault_guard {
+            let visible_items =
+                if window.get_favorites_only() { vault.favorite_items() }
+                else { vault.active_items() };
+
+            let mut visible_items = visible_items;
+            let order = SortOrder::from_id(window.get_sort_order()).unwrap_or_default();
+            sort_items(&mut visible_items, order);
+            let visible_items = Self::filter_items(visible_items, &query, window.get_search_fuzzy());
+
+            let ids: Vec<i32> = visible_items.iter().map(|item| item.id).collect();
+            if !Self::selection_shown(window.get_selected_vault_item().id, &ids) {
+                window.set_selected_vault_item(VaultItem { id: -1, ..VaultItem::default() });
+            }
+            window.set_shown_item_ids(ModelRc::new(VecModel::from(ids)));
+
+            let items: Vec<MainWindowItem> = visible_items
+                .into_iter()
+                .map(|item| MainWindowItem {
+                    id: item.id,
+                    name: item.name.clone().into(),
+                    favorite: item.favorite,
+                })
+                .collect();
+
+            window.set_vault_items(ModelRc::new(VecModel::from(items)));
+        }
+
+        drop(vault_guard);
+        Self::update_trash_items(window, state)
+    }
+
+    /// The items matching the search `query`, all of them when it's empty. Names, usernames
+    /// and URLs are matched ignoring case, see `Query::matches`. With `fuzzy`, notes are
+    /// searched too, abbreviations match, and the best matches come first, see `Query::rank`.
+    /// Items ranked the same keep their order.
+    fn filter_items<'a>(items: Vec<&'a Item>, query: &Query, fuzzy: bool) -> Vec<&'a Item> {
+        if query.is_empty() {
+            return items;
+        }
+        if !fuzzy {
+            return items.into_iter().filter(|item| query.matches(item)).collect();
+        }
+
+        let mut ranked: Vec<(u32, &Item)> = items.into_iter()
+            .filter_map(|item| query.rank(item).map(|rank| (rank, item)))
+            .collect();
+        ranked.sort_by_key(|&(rank, _)| Reverse(rank));
+        ranked.into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// Whether the selected item (-1 for none) is still in the list showing `shown_ids`.
+    /// An item the search hides is deselected, so its details don't stay on screen.
+    fn selection_shown(selected_id: i32, shown_ids: &[i32]) -> bool {
+        selected_id == -1 || shown_ids.contains(&selected_id)
+    }
+
+    /// Updates the list of trashed items in the UI
+    fn update_trash_items(window: &MainWindow, state: &VaultState) -> Result<(), AppError> {
+        let vault_guard = state.lock()?;
+
+        if let Some(vault) = &*vault_guard {
+            let items: Vec<MainWindowItem> = vault.trash()
+                .into_iter()
+                .map(|item| MainWindowItem {
+                    id: item.id,
+                    name: item.name.clone().into(),
+                    favorite: item.favorite,
+                })
+                .collect();
+
+            window.set_trash_items(ModelRc::new(VecModel::from(items)));
+        }
+
+        Ok(())
+    }
+
+    /// Stores the new view state and pushes its properties to the window
+    fn set_view_state(window: &MainWindow, state: ViewState) {
+        let props = state.props();
+
+        window.set_vault_location(props.vault_location.into());
+        window.set_vault_locked(props.locked);
+        window.set_kdf_summary(props.kdf_summary.into());
+        window.set_vault_info(props.vault_info.into());
+        window.set_active_page(props.page);
+
+        *VIEW_STATE.lock().unwrap() = state;
+    }
+
+    /// Opens a file dialog for selecting an existing vault and shows the unlock page for it
+    fn open_unlock_vault(window: &Weak<MainWindow>) {
+        let Some(path) = Self::open_existing_vault() else { return; };
+        Self::show_unlock_page(window, path);
+    }
+
+    /// Shows the unlock page for the vault at `path`, or an error if its header can't be read
+    fn show_unlock_page(window: &Weak<MainWindow>, path: PathBuf) {
+        let window = window.upgrade().unwrap();
+        file::remove_stale_temp_files(&path);
+
+        match file::read_header(&path) {
+            Ok(header) => Self::set_view_state(&window, ViewState::Unlock { path, header }),
+            Err(e) => {
+                let message = Self::open_error_message(&e);
+
+                Self::show_message("Error", message);
+            }
+        }
+    }
+
+    /// Message for a vault file that failed to open. Details are only shown where they help
+    /// the user, or in debug builds.
+    fn open_error_message(e: &FileError) -> String {
+        match e {
+            FileError::PathNotFound(path) => format!("The vault file {} no longer exists.", path.display()),
+            FileError::Truncated | FileError::UnsupportedVersion(_) => e.to_string(),
+            _ if cfg!(debug_assertions) => e.to_string(),
+            _ => "Failed to open vault file.".to_string(),
+        }
+    }
+
+    /// Exits once the user has settled the unsaved `draft`, or returns if they keep editing
+    async fn exit_after_draft(window: &Weak<MainWindow>, state: &VaultState, draft: VaultItem) -> Result<(), AppError> {
+        let choice = Self::ask_unsaved_changes(&draft.name).await;
+        if !Self::settle_draft(state, &draft, choice)? {
+            return Ok(());
+        }
+
+        // Exiting skips the queued background save, the edit is written before
+        if choice == UnsavedChoice::Save
+            && let Some(window) = window.upgrade() {
+            let path = PathBuf::from(window.get_vault_location().as_str());
+            Self::write_vault(state, &path, false).await?;
+        }
+        Self::exit(window, state)
+    }
+
+    /// Exits the entire program, the main window being closed
+    fn exit(window: &Weak<MainWindow>, state: &VaultState) -> ! {
+        if let Some(window) = window.upgrade() {
+            if let Err(e) = Self::save_geometry(&window) {
+                log::warn!("Failed to save window geometry: {}", e);
+            }
+            Self::scrub_on_exit(&window, state);
+        }
+        tempsec::cleanup();
+        clipboard::clear_if_owned();  // No-op after scrub_on_exit, covers an already dropped window
+
+        // process::exit skips destructors, release the vault lock explicitly
+        if let Ok(mut lock) = VAULT_LOCK.lock() {
+            lock.take();
+        }
+        std::process::exit(0);
+    }
+
+    /// Best-effort pass over everything that may still hold secrets before the process exits,
+    /// since `process::exit` skips destructors. Copies in freed memory that was never wiped
+    /// (e.g. Slint's text caches) can't be reached from here.
+    fn scrub_on_exit(window: &MainWindow, state: &VaultState) {
+        Self::clear_vault_contents(window);
+        window.set_vault_name(SharedString::new());
+        window.set_scrub_canary(SharedString::new());
+
+        VAULT_WATCHER.lock().unwrap_or_else(|e| e.into_inner()).take();
+        Self::wipe_vault(state);
+
+        file::release_buffers();
+        clipboard::clear_if_owned();
+
+        #[cfg(debug_assertions)]
+        utils::scrub_check::report();
+    }
+
+    /// Clears the window properties that show vault contents, so no plaintext stays in Slint
+    fn clear_vault_contents(window: &MainWindow) {
+        window.set_selected_vault_item(VaultItem::default());
+        window.set_item_dirty(false);
+        window.set_draft_item(VaultItem::default());
+        window.set_vault_items(ModelRc::default());
+        window.set_trash_items(ModelRc::default());
+        window.set_shown_item_ids(ModelRc::default());
+        window.set_search_text(SharedString::new());
+        window.set_search_hint(SharedString::new());
+    }
+
+    /// Takes the vault out of `state` and zeroizes its items and key
+    fn wipe_vault(state: &VaultState) {
+        let vault = state.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(mut vault) = vault {
+            vault.wipe();
+        }
+    }
+
+    /// Locks the vault from the Lock button, asking first what to do with unsaved edits.
+    /// Auto-lock doesn't ask, nobody is there to answer.
+    async fn request_lock(window: &MainWindow, state: &VaultState) -> Result<(), AppError> {
+        let Some(draft) = Self::unsaved_draft(window, state)? else {
+            return Self::lock_vault(&window.as_weak(), state);
+        };
+
+        let choice = Self::ask_unsaved_changes(&draft.name).await;
+        if !Self::settle_draft(state, &draft, choice)? {
+            return Ok(());
+        }
+        if choice == UnsavedChoice::Save {
+            // Locking wipes the vault, so the edit is written first instead of queued
+            let path = PathBuf::from(window.get_vault_location().as_str());
+            Self::write_vault(state, &path, false).await?;
+        }
+
+        Self::lock_vault(&window.as_weak(), state)
+    }
+
+    /// Closes the vault and returns to the unlock page, keeping the session's file selected.
+    /// The vault is wiped, along with its contents in the window and a copied secret still
+    /// on the clipboard.
+    fn lock_vault(window: &Weak<MainWindow>, state: &VaultState) -> Result<(), AppError> {
+        let window = window.upgrade().unwrap();
+        Self::stop_auto_lock();
+        VAULT_WATCHER.lock()?.take();
+        Self::wipe_vault(state);
+        VAULT_LOCK.lock()?.take();
+        Self::clear_vault_contents(&window);
+        clipboard::clear_if_owned();
+        Self::stop_clipboard_clear(&window);
+        window.set_vault_open(false);
+        window.set_vault_read_only(false);
+        window.set_vault_changed_on_disk(false);
+        window.set_export_passphrase(SharedString::new());
+        window.set_export_confirm_passphrase(SharedString::new());
+        window.set_plaintext_export_password(SharedString::new());
+        Self::apply_vault_appearance(&window, state)?;
+        tempsec::cleanup();
+
+        let view_state = std::mem::take(&mut *VIEW_STATE.lock().unwrap());
+        Self::set_view_state(&window, view_state.locked());
+        Ok(())
+    }
+
+    /// Attempts to open and decrypt an existing vault file
+    /// Repeated requests (double clicks, Enter pressed twice) are ignored while an
+    /// unlock is running or once the vault is open.
+    fn unlock_vault(window: &Weak<MainWindow>, state: &VaultState, location: String, password: String) -> Result<(), AppError> {
+        let Some(in_progress) = InProgressGuard::try_acquire(&UNLOCK_IN_PROGRESS) else {
+            log::debug!("Ignoring unlock request, another unlock is in progress");
+            return Ok(());
+        };
+        if state.lock()?.is_some() {
+            log::debug!("Ignoring unlock request, vault is already open");
+            return Ok(());
+        }
+
+        if let Some(remaining) = UNLOCK_THROTTLER.lock()?.remaining(Instant::now()) {
+            let message = format!(
+                "Too many failed unlock attempts. Try again in {} seconds.",
+                remaining.as_secs_f64().ceil() as u64
+            );
+
+            Self::show_message("Vault Locked", message);
+            return Ok(());
+        }
+
+        let window = window.upgrade().unwrap();
+        let state = state.clone();
+
+        slint::spawn_local(async move {
+            let _in_progress = in_progress;
+
+            window.set_unlocking(true);
+            let result = Self::try_unlock_vault(&window, &state, location, password).await;
+            window.set_unlocking(false);
+            Self::report_error(&window.as_weak(), result);
+        }).map_err(|e| AppError::Generic(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn try_unlock_vault(window: &MainWindow, state: &VaultState, location: String, password: String) -> Result<(), AppError> {
+        let path = PathBuf::from_str(location.as_str()).unwrap();
+
+        // Key derivation and decryption both take a while, run them off the UI thread
+        let (task_path, task_password) = (path.clone(), password.clone());
+        let opened = file::run_blocking(move || file::open_vault(&task_path, &task_password)).await?;
+
+        // Backup opened instead of the damaged vault file, if any
+        let mut restored_from: Option<PathBuf> = None;
+        let (bytes, key) = match opened {
+            Ok(opened) => {
+                UNLOCK_THROTTLER.lock()?.record_success();
+                opened
+            },
+            Err(OpenVaultError::Header(e)) => {
+                let message = Self::open_error_message(&e);
+
+                Self::show_message("Error", message);
+                return Ok(());
+            },
+            Err(OpenVaultError::Payload(e)) => {
+                // If the latest backup opens with the same password, the password was right and
+                // the vault file is damaged
+                let (task_path, task_password) = (path.clone(), password.clone());
+                let backup = file::run_blocking(move || file::open_latest_backup(&task_path, &task_password)).await?;
+
+                let Some((backup, bytes, key)) = backup else {
+                    // A truncated file says nothing about the password, don't count it as a failed attempt
+                    let damaged = matches!(e, FileError::Truncated);
+                    if !damaged {
+                        let delay = UNLOCK_THROTTLER.lock()?.record_failure(Instant::now());
+                        log::warn!("Failed unlock attempt, next attempt allowed in {:?}", delay);
+                    }
+
+                    let message = if damaged { e.to_string() } else { "Failed to open vault file. Check password.".to_string() };
+                    Self::show_message("Error", message);
+                    return Ok(());
+                };
+
+                UNLOCK_THROTTLER.lock()?.record_success();
+                if !Self::offer_backup(&backup).await {
+                    file::recycle_buffer(bytes);
+                    return Ok(());
+                }
+
+                restored_from = Some(backup);
+                (bytes, key)
+            }
+        };
+
+        let decoded = Vault::decode(bytes.as_ref());
+        file::recycle_buffer(bytes);
+
+        match decoded {
+            Ok(mut vault) => {
+                if !Self::claim_vault_file(window, &path).await? {
+                    return Ok(());
+                }
+
+                let (task_path, metadata_path) = (path.clone(), restored_from.clone().unwrap_or_else(|| path.clone()));
+                let (metadata, fingerprint) = file::run_blocking(move || {
+                    let metadata = file::read_vault_metadata(&metadata_path).ok().flatten()
+                        .unwrap_or_else(|| VaultMetadata::for_path(&task_path));
+                    (metadata, FileFingerprint::of(&task_path).ok())
+                }).await?;
+
+                let purged = {
+                    let mut vault_guard = state.lock()?;
+
+                    vault.key = Some(key);
+                    vault.metadata = metadata;
+                    vault.file_fingerprint = fingerprint;
+                    vault.restored_from_backup = restored_from.is_some();
+                    let purged = vault.purge_expired_trash(utils::unix_timestamp());
+
+                    *vault_guard = Some(vault);
+                    window.set_vault_open(true);
+
+                    #[cfg(debug_assertions)]
+                    if let Some(canary) = utils::scrub_check::plant_canary() {
+                        window.set_scrub_canary(canary);
+                    }
+                    window.set_favorites_only(false);
+
+                    let view_state = std::mem::take(&mut *VIEW_STATE.lock().unwrap());
+                    Self::set_view_state(window, view_state.unlocked());
+                    purged
+                };
+                Self::update_vault_items(window, state)?;
+                Self::apply_vault_appearance(window, state)?;
+                Self::watch_vault_file(window, state, &path)?;
+                Self::start_auto_lock(window, state)?;
+                Self::remember_recent_vault(&path)?;
+
+                if window.get_vault_read_only() {
+                    return Ok(());
+                }
+
+                // Migrate before anything else saves, which would upgrade without a backup
+                if file::detect_format(&path) == VaultFormat::Legacy {
+                    Self::offer_legacy_migration(window, state, &path).await?;
+                }
+
+                if purged > 0 {
+                    Self::save_vault_state(&window.as_weak(), state)?;
+                }
+            },
+            Err(e) => {
+                Self::show_message("Decode Error", format!("Failed to decode vault data: {}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the window size and position saved by the last session. A position that is no
+    /// longer on screen is left to the OS.
+    fn restore_geometry(window: &MainWindow, geometry: WindowGeometry) {
+        window.window().set_size(slint::PhysicalSize::new(geometry.width, geometry.height));
+
+        if geometry.is_on_screen() {
+            window.window().set_position(slint::PhysicalPosition::new(geometry.x, geometry.y));
+        } else {
+            log::info!("Saved window position ({}, {}) is off-screen, using the default", geometry.x, geometry.y);
+        }
+    }
+
+    /// Saves the window size and position for the next session. A minimized window reports
+    /// a meaningless position, the geometry from before it was minimized is kept instead.
+    fn save_geometry(window: &MainWindow) -> Result<(), AppError> {
+        if window.window().is_minimized() {
+            return Ok(());
+        }
+
+        let (position, size) = (window.window().position(), window.window().size());
+        let Some(geometry) = WindowGeometry::new(position.x, position.y, size.width.into(), size.height.into()) else {
+            return Ok(());
+        };
+
+        let mut settings = SETTINGS.lock()?;
+        settings.window_geometry = Some(geometry);
+        config::save(&settings).map_err(|e| AppError::IoError(e.to_string()))
+    }
+
+    /// Moves the vault to the front of the recently opened list and saves the settings.
+    /// A failed save only costs the list entry, so it is logged rather than shown.
+    fn remember_recent_vault(path: &Path) -> Result<(), AppError> {
+        if Self::is_snapshot(path) {
+            return Ok(());
+        }
+
+        let mut settings = SETTINGS.lock()?;
+        settings.add_recent_vault(path);
+
+        if let Err(e) = config::save(&settings) {
+            log::warn!("Failed to save settings: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Lists the daily snapshots in the backups directory on the backups page
+    fn browse_backups(window: &Weak<MainWindow>) -> Result<(), AppError> {
+        let window = window.upgrade().unwrap();
+        let Some(dir) = config::backups_dir(&*SETTINGS.lock()?) else {
+            return Err(AppError::Generic("No backups directory found".into()));
+        };
+
+        let entries: Vec<BackupEntry> = snapshot::list_snapshots(&dir)?
+            .into_iter()
+            .map(|snapshot| BackupEntry {
+                path: snapshot.path.display().to_string().into(),
+                vault_name: snapshot.vault_name.into(),
+                created: utils::format_date_time(snapshot.created_at).into(),
+            })
+            .collect();
+
+        window.set_backups(ModelRc::new(VecModel::from(entries)));
+        window.set_backups_location(dir.display().to_string().into());
+        window.set_active_page(Page::Backups);
+        Ok(())
+    }
+
+    /// Whether `path` is a daily snapshot. Snapshots are opened read-only so they stay as taken.
+    fn is_snapshot(path: &Path) -> bool {
+        SETTINGS.lock().ok()
+            .and_then(|settings| config::backups_dir(&settings))
+            .is_some_and(|dir| snapshot::is_snapshot(path, &dir))
+    }
+
+    /// Copies the vault just saved to `path` into the backups directory if it's the first save
+    /// of the day. Runs on its own so the copy never holds up the save.
+    fn schedule_snapshot(path: &Path) -> Result<(), AppError> {
+        let (dir, retention) = {
+            let settings = SETTINGS.lock()?;
+            (config::backups_dir(&settings), settings.snapshot_retention())
+        };
+        let Some(dir) = dir else {
+            return Ok(());
+        };
+
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            match snapshot::take_daily_snapshot(&path, &dir, retention, utils::unix_timestamp()) {
+                Ok(Some(snapshot)) => log::info!("Saved daily snapshot {}", snapshot.display()),
+                Ok(None) => {},
+                Err(e) => log::warn!("Failed to save daily snapshot of {}: {}", path.display(), e),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Takes the file lock for the vault being opened. If another window already holds it,
+    /// asks whether to open the vault read-only instead. Returns false if the user declined.
+    /// Snapshots are always opened read-only without a lock.
+    async fn claim_vault_file(window: &MainWindow, path: &Path) -> Result<bool, AppError> {
+        if Self::is_snapshot(path) {
+            window.set_vault_read_only(true);
+            return Ok(true);
+        }
+
+        match VaultLock::acquire(path) {
+            Ok(lock) => {
+                *VAULT_LOCK.lock()? = Some(lock);
+                window.set_vault_read_only(false);
+                Ok(true)
+            },
+            Err(VaultLockError::Held(pid)) => {
+                log::info!("Vault lock is held by process {:?}", pid);
+
+                let read_only = DialogWindowHandler::show_message(
+                    "Vault In Use",
+                    "This vault is open in another window.\n\nOpen it read-only?",
+                    DialogButtons::YesNo,
+                ).await == DialogResult::Yes;
+                window.set_vault_read_only(read_only);
+                Ok(read_only)
+            },
+            Err(e) => {
+                // Locking isn't supported everywhere (e.g. some network shares), don't refuse to open
+                log::warn!("Failed to lock vault file {}: {}", path.display(), e);
+                window.set_vault_read_only(false);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Asks whether to open `backup` after the vault file failed to decrypt but the backup
+    /// opened with the same password. Returns true if the user accepted.
+    async fn offer_backup(backup: &Path) -> bool {
+        let saved = fs::metadata(backup).ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| format!(" from {}", utils::format_date(modified.as_secs())))
+            .unwrap_or_default();
+
+        let description = format!(
+            "Your vault file appears to be corrupted. Open the backup{} instead?\n\n\
+            The next save replaces the vault file, the damaged one is kept with a .corrupt extension.",
+            saved
+        );
+
+        DialogWindowHandler::show_message("Vault Damaged", &description, DialogButtons::YesNo).await == DialogResult::Yes
+    }
+
+    /// Opens a system file picker to select a vault file, starting where a vault was last
+    /// opened, or in the default vault directory
+    fn open_existing_vault() -> Option<PathBuf> {
+        let dialog = rfd::FileDialog::new()
+            .set_title("Select Vault File")
+            .add_filter("Vault Files", &["vault"]);
+
+        show_file_dialog(dialog, DialogAction::Open, DialogContext::OpenVault)
+    }
+
+    /// Opens the CreateVaultWindow if it's not already visible and
+    /// disables input on the main window while open.
+    fn open_create_vault_window(window_weak: &Weak<MainWindow>, create_vault_window_handler: &Arc<Mutex<CreateVaultWindowHandler>>) {
+        Self::open_modal_window(window_weak, create_vault_window_handler);
+    }
+
+    /// Shows a child window and keeps the main window from taking input until it is closed.
+    /// The settings window goes through here as well once it exists.
+    fn open_modal_window<H: WindowHandler>(window_weak: &Weak<MainWindow>, child_handler: &Arc<Mutex<H>>) {
+        if let Ok(mut handler) = child_handler.lock()
+            && !handler.get_visible() {
+            let guard = Self::modal_guard_for(window_weak);
+            if let Err(e) = handler.show_modal(guard) {
+                log::error!("Failed to show window: {}", e);
+            }
+        }
+    }
+}
+
+impl WindowHandler for MainWindowHandler {
+    type Component = MainWindow;
+
+    fn get_window(&self) -> Weak<Self::Component> {
+        self.window.clone()
+    }
+
+    fn get_visible(&self) -> bool {
+        if let Ok(visible) = self.visible.lock() {
+            return *visible;
+        }
+
+        false
+    }
+
+    fn get_visible_arc(&self) -> Arc<Mutex<bool>> {
+        self.visible.clone()
+    }
+
+    fn set_visible(&mut self, value: bool) {
+        if let Ok(mut visible) = self.visible.lock() {
+            *visible = value;
+        }
+    }
+
+    /// Input stays off until the last child window holding a guard is closed
+    fn set_input_enabled(window: &MainWindow, enabled: bool) {
+        let open = MODAL_WINDOWS.with(|count| {
+            let open = if enabled { count.get().saturating_sub(1) } else { count.get() + 1 };
+            count.set(open);
+            open
+        });
+        window.set_disable_input(open > 0);
+    }
+
+    fn initialize(&mut self) -> UiResult<()> {
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        let window_weak = window.as_weak();
+        let state = self.state.clone();
+        let asking = Rc::new(Cell::new(false));  // Closing again while asked doesn't ask twice
+        window.window().on_close_requested(move || {
+            let draft = window_weak.upgrade().map(|window| Self::unsaved_draft(&window, &state));
+            let Some(Ok(Some(draft))) = draft else {
+                Self::exit(&window_weak, &state);
+            };
+            if asking.replace(true) {
+                return slint::CloseRequestResponse::KeepWindowShown;
+            }
+
+            let task_window = window_weak.clone();
+            let task_state = state.clone();
+            let task_asking = asking.clone();
+            let spawned = slint::spawn_local(async move {
+                let result = Self::exit_after_draft(&task_window, &task_state, draft).await;
+                task_asking.set(false);
+                Self::report_error(&task_window, result);
+            });
+            if let Err(e) = spawned {
+                asking.set(false);
+                Self::report_error(&window_weak, Err(AppError::Generic(e.to_string())));
+            }
+            slint::CloseRequestResponse::KeepWindowShown
+        });
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    use crate::utils::crypto::{ArgonParams, Crypto};
+
+    /// Stand-in for a network share that takes a while to answer
+    const SLOW_SHARE_LATENCY: Duration = Duration::from_millis(300);
+
+    fn slow_write(
+        bytes: &[u8], path: &Path, key: &ArgonKey, metadata: &VaultMetadata, backup_depth: usize,
+        expected: Option<&FileFingerprint>
+    ) -> Result<GuardedWrite, FileError> {
+        thread::sleep(SLOW_SHARE_LATENCY);
+        file::write_if_unchanged(bytes, path, key, metadata, backup_depth, expected)
+    }
+
+    #[test]
+    fn test_in_progress_guard_rejects_second_acquire() {
+        let flag = AtomicBool::new(false);
+
+        let guard = InProgressGuard::try_acquire(&flag).expect("First acquire failed");
+        assert!(InProgressGuard::try_acquire(&flag).is_none());
+
+        drop(guard);
+        assert!(InProgressGuard::try_acquire(&flag).is_some(), "Flag must be released on drop");
+    }
+
+    #[test]
+    fn test_rapid_unlock_attempts_initialize_once() {
+        let flag = AtomicBool::new(false);
+        let initialized = AtomicUsize::new(0);
+        let barrier = Barrier::new(2);
+
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    barrier.wait();
+                    if let Some(_guard) = InProgressGuard::try_acquire(&flag) {
+                        // Stand-in for key derivation, long enough for the other attempt to arrive
+                        thread::sleep(Duration::from_millis(50));
+                        initialized.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(initialized.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_first_failure_locks_for_one_second() {
+        let now = Instant::now();
+        let mut throttler = UnlockThrottler::new();
+        assert_eq!(throttler.remaining(now), None);
+
+        assert_eq!(throttler.record_failure(now), Duration::from_secs(1));
+        assert_eq!(throttler.remaining(now), Some(Duration::from_secs(1)));
+        assert_eq!(throttler.remaining(now + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn test_fifth_failure_locks_for_sixteen_seconds_and_delay_is_capped() {
+        let now = Instant::now();
+        let mut throttler = UnlockThrottler::new();
+
+        for _ in 0..4 {
+            throttler.record_failure(now);
+        }
+        assert_eq!(throttler.record_failure(now), Duration::from_secs(16));
+
+        for _ in 0..100 {
+            throttler.record_failure(now);
+        }
+        assert_eq!(throttler.record_failure(now), UNLOCK_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_success_resets_throttler() {
+        let now = Instant::now();
+        let mut throttler = UnlockThrottler::new();
+        throttler.record_failure(now);
+        throttler.record_failure(now);
+
+        throttler.record_success();
+
+        assert_eq!(throttler.remaining(now), None);
+        assert_eq!(throttler.record_failure(now), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_lockout_from_previous_session_is_respected() {
+        let now = Instant::now();
+        let now_unix = 1_700_000_000;
+
+        let mut previous = UnlockThrottler::new();
+        for _ in 0..3 {
+            previous.record_failure(now);
+        }
+        let saved = previous.locked_until_unix(now, now_unix);
+        assert_eq!(saved, Some(now_unix + 4));
+
+        // Next session starts a second later
+        let later = now + Duration::from_secs(1);
+        let restored = UnlockThrottler::restore(previous.attempts, saved, later, now_unix + 1);
+        assert_eq!(restored.remaining(later), Some(Duration::from_secs(3)));
+
+        let expired = UnlockThrottler::restore(previous.attempts, saved, later, now_unix + 10);
+        assert_eq!(expired.remaining(later), None);
+    }
+
+    /// Vault with items 0..count, like one after adding `count` items
+    fn vault_with_items(count: i32) -> Vault {
+        let mut vault = Vault::new();
+        vault.items.clear();
+        vault.nonce = 0;
+        for _ in 0..count {
+            vault.items.push(Item {
+                id: vault.nonce,
+                name: format!("Item {}", vault.nonce),
+                ..Default::default()
+            });
+            vault.nonce += 1;
+        }
+        vault
+    }
+
+    fn with_vault<T>(state: &VaultState, f: impl FnOnce(&Vault) -> T) -> T {
+        f(state.lock().unwrap().as_ref().expect("Vault was closed"))
+    }
+
+    #[test]
+    fn test_trash_and_restore_item() {
+        let state = VaultState::with_vault(vault_with_items(3));
+
+        MainWindowHandler::trash_item(&state, 1, 1_700_000_000).expect("Trash failed");
+        with_vault(&state, |vault| {
+            assert_eq!(vault.active_items().iter().map(|item| item.id).collect::<Vec<_>>(), vec![0, 2]);
+            assert_eq!(vault.trash()[0].deleted_at, Some(1_700_000_000));
+        });
+
+        MainWindowHandler::restore_item(&state, 1).expect("Restore failed");
+        with_vault(&state, |vault| assert_eq!(vault.active_items().len(), 3));
+    }
+
+    #[test]
+    fn test_filter_items_by_search() {
+        let vault = vault_with_items(3);
+        let items = vault.active_items();
+
+        let (query, _) = Query::parse_lenient("ITEM 1");
+        let shown: Vec<i32> = MainWindowHandler::filter_items(items.clone(), &query, false).iter().map(|item| item.id).collect();
+        assert_eq!(shown, vec![1]);
+
+        let (cleared, _) = Query::parse_lenient("");
+        assert_eq!(MainWindowHandler::filter_items(items, &cleared, false).len(), 3, "Clearing the search shows every item");
+    }
+
+    #[test]
+    fn test_fuzzy_filter_ranks_prefix_matches_first() {
+        let mut vault = vault_with_items(3);
+        vault.items[0].name = "My GitHub".to_string();
+        vault.items[1].name = "Get it done".to_string();
+        vault.items[2].name = "GitHub".to_string();
+
+        let (query, _) = Query::parse_lenient("ghub");
+        let shown: Vec<i32> = MainWindowHandler::filter_items(vault.active_items(), &query, true).iter().map(|item| item.id).collect();
+        assert_eq!(shown, vec![0, 2], "Equally good matches keep their order");
+
+        let (query, _) = Query::parse_lenient("git");
+        let shown: Vec<i32> = MainWindowHandler::filter_items(vault.active_items(), &query, true).iter().map(|item| item.id).collect();
+        assert_eq!(shown, vec![2, 0, 1], "Prefix, then substring, then the abbreviation");
+    }
+
+    #[test]
+    fn test_hidden_selection_is_cleared() {
+        assert!(MainWindowHandler::selection_shown(1, &[0, 1]));
+        assert!(!MainWindowHandler::selection_shown(2, &[0, 1]));
+        assert!(MainWindowHandler::selection_shown(-1, &[]), "Nothing selected stays that way");
+    }
+
+    #[test]
+    fn test_purge_only_removes_trashed_items() {
+        let state = VaultState::with_vault(vault_with_items(3));
+        MainWindowHandler::trash_item(&state, 0, 1_700_000_000).expect("Trash failed");
+        MainWindowHandler::trash_item(&state, 1, 1_700_000_000).expect("Trash failed");
+
+        MainWindowHandler::purge_item(&state, 2).expect("Purge failed");
+        MainWindowHandler::purge_item(&state, 0).expect("Purge failed");
+        with_vault(&state, |vault| {
+            assert_eq!(vault.items.len(), 2, "Active items must not be purged");
+            assert_eq!(vault.trash().len(), 1);
+        });
+
+        MainWindowHandler::purge_trash(&state).expect("Purge failed");
+        with_vault(&state, |vault| {
+            assert!(vault.trash().is_empty());
+            assert_eq!(vault.items.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_flip_favorite_reports_missing_items() {
+        let state = VaultState::with_vault(vault_with_items(2));
+
+        assert!(MainWindowHandler::flip_favorite(&state, 1).expect("Toggle failed"));
+        with_vault(&state, |vault| assert_eq!(vault.favorite_items()[0].id, 1));
+
+        assert!(!MainWindowHandler::flip_favorite(&state, 42).expect("Toggle failed"));
+        assert!(!MainWindowHandler::flip_favorite(&VaultState::default(), 1).expect("Toggle failed"));
+    }
+
+    #[test]
+    fn test_remove_items_in_one_pass() {
+        let state = VaultState::with_vault(vault_with_items(4));
+
+        assert_eq!(MainWindowHandler::remove_items(&state, &[0, 2, 9]).expect("Remove failed"), 2);
+        with_vault(&state, |vault| assert_eq!(vault.items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![1, 3]));
+
+        assert_eq!(MainWindowHandler::remove_items(&state, &[]).expect("Remove failed"), 0);
+        assert_eq!(MainWindowHandler::remove_items(&VaultState::default(), &[1]).expect("Remove failed"), 0);
+    }
+
+    #[test]
+    fn test_insert_blank_item_uses_next_id() {
+        let state = VaultState::with_vault(vault_with_items(2));
+
+        assert_eq!(MainWindowHandler::insert_blank_item(&state).expect("Insert failed"), Some(2));
+        assert_eq!(MainWindowHandler::insert_blank_item(&state).expect("Insert failed"), Some(3));
+        with_vault(&state, |vault| {
+            assert_eq!(vault.nonce, 4);
+            assert_eq!(vault.items[3].name, "New Item");
+            assert!(vault.items[3].created_at > 0, "New items record when they were added");
+            assert_eq!(vault.items[3].created_at, vault.items[3].modified_at);
+        });
+
+        assert_eq!(MainWindowHandler::insert_blank_item(&VaultState::default()).expect("Insert failed"), None);
+    }
+
+    #[test]
+    fn test_store_item_updates_matching_item() {
+        let state = VaultState::with_vault(vault_with_items(2));
+        let edited = VaultItem {
+            id: 1,
+            name: "GitHub".into(),
+            username: "admin".into(),
+            password: "hunter2".into(),
+            url: "https://github.com".into(),
+            notes: "work".into(),
+            ..Default::default()
+        };
+
+        MainWindowHandler::store_item(&state, &edited).expect("Store failed");
+
+        with_vault(&state, |vault| {
+            let item = &vault.items[1];
+            assert_eq!((item.name.as_str(), item.username.as_str(), item.password.as_str()), ("GitHub", "admin", "hunter2"));
+            assert_eq!(vault.items[0].name, "Item 0", "Other items must be left alone");
+        });
+    }
+
+    #[test]
+    fn test_store_item_tracks_password_changes() {
+        let state = VaultState::with_vault(vault_with_items(1));
+        let mut edited = VaultItem { id: 0, name: "Item 0".into(), ..Default::default() };
+
+        // Password is unchanged (empty), only the edit time moves
+        MainWindowHandler::store_item(&state, &edited).expect("Store failed");
+        with_vault(&state, |vault| {
+            assert!(vault.items[0].modified_at > 0);
+            assert_eq!(vault.items[0].password_changed_at, 0);
+        });
+
+        edited.password = "hunter2".into();
+        MainWindowHandler::store_item(&state, &edited).expect("Store failed");
+        with_vault(&state, |vault| assert!(vault.items[0].password_changed_at > 0));
+    }
+
+    fn draft_of(id: i32, password: &str) -> VaultItem {
+        VaultItem { id, name: format!("Item {}", id).into(), password: password.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_save_then_switch_stores_draft() {
+        let state = VaultState::with_vault(vault_with_items(2));
+        let draft = draft_of(0, "hunter2");
+
+        let pending = MainWindowHandler::pending_draft(&state, draft).expect("Lookup failed").expect("No draft");
+        assert!(MainWindowHandler::settle_draft(&state, &pending, UnsavedChoice::Save).expect("Settle failed"));
+
+        with_vault(&state, |vault| {
+            assert_eq!(vault.items[0].password, "hunter2");
+            assert!(vault.items[1].password.is_empty(), "The item switched to must be left alone");
+        });
+    }
+
+    #[test]
+    fn test_discard_then_switch_leaves_item_alone() {
+        let state = VaultState::with_vault(vault_with_items(2));
+        let draft = draft_of(0, "hunter2");
+
+        assert!(MainWindowHandler::settle_draft(&state, &draft, UnsavedChoice::Discard).expect("Settle failed"));
+        with_vault(&state, |vault| {
+            assert!(vault.items[0].password.is_empty());
+            assert_eq!(vault.items[0].modified_at, 0);
+        });
+    }
+
+    #[test]
+    fn test_cancel_keeps_editing() {
+        let state = VaultState::with_vault(vault_with_items(1));
+
+        assert!(!MainWindowHandler::settle_draft(&state, &draft_of(0, "hunter2"), UnsavedChoice::Cancel).expect("Settle failed"));
+        with_vault(&state, |vault| assert!(vault.items[0].password.is_empty()));
+    }
+
+    #[test]
+    fn test_pending_draft_tolerates_items_not_in_vault() {
+        let state = VaultState::with_vault(vault_with_items(1));
+
+        assert!(MainWindowHandler::pending_draft(&state, draft_of(-1, "")).expect("Lookup failed").is_none());
+        assert!(MainWindowHandler::pending_draft(&state, draft_of(5, "hunter2")).expect("Lookup failed").is_none());
+        assert!(MainWindowHandler::pending_draft(&VaultState::default(), draft_of(0, "")).expect("Lookup failed").is_none());
+    }
+
+    #[test]
+    fn test_store_password_replaces_only_the_password() {
+        let state = VaultState::with_vault(vault_with_items(2));
+        let mut password = ZeroByte::default();
+        password.extend_from_slice(b"Xk3#pq9!Lm2$");
+
+        MainWindowHandler::store_password(&state, 1, &password).expect("Store failed");
+
+        with_vault(&state, |vault| {
+            let item = &vault.items[1];
+            assert_eq!((item.name.as_str(), item.password.as_str()), ("Item 1", "Xk3#pq9!Lm2$"));
+            assert!(item.password_changed_at > 0 && item.modified_at > 0);
+            assert!(vault.items[0].password.is_empty(), "Other items must be left alone");
+        });
+    }
+
+    #[test]
+    fn test_store_appearance_ignores_invalid_indexes() {
+        let state = VaultState::with_vault(Vault::new());
+
+        assert!(MainWindowHandler::store_accent(&state, 2).expect("Store failed"));
+        assert!(!MainWindowHandler::store_accent(&state, ACCENT_PALETTE.len() as i32).expect("Store failed"));
+        assert!(!MainWindowHandler::store_accent(&state, -1).expect("Store failed"));
+        with_vault(&state, |vault| assert_eq!(vault.metadata.accent_color, ACCENT_PALETTE[2]));
+
+        assert!(MainWindowHandler::store_icon(&state, 0).expect("Store failed"));
+        with_vault(&state, |vault| assert_eq!(vault.metadata.icon.as_deref(), Some(VaultIcon::ALL[0].id())));
+        assert!(!MainWindowHandler::store_icon(&state, 99).expect("Store failed"));
+        assert!(MainWindowHandler::store_icon(&state, -1).expect("Store failed"));
+        with_vault(&state, |vault| assert_eq!(vault.metadata.icon, None));
+    }
+
+    #[tokio::test]
+    async fn test_health_report_is_cached_until_items_change() {
+        let state = VaultState::with_vault(vault_with_items(3));
+
+        let report = MainWindowHandler::compute_health_report(&state).await
+            .expect("Health report failed")
+            .expect("No vault open");
+        assert_eq!(report.total_items, 3);
+        assert!(with_vault(&state, |vault| vault.cached_health_report() == Some(&report)));
+
+        MainWindowHandler::trash_item(&state, 0, 1_700_000_000).expect("Trash failed");
+        assert!(with_vault(&state, |vault| vault.cached_health_report().is_none()));
+
+        let report = MainWindowHandler::compute_health_report(&state).await
+            .expect("Health report failed")
+            .expect("No vault open");
+        assert_eq!(report.total_items, 2);
+    }
+
+    #[tokio::test]
+    async fn test_health_report_without_vault() {
+        let state = VaultState::default();
+        let report = MainWindowHandler::compute_health_report(&state).await.expect("Health report failed");
+        assert!(report.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_delete_leaves_vault_file_untouched() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+
+        let mut vault = vault_with_items(2);
+        vault.key = Some(Crypto::derive_argon_key(b"password", None, ArgonParams::default()).expect("Key derivation failed"));
+        let state = VaultState::with_vault(vault);
+        MainWindowHandler::write_vault(&state, &path, false).await.expect("Save failed");
+        let written = fs::read(&path).expect("Failed to read vault file");
+
+        let mut asked_about = None;
+        let trashed = MainWindowHandler::trash_if_confirmed(&state, 1, 1_700_000_000, async |name: &str| {
+            asked_about = Some(name.to_string());
+            false
+        }).await.expect("Delete failed");
+
+        assert!(!trashed);
+        assert_eq!(asked_about.as_deref(), Some("Item 1"), "The prompt must name the item");
+        with_vault(&state, |vault| assert_eq!(vault.active_items().len(), 2));
+        assert_eq!(fs::read(&path).expect("Failed to read vault file"), written);
+        assert!(!file::backup_path(&path, 1).exists(), "Nothing may have been saved");
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_delete_trashes_item() {
+        let state = VaultState::with_vault(vault_with_items(2));
+
+        assert!(MainWindowHandler::trash_if_confirmed(&state, 0, 1_700_000_000, async |_: &str| true).await.expect("Delete failed"));
+        with_vault(&state, |vault| assert_eq!(vault.trash()[0].id, 0));
+
+        let mut asked = false;
+        let confirm = async |_: &str| { asked = true; true };
+        assert!(!MainWindowHandler::trash_if_confirmed(&state, 9, 1_700_000_000, confirm).await.expect("Delete failed"));
+        assert!(!asked, "Missing items aren't asked about");
+    }
+
+    #[tokio::test]
+    async fn test_poisoned_vault_is_reported_instead_of_panicking() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let state = VaultState::with_vault(Vault::new());
+
+        let poisoner = state.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock();
+            panic!("Poisoning the vault mutex on purpose");
+        }).join();
+
+        let result = MainWindowHandler::write_vault(&state, &path, false).await;
+
+        assert!(matches!(result, Err(AppError::PoisedState)), "Got {:?}", result);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_save_after_restoring_from_backup_sets_damaged_file_aside() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        fs::write(&path, b"damaged vault").expect("Failed to write");
+
+        let mut vault = Vault::new();
+        vault.key = Some(Crypto::derive_argon_key(b"password", None, ArgonParams::default()).expect("Key derivation failed"));
+        vault.file_fingerprint = FileFingerprint::of(&path).ok();
+        vault.restored_from_backup = true;
+        let state = VaultState::with_vault(vault);
+
+        MainWindowHandler::write_vault(&state, &path, false).await.expect("Save failed");
+
+        assert_eq!(fs::read(file::corrupt_path(&path)).unwrap(), b"damaged vault");
+        assert!(file::open_vault(&path, "password").is_ok());
+        assert!(!file::backup_path(&path, 1).exists(), "The damaged file must not become a backup");
+        assert!(!with_vault(&state, |vault| vault.restored_from_backup));
+    }
+
+    #[tokio::test]
+    async fn test_slow_save_keeps_event_loop_responsive() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+
+        let mut vault = Vault::new();
+        vault.key = Some(Crypto::derive_argon_key(b"password", None, ArgonParams::default()).expect("Key derivation failed"));
+        let state = VaultState::with_vault(vault);
+
+        // The test runtime is single threaded like the UI thread, so ticks only happen
+        // while the save is waiting on the blocking pool
+        let save = MainWindowHandler::write_vault_with(&state, &path, false, slow_write);
+        tokio::pin!(save);
+        let mut ticks = 0;
+
+        let result = loop {
+            tokio::select! {
+                result = &mut save => break result,
+                _ = tokio::time::sleep(Duration::from_millis(10)) => {
+                    // UI callbacks lock the vault to refresh the item list
+                    assert!(state.try_lock().is_ok(), "Vault must not stay locked during the write");
+                    ticks += 1;
+                }
+            }
+        };
+        let vault = state.lock().unwrap().take().expect("Vault was closed");
+
+        result.expect("Save failed");
+        assert!(ticks >= 10, "Event loop was blocked during the save ({} ticks)", ticks);
+        assert!(path.exists());
+        assert_eq!(vault.file_fingerprint, Some(FileFingerprint::of(&path).expect("Failed to fingerprint")));
+    }
+}
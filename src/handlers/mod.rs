@@ -1,10 +1,98 @@
 pub(super) mod dialog_window;
+pub(super) mod file_dialog;
 pub(super) mod main_window;
+pub(super) mod password_generator_window;
 pub(super) mod create_vault_window;
+pub(super) mod vault_state;
+pub(super) mod verify_vault_window;
+pub(super) mod view_state;
 
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use slint::{ComponentHandle, Weak};
+use slint::winit_030::{winit::event::WindowEvent, WinitWindowAccessor, WinitWindowEventResult};
+use slint::winit_030::winit::raw_window_handle::{HasDisplayHandle, RawDisplayHandle};
+
+use crate::errors::ui_errors::{UiError, UiResult};
+use crate::handlers::main_window::MainWindowHandler;
+use crate::utils::clipboard;
+
+
+/// Keeps a parent window from taking input while a child window is open.
+/// Input is enabled again when the guard is dropped.
+#[must_use = "Input is enabled again as soon as the guard is dropped"]
+pub(super) struct ModalGuard {
+    release: Option<Box<dyn FnOnce()>>,
+}
+
+impl ModalGuard {
+    fn new(release: impl FnOnce() + 'static) -> Self {
+        Self { release: Some(Box::new(release)) }
+    }
+}
+
+impl Drop for ModalGuard {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            release();
+        }
+    }
+}
+
+/// Holds the guard of the window that opened a child until the child is closed,
+/// whether through its own buttons or the close button of the title bar
+#[derive(Clone, Default)]
+pub(super) struct ModalSlot(Rc<RefCell<Option<ModalGuard>>>);
+
+impl ModalSlot {
+    fn hold(&self, guard: ModalGuard) {
+        // Replacing a guard drops it, so the previous owner is released first
+        self.0.replace(Some(guard));
+    }
+
+    fn release(&self) {
+        // Take before dropping, the guard may call back into the UI
+        let guard = self.0.borrow_mut().take();
+        drop(guard);
+    }
+}
+
+/// Child windows having focus counts as the app having focus, for locking on focus loss
+fn follow_focus(window: &slint::Window) {
+    window.on_winit_window_event(|_, event| {
+        if let WindowEvent::Focused(focused) = event {
+            MainWindowHandler::note_focus(*focused);
+        }
+        WinitWindowEventResult::Propagate
+    });
+}
+
+/// Lets the clipboard serve copies through `window`'s Wayland connection, see
+/// `clipboard::Backend`. Does nothing on X11 and Windows, or before winit made the window.
+fn attach_clipboard(window: &slint::Window) {
+    let display = window.with_winit_window(|winit_window| {
+        match winit_window.display_handle().map(|handle| handle.as_raw()) {
+            Ok(RawDisplayHandle::Wayland(handle)) => Some(handle.display),
+            _ => None,
+        }
+    });
+    if let Some(Some(display)) = display {
+        // SAFETY: the display belongs to the event loop, which outlives every window on this thread
+        unsafe { clipboard::attach_wayland(display) };
+    }
+}
+
+/// What closing a window through its title bar does to the handler state
+fn close_requested(visible: &Arc<Mutex<bool>>, modal: Option<&ModalSlot>) {
+    if let Ok(mut visible) = visible.lock() {
+        *visible = false;
+    }
+    if let Some(modal) = modal {
+        modal.release();
+    }
+}
 
 
 pub(super) trait WindowHandler {
@@ -14,46 +102,263 @@ pub(super) trait WindowHandler {
     fn get_visible(&self) -> bool;
     fn get_visible_arc(&self) -> Arc<Mutex<bool>>;
     fn set_visible(&mut self, value: bool);
+
+    /// Where a child window keeps the guard of the window that opened it.
+    /// Only windows that are shown with `show_modal` need one.
+    fn modal_slot(&self) -> Option<ModalSlot> {
+        None
+    }
+
+    /// Turns input on or off for `window`. Windows without a way to block input ignore it.
+    fn set_input_enabled(_window: &Self::Component, _enabled: bool) { }
     
 
-    fn initialize(&mut self) {
-        if let Some(window) = self.get_window().upgrade() {
-            let visible = self.get_visible_arc();
+    fn initialize(&mut self) -> UiResult<()> {
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        let visible = self.get_visible_arc();
+        let modal = self.modal_slot();
+        follow_focus(window.window());
+
+        window.window().on_close_requested(move || {
+            close_requested(&visible, modal.as_ref());
+            slint::CloseRequestResponse::HideWindow
+        });
 
-            window.window().on_close_requested(move || {
-                if let Ok(mut visible) = visible.lock() {
-                    *visible = false;    
-                }
-                slint::CloseRequestResponse::HideWindow
-            });
+        Ok(())
+    }
+
+    /// Shows the window and runs the event loop until it exits.
+    /// Returns `Ok(())` when the user closes the window, `UiError::InvalidHandle` if the window
+    /// has been dropped and `UiError::Platform` if the event loop itself fails.
+    fn run(&mut self) -> UiResult<()> {
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        self.initialize()?;
+        self.set_visible(true);
+        window.run()?;
+
+        Ok(())
+    }
+
+    /// Shows the window unless it is already visible.
+    /// Returns `UiError::InvalidHandle` if the window has been dropped.
+    fn show(&mut self) -> UiResult<()> {
+        if self.get_visible() {
+            return Ok(());
         }
+
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        self.initialize()?;
+        window.show()?;
+        self.set_visible(true);
+
+        Ok(())
     }
 
-    fn run(&mut self) {
-        if let Some(window) = self.get_window().upgrade() {
-            self.initialize();
-            self.set_visible(true);
-            window.run().expect("Failed to run window");
+    /// Shows the window and holds `guard` until it is closed.
+    /// The guard is dropped right away if the window can't be shown.
+    fn show_modal(&mut self, guard: ModalGuard) -> UiResult<()> {
+        self.show()?;
+        if let Some(modal) = self.modal_slot() {
+            modal.hold(guard);
         }
+
+        Ok(())
     }
 
-    fn show(&mut self) {
-        if self.get_visible() {
-            return;
+    /// Hides the window. Returns `UiError::InvalidHandle` if the window has been dropped.
+    fn hide(&mut self) -> UiResult<()> {
+        if let Some(modal) = self.modal_slot() {
+            modal.release();
         }
-        
-        if let Some(window) = self.get_window().upgrade() {
-            self.initialize();
-            self.set_visible(true);
-            window.show().expect("Failed to show window");
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        self.set_visible(false);
+        window.hide()?;
+
+        Ok(())
+    }
+
+    /// Returns `UiError::InvalidHandle` if the window has been dropped.
+    #[allow(dead_code)]  // Main window callbacks only hold a weak handle, see `modal_guard_for`
+    fn disable_input(&self) -> UiResult<()> {
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        Self::set_input_enabled(&window, false);
+
+        Ok(())
+    }
+
+    /// Returns `UiError::InvalidHandle` if the window has been dropped.
+    #[allow(dead_code)]  // Main window callbacks only hold a weak handle, see `modal_guard_for`
+    fn enable_input(&self) -> UiResult<()> {
+        let window = self.get_window().upgrade().ok_or(UiError::InvalidHandle)?;
+        Self::set_input_enabled(&window, true);
+
+        Ok(())
+    }
+
+    /// Disables input on this window until the returned guard is dropped.
+    /// Pass the guard to the child window's `show_modal`.
+    #[allow(dead_code)]  // Main window callbacks only hold a weak handle, see `modal_guard_for`
+    fn modal_guard(&self) -> ModalGuard where Self: Sized + 'static {
+        if let Err(e) = self.disable_input() {
+            log::warn!("Failed to disable window input: {}", e);
         }
+        Self::input_release(self.get_window())
     }
 
-    fn hide(&mut self) {
-        if let Some(window) = self.get_window().upgrade() {
-            self.set_visible(false);
-            window.hide().expect("Failed to hide window");
+    /// `modal_guard` for callbacks that only hold a weak handle to the window
+    fn modal_guard_for(window: &Weak<Self::Component>) -> ModalGuard where Self: Sized + 'static {
+        match window.upgrade() {
+            Some(window) => Self::set_input_enabled(&window, false),
+            None => log::warn!("Failed to disable window input: {}", UiError::InvalidHandle),
         }
+        Self::input_release(window.clone())
+    }
+
+    /// Guard that enables input on `window` again
+    fn input_release(window: Weak<Self::Component>) -> ModalGuard where Self: Sized + 'static {
+        ModalGuard::new(move || {
+            if let Some(window) = window.upgrade() {
+                Self::set_input_enabled(&window, true);
+            }
+        })
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use crate::VerifyVaultWindow;
+
+    /// Handler whose window has already been dropped
+    struct DroppedWindowHandler {
+        visible: Arc<Mutex<bool>>,
+        modal: ModalSlot,
+    }
+
+    impl DroppedWindowHandler {
+        fn new(visible: bool) -> Self {
+            Self { visible: Arc::new(Mutex::new(visible)), modal: ModalSlot::default() }
+        }
+    }
+
+    /// Guard that counts how often it re-enabled input
+    fn counting_guard() -> (ModalGuard, Rc<Cell<usize>>) {
+        let released = Rc::new(Cell::new(0));
+        let counter = released.clone();
+        (ModalGuard::new(move || counter.set(counter.get() + 1)), released)
+    }
+
+    impl WindowHandler for DroppedWindowHandler {
+        type Component = VerifyVaultWindow;
+
+        fn get_window(&self) -> Weak<Self::Component> {
+            Weak::default()
+        }
+
+        fn get_visible(&self) -> bool {
+            *self.visible.lock().unwrap()
+        }
+
+        fn get_visible_arc(&self) -> Arc<Mutex<bool>> {
+            self.visible.clone()
+        }
+
+        fn set_visible(&mut self, value: bool) {
+            *self.visible.lock().unwrap() = value;
+        }
+
+        fn modal_slot(&self) -> Option<ModalSlot> {
+            Some(self.modal.clone())
+        }
+    }
+
+    #[test]
+    fn test_show_fails_on_dropped_window() {
+        let mut handler = DroppedWindowHandler::new(false);
+
+        assert!(matches!(handler.show(), Err(UiError::InvalidHandle)));
+        assert!(!handler.get_visible(), "A window that failed to show must not be marked visible");
+    }
+
+    #[test]
+    fn test_show_is_ok_when_already_visible() {
+        let mut handler = DroppedWindowHandler::new(true);
+        assert!(handler.show().is_ok());
+    }
+
+    #[test]
+    fn test_hide_and_run_fail_on_dropped_window() {
+        let mut handler = DroppedWindowHandler::new(true);
+
+        assert!(matches!(handler.hide(), Err(UiError::InvalidHandle)));
+        assert!(matches!(handler.run(), Err(UiError::InvalidHandle)));
+        assert!(matches!(handler.initialize(), Err(UiError::InvalidHandle)));
+    }
+
+    #[test]
+    fn test_guard_enables_input_once_when_dropped() {
+        let (guard, released) = counting_guard();
+        assert_eq!(released.get(), 0);
+
+        drop(guard);
+        assert_eq!(released.get(), 1);
+    }
+
+    #[test]
+    fn test_close_button_releases_guard() {
+        let handler = DroppedWindowHandler::new(true);
+        let (guard, released) = counting_guard();
+        handler.modal.hold(guard);
+
+        close_requested(&handler.get_visible_arc(), handler.modal_slot().as_ref());
+        assert_eq!(released.get(), 1, "Closing the child must enable input on the parent");
+        assert!(handler.modal.0.borrow().is_none());
+        assert!(!handler.get_visible());
+
+        close_requested(&handler.get_visible_arc(), handler.modal_slot().as_ref());
+        assert_eq!(released.get(), 1, "A guard is only released once");
+    }
+
+    #[test]
+    fn test_hide_releases_guard_even_on_dropped_window() {
+        let mut handler = DroppedWindowHandler::new(true);
+        let (guard, released) = counting_guard();
+        handler.modal.hold(guard);
+
+        assert!(handler.hide().is_err());
+        assert_eq!(released.get(), 1);
+    }
+
+    #[test]
+    fn test_show_modal_failure_releases_guard() {
+        let mut handler = DroppedWindowHandler::new(false);
+        let (guard, released) = counting_guard();
+
+        assert!(matches!(handler.show_modal(guard), Err(UiError::InvalidHandle)));
+        assert_eq!(released.get(), 1, "The parent must not stay disabled when the child can't open");
+        assert!(handler.modal.0.borrow().is_none());
+    }
+
+    #[test]
+    fn test_new_guard_releases_the_previous_one() {
+        let handler = DroppedWindowHandler::new(true);
+        let (first, first_released) = counting_guard();
+        let (second, second_released) = counting_guard();
+
+        handler.modal.hold(first);
+        handler.modal.hold(second);
+        assert_eq!(first_released.get(), 1);
+        assert_eq!(second_released.get(), 0);
+    }
+
+    #[test]
+    fn test_input_on_dropped_window() {
+        let handler = DroppedWindowHandler::new(false);
+
+        assert!(matches!(handler.disable_input(), Err(UiError::InvalidHandle)));
+        assert!(matches!(handler.enable_input(), Err(UiError::InvalidHandle)));
+        drop(handler.modal_guard());
+    }
+}
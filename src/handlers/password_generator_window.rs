@@ -0,0 +1,197 @@
+use std::sync::{Arc, Mutex};
+
+use slint::{ComponentHandle, SharedString, Weak};
+
+use crate::{PasswordGeneratorWindow, PasswordOptions};
+use crate::handlers::WindowHandler;
+use crate::utils::clipboard;
+use crate::utils::crypto::{Crypto, GeneratorOptions, MAX_GENERATED_LEN, MIN_GENERATED_LEN};
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Called with the generated password when the user picks it for the selected item
+pub(crate) type UsePassword = Box<dyn Fn(&ZeroByte)>;
+
+/// Coordinates the PasswordGeneratorWindow, which makes random passwords to copy or put in
+/// the item selected in the main window.
+/// Holds ownership to prevent premature drop and supports weak upgrade for event binding.
+pub(crate) struct PasswordGeneratorWindowHandler {
+    _window_strong: PasswordGeneratorWindow,
+    window: Weak<PasswordGeneratorWindow>,
+    visible: Arc<Mutex<bool>>,
+    generated: Arc<Mutex<ZeroByte>>,  // The password shown in the preview
+}
+
+impl PasswordGeneratorWindowHandler {
+    /// Creates a new `PasswordGeneratorWindowHandler` and sets up window behavior.
+    /// Panics on window creation failure (app can't continue without it).
+    pub(crate) async fn new(use_password: UsePassword) -> Arc<Mutex<Self>> {
+        let window = PasswordGeneratorWindow::new().expect("Failed to create new PasswordGeneratorWindow");
+        window.set_win_title("Password Generator".into());
+        window.set_min_length(MIN_GENERATED_LEN as i32);
+        window.set_max_length(MAX_GENERATED_LEN as i32);
+        let weak = window.as_weak();
+        let handler = Self {
+            _window_strong: window,
+            window: weak,
+            visible: Arc::new(Mutex::new(false)),
+            generated: Arc::new(Mutex::new(ZeroByte::default())),
+        };
+
+        // Only ever used from the UI thread; Arc<Mutex> mirrors the other handlers' visibility state
+        #[allow(clippy::arc_with_non_send_sync)]
+        let handler = Arc::new(Mutex::new(handler));
+        Self::setup(&handler, use_password);
+
+        handler
+    }
+
+    /// Shows the window with a fresh password. `can_use` enables putting it in the item
+    /// selected in the main window.
+    pub(crate) fn open(&mut self, can_use: bool) {
+        let Some(window) = self.get_window().upgrade() else { return; };
+        window.set_can_use(can_use);
+        window.set_show_preview(false);
+        window.invoke_regenerate();
+
+        if let Err(e) = self.show() {
+            log::error!("Failed to show window: {}", e);
+        }
+    }
+
+    fn setup(handler_arc: &Arc<Mutex<Self>>, use_password: UsePassword) {
+        let (window, generated) = {
+            let handler = handler_arc.lock().unwrap();
+            (handler.get_window().upgrade().unwrap(), Arc::clone(&handler.generated))
+        };
+
+        let generated_regenerate = Arc::clone(&generated);
+        window.on_regenerate_password(move |options: PasswordOptions| {
+            Self::regenerate(&generated_regenerate, options)
+        });
+
+        let generated_copy = Arc::clone(&generated);
+        let window_weak = window.as_weak();
+        window.on_copy_password(move || {
+            if let Some(window) = window_weak.upgrade() {
+                super::attach_clipboard(window.window());
+            }
+            if let Ok(generated) = generated_copy.lock()
+                && let Ok(password) = std::str::from_utf8(generated.as_ref())
+                && let Err(e) = clipboard::copy_text(password) {
+                log::warn!("{}", e);
+            }
+        });
+
+        let handler_arc_use = Arc::clone(handler_arc);
+        let generated_use = Arc::clone(&generated);
+        window.on_use_password(move || {
+            if let Ok(generated) = generated_use.lock() {
+                use_password(&generated);
+            }
+            Self::close(&handler_arc_use);
+        });
+
+        let handler_arc_close = Arc::clone(handler_arc);
+        window.on_close(move || Self::close(&handler_arc_close));
+    }
+
+    /// Replaces the stored password with a new one and returns it for the preview, or an
+    /// empty string if the options don't allow one
+    fn regenerate(generated: &Mutex<ZeroByte>, options: PasswordOptions) -> SharedString {
+        let Ok(mut generated) = generated.lock() else { return SharedString::new(); };
+        generated.clear();
+
+        match Crypto::generate_password(Self::generator_options(&options)) {
+            Ok(password) => {
+                generated.extend_from_slice(password.as_ref());
+                std::str::from_utf8(generated.as_ref()).unwrap_or_default().into()
+            },
+            Err(e) => {
+                log::warn!("Can't generate a password: {}", e);
+                SharedString::new()
+            },
+        }
+    }
+
+    fn generator_options(options: &PasswordOptions) -> GeneratorOptions {
+        GeneratorOptions {
+            length: usize::try_from(options.length).unwrap_or_default(),
+            uppercase: options.uppercase,
+            lowercase: options.lowercase,
+            digits: options.digits,
+            symbols: options.symbols,
+        }
+    }
+
+    /// Hides the window and wipes the password it showed
+    fn close(handler_arc: &Arc<Mutex<Self>>) {
+        let Ok(mut handler) = handler_arc.lock() else { return; };
+
+        if let Ok(mut generated) = handler.generated.lock() {
+            generated.clear();
+        }
+        if let Some(window) = handler.get_window().upgrade() {
+            window.set_preview(SharedString::new());
+        }
+        if let Err(e) = handler.hide() {
+            log::error!("Failed to hide window: {}", e);
+        }
+    }
+}
+
+impl WindowHandler for PasswordGeneratorWindowHandler {
+    type Component = PasswordGeneratorWindow;
+
+    fn get_window(&self) -> Weak<Self::Component> {
+        self.window.clone()
+    }
+
+    fn get_visible(&self) -> bool {
+        if let Ok(visible) = self.visible.lock() {
+            return *visible;
+        }
+
+        false
+    }
+
+    fn get_visible_arc(&self) -> Arc<Mutex<bool>> {
+        self.visible.clone()
+    }
+
+    fn set_visible(&mut self, value: bool) {
+        if let Ok(mut visible) = self.visible.lock() {
+            *visible = value;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(length: i32, digits_only: bool) -> PasswordOptions {
+        PasswordOptions { length, uppercase: !digits_only, lowercase: !digits_only, digits: true, symbols: !digits_only }
+    }
+
+    #[test]
+    fn test_regenerate_stores_the_previewed_password() {
+        let generated = Mutex::new(ZeroByte::default());
+
+        let preview = PasswordGeneratorWindowHandler::regenerate(&generated, options(16, true));
+        assert_eq!(preview.len(), 16);
+        assert!(preview.bytes().all(|byte| byte.is_ascii_digit()));
+        assert_eq!(generated.lock().unwrap().as_ref(), preview.as_bytes());
+    }
+
+    #[test]
+    fn test_invalid_options_clear_the_password() {
+        let generated = Mutex::new(ZeroByte::default());
+        PasswordGeneratorWindowHandler::regenerate(&generated, options(16, false));
+
+        let preview = PasswordGeneratorWindowHandler::regenerate(&generated, options(-1, false));
+        assert!(preview.is_empty());
+        assert_eq!(generated.lock().unwrap().len(), 0, "No stale password may be copied or used");
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::{Arc, LockResult, Mutex, MutexGuard};
+
+use crate::models::vault::Vault;
+
+
+/// The open vault, None while no vault is unlocked. Clones share the same vault, so the
+/// window's callbacks and background tasks each hold one instead of using a global.
+#[derive(Clone, Default)]
+pub(crate) struct VaultState {
+    vault: Arc<Mutex<Option<Vault>>>,
+}
+
+impl VaultState {
+    pub(crate) fn lock(&self) -> LockResult<MutexGuard<'_, Option<Vault>>> {
+        self.vault.lock()
+    }
+}
+
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use std::sync::TryLockResult;
+
+    use super::*;
+
+    impl VaultState {
+        /// State with `vault` already open
+        pub(crate) fn with_vault(vault: Vault) -> Self {
+            Self { vault: Arc::new(Mutex::new(Some(vault))) }
+        }
+
+        pub(crate) fn try_lock(&self) -> TryLockResult<MutexGuard<'_, Option<Vault>>> {
+            self.vault.try_lock()
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use slint::{ComponentHandle, SharedString, Weak};
+use zeroize::Zeroize;
+
+use crate::VerifyVaultWindow;
+use crate::handlers::file_dialog::{show_file_dialog, DialogAction};
+use crate::handlers::WindowHandler;
+use crate::models::settings::DialogContext;
+use crate::utils::file;
+
+
+/// Coordinates the VerifyVaultWindow, which checks a vault file without opening it in the main window.
+/// Holds ownership to prevent premature drop and supports weak upgrade for event binding.
+pub(crate) struct VerifyVaultWindowHandler {
+    _window_strong: VerifyVaultWindow,
+    window: Weak<VerifyVaultWindow>,
+    visible: Arc<Mutex<bool>>,
+}
+
+impl VerifyVaultWindowHandler {
+    /// Creates a new `VerifyVaultWindowHandler` and sets up window behavior.
+    /// Panics on window creation failure (app can't continue without it).
+    pub(crate) async fn new() -> Arc<Mutex<Self>> {
+        let window = VerifyVaultWindow::new().expect("Failed to create new VerifyVaultWindow");
+        window.set_win_title("Verify Vault File".into());
+        let weak = window.as_weak();
+        let handler = Self {
+            _window_strong: window,
+            window: weak,
+            visible: Arc::new(Mutex::new(false)),
+        };
+
+        // Only ever used from the UI thread; Arc<Mutex> mirrors the other handlers' visibility state
+        #[allow(clippy::arc_with_non_send_sync)]
+        let handler = Arc::new(Mutex::new(handler));
+        Self::setup(&handler);
+
+        handler
+    }
+
+    fn setup(handler_arc: &Arc<Mutex<Self>>) {
+        let window = handler_arc.lock().unwrap().get_window().upgrade().unwrap();
+
+        let window_weak_choose = window.as_weak();
+        window.on_choose_file(move || {
+            if let Some(window) = window_weak_choose.upgrade()
+                && let Some(path) = Self::open_file_dialog() {
+                window.set_vault_location(path.display().to_string().into());
+                window.set_report(SharedString::new());
+            }
+        });
+
+        let window_weak_verify = window.as_weak();
+        window.on_verify(move |password: SharedString| {
+            let Some(window) = window_weak_verify.upgrade() else { return; };
+            let path = PathBuf::from(window.get_vault_location().as_str());
+            let window_weak = window_weak_verify.clone();
+
+            window.set_busy(true);
+            window.set_report(SharedString::new());
+
+            // Key derivation and decryption take a while, keep them off the UI thread
+            slint::spawn_local(async move {
+                let mut password = password.to_string();
+                let report = file::run_blocking(move || {
+                    let report = file::verify_vault(&path, &password);
+                    password.zeroize();
+                    report
+                }).await;
+
+                if let Some(window) = window_weak.upgrade() {
+                    let summary = match report {
+                        Ok(report) if report.passed() => format!("The vault file is healthy.\n\n{}", report),
+                        Ok(report) => format!("The vault file has problems.\n\n{}", report),
+                        Err(e) => format!("The check could not be run: {}", e),
+                    };
+
+                    window.set_report(summary.into());
+                    window.set_vault_password(SharedString::new());
+                    window.set_busy(false);
+                }
+            }).ok();
+        });
+
+        let handler_arc_close = Arc::clone(handler_arc);
+        window.on_close(move || {
+            if let Ok(mut handler) = handler_arc_close.lock()
+                && let Err(e) = handler.hide() {
+                log::error!("Failed to hide window: {}", e);
+            }
+        });
+    }
+
+    /// Opens a file dialog for picking the vault file to verify
+    fn open_file_dialog() -> Option<PathBuf> {
+        let dialog = rfd::FileDialog::new()
+            .set_title("Select Vault File")
+            .add_filter("Vault Files", &["vault", "bak1", "bak2", "bak3"]);
+
+        show_file_dialog(dialog, DialogAction::Open, DialogContext::OpenVault)
+    }
+}
+
+impl WindowHandler for VerifyVaultWindowHandler {
+    type Component = VerifyVaultWindow;
+
+    fn get_window(&self) -> Weak<Self::Component> {
+        self.window.clone()
+    }
+
+    fn get_visible(&self) -> bool {
+        if let Ok(visible) = self.visible.lock() {
+            return *visible;
+        }
+
+        false
+    }
+
+    fn get_visible_arc(&self) -> Arc<Mutex<bool>> {
+        self.visible.clone()
+    }
+
+    fn set_visible(&mut self, value: bool) {
+        if let Ok(mut visible) = self.visible.lock() {
+            *visible = value;
+        }
+    }
+}
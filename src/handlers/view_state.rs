@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+
+use crate::utils::file::VaultFileHeader;
+use crate::{utils, Page};
+
+
+/// What the main window is currently showing, along with the session data that page needs.
+/// The handler keeps the current state and pushes it to the window with `UiProps`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) enum ViewState {
+    /// Nothing selected, choose between creating and opening a vault
+    #[default]
+    Setup,
+    /// A vault file was just picked and is waiting for its password
+    Unlock { path: PathBuf, header: VaultFileHeader },
+    /// The session's vault was locked. Path and header are kept so unlocking
+    /// again doesn't need the file picker.
+    Locked { path: PathBuf, header: VaultFileHeader },
+    /// Vault is unlocked
+    Vault { path: PathBuf, header: VaultFileHeader },
+}
+
+/// Main window properties derived from a `ViewState`
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct UiProps {
+    pub page: Page,
+    pub vault_location: String,
+    pub locked: bool,        // Location is read-only and "change file" is offered instead of cancel
+    pub kdf_summary: String,
+    pub vault_info: String,  // Name and creation date from the unencrypted metadata
+}
+
+impl ViewState {
+    /// Path and header of the vault this session is working with, if any
+    fn session(&self) -> Option<(&PathBuf, &VaultFileHeader)> {
+        match self {
+            Self::Setup => None,
+            Self::Unlock { path, header }
+                | Self::Locked { path, header }
+                | Self::Vault { path, header } => Some((path, header)),
+        }
+    }
+
+    /// State after a successful unlock. Only valid from `Unlock` and `Locked`.
+    pub(crate) fn unlocked(self) -> Self {
+        match self {
+            Self::Unlock { path, header } | Self::Locked { path, header } => Self::Vault { path, header },
+            other => other,
+        }
+    }
+
+    /// State after locking an open vault. Other states are returned unchanged.
+    pub(crate) fn locked(self) -> Self {
+        match self {
+            Self::Vault { path, header } => Self::Locked { path, header },
+            other => other,
+        }
+    }
+
+    pub(crate) fn props(&self) -> UiProps {
+        let page = match self {
+            Self::Setup => Page::Setup,
+            Self::Unlock { .. } | Self::Locked { .. } => Page::UnlockVault,
+            Self::Vault { .. } => Page::Vault,
+        };
+
+        UiProps {
+            page,
+            vault_location: self.session().map(|(path, _)| path.display().to_string()).unwrap_or_default(),
+            locked: matches!(self, Self::Locked { .. }),
+            kdf_summary: self.session().map(|(_, header)| kdf_summary(header)).unwrap_or_default(),
+            vault_info: self.session().map(|(_, header)| vault_info(header)).unwrap_or_default(),
+        }
+    }
+}
+
+/// Short human readable description of the header's key derivation settings
+fn kdf_summary(header: &VaultFileHeader) -> String {
+    let params = header.params;
+    format!(
+        "{}, {} MB memory, {} iterations, {} lanes",
+        params.algorithm.name(),
+        params.memory_cost / 1000,
+        params.time_cost,
+        params.parallelism,
+    )
+}
+
+/// Vault name, creation date and whether it needs a hardware key, empty for files without metadata
+fn vault_info(header: &VaultFileHeader) -> String {
+    let Some(metadata) = &header.metadata else { return String::new(); };
+
+    let mut info = metadata.name();
+    if metadata.created_at > 0 {
+        info = format!("{}, created {}", info, utils::format_date(metadata.created_at));
+    }
+    if metadata.requires_hardware_key() {
+        info.push_str(", needs a hardware key");
+    }
+
+    info
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::crypto::{ArgonParams, KdfAlgorithm};
+    use crate::utils::file::{PayloadCompression, VaultMetadata};
+
+    fn header() -> VaultFileHeader {
+        VaultFileHeader {
+            version: 1,
+            salt: [7u8; 16],
+            params: ArgonParams { algorithm: KdfAlgorithm::Argon2d, ..Default::default() },
+            metadata: Some(VaultMetadata::new("Work", 1_700_000_000)),
+            compression: PayloadCompression::None,
+            payload_len: None,
+        }
+    }
+
+    fn unlock_state() -> ViewState {
+        ViewState::Unlock { path: PathBuf::from("/tmp/work.vault"), header: header() }
+    }
+
+    #[test]
+    fn test_locked_state_keeps_path_and_header() {
+        let state = unlock_state().unlocked().locked();
+
+        assert_eq!(state.props(), UiProps {
+            page: Page::UnlockVault,
+            vault_location: "/tmp/work.vault".into(),
+            locked: true,
+            kdf_summary: "Argon2d, 15 MB memory, 50 iterations, 2 lanes".into(),
+            vault_info: "Work, created 2023-11-14".into(),
+        });
+    }
+
+    #[test]
+    fn test_unlock_state_is_not_locked() {
+        let props = unlock_state().props();
+
+        assert_eq!(props.page, Page::UnlockVault);
+        assert_eq!(props.vault_location, "/tmp/work.vault");
+        assert!(!props.locked);
+        assert!(!props.kdf_summary.is_empty());
+    }
+
+    #[test]
+    fn test_unlocking_a_locked_session_returns_to_vault() {
+        let state = unlock_state().unlocked().locked().unlocked();
+        let props = state.props();
+
+        assert_eq!(state, ViewState::Vault { path: PathBuf::from("/tmp/work.vault"), header: header() });
+        assert_eq!(props.page, Page::Vault);
+        assert_eq!(props.vault_location, "/tmp/work.vault", "Saving relies on the vault location");
+        assert!(!props.locked);
+    }
+
+    #[test]
+    fn test_setup_state_has_no_session() {
+        let props = ViewState::Setup.locked().props();
+
+        assert_eq!(props.page, Page::Setup);
+        assert!(props.vault_location.is_empty());
+        assert!(!props.locked);
+        assert!(props.kdf_summary.is_empty());
+        assert!(props.vault_info.is_empty());
+    }
+
+    #[test]
+    fn test_vault_info_without_metadata_or_date() {
+        let mut header = header();
+        header.metadata.as_mut().unwrap().created_at = 0;
+        assert_eq!(vault_info(&header), "Work");
+
+        header.metadata = None;
+        assert_eq!(vault_info(&header), "");
+    }
+
+    #[test]
+    fn test_vault_info_mentions_hardware_key() {
+        let mut header = header();
+        header.metadata.as_mut().unwrap().fido2_credential_id = Some(vec![1, 2, 3]);
+
+        assert_eq!(vault_info(&header), "Work, created 2023-11-14, needs a hardware key");
+    }
+}
@@ -1,25 +1,61 @@
 // Prevent console window in addition to Slint window in Windows release builds when, e.g., starting the app via file manager. Ignored on other platforms.
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+// The CLI build keeps the console so commands can print to it.
+#![cfg_attr(all(not(debug_assertions), not(feature = "cli")), windows_subsystem = "windows")]
 
+#[cfg(feature = "cli")]
+mod cli;
 mod errors;
 mod handlers;
 mod models;
 mod utils;
 
 use handlers::WindowHandler;
+use handlers::create_vault_window::CreateVaultWindowHandler;
 use handlers::main_window::MainWindowHandler;
 
 slint::include_modules!();
 
 #[tokio::main]
 async fn main() {
+    #[cfg(feature = "cli")]
+    run_cli_if_requested();
+
     #[cfg(debug_assertions)]
     print_debug_message();
 
+    let settings = utils::config::load();
+    if settings.auto_calibrate_on_first_run {
+        CreateVaultWindowHandler::calibrate_in_background();
+    }
+
     // Start the main window
-    let mut main_window_handler = MainWindowHandler::new().await;
+    let mut main_window_handler = MainWindowHandler::new(settings).await;
     main_window_handler.get_window().upgrade().unwrap().set_win_title("NoPass".into());
-    main_window_handler.run();
+    if let Err(e) = main_window_handler.run() {
+        eprintln!("Failed to run main window: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Runs a headless command and exits instead of opening the window when one was given on the
+/// command line. Without a terminal the window still opens, so a file manager launch that
+/// passes a path along never ends up in the CLI.
+#[cfg(feature = "cli")]
+fn run_cli_if_requested() {
+    use std::io::IsTerminal;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let has_terminal = std::io::stdout().is_terminal() || std::io::stdin().is_terminal() || std::io::stderr().is_terminal();
+    if !cli::is_cli_invocation(&args) || !(has_terminal || args.iter().any(|arg| arg == "--vault")) {
+        return;
+    }
+
+    let result = cli::CliArgs::parse(&args).and_then(cli::run_cli);
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
 }
 
 /// Display prominent debug build warning if the debug feature in enabled
@@ -0,0 +1,131 @@
+use crate::errors::appearance_errors::AppearanceError;
+
+
+/// Accent colors a vault can pick from. Limited to a curated set that keeps enough
+/// contrast against the dark UI, rather than allowing arbitrary colors.
+pub(crate) const ACCENT_PALETTE: [&str; 8] = [
+    "#00b48a",  // Teal, the app's own accent
+    "#3d8bfd",  // Blue
+    "#8c6cf2",  // Purple
+    "#e0568f",  // Pink
+    "#f08c3c",  // Orange
+    "#f5c542",  // Yellow
+    "#5cb85c",  // Green
+    "#9a9a9a",  // Gray
+];
+
+pub(crate) const DEFAULT_ACCENT: &str = ACCENT_PALETTE[0];
+
+/// Small icons bundled with the app that a vault can be marked with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum VaultIcon {
+    Key,
+    Briefcase,
+    Home,
+    Shield,
+    Star,
+}
+
+impl VaultIcon {
+    pub(crate) const ALL: [Self; 5] = [Self::Key, Self::Briefcase, Self::Home, Self::Shield, Self::Star];
+
+    /// Stable identifier stored in the vault metadata
+    pub(crate) fn id(self) -> &'static str {
+        match self {
+            Self::Key => "key",
+            Self::Briefcase => "briefcase",
+            Self::Home => "home",
+            Self::Shield => "shield",
+            Self::Star => "star",
+        }
+    }
+
+    pub(crate) fn from_id(id: &str) -> Result<Self, AppearanceError> {
+        Self::ALL.into_iter()
+            .find(|icon| icon.id() == id)
+            .ok_or_else(|| AppearanceError::UnknownIcon(id.to_string()))
+    }
+
+    /// SVG data embedded at build time
+    pub(crate) fn svg(self) -> &'static [u8] {
+        match self {
+            Self::Key => include_bytes!("../../ui/assets/icons/vault/key.svg"),
+            Self::Briefcase => include_bytes!("../../ui/assets/icons/vault/briefcase.svg"),
+            Self::Home => include_bytes!("../../ui/assets/icons/vault/home.svg"),
+            Self::Shield => include_bytes!("../../ui/assets/icons/vault/shield.svg"),
+            Self::Star => include_bytes!("../../ui/assets/icons/vault/star.svg"),
+        }
+    }
+}
+
+/// Checks that `color` is a `#rrggbb` color from `ACCENT_PALETTE`, returning the palette entry
+pub(crate) fn validate_accent(color: &str) -> Result<&'static str, AppearanceError> {
+    let normalized = color.trim().to_lowercase();
+    parse_hex(&normalized).ok_or_else(|| AppearanceError::InvalidColor(color.to_string()))?;
+
+    ACCENT_PALETTE.into_iter()
+        .find(|&accent| accent == normalized.as_str())
+        .ok_or(AppearanceError::NotInPalette(normalized))
+}
+
+/// Accent to show for a stored value. Anything outside the palette (e.g. a file edited
+/// by hand) falls back to the default.
+pub(crate) fn accent_or_default(color: &str) -> &'static str {
+    validate_accent(color).unwrap_or(DEFAULT_ACCENT)
+}
+
+/// Red, green and blue components of a `#rrggbb` color
+pub(crate) fn parse_hex(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.is_ascii())?;
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+    Some((component(0)?, component(2)?, component(4)?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_entries_are_valid_and_unique() {
+        for (i, color) in ACCENT_PALETTE.iter().enumerate() {
+            assert!(parse_hex(color).is_some(), "{} is not a #rrggbb color", color);
+            assert_eq!(validate_accent(color), Ok(*color));
+            assert!(!ACCENT_PALETTE[..i].contains(color), "{} is listed twice", color);
+        }
+    }
+
+    #[test]
+    fn test_palette_keeps_contrast_against_dark_background() {
+        // Relative luminance well above the #1c1c1c window background
+        for color in ACCENT_PALETTE {
+            let (r, g, b) = parse_hex(color).unwrap();
+            let luminance = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+            assert!(luminance > 100.0, "{} is too dark", color);
+        }
+    }
+
+    #[test]
+    fn test_validate_accent_normalizes_case_and_whitespace() {
+        assert_eq!(validate_accent(" #3D8BFD "), Ok("#3d8bfd"));
+    }
+
+    #[test]
+    fn test_validate_accent_rejects_invalid_and_off_palette_colors() {
+        assert_eq!(validate_accent("blue"), Err(AppearanceError::InvalidColor("blue".into())));
+        assert_eq!(validate_accent("#12345"), Err(AppearanceError::InvalidColor("#12345".into())));
+        assert_eq!(validate_accent("#gg0000"), Err(AppearanceError::InvalidColor("#gg0000".into())));
+        assert_eq!(validate_accent("#000000"), Err(AppearanceError::NotInPalette("#000000".into())));
+        assert_eq!(accent_or_default("#000000"), DEFAULT_ACCENT);
+    }
+
+    #[test]
+    fn test_icon_ids_round_trip_and_svgs_are_embedded() {
+        for icon in VaultIcon::ALL {
+            assert_eq!(VaultIcon::from_id(icon.id()), Ok(icon));
+            assert!(icon.svg().starts_with(b"<svg"));
+        }
+        assert_eq!(VaultIcon::from_id("../secret"), Err(AppearanceError::UnknownIcon("../secret".into())));
+    }
+}
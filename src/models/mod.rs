@@ -1 +1,3 @@
+pub(super) mod appearance;
+pub(super) mod settings;
 pub(super) mod vault;
\ No newline at end of file
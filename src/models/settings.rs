@@ -0,0 +1,363 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::models::vault::SortOrder;
+use crate::utils::crypto::ArgonParams;
+use crate::utils::snapshot::SnapshotRetention;
+
+
+/// Number of recently opened vaults remembered
+pub(crate) const MAX_RECENT_VAULTS: usize = 10;
+
+// Bounds for a restored window size, in physical pixels
+const MIN_WINDOW_WIDTH: u32 = 400;
+const MIN_WINDOW_HEIGHT: u32 = 300;
+const MAX_WINDOW_SIZE: u32 = 16384;
+
+/// Part of the window's title bar that must be on screen for a saved position to be used
+const MIN_VISIBLE_TITLE_BAR: i64 = 100;
+/// How far the top of a window may sit above the desktop, e.g. maximized windows on Windows
+/// are placed a few pixels past the edges
+const MAX_TOP_OVERHANG: i64 = 32;
+/// Largest desktop coordinate a saved position may have. There is no monitor list to check
+/// against, so anything beyond a large multi-monitor desktop is treated as off-screen.
+const MAX_DESKTOP_EXTENT: i64 = 16384;
+
+/// Application preferences, stored by `utils::config`. Missing fields take their default,
+/// so settings files written by older versions keep loading.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct Settings {
+    /// Seconds before a copied secret is cleared from the clipboard, None to keep it
+    #[serde(with = "zero_is_never")]
+    pub(crate) clipboard_clear_secs: Option<u64>,
+    /// Seconds without interaction before the vault locks itself, None to never lock
+    #[serde(with = "zero_is_never")]
+    pub(crate) auto_lock_secs: Option<u64>,
+    /// Seconds the app may be in the background before the vault locks itself, None to
+    /// ignore focus. Meant for shared machines.
+    #[serde(with = "zero_is_never")]
+    pub(crate) lock_on_focus_loss_secs: Option<u64>,
+    /// Most recently opened first
+    pub(crate) recent_vaults: Vec<PathBuf>,
+    /// Where daily snapshots of saved vaults go, None for `backups` in the app data directory
+    pub(crate) backups_dir: Option<PathBuf>,
+    /// Most daily snapshots kept per vault, 0 for no limit
+    pub(crate) snapshot_retention_count: usize,
+    /// Days a daily snapshot is kept, None to keep them regardless of age
+    #[serde(with = "zero_is_never")]
+    pub(crate) snapshot_retention_days: Option<u64>,
+    /// Whether key derivation is tuned to this machine the next time the app starts
+    pub(crate) auto_calibrate_on_first_run: bool,
+    /// Key derivation parameters for new vaults, None for the built-in defaults
+    pub(crate) argon_params: Option<ArgonParams>,
+    /// Whether large vaults are compressed before they are encrypted and written
+    pub(crate) compression_enabled: bool,
+    /// Directory the vault file picker starts in, None for the platform's default
+    pub(crate) default_vault_path: Option<PathBuf>,
+    /// Directories of the files last chosen in each kind of file picker
+    pub(crate) last_directories: LastDirectories,
+    /// Whether deleting an item asks first. Off skips the prompt, the item still goes to the trash.
+    pub(crate) confirm_delete: bool,
+    /// Order the item list was last shown in
+    pub(crate) sort_order: SortOrder,
+    /// Main window position and size when it was last closed
+    #[serde(deserialize_with = "valid_geometry")]
+    pub(crate) window_geometry: Option<WindowGeometry>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            clipboard_clear_secs: Some(30),
+            auto_lock_secs: Some(5 * 60),
+            lock_on_focus_loss_secs: None,
+            recent_vaults: Vec::new(),
+            backups_dir: None,
+            snapshot_retention_count: 30,
+            snapshot_retention_days: Some(90),
+            auto_calibrate_on_first_run: true,
+            argon_params: None,
+            compression_enabled: true,
+            default_vault_path: None,
+            last_directories: LastDirectories::default(),
+            confirm_delete: true,
+            sort_order: SortOrder::default(),
+            window_geometry: None,
+        }
+    }
+}
+
+impl Settings {
+    pub(crate) fn snapshot_retention(&self) -> SnapshotRetention {
+        SnapshotRetention {
+            max_count: self.snapshot_retention_count,
+            max_age_secs: self.snapshot_retention_days.map(|days| days.saturating_mul(86_400)),
+        }
+    }
+
+    /// Moves `path` to the front of the recent vaults, dropping the oldest beyond `MAX_RECENT_VAULTS`
+    pub(crate) fn add_recent_vault(&mut self, path: &Path) {
+        self.recent_vaults.retain(|recent| recent != path);
+        self.recent_vaults.insert(0, path.to_path_buf());
+        self.recent_vaults.truncate(MAX_RECENT_VAULTS);
+    }
+
+    /// Directory the file picker for `context` starts in: the one last used for it, or the
+    /// default vault directory for pickers of vault files
+    pub(crate) fn last_directory(&self, context: DialogContext) -> Option<&Path> {
+        let dirs = &self.last_directories;
+        match context {
+            DialogContext::OpenVault => dirs.open_vault.as_deref().or(self.default_vault_path.as_deref()),
+            DialogContext::SaveVault => dirs.save_vault.as_deref().or(self.default_vault_path.as_deref()),
+            DialogContext::ImportExport => dirs.import_export.as_deref(),
+        }
+    }
+
+    /// Remembers the directory of `chosen` for the next picker of `context`.
+    /// Returns whether it differs from the one remembered before.
+    pub(crate) fn remember_directory(&mut self, context: DialogContext, chosen: &Path) -> bool {
+        let Some(dir) = chosen.parent().filter(|dir| !dir.as_os_str().is_empty()) else {
+            return false;
+        };
+
+        let dirs = &mut self.last_directories;
+        let remembered = match context {
+            DialogContext::OpenVault => &mut dirs.open_vault,
+            DialogContext::SaveVault => &mut dirs.save_vault,
+            DialogContext::ImportExport => &mut dirs.import_export,
+        };
+        if remembered.as_deref() == Some(dir) {
+            return false;
+        }
+
+        *remembered = Some(dir.to_path_buf());
+        true
+    }
+}
+
+/// What a file picker is for. Each kind starts in the directory last used for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DialogContext {
+    OpenVault,
+    SaveVault,
+    ImportExport,
+}
+
+/// Last directory chosen in the file pickers of each `DialogContext`
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct LastDirectories {
+    pub(crate) open_vault: Option<PathBuf>,
+    pub(crate) save_vault: Option<PathBuf>,
+    pub(crate) import_export: Option<PathBuf>,
+}
+
+/// Main window position and size in physical pixels
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct WindowGeometry {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl WindowGeometry {
+    /// Geometry with the size clamped to sensible bounds. None for an empty or negative size.
+    pub(crate) fn new(x: i32, y: i32, width: i64, height: i64) -> Option<Self> {
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        let clamp = |size: i64, min: u32| u32::try_from(size).unwrap_or(MAX_WINDOW_SIZE).clamp(min, MAX_WINDOW_SIZE);
+        Some(Self { x, y, width: clamp(width, MIN_WINDOW_WIDTH), height: clamp(height, MIN_WINDOW_HEIGHT) })
+    }
+
+    /// Whether enough of the title bar is on the desktop to grab the window. A position saved on
+    /// a monitor that has since been unplugged fails this, the OS then places the window.
+    pub(crate) fn is_on_screen(&self) -> bool {
+        let (x, y, width) = (i64::from(self.x), i64::from(self.y), i64::from(self.width));
+
+        x + width >= MIN_VISIBLE_TITLE_BAR
+            && x <= MAX_DESKTOP_EXTENT - MIN_VISIBLE_TITLE_BAR
+            && (-MAX_TOP_OVERHANG..=MAX_DESKTOP_EXTENT - MIN_VISIBLE_TITLE_BAR).contains(&y)
+    }
+}
+
+/// Reads a saved window geometry, dropping one with an invalid size instead of failing the
+/// whole settings file
+fn valid_geometry<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<WindowGeometry>, D::Error> {
+    #[derive(Deserialize)]
+    struct StoredGeometry {
+        x: i32,
+        y: i32,
+        width: i64,
+        height: i64,
+    }
+
+    let stored = Option::<StoredGeometry>::deserialize(deserializer)?;
+    Ok(stored.and_then(|stored| WindowGeometry::new(stored.x, stored.y, stored.width, stored.height)))
+}
+
+/// TOML has no null, so an optional delay is stored as 0 when it is turned off. A missing
+/// field still takes the default instead.
+mod zero_is_never {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.unwrap_or(0))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        let value = u64::deserialize(deserializer)?;
+        Ok((value != 0).then_some(value))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_geometry_round_trip() {
+        let settings = Settings { window_geometry: WindowGeometry::new(-40, 25, 1280, 720), ..Settings::default() };
+
+        let encoded = toml::to_string(&settings).expect("Serialization failed");
+        let decoded: Settings = toml::from_str(&encoded).expect("Deserialization failed");
+
+        assert_eq!(decoded, settings);
+        assert_eq!(decoded.window_geometry, Some(WindowGeometry { x: -40, y: 25, width: 1280, height: 720 }));
+    }
+
+    #[test]
+    fn test_invalid_window_size_is_dropped() {
+        let settings: Settings = toml::from_str("auto_lock_secs = 60\n[window_geometry]\nx = 10\ny = 10\nwidth = -800\nheight = 600\n")
+            .expect("Deserialization failed");
+
+        assert_eq!(settings.window_geometry, None);
+        assert_eq!(settings.auto_lock_secs, Some(60));
+        assert_eq!(WindowGeometry::new(0, 0, 800, 0), None);
+    }
+
+    #[test]
+    fn test_window_size_is_clamped() {
+        let small = WindowGeometry::new(0, 0, 10, 10).expect("Size is valid");
+        assert_eq!((small.width, small.height), (MIN_WINDOW_WIDTH, MIN_WINDOW_HEIGHT));
+
+        let large = WindowGeometry::new(0, 0, 100_000, i64::MAX).expect("Size is valid");
+        assert_eq!((large.width, large.height), (MAX_WINDOW_SIZE, MAX_WINDOW_SIZE));
+    }
+
+    #[test]
+    fn test_off_screen_positions_are_detected() {
+        let at = |x, y| WindowGeometry::new(x, y, 800, 600).expect("Size is valid");
+
+        assert!(at(0, 0).is_on_screen());
+        assert!(at(1920, 200).is_on_screen(), "Second monitor to the right");
+        assert!(at(-600, 100).is_on_screen(), "Partly off the left edge");
+        assert!(at(-8, -8).is_on_screen(), "Maximized on Windows");
+
+        assert!(!at(-5000, 100).is_on_screen(), "Monitor to the left was unplugged");
+        assert!(!at(100, -300).is_on_screen(), "Title bar above the desktop");
+        assert!(!at(-32000, -32000).is_on_screen(), "Saved while minimized on Windows");
+        assert!(!at(40000, 100).is_on_screen());
+    }
+
+    #[test]
+    fn test_add_recent_vault_moves_existing_path_to_front() {
+        let mut settings = Settings::default();
+        settings.add_recent_vault(Path::new("a.vault"));
+        settings.add_recent_vault(Path::new("b.vault"));
+        settings.add_recent_vault(Path::new("a.vault"));
+
+        assert_eq!(settings.recent_vaults, vec![PathBuf::from("a.vault"), PathBuf::from("b.vault")]);
+    }
+
+    #[test]
+    fn test_last_directories_round_trip() {
+        let mut settings = Settings::default();
+        settings.remember_directory(DialogContext::OpenVault, Path::new("/vaults/work.vault"));
+        settings.remember_directory(DialogContext::ImportExport, Path::new("/exports/work.csv"));
+
+        let encoded = toml::to_string(&settings).expect("Serialization failed");
+        let decoded: Settings = toml::from_str(&encoded).expect("Deserialization failed");
+
+        assert_eq!(decoded, settings);
+        assert_eq!(decoded.last_directory(DialogContext::OpenVault), Some(Path::new("/vaults")));
+        assert_eq!(decoded.last_directory(DialogContext::SaveVault), None);
+        assert_eq!(decoded.last_directory(DialogContext::ImportExport), Some(Path::new("/exports")));
+    }
+
+    #[test]
+    fn test_calibrated_params_round_trip() {
+        let params = ArgonParams { memory_cost: 262_144, time_cost: 3, ..ArgonParams::default() };
+        let settings = Settings { auto_calibrate_on_first_run: false, argon_params: Some(params), ..Settings::default() };
+
+        let encoded = toml::to_string(&settings).expect("Serialization failed");
+        let decoded: Settings = toml::from_str(&encoded).expect("Deserialization failed");
+        assert_eq!(decoded.argon_params, Some(params));
+        assert!(!decoded.auto_calibrate_on_first_run);
+
+        let settings: Settings = toml::from_str("auto_lock_secs = 60\n").expect("Deserialization failed");
+        assert!(settings.auto_calibrate_on_first_run, "Existing settings files get calibrated once");
+    }
+
+    #[test]
+    fn test_focus_loss_lock_is_off_by_default() {
+        let settings: Settings = toml::from_str("auto_lock_secs = 60\n").expect("Deserialization failed");
+        assert_eq!(settings.lock_on_focus_loss_secs, None);
+
+        let settings: Settings = toml::from_str("lock_on_focus_loss_secs = 15\n").expect("Deserialization failed");
+        assert_eq!(settings.lock_on_focus_loss_secs, Some(15));
+    }
+
+    #[test]
+    fn test_settings_without_last_directories_still_load() {
+        let settings: Settings = toml::from_str("auto_lock_secs = 60\n").expect("Deserialization failed");
+        assert_eq!(settings.last_directories, LastDirectories::default());
+    }
+
+    #[test]
+    fn test_delete_confirmation_is_on_by_default() {
+        let settings: Settings = toml::from_str("auto_lock_secs = 60\n").expect("Deserialization failed");
+        assert!(settings.confirm_delete);
+
+        let settings: Settings = toml::from_str("confirm_delete = false\n").expect("Deserialization failed");
+        assert!(!settings.confirm_delete);
+    }
+
+    #[test]
+    fn test_last_directory_falls_back_to_default_vault_path() {
+        let mut settings = Settings { default_vault_path: Some(PathBuf::from("/vaults")), ..Settings::default() };
+        assert_eq!(settings.last_directory(DialogContext::OpenVault), Some(Path::new("/vaults")));
+        assert_eq!(settings.last_directory(DialogContext::SaveVault), Some(Path::new("/vaults")));
+        assert_eq!(settings.last_directory(DialogContext::ImportExport), None, "Exports don't go with the vaults");
+
+        settings.remember_directory(DialogContext::SaveVault, Path::new("/elsewhere/new.vault"));
+        assert_eq!(settings.last_directory(DialogContext::SaveVault), Some(Path::new("/elsewhere")));
+        assert_eq!(settings.last_directory(DialogContext::OpenVault), Some(Path::new("/vaults")), "Contexts are kept apart");
+    }
+
+    #[test]
+    fn test_remember_directory_reports_changes() {
+        let mut settings = Settings::default();
+
+        assert!(settings.remember_directory(DialogContext::OpenVault, Path::new("/vaults/a.vault")));
+        assert!(!settings.remember_directory(DialogContext::OpenVault, Path::new("/vaults/b.vault")));
+        assert!(!settings.remember_directory(DialogContext::OpenVault, Path::new("relative.vault")), "No directory to remember");
+        assert_eq!(settings.last_directory(DialogContext::OpenVault), Some(Path::new("/vaults")));
+    }
+
+    #[test]
+    fn test_add_recent_vault_keeps_the_newest() {
+        let mut settings = Settings::default();
+        for n in 0..MAX_RECENT_VAULTS + 3 {
+            settings.add_recent_vault(&PathBuf::from(format!("{}.vault", n)));
+        }
+
+        assert_eq!(settings.recent_vaults.len(), MAX_RECENT_VAULTS);
+        assert_eq!(settings.recent_vaults[0], PathBuf::from(format!("{}.vault", MAX_RECENT_VAULTS + 2)));
+    }
+}
@@ -1,9 +1,55 @@
+use std::borrow::Borrow;
+use std::cmp::{Ordering, Reverse};
+use std::fmt;
+use std::sync::atomic::{self, AtomicU64};
+
+use bincode::config::standard;
+use bincode::error::EncodeError;
+use bincode::serde::decode_from_slice;
 use serde::{Serialize, Deserialize};
+use zeroize::Zeroize;
 
+use crate::errors::migration_errors::MigrationError;
+use crate::errors::vault_errors::VaultError;
 use crate::utils::crypto::ArgonKey;
+use crate::utils::file::{FileFingerprint, VaultMetadata};
+use crate::utils::password_strength;
+use crate::utils::zero_byte::ZeroByte;
+use crate::utils;
 
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+/// How long soft-deleted items stay in the trash before being purged on vault open
+pub(crate) const TRASH_RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Passwords unchanged for longer than this are reported as expired
+pub(crate) const PASSWORD_MAX_AGE_SECS: u64 = 365 * 24 * 60 * 60;
+
+/// Schema version of the vault payload written by `Vault::encode`, see `Vault::migrate`.
+/// Every change to the encoded `Item` is a new version:
+///   0: `id`, `name`, `username`, `password`, `url` and `notes`, as in the first release
+///   1: `deleted_at`
+///   2: `favorite`
+///   3: `custom_fields`
+///   4: `modified_at` and `password_changed_at`
+///   5: `created_at`
+///   6: the payload starts with `VERSION_MARKER` and the version, the vault is laid out as in 5
+pub(crate) const VAULT_VERSION: u16 = 6;
+
+/// First byte of a payload that records its schema version. Unversioned payloads start with
+/// the nonce as a bincode varint, which never starts with 0xFF.
+const VERSION_MARKER: u8 = 0xFF;
+/// First schema version that records itself. Payloads without the marker are one of the
+/// versions before it.
+const FIRST_MARKED_VERSION: u16 = 6;
+
+/// Source of `Vault::version` values, shared so that no two vaults or edits ever get the same one
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, atomic::Ordering::Relaxed)
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug, Zeroize)]
 pub(crate) struct Item {
     pub id: i32,
     pub name: String,
@@ -11,6 +57,308 @@ pub(crate) struct Item {
     pub password: String,
     pub url: String,
     pub notes: String,
+    pub deleted_at: Option<u64>,  // Unix timestamp the item was moved to the trash
+    pub favorite: bool,
+    pub custom_fields: Vec<CustomField>,
+    pub modified_at: u64,  // Unix timestamp of the last edit, 0 if unknown (e.g. imported items)
+    pub password_changed_at: u64,  // Unix timestamp the password was last changed, 0 if unknown
+    pub created_at: u64,  // Unix timestamp the item was added, 0 if unknown (e.g. saved before it was recorded)
+}
+
+impl Item {
+    /// Whole days since the item was last edited. Items with an unknown edit time count
+    /// from the epoch, as the oldest there are.
+    pub(crate) fn age_days(&self) -> u64 {
+        self.age_days_at(utils::unix_timestamp())
+    }
+
+    fn age_days_at(&self, now: u64) -> u64 {
+        now.saturating_sub(self.modified_at) / 86_400
+    }
+}
+
+/// Summary safe to print, e.g. from the CLI. Leaves out the username, password, URL, notes
+/// and custom fields, unlike `Debug`.
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Item {{ id: {}, name: {:?}, has_password: {} }}", self.id, self.name, !self.password.is_empty())
+    }
+}
+
+/// Item layout of a schema version, see `VAULT_VERSION`. Each older layout converts into the
+/// next one, so a vault is migrated one version at a time.
+trait ItemSchema: for<'de> Deserialize<'de> {
+    /// Runs the item through every migration step after its own version
+    fn into_current(self) -> Item;
+}
+
+impl ItemSchema for Item {
+    fn into_current(self) -> Item {
+        self
+    }
+}
+
+/// Item as saved by schema version 0, the original layout without a trash
+#[derive(Deserialize)]
+struct ItemV0 {
+    id: i32,
+    name: String,
+    username: String,
+    password: String,
+    url: String,
+    notes: String,
+}
+
+impl From<ItemV0> for ItemV1 {
+    fn from(old: ItemV0) -> Self {
+        Self {
+            id: old.id,
+            name: old.name,
+            username: old.username,
+            password: old.password,
+            url: old.url,
+            notes: old.notes,
+            deleted_at: None,
+        }
+    }
+}
+
+impl ItemSchema for ItemV0 {
+    fn into_current(self) -> Item {
+        ItemV1::from(self).into_current()
+    }
+}
+
+/// Item as saved by schema version 1, before favorites
+#[derive(Deserialize)]
+struct ItemV1 {
+    id: i32,
+    name: String,
+    username: String,
+    password: String,
+    url: String,
+    notes: String,
+    deleted_at: Option<u64>,
+}
+
+impl From<ItemV1> for ItemV2 {
+    fn from(old: ItemV1) -> Self {
+        Self {
+            id: old.id,
+            name: old.name,
+            username: old.username,
+            password: old.password,
+            url: old.url,
+            notes: old.notes,
+            deleted_at: old.deleted_at,
+            favorite: false,
+        }
+    }
+}
+
+impl ItemSchema for ItemV1 {
+    fn into_current(self) -> Item {
+        ItemV2::from(self).into_current()
+    }
+}
+
+/// Item as saved by schema version 2, before custom fields
+#[derive(Deserialize)]
+struct ItemV2 {
+    id: i32,
+    name: String,
+    username: String,
+    password: String,
+    url: String,
+    notes: String,
+    deleted_at: Option<u64>,
+    favorite: bool,
+}
+
+impl From<ItemV2> for ItemV3 {
+    fn from(old: ItemV2) -> Self {
+        Self {
+            id: old.id,
+            name: old.name,
+            username: old.username,
+            password: old.password,
+            url: old.url,
+            notes: old.notes,
+            deleted_at: old.deleted_at,
+            favorite: old.favorite,
+            custom_fields: Vec::new(),
+        }
+    }
+}
+
+impl ItemSchema for ItemV2 {
+    fn into_current(self) -> Item {
+        ItemV3::from(self).into_current()
+    }
+}
+
+/// Item as saved by schema version 3, before edit times were recorded
+#[derive(Deserialize)]
+struct ItemV3 {
+    id: i32,
+    name: String,
+    username: String,
+    password: String,
+    url: String,
+    notes: String,
+    deleted_at: Option<u64>,
+    favorite: bool,
+    custom_fields: Vec<CustomField>,
+}
+
+/// Edit times of items from before they were recorded are unknown
+impl From<ItemV3> for ItemV4 {
+    fn from(old: ItemV3) -> Self {
+        Self {
+            id: old.id,
+            name: old.name,
+            username: old.username,
+            password: old.password,
+            url: old.url,
+            notes: old.notes,
+            deleted_at: old.deleted_at,
+            favorite: old.favorite,
+            custom_fields: old.custom_fields,
+            modified_at: 0,
+            password_changed_at: 0,
+        }
+    }
+}
+
+impl ItemSchema for ItemV3 {
+    fn into_current(self) -> Item {
+        ItemV4::from(self).into_current()
+    }
+}
+
+/// Item as saved by schema version 4, before `created_at` was recorded
+#[derive(Deserialize)]
+struct ItemV4 {
+    id: i32,
+    name: String,
+    username: String,
+    password: String,
+    url: String,
+    notes: String,
+    deleted_at: Option<u64>,
+    favorite: bool,
+    custom_fields: Vec<CustomField>,
+    modified_at: u64,
+    password_changed_at: u64,
+}
+
+impl From<ItemV4> for Item {
+    fn from(old: ItemV4) -> Self {
+        Self {
+            id: old.id,
+            name: old.name,
+            username: old.username,
+            password: old.password,
+            url: old.url,
+            notes: old.notes,
+            deleted_at: old.deleted_at,
+            favorite: old.favorite,
+            custom_fields: old.custom_fields,
+            modified_at: old.modified_at,
+            password_changed_at: old.password_changed_at,
+            created_at: 0,
+        }
+    }
+}
+
+impl ItemSchema for ItemV4 {
+    fn into_current(self) -> Item {
+        Item::from(self)
+    }
+}
+
+/// Vault body as saved by every schema version, with the items laid out as `I`. The key is
+/// always left out, see `Vault::encode`.
+#[derive(Deserialize)]
+struct VaultLayout<I> {
+    nonce: i32,
+    items: Vec<I>,
+    key: Option<ArgonKey>,
+}
+
+/// Extra named value on an item beyond the fixed fields
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Zeroize)]
+pub(crate) struct CustomField {
+    pub name: String,
+    pub value: String,
+    pub hidden: bool,  // Masked in the UI like a password
+}
+
+/// Order the item list is shown in
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]  // Reads as "sort by name", matching the picker
+pub(crate) enum SortOrder {
+    #[default]
+    ByNameAsc,
+    ByNameDesc,
+    ByDateModifiedDesc,  // Most recently edited first
+    ByPasswordAgeDesc,  // Oldest password first
+    ByCreatedAsc,  // Order the items were added in
+}
+
+impl SortOrder {
+    /// Position in the sort picker
+    pub(crate) fn id(self) -> i32 {
+        match self {
+            Self::ByNameAsc => 0,
+            Self::ByNameDesc => 1,
+            Self::ByDateModifiedDesc => 2,
+            Self::ByPasswordAgeDesc => 3,
+            Self::ByCreatedAsc => 4,
+        }
+    }
+
+    pub(crate) fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Self::ByNameAsc),
+            1 => Some(Self::ByNameDesc),
+            2 => Some(Self::ByDateModifiedDesc),
+            3 => Some(Self::ByPasswordAgeDesc),
+            4 => Some(Self::ByCreatedAsc),
+            _ => None,
+        }
+    }
+}
+
+/// Sorts items in place. The sort is stable, items that compare equal keep their order.
+pub(crate) fn sort_items<I: Borrow<Item>>(items: &mut [I], order: SortOrder) {
+    match order {
+        SortOrder::ByNameAsc => items.sort_by(|a, b| compare_names(a.borrow(), b.borrow())),
+        SortOrder::ByNameDesc => items.sort_by(|a, b| compare_names(b.borrow(), a.borrow())),
+        SortOrder::ByDateModifiedDesc => items.sort_by_key(|item| Reverse(item.borrow().modified_at)),
+        SortOrder::ByPasswordAgeDesc => items.sort_by_key(|item| item.borrow().password_changed_at),
+        // Items are stored in the order they were added, which breaks ties between items
+        // created the same second or before creation times were recorded (0)
+        SortOrder::ByCreatedAsc => items.sort_by_key(|item| item.borrow().created_at),
+    }
+}
+
+/// Case-insensitive (ASCII) name comparison over the borrowed name bytes, so no
+/// lowercased copies of the names are left behind in memory
+fn compare_names(a: &Item, b: &Item) -> Ordering {
+    a.name.bytes().map(|c| c.to_ascii_lowercase())
+        .cmp(b.name.bytes().map(|c| c.to_ascii_lowercase()))
+}
+
+/// Security audit of the active items, see `Vault::health_report`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct VaultHealthReport {
+    pub total_items: usize,
+    pub weak_passwords: Vec<i32>,
+    pub duplicate_passwords: Vec<Vec<i32>>,  // Groups of items sharing one password
+    pub expired_items: Vec<i32>,  // Password older than PASSWORD_MAX_AGE_SECS
+    pub items_without_url: Vec<i32>,
+    pub average_password_strength: f64,  // Mean password_strength::score (0-4), 0.0 without passwords
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -18,6 +366,18 @@ pub(crate) struct Vault {
     pub nonce: i32,
     pub items: Vec<Item>,
     pub key: Option<ArgonKey>,
+    #[serde(skip)]
+    pub metadata: VaultMetadata,  // Stored unencrypted in the file header, not in the vault body
+    #[serde(skip)]
+    pub file_fingerprint: Option<FileFingerprint>,  // Vault file as of the last read or write
+    #[serde(skip)]
+    pub changed_on_disk: bool,  // Another program changed the file and the user kept this version
+    #[serde(skip)]
+    pub restored_from_backup: bool,  // The vault file is damaged, the next save sets it aside first
+    #[serde(skip, default = "next_version")]
+    pub version: u64,  // Changes whenever the items do, see `mark_changed`
+    #[serde(skip)]
+    health_cache: Option<(u64, VaultHealthReport)>,  // Last report and the version it was computed for
 }
 
 impl Vault {
@@ -26,15 +386,1006 @@ impl Vault {
             nonce: 1,
             items: vec![
                 Item {
-                    id: 0,
                     name: "New Item".into(),
-                    username: String::new(),
-                    password: String::new(),
-                    url: String::new(),
-                    notes: String::new(),
+                    ..Default::default()
                 },
             ],
             key: None,
+            metadata: VaultMetadata::default(),
+            file_fingerprint: None,
+            changed_on_disk: false,
+            restored_from_backup: false,
+            version: next_version(),
+            health_cache: None,
+        }
+    }
+
+    /// Decodes a vault payload of any schema version, migrating older ones to the current
+    /// layout. Payloads from before the version was recorded are tried as each of those
+    /// versions, newest first; the error of the newest is returned if none fits.
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, MigrationError> {
+        if let [VERSION_MARKER, low, high, body @ ..] = bytes {
+            return Self::migrate(body, u16::from_le_bytes([*low, *high]));
+        }
+
+        let mut newest_error = None;
+        for version in (0..FIRST_MARKED_VERSION).rev() {
+            match Self::migrate(bytes, version) {
+                Ok(vault) => return Ok(vault),
+                Err(e) => { newest_error.get_or_insert(e); },
+            }
+        }
+        Err(newest_error.unwrap_or(MigrationError::UnsupportedVersion(0)))
+    }
+
+    /// Decodes a vault body saved with schema `from_version` and brings it up to
+    /// `VAULT_VERSION` one version at a time. Fields a version added get the value they have
+    /// for items from before it was recorded. A body that decodes with bytes left over isn't
+    /// that version; its items are wiped before the error is returned.
+    pub(crate) fn migrate(raw: &[u8], from_version: u16) -> Result<Vault, MigrationError> {
+        match from_version {
+            0 => Self::decode_layout::<ItemV0>(raw, from_version),
+            1 => Self::decode_layout::<ItemV1>(raw, from_version),
+            2 => Self::decode_layout::<ItemV2>(raw, from_version),
+            3 => Self::decode_layout::<ItemV3>(raw, from_version),
+            4 => Self::decode_layout::<ItemV4>(raw, from_version),
+            // Version 6 only put the version in front, the vault itself is unchanged
+            5 | 6 => Self::decode_layout::<Item>(raw, from_version),
+            unknown => Err(MigrationError::UnsupportedVersion(unknown)),
+        }
+    }
+
+    /// Decodes a vault body with items laid out as `I` and migrates the items
+    fn decode_layout<I: ItemSchema>(raw: &[u8], version: u16) -> Result<Vault, MigrationError> {
+        let layout = Self::decode_exact::<VaultLayout<I>>(raw, version, |discarded| {
+            discarded.items.into_iter().map(I::into_current).collect::<Vec<_>>().zeroize();
+        })?;
+
+        let mut vault = Vault::new();
+        vault.nonce = layout.nonce;
+        vault.items = layout.items.into_iter().map(I::into_current).collect();
+        vault.key = layout.key;
+        Ok(vault.with_valid_nonce())
+    }
+
+    /// Decodes `raw` as `T`, which has to account for every byte. bincode can't tell a
+    /// missing field from the next value, so a shorter read means another layout; `wipe`
+    /// gets what was decoded anyway.
+    fn decode_exact<T: for<'de> Deserialize<'de>>(raw: &[u8], version: u16, wipe: impl FnOnce(T)) -> Result<T, MigrationError> {
+        match decode_from_slice::<T, _>(raw, standard())? {
+            (decoded, read) if read == raw.len() => Ok(decoded),
+            (decoded, _) => {
+                wipe(decoded);
+                Err(MigrationError::TrailingBytes { version })
+            },
+        }
+    }
+
+    /// Zeroizes the items and the key before the vault is dropped, e.g. when it is locked
+    pub(crate) fn wipe(&mut self) {
+        self.items.zeroize();
+        if let Some(mut key) = self.key.take() {
+            key.wipe();
+        }
+        self.health_cache = None;
+    }
+
+    /// Serializes the vault without its key straight into a `ZeroByte`, for encrypting.
+    /// The payload starts with `VERSION_MARKER` and `VAULT_VERSION`.
+    pub(crate) fn encode(&mut self) -> Result<ZeroByte, EncodeError> {
+        let key = self.key.take();
+        // bincode writes a u8 and a byte array as they are, no varints
+        let encoded = ZeroByte::from_encoder(&(VERSION_MARKER, VAULT_VERSION.to_le_bytes(), &*self));
+        self.key = key;
+
+        encoded
+    }
+
+    /// Hands out the ID for a new item. IDs are never reused, so running past `i32::MAX` is an
+    /// error rather than a wrap around to IDs that may still be taken.
+    pub(crate) fn next_id(&mut self) -> Result<i32, VaultError> {
+        let id = self.nonce;
+        self.nonce = id.checked_add(1).ok_or(VaultError::IdExhausted)?;
+        Ok(id)
+    }
+
+    /// Sets `nonce` to one past the highest item ID, e.g. for a vault saved with a stale one
+    pub(crate) fn recalculate_nonce(&mut self) {
+        self.nonce = self.items.iter().map(|item| item.id).max().map_or(0, |id| id.saturating_add(1));
+    }
+
+    /// A vault whose nonce would hand out an ID that is already taken gets a fresh one
+    fn with_valid_nonce(mut self) -> Self {
+        if self.items.iter().any(|item| item.id >= self.nonce) {
+            log::warn!("Vault nonce {} is stale, recalculating it from the item IDs", self.nonce);
+            self.recalculate_nonce();
+        }
+        self
+    }
+
+    /// Records a change to the items, invalidating the cached health report
+    pub(crate) fn mark_changed(&mut self) {
+        self.version = next_version();
+    }
+
+    /// The item with the given ID, trashed or not
+    pub(crate) fn item_by_id(&self, item_id: i32) -> Option<&Item> {
+        self.items.iter().find(|item| item.id == item_id)
+    }
+
+    /// The item with the given ID, trashed or not. Call `mark_changed` after changing it.
+    pub(crate) fn item_by_id_mut(&mut self, item_id: i32) -> Option<&mut Item> {
+        self.items.iter_mut().find(|item| item.id == item_id)
+    }
+
+    /// Position of the item with the given ID in `items`
+    pub(crate) fn item_index_by_id(&self, item_id: i32) -> Option<usize> {
+        self.items.iter().position(|item| item.id == item_id)
+    }
+
+    /// Items that have not been moved to the trash
+    pub(crate) fn active_items(&self) -> Vec<&Item> {
+        self.items.iter().filter(|item| item.deleted_at.is_none()).collect()
+    }
+
+    /// Active items marked as favorite
+    pub(crate) fn favorite_items(&self) -> Vec<&Item> {
+        self.active_items().into_iter().filter(|item| item.favorite).collect()
+    }
+
+    /// Flips the favorite flag of an item. Returns the new value, or None if no such item exists.
+    pub(crate) fn toggle_favorite(&mut self, item_id: i32) -> Option<bool> {
+        let item = self.item_by_id_mut(item_id)?;
+        item.favorite = !item.favorite;
+        let favorite = item.favorite;
+        self.mark_changed();
+        Some(favorite)
+    }
+
+    /// Soft-deleted items
+    pub(crate) fn trash(&self) -> Vec<&Item> {
+        self.items.iter().filter(|item| item.deleted_at.is_some()).collect()
+    }
+
+    /// Moves an item to the trash. Returns false if no such item exists.
+    pub(crate) fn soft_delete_item(&mut self, item_id: i32, now: u64) -> bool {
+        match self.item_by_id_mut(item_id) {
+            Some(item) => {
+                item.deleted_at.get_or_insert(now);
+                self.mark_changed();
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Restores an item from the trash. Returns false if the item isn't in the trash.
+    pub(crate) fn restore_item(&mut self, item_id: i32) -> bool {
+        match self.item_by_id_mut(item_id).filter(|item| item.deleted_at.is_some()) {
+            Some(item) => {
+                item.deleted_at = None;
+                self.mark_changed();
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Removes a trashed item from the vault for good, zeroizing its contents
+    pub(crate) fn permanently_delete_item(&mut self, item_id: i32) -> bool {
+        match self.item_index_by_id(item_id) {
+            Some(index) if self.items[index].deleted_at.is_some() => {
+                self.items.remove(index).zeroize();
+                self.mark_changed();
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Zeroizes and removes every item in the trash, returning how many were removed
+    pub(crate) fn empty_trash(&mut self) -> usize {
+        self.remove_trash_where(|_| true)
+    }
+
+    /// Permanently deletes trashed items deleted more than `TRASH_RETENTION_SECS` before `now`
+    pub(crate) fn purge_expired_trash(&mut self, now: u64) -> usize {
+        let cutoff = now.saturating_sub(TRASH_RETENTION_SECS);
+        self.remove_trash_where(|item| item.deleted_at.is_some_and(|deleted_at| deleted_at < cutoff))
+    }
+
+    /// Removes the items with the given IDs for good, trashed or not, zeroizing their contents.
+    /// Returns how many were removed; IDs without an item are ignored.
+    pub(crate) fn delete_items(&mut self, ids: &[i32]) -> usize {
+        self.remove_where(|item| ids.contains(&item.id))
+    }
+
+    fn remove_trash_where(&mut self, predicate: impl Fn(&Item) -> bool) -> usize {
+        self.remove_where(|item| item.deleted_at.is_some() && predicate(item))
+    }
+
+    fn remove_where(&mut self, predicate: impl Fn(&Item) -> bool) -> usize {
+        let mut removed = 0;
+        let mut index = 0;
+
+        while index < self.items.len() {
+            let item = &self.items[index];
+            if predicate(item) {
+                self.items.remove(index).zeroize();
+                removed += 1;
+            } else {
+                index += 1;
+            }
+        }
+
+        if removed > 0 {
+            self.mark_changed();
+        }
+        removed
+    }
+
+    /// Active items whose password scores below `password_strength::MIN_SCORE`.
+    /// Items without a password are not counted.
+    pub(crate) fn weak_items(&self) -> Vec<i32> {
+        self.active_items()
+            .into_iter()
+            .filter(|item| !item.password.is_empty() && password_strength::score(&item.password) < password_strength::MIN_SCORE)
+            .map(|item| item.id)
+            .collect()
+    }
+
+    /// Groups of active items sharing the same password, ordered by their lowest ID.
+    /// Items without a password are not counted.
+    pub(crate) fn find_duplicate_passwords(&self) -> Vec<Vec<i32>> {
+        let mut items: Vec<&Item> = self.active_items()
+            .into_iter()
+            .filter(|item| !item.password.is_empty())
+            .collect();
+        items.sort_by(|a, b| a.password.cmp(&b.password).then(a.id.cmp(&b.id)));
+
+        let mut groups: Vec<Vec<i32>> = items
+            .chunk_by(|a, b| a.password == b.password)
+            .filter(|group| group.len() > 1)
+            .map(|group| group.iter().map(|item| item.id).collect())
+            .collect();
+        groups.sort_by_key(|group| group[0]);
+        groups
+    }
+
+    /// Active items whose password was last changed more than `PASSWORD_MAX_AGE_SECS` before `now`.
+    /// Items with an unknown password age are not counted.
+    pub(crate) fn expired_items(&self, now: u64) -> Vec<i32> {
+        let cutoff = now.saturating_sub(PASSWORD_MAX_AGE_SECS);
+        self.active_items()
+            .into_iter()
+            .filter(|item| item.password_changed_at != 0 && item.password_changed_at < cutoff)
+            .map(|item| item.id)
+            .collect()
+    }
+
+    pub(crate) fn health_report(&self) -> VaultHealthReport {
+        self.health_report_at(utils::unix_timestamp())
+    }
+
+    /// Audits the active items, counting password ages from `now`
+    pub(crate) fn health_report_at(&self, now: u64) -> VaultHealthReport {
+        let active_items = self.active_items();
+        let scores: Vec<u8> = active_items
+            .iter()
+            .filter(|item| !item.password.is_empty())
+            .map(|item| password_strength::score(&item.password))
+            .collect();
+        let average_password_strength = match scores.len() {
+            0 => 0.0,
+            count => scores.iter().map(|&score| f64::from(score)).sum::<f64>() / count as f64,
+        };
+
+        VaultHealthReport {
+            total_items: active_items.len(),
+            weak_passwords: self.weak_items(),
+            duplicate_passwords: self.find_duplicate_passwords(),
+            expired_items: self.expired_items(now),
+            items_without_url: active_items.iter().filter(|item| item.url.trim().is_empty()).map(|item| item.id).collect(),
+            average_password_strength,
+        }
+    }
+
+    /// The last health report, if the items haven't changed since it was computed
+    pub(crate) fn cached_health_report(&self) -> Option<&VaultHealthReport> {
+        match &self.health_cache {
+            Some((version, report)) if *version == self.version => Some(report),
+            _ => None,
+        }
+    }
+
+    /// Keeps `report` for `cached_health_report`, unless the items changed after `version`
+    /// (the version the report was computed from)
+    pub(crate) fn cache_health_report(&mut self, version: u64, report: VaultHealthReport) {
+        if version == self.version {
+            self.health_cache = Some((version, report));
+        }
+    }
+}
+
+/// Summary safe to print, e.g. from the CLI: the name, the number of items outside the trash
+/// and the creation date. Nothing from the items themselves, unlike `Debug`.
+impl fmt::Display for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let created = match self.metadata.created_at {
+            0 => "unknown".to_string(),
+            created_at => utils::format_date(created_at),
+        };
+        write!(f, "Vault {{ name: {:?}, items: {}, created: {} }}", self.metadata.name(), self.active_items().len(), created)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::config::standard;
+    use bincode::serde::{encode_to_vec, decode_from_slice};
+    use crate::utils::crypto::{ArgonParams, Crypto, KdfAlgorithm};
+
+    const NOW: u64 = 1_700_000_000;
+    const TEST_PARAMS: ArgonParams = ArgonParams { algorithm: KdfAlgorithm::Argon2id, memory_cost: 64, time_cost: 1, parallelism: 1 };
+
+    fn vault_with_items(count: i32) -> Vault {
+        let mut vault = Vault::new();
+        vault.items = (0..count)
+            .map(|id| Item {
+                id,
+                name: format!("Item {}", id),
+                password: format!("secret-{}", id),
+                ..Default::default()
+            })
+            .collect();
+        vault.nonce = count;
+        vault
+    }
+
+    fn ids(items: Vec<&Item>) -> Vec<i32> {
+        items.iter().map(|item| item.id).collect()
+    }
+
+    #[test]
+    fn test_item_by_id_finds_the_item() {
+        let vault = vault_with_items(3);
+
+        assert_eq!(vault.item_by_id(2).map(|item| item.name.as_str()), Some("Item 2"));
+        assert_eq!(vault.item_index_by_id(2), Some(2));
+    }
+
+    #[test]
+    fn test_item_by_id_without_such_item() {
+        let mut vault = vault_with_items(3);
+
+        assert!(vault.item_by_id(-1).is_none());
+        assert!(vault.item_by_id_mut(-1).is_none());
+        assert_eq!(vault.item_index_by_id(-1), None);
+    }
+
+    #[test]
+    fn test_item_by_id_in_empty_vault() {
+        let mut vault = vault_with_items(0);
+
+        assert!(vault.item_by_id(0).is_none());
+        assert!(vault.item_by_id_mut(0).is_none());
+        assert_eq!(vault.item_index_by_id(0), None);
+    }
+
+    #[test]
+    fn test_item_by_id_mut_changes_the_item() {
+        let mut vault = vault_with_items(3);
+
+        vault.item_by_id_mut(1).expect("Missing item").username = "alice".into();
+        assert_eq!(vault.items[1].username, "alice");
+        assert_eq!(vault.items[0].username, "", "Other items are left alone");
+    }
+
+    #[test]
+    fn test_soft_delete_moves_item_to_trash() {
+        let mut vault = vault_with_items(3);
+
+        assert!(vault.soft_delete_item(1, NOW));
+        assert_eq!(ids(vault.active_items()), vec![0, 2]);
+        assert_eq!(ids(vault.trash()), vec![1]);
+        assert_eq!(vault.items.len(), 3, "Soft delete must keep the item in the vault");
+        assert_eq!(vault.items[1].deleted_at, Some(NOW));
+        assert!(!vault.soft_delete_item(42, NOW));
+    }
+
+    #[test]
+    fn test_restore_returns_item_from_trash() {
+        let mut vault = vault_with_items(2);
+        vault.soft_delete_item(0, NOW);
+
+        assert!(vault.restore_item(0));
+        assert_eq!(ids(vault.active_items()), vec![0, 1]);
+        assert!(vault.trash().is_empty());
+        assert!(!vault.restore_item(1), "Active items can't be restored");
+    }
+
+    #[test]
+    fn test_permanently_delete_only_removes_trashed_items() {
+        let mut vault = vault_with_items(2);
+        vault.soft_delete_item(0, NOW);
+
+        assert!(!vault.permanently_delete_item(1), "Active items must be trashed first");
+        assert!(vault.permanently_delete_item(0));
+        assert_eq!(ids(vault.active_items()), vec![1]);
+        assert!(vault.trash().is_empty());
+    }
+
+    #[test]
+    fn test_empty_trash_removes_all_trashed_items() {
+        let mut vault = vault_with_items(4);
+        vault.soft_delete_item(0, NOW);
+        vault.soft_delete_item(2, NOW);
+
+        assert_eq!(vault.empty_trash(), 2);
+        assert_eq!(ids(vault.active_items()), vec![1, 3]);
+        assert!(vault.trash().is_empty());
+    }
+
+    #[test]
+    fn test_purge_expired_trash_keeps_recent_deletions() {
+        let mut vault = vault_with_items(3);
+        vault.soft_delete_item(0, NOW - TRASH_RETENTION_SECS - 1);
+        vault.soft_delete_item(1, NOW - 60);
+
+        assert_eq!(vault.purge_expired_trash(NOW), 1);
+        assert_eq!(ids(vault.trash()), vec![1]);
+        assert_eq!(ids(vault.active_items()), vec![2]);
+    }
+
+    #[test]
+    fn test_toggle_favorite_flips_flag() {
+        let mut vault = vault_with_items(2);
+
+        assert_eq!(vault.toggle_favorite(1), Some(true));
+        assert!(vault.items[1].favorite);
+        assert_eq!(vault.toggle_favorite(1), Some(false));
+        assert!(!vault.items[1].favorite);
+        assert_eq!(vault.toggle_favorite(42), None);
+    }
+
+    #[test]
+    fn test_favorite_items_excludes_other_and_trashed_items() {
+        let mut vault = vault_with_items(4);
+        vault.toggle_favorite(0);
+        vault.toggle_favorite(2);
+        vault.toggle_favorite(3);
+        vault.soft_delete_item(3, NOW);
+
+        assert_eq!(ids(vault.favorite_items()), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_favorite_flag_round_trips_through_bincode() {
+        let mut vault = vault_with_items(2);
+        vault.toggle_favorite(1);
+
+        let encoded = encode_to_vec(&vault, standard()).expect("Encode failed");
+        let (decoded, _): (Vault, usize) = decode_from_slice(&encoded, standard()).expect("Decode failed");
+
+        assert!(!decoded.items[0].favorite);
+        assert!(decoded.items[1].favorite);
+    }
+
+    #[test]
+    fn test_timestamps_round_trip_through_bincode() {
+        let mut vault = vault_with_items(1);
+        vault.items[0].created_at = NOW - 100;
+        vault.items[0].modified_at = NOW;
+
+        let encoded = encode_to_vec(&vault, standard()).expect("Encode failed");
+        let decoded = Vault::decode(&encoded).expect("Decode failed");
+
+        assert_eq!((decoded.items[0].created_at, decoded.items[0].modified_at), (NOW - 100, NOW));
+    }
+
+    #[test]
+    fn test_encode_leaves_out_the_key() {
+        let mut vault = vault_with_items(2);
+        vault.key = Some(Crypto::derive_argon_key(b"password", None, TEST_PARAMS).expect("Key derivation failed"));
+
+        let encoded = vault.encode().expect("Encode failed");
+        assert!(vault.key.is_some(), "The open vault keeps its key");
+
+        let decoded = Vault::decode(encoded.as_ref()).expect("Decode failed");
+        assert!(decoded.key.is_none());
+        assert_eq!(decoded.items[1].password, "secret-1");
+        let (header, body) = encoded.as_ref().split_at(3);
+        assert_eq!(header, [VERSION_MARKER, VAULT_VERSION as u8, 0]);
+        assert_eq!(body, encode_to_vec(&decoded, standard()).expect("Encode failed"), "Same bytes as bincode's own encoding");
+    }
+
+    #[test]
+    fn test_item_names_survive_round_trip() {
+        let mut vault = vault_with_items(2);
+        vault.items[0].name = "Bänk — Online 🏦".into();
+        vault.items[1].name = String::new();
+
+        let decoded = Vault::decode(vault.encode().expect("Encode failed").as_ref()).expect("Decode failed");
+        let names: Vec<&str> = decoded.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["Bänk — Online 🏦", ""]);
+    }
+
+    #[test]
+    fn test_wipe_clears_items_and_key() {
+        let mut vault = vault_with_items(2);
+        vault.key = Some(Crypto::derive_argon_key(b"password", None, TEST_PARAMS).expect("Key derivation failed"));
+        vault.health_report();
+
+        vault.wipe();
+        assert!(vault.items.is_empty());
+        assert!(vault.key.is_none());
+        assert_eq!(vault.health_report().total_items, 0, "No report of the wiped items is kept");
+    }
+
+    #[test]
+    fn test_next_id_hands_out_increasing_ids() {
+        let mut vault = vault_with_items(3);
+
+        assert_eq!(vault.next_id(), Ok(3));
+        assert_eq!(vault.next_id(), Ok(4));
+        assert_eq!(vault.nonce, 5);
+    }
+
+    #[test]
+    fn test_next_id_refuses_to_wrap() {
+        let mut vault = vault_with_items(0);
+        vault.nonce = i32::MAX - 1;
+
+        assert_eq!(vault.next_id(), Ok(i32::MAX - 1));
+        assert_eq!(vault.next_id(), Err(VaultError::IdExhausted));
+        assert_eq!(vault.nonce, i32::MAX, "A failed call must not change the nonce");
+    }
+
+    #[test]
+    fn test_recalculate_nonce_follows_highest_id() {
+        let mut vault = vault_with_items(0);
+        vault.recalculate_nonce();
+        assert_eq!(vault.nonce, 0);
+
+        // Gaps left by deleted items are not filled in
+        vault = vault_with_items(6);
+        vault.items.retain(|item| [1, 4].contains(&item.id));
+        vault.nonce = 2;
+        vault.recalculate_nonce();
+        assert_eq!(vault.nonce, 5);
+    }
+
+    #[test]
+    fn test_stale_nonce_is_recalculated_on_decode() {
+        let mut vault = vault_with_items(3);
+        vault.nonce = 1;
+
+        let encoded = encode_to_vec(&vault, standard()).expect("Encode failed");
+        assert_eq!(Vault::decode(&encoded).expect("Decode failed").nonce, 3);
+
+        // A nonce past the highest ID is kept, IDs of deleted items aren't reused
+        vault.nonce = 10;
+        let encoded = encode_to_vec(&vault, standard()).expect("Encode failed");
+        assert_eq!(Vault::decode(&encoded).expect("Decode failed").nonce, 10);
+    }
+
+    #[derive(Serialize)]
+    struct OldItem<'a> {
+        id: i32,
+        name: &'a str,
+        username: &'a str,
+        password: &'a str,
+        url: &'a str,
+        notes: &'a str,
+        deleted_at: Option<u64>,
+        favorite: bool,
+        custom_fields: Vec<CustomField>,
+        modified_at: u64,
+        password_changed_at: u64,
+    }
+
+    #[test]
+    fn test_vault_saved_without_created_at_still_decodes() {
+        let old_items: Vec<OldItem> = [(3, "Mail"), (5, "Bank")].into_iter()
+            .map(|(id, name)| OldItem {
+                id, name, username: "alice", password: "hunter2", url: "", notes: "",
+                deleted_at: None, favorite: id == 5, custom_fields: Vec::new(), modified_at: NOW, password_changed_at: NOW,
+            })
+            .collect();
+        let encoded = encode_to_vec((6i32, &old_items, Option::<ArgonKey>::None), standard()).expect("Encode failed");
+
+        let vault = Vault::decode(&encoded).expect("Decode failed");
+
+        assert_eq!(vault.nonce, 6);
+        assert_eq!(vault.items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![3, 5]);
+        assert_eq!(vault.items[1].name, "Bank");
+        assert!(vault.items[1].favorite);
+        assert!(vault.items.iter().all(|item| item.created_at == 0 && item.modified_at == NOW));
+    }
+
+    // Fixtures of each schema version's payload: nonce 2 and item 1 "Mail" (alice / hunter2)
+    // without URL or notes, no key. Versions that record them have the item in the
+    // favorites, edited at 1000, its password changed at 900 and created at 800. bincode
+    // varints, zigzag for `i32`.
+
+    /// Schema version 0, the original layout
+    const VERSION_0_FIXTURE: &[u8] = &[
+        0x04, 0x01,                                        // nonce 2, 1 item
+        0x02, 0x04, b'M', b'a', b'i', b'l',                // id 1, name
+        0x05, b'a', b'l', b'i', b'c', b'e',                // username
+        0x07, b'h', b'u', b'n', b't', b'e', b'r', b'2',    // password
+        0x00, 0x00,                                        // url, notes
+        0x00,                                              // key None
+    ];
+
+    /// Schema version 1, before favorites
+    const VERSION_1_FIXTURE: &[u8] = &[
+        0x04, 0x01,                                        // nonce 2, 1 item
+        0x02, 0x04, b'M', b'a', b'i', b'l',                // id 1, name
+        0x05, b'a', b'l', b'i', b'c', b'e',                // username
+        0x07, b'h', b'u', b'n', b't', b'e', b'r', b'2',    // password
+        0x00, 0x00,                                        // url, notes
+        0x00,                                              // deleted_at None
+        0x00,                                              // key None
+    ];
+
+    /// Schema version 2, before custom fields
+    const VERSION_2_FIXTURE: &[u8] = &[
+        0x04, 0x01,                                        // nonce 2, 1 item
+        0x02, 0x04, b'M', b'a', b'i', b'l',                // id 1, name
+        0x05, b'a', b'l', b'i', b'c', b'e',                // username
+        0x07, b'h', b'u', b'n', b't', b'e', b'r', b'2',    // password
+        0x00, 0x00,                                        // url, notes
+        0x00, 0x01,                                        // deleted_at None, favorite
+        0x00,                                              // key None
+    ];
+
+    /// Schema version 3, before edit times
+    const VERSION_3_FIXTURE: &[u8] = &[
+        0x04, 0x01,                                        // nonce 2, 1 item
+        0x02, 0x04, b'M', b'a', b'i', b'l',                // id 1, name
+        0x05, b'a', b'l', b'i', b'c', b'e',                // username
+        0x07, b'h', b'u', b'n', b't', b'e', b'r', b'2',    // password
+        0x00, 0x00,                                        // url, notes
+        0x00, 0x01, 0x00,                                  // deleted_at None, favorite, no custom fields
+        0x00,                                              // key None
+    ];
+
+    /// Schema version 4, before `created_at`
+    const VERSION_4_FIXTURE: &[u8] = &[
+        0x04, 0x01,                                        // nonce 2, 1 item
+        0x02, 0x04, b'M', b'a', b'i', b'l',                // id 1, name
+        0x05, b'a', b'l', b'i', b'c', b'e',                // username
+        0x07, b'h', b'u', b'n', b't', b'e', b'r', b'2',    // password
+        0x00, 0x00,                                        // url, notes
+        0x00, 0x01, 0x00,                                  // deleted_at None, favorite, no custom fields
+        0xFB, 0xE8, 0x03, 0xFB, 0x84, 0x03,                // modified_at 1000, password_changed_at 900
+        0x00,                                              // key None
+    ];
+
+    /// Schema version 5, with `created_at`
+    const VERSION_5_FIXTURE: &[u8] = &[
+        0x04, 0x01,
+        0x02, 0x04, b'M', b'a', b'i', b'l',
+        0x05, b'a', b'l', b'i', b'c', b'e',
+        0x07, b'h', b'u', b'n', b't', b'e', b'r', b'2',
+        0x00, 0x00,
+        0x00, 0x01, 0x00,
+        0xFB, 0xE8, 0x03, 0xFB, 0x84, 0x03,
+        0xFB, 0x20, 0x03,                                  // created_at 800
+        0x00,
+    ];
+
+    /// Checks the fixture vault after migrating it from `version`. Fields that version didn't
+    /// record have their defaults.
+    fn assert_fixture_item(vault: &Vault, version: u16) {
+        assert_eq!(vault.nonce, 2);
+        assert!(vault.key.is_none());
+
+        let item = &vault.items[0];
+        assert_eq!((item.id, item.name.as_str(), item.username.as_str(), item.password.as_str()), (1, "Mail", "alice", "hunter2"));
+        assert!(item.url.is_empty() && item.notes.is_empty());
+        assert_eq!(item.deleted_at, None);
+        assert_eq!(item.favorite, version >= 2);
+        assert!(item.custom_fields.is_empty());
+
+        let (modified_at, password_changed_at) = if version >= 4 { (1000, 900) } else { (0, 0) };
+        let created_at = if version >= 5 { 800 } else { 0 };
+        assert_eq!((item.modified_at, item.password_changed_at, item.created_at), (modified_at, password_changed_at, created_at));
+    }
+
+    /// The fixture of `version` decodes both on its own and through `decode`, and not as the
+    /// version after it
+    fn assert_fixture_migrates(fixture: &[u8], version: u16) {
+        assert_fixture_item(&Vault::migrate(fixture, version).expect("Migration failed"), version);
+        assert_fixture_item(&Vault::decode(fixture).expect("Decode failed"), version);
+        assert!(Vault::migrate(fixture, version + 1).is_err(), "Version {} fixture decoded as the next version", version);
+    }
+
+    #[test]
+    fn test_version_0_fixture_migrates() {
+        assert_fixture_migrates(VERSION_0_FIXTURE, 0);
+    }
+
+    #[test]
+    fn test_baseline_vault_file_opens() {
+        // Written by the first release, before vault files had a header
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/baseline.vault");
+        let (bytes, _key) = utils::file::open_vault(&path, "baseline password").expect("Failed to open baseline vault");
+        let vault = Vault::decode(bytes.as_ref()).expect("Decode failed");
+
+        assert_eq!(vault.nonce, 2);
+        assert_eq!(vault.items.len(), 2);
+        let item = &vault.items[1];
+        assert_eq!((item.id, item.name.as_str(), item.username.as_str(), item.password.as_str()), (1, "GitHub", "octocat", "hunter2"));
+        assert_eq!((item.url.as_str(), item.notes.as_str()), ("https://github.com", "Recovery codes in the safe"));
+        assert_eq!(item.deleted_at, None);
+        assert!(!item.favorite && item.custom_fields.is_empty());
+    }
+
+    #[test]
+    fn test_version_1_fixture_migrates() {
+        assert_fixture_migrates(VERSION_1_FIXTURE, 1);
+    }
+
+    #[test]
+    fn test_version_2_fixture_migrates() {
+        assert_fixture_migrates(VERSION_2_FIXTURE, 2);
+    }
+
+    #[test]
+    fn test_version_3_fixture_migrates() {
+        assert_fixture_migrates(VERSION_3_FIXTURE, 3);
+    }
+
+    #[test]
+    fn test_version_4_fixture_migrates() {
+        assert_fixture_migrates(VERSION_4_FIXTURE, 4);
+    }
+
+    #[test]
+    fn test_version_5_fixture_migrates() {
+        assert_fixture_item(&Vault::migrate(VERSION_5_FIXTURE, 5).expect("Migration failed"), 5);
+        assert_fixture_item(&Vault::decode(VERSION_5_FIXTURE).expect("Decode failed"), 5);
+    }
+
+    #[test]
+    fn test_version_6_fixture_decodes() {
+        let mut fixture = vec![VERSION_MARKER, 6, 0];
+        fixture.extend_from_slice(VERSION_5_FIXTURE);
+
+        let mut vault = Vault::decode(&fixture).expect("Decode failed");
+        assert_fixture_item(&vault, 6);
+        assert_eq!(vault.encode().expect("Encode failed").as_ref(), fixture, "Current vaults encode as version 6");
+    }
+
+    #[test]
+    fn test_newer_schema_is_rejected() {
+        let mut fixture = vec![VERSION_MARKER, 7, 0];
+        fixture.extend_from_slice(VERSION_5_FIXTURE);
+
+        assert!(matches!(Vault::decode(&fixture), Err(MigrationError::UnsupportedVersion(7))));
+    }
+
+    #[test]
+    fn test_payload_with_trailing_bytes_is_rejected() {
+        let mut fixture = vec![VERSION_MARKER, 6, 0];
+        fixture.extend_from_slice(VERSION_5_FIXTURE);
+        fixture.push(0x00);
+
+        assert!(matches!(Vault::decode(&fixture), Err(MigrationError::TrailingBytes { version: 6 })));
+    }
+
+    #[test]
+    fn test_age_days_counts_whole_days_since_last_edit() {
+        let mut item = vault_with_items(1).items.remove(0);
+        item.modified_at = NOW;
+
+        assert_eq!(item.age_days_at(NOW), 0);
+        assert_eq!(item.age_days_at(NOW + 86_399), 0);
+        assert_eq!(item.age_days_at(NOW + 3 * 86_400), 3);
+        assert_eq!(item.age_days_at(NOW - 10), 0, "A clock set back must not underflow");
+    }
+
+    /// Five items with known names, edit times and password ages
+    fn sortable_vault() -> Vault {
+        let mut vault = vault_with_items(5);
+        let fixture = [
+            ("github", NOW - 10, NOW - 500),
+            ("Amazon", NOW - 40, NOW - 100),
+            ("zoho", NOW - 20, NOW - 300),
+            ("Bank", NOW - 50, NOW - 400),
+            ("email", NOW - 30, NOW - 200),
+        ];
+
+        for (item, (name, modified_at, password_changed_at)) in vault.items.iter_mut().zip(fixture) {
+            item.name = name.into();
+            item.modified_at = modified_at;
+            item.password_changed_at = password_changed_at;
+        }
+        vault
+    }
+
+    fn sorted_ids(order: SortOrder) -> Vec<i32> {
+        let vault = sortable_vault();
+        let mut items = vault.active_items();
+        sort_items(&mut items, order);
+        ids(items)
+    }
+
+    #[test]
+    fn test_sort_by_name_ascending_ignores_case() {
+        assert_eq!(sorted_ids(SortOrder::ByNameAsc), vec![1, 3, 4, 0, 2]);
+    }
+
+    #[test]
+    fn test_sort_by_name_descending() {
+        assert_eq!(sorted_ids(SortOrder::ByNameDesc), vec![2, 0, 4, 3, 1]);
+    }
+
+    #[test]
+    fn test_sort_by_date_modified_newest_first() {
+        assert_eq!(sorted_ids(SortOrder::ByDateModifiedDesc), vec![0, 2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn test_sort_by_password_age_oldest_first() {
+        assert_eq!(sorted_ids(SortOrder::ByPasswordAgeDesc), vec![0, 3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn test_sort_by_creation_keeps_insertion_order_for_ties() {
+        let mut vault = sortable_vault();
+        for (item, created_at) in vault.items.iter_mut().zip([NOW - 5, 0, NOW - 9, 0, NOW - 5]) {
+            item.created_at = created_at;
         }
+        let mut items = vault.active_items();
+        sort_items(&mut items, SortOrder::ByCreatedAsc);
+
+        assert_eq!(ids(items), vec![1, 3, 2, 0, 4]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sort_owned_items() {
+        let mut items = sortable_vault().items;
+        sort_items(&mut items, SortOrder::ByNameAsc);
+
+        let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["Amazon", "Bank", "email", "github", "zoho"]);
+    }
+
+    #[test]
+    fn test_sort_order_ids_round_trip() {
+        for order in [SortOrder::ByNameAsc, SortOrder::ByNameDesc, SortOrder::ByDateModifiedDesc, SortOrder::ByPasswordAgeDesc, SortOrder::ByCreatedAsc] {
+            assert_eq!(SortOrder::from_id(order.id()), Some(order));
+        }
+        assert_eq!(SortOrder::from_id(5), None);
+    }
+
+    #[test]
+    fn test_delete_items_ignores_unknown_ids() {
+        let mut vault = vault_with_items(5);
+        vault.soft_delete_item(3, NOW);
+
+        assert_eq!(vault.delete_items(&[1, 3, 7, -1]), 2);
+        assert_eq!(ids(vault.active_items()), vec![0, 2, 4]);
+        assert!(vault.trash().is_empty(), "Trashed items can be bulk deleted too");
+    }
+
+    #[test]
+    fn test_delete_items_with_no_ids_removes_nothing() {
+        let mut vault = vault_with_items(3);
+
+        assert_eq!(vault.delete_items(&[]), 0);
+        assert_eq!(ids(vault.active_items()), vec![0, 1, 2]);
+    }
+
+    fn audited_vault() -> Vault {
+        let mut vault = vault_with_items(6);
+        let passwords = ["password", "Tr0ub4dor&3", "Tr0ub4dor&3", "", "correct horse battery staple", "kitten12"];
+        for (item, password) in vault.items.iter_mut().zip(passwords) {
+            item.password = password.into();
+            item.url = format!("https://example.com/{}", item.id);
+        }
+        vault.items[2].url = String::new();
+        vault.items[4].password_changed_at = NOW - PASSWORD_MAX_AGE_SECS - 1;
+        vault.items[5].password_changed_at = NOW - 60;
+        vault
+    }
+
+    #[test]
+    fn test_weak_items_skips_items_without_password() {
+        let vault = audited_vault();
+        assert_eq!(vault.weak_items(), vec![0]);
+    }
+
+    #[test]
+    fn test_find_duplicate_passwords_ignores_trashed_items() {
+        let mut vault = audited_vault();
+        vault.items[4].password = "kitten12".into();
+        assert_eq!(vault.find_duplicate_passwords(), vec![vec![1, 2], vec![4, 5]]);
+
+        vault.soft_delete_item(5, NOW);
+        assert_eq!(vault.find_duplicate_passwords(), vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_health_report_counts_active_items() {
+        let mut vault = audited_vault();
+        vault.soft_delete_item(0, NOW);
+
+        let report = vault.health_report_at(NOW);
+
+        assert_eq!(report.total_items, 5);
+        assert!(report.weak_passwords.is_empty());
+        assert_eq!(report.duplicate_passwords, vec![vec![1, 2]]);
+        assert_eq!(report.expired_items, vec![4], "Unknown password ages are not expired");
+        assert_eq!(report.items_without_url, vec![2]);
+        let scores = [4.0, 4.0, 4.0, f64::from(password_strength::score("kitten12"))];
+        assert_eq!(report.average_password_strength, scores.iter().sum::<f64>() / 4.0);
+    }
+
+    #[test]
+    fn test_health_report_of_empty_vault() {
+        let mut vault = vault_with_items(0);
+        vault.items.clear();
+
+        assert_eq!(vault.health_report_at(NOW), VaultHealthReport::default());
+    }
+
+    #[test]
+    fn test_health_cache_is_dropped_on_change() {
+        let mut vault = audited_vault();
+        let version = vault.version;
+        vault.cache_health_report(version, vault.health_report_at(NOW));
+        assert!(vault.cached_health_report().is_some());
+
+        vault.toggle_favorite(1);
+        assert!(vault.cached_health_report().is_none());
+
+        let version = vault.version;
+        vault.soft_delete_item(1, NOW);
+        vault.cache_health_report(version, VaultHealthReport::default());
+        assert!(vault.cached_health_report().is_none(), "Reports computed before a change must not be cached");
+    }
+
+    /// Fails if `s` shows `item`'s username or password
+    fn assert_no_sensitive_data_in_display(s: &str, item: &Item) {
+        for secret in [&item.username, &item.password] {
+            assert!(secret.is_empty() || !s.contains(secret.as_str()), "Display output leaks {:?}: {}", secret, s);
+        }
+    }
+
+    #[test]
+    fn test_item_display_hides_credentials() {
+        let mut item = vault_with_items(1).items.remove(0);
+        item.name = "GitHub".to_string();
+        item.username = "octocat@example.com".to_string();
+
+        let shown = item.to_string();
+        assert_eq!(shown, "Item { id: 0, name: \"GitHub\", has_password: true }");
+        assert_no_sensitive_data_in_display(&shown, &item);
+
+        item.password.clear();
+        assert!(item.to_string().ends_with("has_password: false }"));
+    }
+
+    #[test]
+    fn test_vault_display_summarizes_without_items() {
+        let mut vault = vault_with_items(3);
+        vault.metadata = VaultMetadata::new("My Passwords", 1_705_276_800);
+        vault.items[0].username = "alice".to_string();
+        vault.soft_delete_item(2, NOW);
+
+        let shown = vault.to_string();
+        assert_eq!(shown, "Vault { name: \"My Passwords\", items: 2, created: 2024-01-15 }");
+        for item in &vault.items {
+            assert_no_sensitive_data_in_display(&shown, item);
+        }
+
+        vault.metadata.created_at = 0;
+        assert!(vault.to_string().ends_with("created: unknown }"));
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::{Duration, Instant};
+
+
+/// How often the open vault is checked for inactivity
+pub(crate) const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Why the vault locked itself
+#[derive(Debug, PartialEq)]
+pub(crate) enum LockReason {
+    Idle,
+    FocusLost,
+}
+
+/// Decides when the open vault has gone unused, or been left in the background, for long
+/// enough to lock itself. Times are passed in rather than read from the clock, so the timer
+/// driving this can be tested.
+#[derive(Debug)]
+pub(crate) struct AutoLock {
+    timeout: Option<Duration>,        // None while no vault is open or auto-lock is off
+    last_activity: Option<Instant>,
+    focus_grace: Option<Duration>,    // None unless locking on focus loss is on
+    focus_lost_at: Option<Instant>,
+}
+
+impl AutoLock {
+    pub(crate) const fn new() -> Self {
+        Self { timeout: None, last_activity: None, focus_grace: None, focus_lost_at: None }
+    }
+
+    /// Starts counting from `now` for a vault that was just unlocked. A `timeout` of None
+    /// leaves the vault open however long it is unused, a `focus_grace` of None however long
+    /// the app is in the background.
+    pub(crate) fn arm(&mut self, now: Instant, timeout: Option<Duration>, focus_grace: Option<Duration>) {
+        self.timeout = timeout;
+        self.last_activity = Some(now);
+        self.focus_grace = focus_grace;
+        self.focus_lost_at = None;
+    }
+
+    /// Stops counting, e.g. once the vault is locked
+    pub(crate) fn disarm(&mut self) {
+        self.timeout = None;
+        self.last_activity = None;
+        self.focus_grace = None;
+        self.focus_lost_at = None;
+    }
+
+    pub(crate) fn is_armed(&self) -> bool {
+        self.timeout.is_some() || self.focus_grace.is_some()
+    }
+
+    /// Records that no window of the app has focus since `now`. Losing focus again before it
+    /// is regained doesn't restart the grace period.
+    pub(crate) fn focus_lost(&mut self, now: Instant) {
+        if self.focus_grace.is_some() && self.focus_lost_at.is_none() {
+            self.focus_lost_at = Some(now);
+        }
+    }
+
+    /// Records that a window of the app has focus again, cancelling the grace period
+    pub(crate) fn focus_gained(&mut self) {
+        self.focus_lost_at = None;
+    }
+
+    /// Records user input at `now`
+    pub(crate) fn touch(&mut self, now: Instant) {
+        if self.is_armed() {
+            self.last_activity = Some(now);
+        }
+    }
+
+    /// Whether, and why, the vault should lock at `now`. Disarms itself when it says so, so
+    /// the lock only happens once until the next `arm`.
+    pub(crate) fn poll(&mut self, now: Instant) -> Option<LockReason> {
+        let elapsed = |limit: Option<Duration>, since: Option<Instant>| match (limit, since) {
+            (Some(limit), Some(since)) => now.saturating_duration_since(since) >= limit,
+            _ => false,
+        };
+
+        let reason =
+            if elapsed(self.focus_grace, self.focus_lost_at) { Some(LockReason::FocusLost) }
+            else if elapsed(self.timeout, self.last_activity) { Some(LockReason::Idle) }
+            else { None };
+
+        if reason.is_some() {
+            self.disarm();
+        }
+        reason
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5 * 60);
+    const GRACE: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn test_locks_once_after_the_timeout() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.arm(start, Some(TIMEOUT), None);
+
+        assert_eq!(auto_lock.poll(start + TIMEOUT - CHECK_INTERVAL), None);
+        assert_eq!(auto_lock.poll(start + TIMEOUT), Some(LockReason::Idle));
+        assert!(!auto_lock.is_armed());
+        assert_eq!(auto_lock.poll(start + TIMEOUT * 2), None, "A locked vault isn't locked again");
+    }
+
+    #[test]
+    fn test_activity_restarts_the_countdown() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.arm(start, Some(TIMEOUT), None);
+
+        auto_lock.touch(start + TIMEOUT - Duration::from_secs(1));
+        assert_eq!(auto_lock.poll(start + TIMEOUT), None);
+        assert_eq!(auto_lock.poll(start + TIMEOUT * 2), Some(LockReason::Idle));
+    }
+
+    #[test]
+    fn test_never_locks_without_timeout() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.arm(start, None, None);
+
+        assert!(!auto_lock.is_armed());
+        assert_eq!(auto_lock.poll(start + Duration::from_secs(365 * 86_400)), None);
+    }
+
+    #[test]
+    fn test_disarmed_ignores_activity() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.touch(start);
+        assert_eq!(auto_lock.poll(start + TIMEOUT), None);
+
+        auto_lock.arm(start, Some(TIMEOUT), None);
+        auto_lock.disarm();
+        auto_lock.touch(start + TIMEOUT);
+        assert_eq!(auto_lock.poll(start + TIMEOUT * 2), None);
+    }
+
+    #[test]
+    fn test_locks_after_focus_grace() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.arm(start, Some(TIMEOUT), Some(GRACE));
+
+        auto_lock.focus_lost(start);
+        auto_lock.focus_lost(start + GRACE / 2);
+        assert_eq!(auto_lock.poll(start + GRACE - Duration::from_secs(1)), None);
+        assert_eq!(auto_lock.poll(start + GRACE), Some(LockReason::FocusLost), "Losing focus again doesn't restart the grace period");
+        assert!(!auto_lock.is_armed());
+    }
+
+    #[test]
+    fn test_regaining_focus_cancels_grace() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.arm(start, None, Some(GRACE));
+        assert!(auto_lock.is_armed());
+
+        // Focus moving to one of our own windows: lost by one, gained by the other
+        auto_lock.focus_lost(start);
+        auto_lock.focus_gained();
+        assert_eq!(auto_lock.poll(start + GRACE * 10), None);
+
+        auto_lock.focus_lost(start + GRACE * 10);
+        assert_eq!(auto_lock.poll(start + GRACE * 11), Some(LockReason::FocusLost));
+    }
+
+    #[test]
+    fn test_focus_ignored_when_off() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.arm(start, Some(TIMEOUT), None);
+
+        auto_lock.focus_lost(start);
+        assert_eq!(auto_lock.poll(start + TIMEOUT - Duration::from_secs(1)), None);
+        assert_eq!(auto_lock.poll(start + TIMEOUT), Some(LockReason::Idle));
+    }
+
+    #[test]
+    fn test_rearming_forgets_focus_loss() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::new();
+        auto_lock.arm(start, None, Some(GRACE));
+        auto_lock.focus_lost(start);
+
+        auto_lock.arm(start + GRACE * 2, None, Some(GRACE));
+        assert_eq!(auto_lock.poll(start + GRACE * 2), None);
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Maximum number of idle buffers kept around for reuse
+const MAX_POOLED: usize = 3;
+/// Pooled buffers are released if the pool hasn't been used for this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Small pool of reusable `ZeroByte` buffers for the file encrypt/decrypt pipeline,
+/// so saving a large vault doesn't allocate and free multi-megabyte buffers every time.
+///
+/// Buffers are moved out on checkout, so a buffer is never shared between tasks,
+/// and are zeroized when checked back in.
+pub(crate) struct BufferPool {
+    buffers: Vec<ZeroByte>,
+    high_water: usize,
+    last_used: Option<Instant>,
+}
+
+impl BufferPool {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buffers: Vec::new(),
+            high_water: 0,
+            last_used: None,
+        }
+    }
+
+    /// Takes an empty buffer with at least the high-water mark of capacity
+    pub(crate) fn checkout(&mut self) -> ZeroByte {
+        let now = Instant::now();
+        self.shrink_if_idle(now);
+        self.last_used = Some(now);
+
+        let mut buffer = self.buffers.pop().unwrap_or_default();
+        buffer.reserve(self.high_water);
+        buffer
+    }
+
+    /// Zeroizes a buffer and keeps it for reuse if the pool isn't full
+    pub(crate) fn checkin(&mut self, mut buffer: ZeroByte) {
+        self.high_water = self.high_water.max(buffer.len());
+        buffer.clear();
+
+        if self.buffers.len() < MAX_POOLED {
+            self.buffers.push(buffer);
+        }
+    }
+
+    /// Frees every pooled buffer. Buffers are already zeroized while pooled
+    /// and are zeroized again on drop.
+    pub(crate) fn release(&mut self) {
+        self.buffers.clear();
+        self.high_water = 0;
+    }
+
+    /// Best-effort scrub before exit. Pooled buffers are already zeroized up to the largest
+    /// length they ever held, so freeing them (which zeroizes the full capacity) is enough.
+    pub(crate) fn scrub_hint(&mut self) {
+        self.release();
+        self.last_used = None;
+    }
+
+    fn shrink_if_idle(&mut self, now: Instant) {
+        if let Some(last_used) = self.last_used
+            && now.duration_since(last_used) > IDLE_TIMEOUT {
+            self.release();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkout_filled(pool: &mut BufferPool, len: usize) -> ZeroByte {
+        let mut buffer = pool.checkout();
+        buffer.extend_from_slice(&vec![0xAB; len]);
+        buffer
+    }
+
+    #[test]
+    fn test_checkin_reuses_allocation() {
+        let mut pool = BufferPool::new();
+
+        let buffer = checkout_filled(&mut pool, 4096);
+        let ptr = buffer.as_ref().as_ptr();
+        pool.checkin(buffer);
+
+        let buffer = pool.checkout();
+        assert_eq!(buffer.len(), 0, "Recycled buffers must come back empty");
+        assert_eq!(buffer.as_ref().as_ptr(), ptr, "Recycled buffer should reuse the same allocation");
+    }
+
+    #[test]
+    fn test_checkout_reserves_high_water_mark() {
+        let mut pool = BufferPool::new();
+        let buffer = checkout_filled(&mut pool, 10_000);
+        pool.checkin(buffer);
+
+        // A fresh allocation is still sized for the largest payload seen so far,
+        // so filling it up to the high-water mark must not reallocate
+        pool.buffers.clear();
+        let mut buffer = pool.checkout();
+        buffer.extend_from_slice(&[1]);
+        let ptr = buffer.as_ref().as_ptr();
+        buffer.extend_from_slice(&[1; 9_999]);
+        assert_eq!(buffer.as_ref().as_ptr(), ptr);
+
+        pool.release();
+        assert_eq!(pool.high_water, 0);
+    }
+
+    #[test]
+    fn test_pool_is_bounded() {
+        let mut pool = BufferPool::new();
+        let buffers: Vec<ZeroByte> = (0..5).map(|_| checkout_filled(&mut pool, 16)).collect();
+
+        for buffer in buffers {
+            pool.checkin(buffer);
+        }
+
+        assert_eq!(pool.buffers.len(), MAX_POOLED);
+    }
+
+    #[test]
+    fn test_idle_pool_is_released() {
+        let mut pool = BufferPool::new();
+        let buffer = checkout_filled(&mut pool, 1024);
+        pool.checkin(buffer);
+
+        let last_used = pool.last_used.unwrap();
+        pool.shrink_if_idle(last_used + IDLE_TIMEOUT / 2);
+        assert_eq!(pool.buffers.len(), 1);
+
+        pool.shrink_if_idle(last_used + IDLE_TIMEOUT * 2);
+        assert!(pool.buffers.is_empty());
+        assert_eq!(pool.high_water, 0);
+    }
+}
@@ -0,0 +1,276 @@
+use std::cell::RefCell;
+use std::ffi::{c_void, OsStr};
+use std::ptr::NonNull;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use blake2::{Blake2s256, Digest};
+use copypasta::{ClipboardContext, ClipboardProvider};
+
+use crate::errors::clipboard_errors::ClipboardError;
+use crate::utils::sensitive_clipboard;
+
+
+/// Hash of the text NoPass last put on the clipboard, so it can recognize its own copy
+/// later without keeping the secret itself around
+static LAST_COPIED: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+
+#[cfg(all(unix, not(target_os = "macos")))]
+type WaylandClipboard = copypasta::wayland_clipboard::Clipboard;
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+type WaylandClipboard = copypasta::nop_clipboard::NopClipboardContext;
+
+thread_local! {
+    /// Clipboard on the main window's Wayland connection, once attached. It lives as long as
+    /// the app so the offer of the last copy stays up until it's cleared or replaced.
+    static WAYLAND_CLIPBOARD: RefCell<Option<WaylandClipboard>> = const { RefCell::new(None) };
+}
+
+/// Which clipboard copies go to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backend {
+    /// The data device of the window's own Wayland connection. Wayland only serves an offer
+    /// while the client that made it keeps it alive, and only accepts it from a client with
+    /// a surface, so a short-lived connection of its own like X11 uses doesn't work there.
+    Wayland,
+    /// copypasta's platform clipboard: X11 (including XWayland), Windows or macOS
+    System,
+}
+
+/// Picks the backend from `WAYLAND_DISPLAY` and whether the window's Wayland connection was
+/// attached. A session can set `WAYLAND_DISPLAY` while the window still runs on XWayland,
+/// then the X11 clipboard is the one that works.
+pub(crate) fn select_backend(wayland_display: Option<&OsStr>, wayland_attached: bool) -> Backend {
+    if wayland_display.is_some_and(|display| !display.is_empty()) && wayland_attached {
+        Backend::Wayland
+    } else {
+        Backend::System
+    }
+}
+
+fn backend() -> Backend {
+    let attached = WAYLAND_CLIPBOARD.with_borrow(Option::is_some);
+    select_backend(std::env::var_os("WAYLAND_DISPLAY").as_deref(), attached)
+}
+
+/// Serves the clipboard through the Wayland connection `display` points to, a `wl_display`.
+/// Only the first call takes effect.
+///
+/// # Safety
+/// `display` must stay a valid `wl_display` for the rest of the thread's life, which holds
+/// for the connection of the event loop the UI runs on.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) unsafe fn attach_wayland(display: NonNull<c_void>) {
+    WAYLAND_CLIPBOARD.with_borrow_mut(|clipboard| {
+        if clipboard.is_none() {
+            // SAFETY: upheld by the caller
+            let (_, wayland) = unsafe { copypasta::wayland_clipboard::create_clipboards_from_external(display.as_ptr()) };
+            *clipboard = Some(wayland);
+            log::debug!("Using the Wayland clipboard");
+        }
+    });
+}
+
+/// There is no Wayland on this platform, the system clipboard is always used
+///
+/// # Safety
+/// Nothing is done with `display`
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub(crate) unsafe fn attach_wayland(_display: NonNull<c_void>) {}
+
+/// Runs `f` on the clipboard the current backend selects
+fn with_clipboard<T>(f: impl FnOnce(&mut dyn ClipboardProvider, Backend) -> Result<T, ClipboardError>) -> Result<T, ClipboardError> {
+    match backend() {
+        Backend::Wayland => WAYLAND_CLIPBOARD.with_borrow_mut(|clipboard| match clipboard {
+            Some(clipboard) => f(clipboard, Backend::Wayland),
+            None => Err(ClipboardError::Unavailable("Wayland clipboard not attached".to_string())),
+        }),
+        Backend::System => {
+            let mut ctx = ClipboardContext::new().map_err(|e| ClipboardError::Unavailable(e.to_string()))?;
+            f(&mut ctx, Backend::System)
+        }
+    }
+}
+
+/// Puts `text` on the clipboard, marked as a secret for clipboard managers and history to
+/// skip where the platform has a way to say so, see `sensitive_clipboard::hint_formats`.
+/// Everything NoPass copies comes out of the vault, so every copy is marked. Falls back to
+/// plain text, which is all the Wayland backend can offer.
+pub(crate) fn copy_text(text: &str) -> Result<(), ClipboardError> {
+    let marked = backend() == Backend::System && sensitive_clipboard::copy(text)
+        .inspect_err(|e| log::debug!("Copying without clipboard manager hints: {}", e))
+        .is_ok();
+    if !marked {
+        with_clipboard(|ctx, backend| copy_with(ctx, backend, text))?;
+    }
+    *LAST_COPIED.lock().unwrap_or_else(|e| e.into_inner()) = Some(hash(text));
+    Ok(())
+}
+
+fn copy_with(ctx: &mut dyn ClipboardProvider, backend: Backend, text: &str) -> Result<(), ClipboardError> {
+    ctx.set_contents(text.to_owned()).map_err(|e| ClipboardError::WriteFailed(e.to_string()))?;
+
+    // On KDE under X11 the copy sometimes never arrives unless it is read back while this context
+    // still serves it. Reading it back also shows whether it arrived, so a missed copy is tried once
+    // more. The Wayland clipboard keeps serving on its own, so it doesn't need this.
+    if backend == Backend::System && is_kde() && !ctx.get_contents().is_ok_and(|contents| contents == text) {
+        log::debug!("Clipboard didn't take the copy, retrying");
+        ctx.set_contents(text.to_owned()).map_err(|e| ClipboardError::WriteFailed(e.to_string()))?;
+        let _ = ctx.get_contents();
+    }
+    Ok(())
+}
+
+fn is_kde() -> bool {
+    std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| is_kde_desktop(&desktop))
+}
+
+/// `XDG_CURRENT_DESKTOP` is a colon separated list, e.g. `KDE` or `ubuntu:GNOME`
+fn is_kde_desktop(desktop: &str) -> bool {
+    desktop.split(':').any(|name| name.eq_ignore_ascii_case("KDE"))
+}
+
+/// Best-effort scrub before exit, when the vault locks or when a copied secret times out:
+/// clears the clipboard if NoPass still owns it, i.e. it holds exactly the text last copied
+/// from here. Anything the user copied from elsewhere since is left alone.
+/// On X11 the selection goes away with the process that owns it, but Windows and macOS keep
+/// it after exit, so it's cleared explicitly everywhere. Returns whether it was cleared.
+pub(crate) fn clear_if_owned() -> bool {
+    let Some(last_copied) = LAST_COPIED.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return false;
+    };
+
+    with_clipboard(|ctx, _| {
+        Ok(ctx.get_contents().is_ok_and(|contents| is_owned(&contents, &last_copied))
+            && ctx.set_contents(String::new()).is_ok())
+    })
+    .unwrap_or(false)
+}
+
+/// Whether `contents` is the text whose hash NoPass recorded when copying it
+fn is_owned(contents: &str, last_copied: &[u8; 32]) -> bool {
+    hash(contents) == *last_copied
+}
+
+fn hash(text: &str) -> [u8; 32] {
+    Blake2s256::digest(text.as_bytes()).into()
+}
+
+/// Time left until a copied secret is cleared from the clipboard. Each copy starts it over,
+/// so an older copy's countdown never clears a newer one early. Times are passed in, like
+/// `AutoLock`, so the timer driving this can be tested.
+#[derive(Debug)]
+pub(crate) struct ClearCountdown {
+    deadline: Option<Instant>,
+}
+
+impl ClearCountdown {
+    pub(crate) const fn new() -> Self {
+        Self { deadline: None }
+    }
+
+    /// Starts counting `after` from `now`, replacing any countdown still running
+    pub(crate) fn start(&mut self, now: Instant, after: Duration) {
+        self.deadline = Some(now + after);
+    }
+
+    pub(crate) fn cancel(&mut self) {
+        self.deadline = None;
+    }
+
+    /// Whole seconds left at `now`, rounded up so the indicator only shows 0 once it's done.
+    /// None when nothing is waiting to be cleared.
+    pub(crate) fn seconds_left(&self, now: Instant) -> Option<u64> {
+        let left = self.deadline?.saturating_duration_since(now);
+        Some(left.as_secs() + u64::from(left.subsec_nanos() > 0))
+    }
+
+    /// Whether the clipboard should be cleared at `now`. Stops the countdown when it says so.
+    pub(crate) fn poll(&mut self, now: Instant) -> bool {
+        let due = self.deadline.is_some_and(|deadline| now >= deadline);
+        if due {
+            self.cancel();
+        }
+        due
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFTER: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_countdown_fires_once_at_deadline() {
+        let start = Instant::now();
+        let mut countdown = ClearCountdown::new();
+        assert_eq!(countdown.seconds_left(start), None);
+
+        countdown.start(start, AFTER);
+        assert_eq!(countdown.seconds_left(start), Some(30));
+        assert_eq!(countdown.seconds_left(start + Duration::from_millis(29_500)), Some(1));
+        assert!(!countdown.poll(start + AFTER - Duration::from_millis(1)));
+        assert!(countdown.poll(start + AFTER));
+        assert!(!countdown.poll(start + AFTER * 2), "The clipboard is only cleared once");
+        assert_eq!(countdown.seconds_left(start + AFTER), None);
+    }
+
+    #[test]
+    fn test_newer_copy_restarts_countdown() {
+        let start = Instant::now();
+        let mut countdown = ClearCountdown::new();
+        countdown.start(start, AFTER);
+
+        countdown.start(start + Duration::from_secs(20), AFTER);
+        assert!(!countdown.poll(start + AFTER), "The older copy's deadline no longer applies");
+        assert!(countdown.poll(start + Duration::from_secs(50)));
+    }
+
+    #[test]
+    fn test_cancelled_countdown_never_fires() {
+        let start = Instant::now();
+        let mut countdown = ClearCountdown::new();
+        countdown.start(start, AFTER);
+        countdown.cancel();
+
+        assert!(!countdown.poll(start + AFTER));
+    }
+
+    #[test]
+    fn test_hash_identifies_content() {
+        assert_eq!(hash("hunter2"), hash("hunter2"));
+        assert_ne!(hash("hunter2"), hash("hunter3"));
+    }
+
+    #[test]
+    fn test_only_our_copy_is_owned() {
+        let copied = hash("correct-horse-battery-staple");
+
+        assert!(is_owned("correct-horse-battery-staple", &copied));
+        assert!(!is_owned("something the user copied later", &copied));
+        assert!(!is_owned("correct-horse-battery-staple ", &copied));
+        assert!(!is_owned("", &copied));
+    }
+
+    #[test]
+    fn test_wayland_backend_needs_session_and_connection() {
+        let wayland = Some(OsStr::new("wayland-0"));
+
+        assert_eq!(select_backend(wayland, true), Backend::Wayland);
+        assert_eq!(select_backend(wayland, false), Backend::System, "The window runs on XWayland");
+        assert_eq!(select_backend(None, false), Backend::System);
+        assert_eq!(select_backend(None, true), Backend::System);
+        assert_eq!(select_backend(Some(OsStr::new("")), true), Backend::System);
+    }
+
+    #[test]
+    fn test_kde_detection() {
+        assert!(is_kde_desktop("KDE"));
+        assert!(is_kde_desktop("neon:KDE"));
+        assert!(!is_kde_desktop("ubuntu:GNOME"));
+        assert!(!is_kde_desktop("KDE-like"));
+        assert!(!is_kde_desktop(""));
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::{Read, Write};
+
+use zstd::stream::read::Decoder;
+use zstd::stream::write::Encoder;
+
+use crate::errors::compression_errors::CompressionError;
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Largest output `decompress` is normally allowed to produce, so a crafted vault file can't
+/// exhaust memory. Attachments are stored separately, this only bounds the items.
+pub(crate) const DEFAULT_MAX_OUTPUT_LEN: usize = 64 * 1024 * 1024;
+
+/// Zstd-compresses `bytes` into a new `ZeroByte`. The encoder's internal window isn't
+/// zeroized when it's freed.
+pub(crate) fn compress(bytes: &ZeroByte) -> Result<ZeroByte, CompressionError> {
+    let mut encoder = Encoder::new(ZeroByte::default(), zstd::DEFAULT_COMPRESSION_LEVEL)
+        .map_err(|e| CompressionError::Codec(e.to_string()))?;
+    encoder.write_all(bytes.as_ref()).map_err(|e| CompressionError::Codec(e.to_string()))?;
+    encoder.finish().map_err(|e| CompressionError::Codec(e.to_string()))
+}
+
+/// Decompresses `bytes` into a new `ZeroByte`, failing once the output would exceed
+/// `max_output_len` rather than after producing all of it
+pub(crate) fn decompress(bytes: &ZeroByte, max_output_len: usize) -> Result<ZeroByte, CompressionError> {
+    let mut output = ZeroByte::default();
+    let decoder = Decoder::with_buffer(bytes.as_ref()).map_err(|e| CompressionError::Codec(e.to_string()))?;
+    let mut decoder = decoder.take(max_output_len as u64 + 1);
+
+    match output.extend_from_reader(&mut decoder) {
+        Ok(len) if len > max_output_len => Err(CompressionError::OutputTooLarge { max_output_len }),
+        Ok(_) => Ok(output),
+        Err(e) => Err(CompressionError::Codec(e.to_string())),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::config::standard;
+    use bincode::serde::encode_to_vec;
+
+    use crate::models::vault::{Item, Vault};
+
+    fn zero_byte(bytes: &[u8]) -> ZeroByte {
+        let mut zero_byte = ZeroByte::default();
+        zero_byte.extend_from_slice(bytes);
+        zero_byte
+    }
+
+    /// A serialized vault with the kind of repetitive fields real ones have
+    fn realistic_vault() -> ZeroByte {
+        let mut vault = Vault::new();
+        vault.items = (0..500)
+            .map(|id| Item {
+                id,
+                name: format!("Account {}", id),
+                username: format!("user{}@example.com", id),
+                password: format!("Xk9#mP2$vL{}qR7!", id * 7919),
+                url: format!("https://login.example{}.com/signin", id % 20),
+                notes: "Security questions: first pet, mother's maiden name. Renew every 90 days.".into(),
+                modified_at: 1_700_000_000,
+                password_changed_at: 1_700_000_000,
+                created_at: 1_700_000_000,
+                ..Default::default()
+            })
+            .collect();
+
+        zero_byte(&encode_to_vec(&vault, standard()).expect("Encoding failed"))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for original in [zero_byte(b""), zero_byte(b"x"), zero_byte(&[0u8, 255, 1, 254]), realistic_vault()] {
+            let compressed = compress(&original).expect("Compression failed");
+            let decompressed = decompress(&compressed, DEFAULT_MAX_OUTPUT_LEN).expect("Decompression failed");
+            assert_eq!(decompressed, original);
+        }
+    }
+
+    #[test]
+    fn test_realistic_vault_compresses_well() {
+        let original = realistic_vault();
+        let compressed = compress(&original).expect("Compression failed");
+
+        assert!(
+            compressed.len() * 3 < original.len(),
+            "Expected at least 3:1, got {} -> {} bytes", original.len(), compressed.len()
+        );
+    }
+
+    #[test]
+    fn test_oversized_output_is_rejected() {
+        let bomb = compress(&zero_byte(&vec![0u8; 1024 * 1024])).expect("Compression failed");
+        assert!(bomb.len() < 8 * 1024);
+
+        assert_eq!(decompress(&bomb, 1024 * 1024).expect("Decompression failed").len(), 1024 * 1024);
+        assert!(matches!(
+            decompress(&bomb, 1024 * 1024 - 1),
+            Err(CompressionError::OutputTooLarge { max_output_len }) if max_output_len == 1024 * 1024 - 1
+        ));
+    }
+
+    #[test]
+    fn test_invalid_stream_is_rejected() {
+        assert!(matches!(decompress(&zero_byte(b"not zstd data \xff\xff"), 1024), Err(CompressionError::Codec(_))));
+    }
+}
@@ -0,0 +1,236 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+
+use crate::errors::config_errors::ConfigError;
+use crate::models::settings::Settings;
+use crate::utils::file;
+
+
+const APP_DIR_NAME: &str = "nopass";
+const SETTINGS_FILE_NAME: &str = "settings.toml";
+const BACKUPS_DIR_NAME: &str = "backups";
+
+/// File next to the executable that switches on portable mode
+const PORTABLE_FLAG: &str = "portable.flag";
+const PORTABLE_ARG: &str = "--portable";
+/// Directory next to the executable holding the app data in portable mode
+const PORTABLE_DIR_NAME: &str = "data";
+
+static APP_DATA_DIR: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    let exe_dir = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let portable_arg = env::args().skip(1).any(|arg| arg == PORTABLE_ARG);
+
+    let dir = resolve_app_data_dir(exe_dir.as_deref(), portable_arg, platform_config_dir());
+    if let Some(dir) = &dir {
+        log::info!("Using app data directory {}", dir.display());
+    }
+    dir
+});
+
+/// Directory for the files NoPass keeps for itself, like its settings. Every app data path is
+/// resolved from here, so portable mode only has to change this one directory.
+pub(crate) fn app_data_dir() -> Option<PathBuf> {
+    APP_DATA_DIR.clone()
+}
+
+/// In portable mode, turned on by `portable.flag` next to the executable or the `--portable`
+/// argument, app data lives in a directory next to the executable instead of the platform's
+fn resolve_app_data_dir(exe_dir: Option<&Path>, portable_arg: bool, platform_dir: Option<PathBuf>) -> Option<PathBuf> {
+    match exe_dir {
+        Some(exe_dir) if portable_arg || exe_dir.join(PORTABLE_FLAG).is_file() => Some(exe_dir.join(PORTABLE_DIR_NAME)),
+        _ => platform_dir,
+    }
+}
+
+/// Platform config directory for NoPass: `$XDG_CONFIG_HOME/nopass` (or `~/.config/nopass`)
+/// on Linux, `~/Library/Application Support/nopass` on macOS and `%APPDATA%\nopass` on Windows
+fn platform_config_dir() -> Option<PathBuf> {
+    let non_empty = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+
+    let base = if cfg!(windows) {
+        non_empty("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        non_empty("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        non_empty("XDG_CONFIG_HOME").or_else(|| non_empty("HOME").map(|home| home.join(".config")))
+    };
+
+    base.map(|base| base.join(APP_DIR_NAME))
+}
+
+pub(crate) fn settings_path() -> Option<PathBuf> {
+    app_data_dir().map(|dir| dir.join(SETTINGS_FILE_NAME))
+}
+
+/// Directory daily vault snapshots are kept in, the one set in `settings` or the app data default
+pub(crate) fn backups_dir(settings: &Settings) -> Option<PathBuf> {
+    settings.backups_dir.clone().or_else(|| app_data_dir().map(|dir| dir.join(BACKUPS_DIR_NAME)))
+}
+
+/// Loads the settings, falling back to the defaults if there are none or they can't be read
+pub(crate) fn load() -> Settings {
+    match settings_path() {
+        Some(path) => load_from(&path),
+        None => {
+            log::warn!("No config directory found, using default settings");
+            Settings::default()
+        },
+    }
+}
+
+pub(crate) fn save(settings: &Settings) -> Result<(), ConfigError> {
+    save_to(&settings_path().ok_or(ConfigError::NoConfigDir)?, settings)
+}
+
+/// Reads the settings at `path`. A damaged or half-written file is logged and replaced by
+/// the defaults rather than stopping the app from starting.
+fn load_from(path: &Path) -> Settings {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Settings::default(),
+        Err(e) => {
+            log::warn!("Failed to read settings from {}: {}", path.display(), e);
+            return Settings::default();
+        },
+    };
+
+    toml::from_str(&contents).unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid settings file {}: {}", path.display(), e);
+        Settings::default()
+    })
+}
+
+/// Writes the settings to `path` atomically, creating its directory if needed
+fn save_to(path: &Path, settings: &Settings) -> Result<(), ConfigError> {
+    let contents = toml::to_string_pretty(settings).map_err(|e| ConfigError::EncodingFailed(e.to_string()))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    file::write_atomically(path, contents.as_bytes(), 0)?;
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::settings::{LastDirectories, WindowGeometry};
+    use crate::models::vault::SortOrder;
+    use crate::utils::crypto::ArgonParams;
+
+    #[test]
+    fn test_platform_dir_is_used_without_portable_flag() {
+        let exe_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let platform_dir = PathBuf::from("/home/user/.config/nopass");
+
+        assert_eq!(resolve_app_data_dir(Some(exe_dir.path()), false, Some(platform_dir.clone())), Some(platform_dir.clone()));
+        assert_eq!(resolve_app_data_dir(None, true, Some(platform_dir.clone())), Some(platform_dir));
+    }
+
+    #[test]
+    fn test_portable_flag_file_keeps_data_next_to_executable() {
+        let exe_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        fs::write(exe_dir.path().join(PORTABLE_FLAG), "").expect("Failed to write");
+
+        let dir = resolve_app_data_dir(Some(exe_dir.path()), false, Some(PathBuf::from("/home/user/.config/nopass")));
+        assert_eq!(dir, Some(exe_dir.path().join(PORTABLE_DIR_NAME)));
+
+        // Without a platform directory at all
+        assert_eq!(resolve_app_data_dir(Some(exe_dir.path()), false, None), Some(exe_dir.path().join(PORTABLE_DIR_NAME)));
+    }
+
+    #[test]
+    fn test_portable_argument_keeps_data_next_to_executable() {
+        let exe_dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+        let dir = resolve_app_data_dir(Some(exe_dir.path()), true, Some(PathBuf::from("/home/user/.config/nopass")));
+        assert_eq!(dir, Some(exe_dir.path().join(PORTABLE_DIR_NAME)));
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("nested").join(SETTINGS_FILE_NAME);
+
+        let mut settings = Settings { clipboard_clear_secs: None, auto_lock_secs: Some(60), ..Settings::default() };
+        settings.add_recent_vault(Path::new("/home/user/work.vault"));
+        save_to(&path, &settings).expect("Save failed");
+
+        assert_eq!(load_from(&path), settings);
+    }
+
+    #[test]
+    fn test_every_field_round_trips() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join(SETTINGS_FILE_NAME);
+
+        // Every field away from its default, so a field that isn't written back would show up
+        let settings = Settings {
+            clipboard_clear_secs: Some(12),
+            auto_lock_secs: None,
+            lock_on_focus_loss_secs: Some(20),
+            recent_vaults: vec![PathBuf::from("/home/user/work.vault"), PathBuf::from("/home/user/home.vault")],
+            backups_dir: Some(PathBuf::from("/mnt/backups")),
+            snapshot_retention_count: 7,
+            snapshot_retention_days: None,
+            auto_calibrate_on_first_run: false,
+            argon_params: Some(ArgonParams { memory_cost: 131_072, time_cost: 3, ..ArgonParams::default() }),
+            compression_enabled: false,
+            default_vault_path: Some(PathBuf::from("/home/user/vaults")),
+            last_directories: LastDirectories {
+                open_vault: Some(PathBuf::from("/home/user/shared")),
+                save_vault: Some(PathBuf::from("/home/user/new")),
+                import_export: Some(PathBuf::from("/home/user/exports")),
+            },
+            confirm_delete: false,
+            sort_order: SortOrder::ByPasswordAgeDesc,
+            window_geometry: WindowGeometry::new(-40, 25, 1280, 720),
+        };
+        assert_ne!(settings, Settings::default());
+        save_to(&path, &settings).expect("Save failed");
+
+        assert_eq!(load_from(&path), settings);
+    }
+
+    #[test]
+    fn test_missing_file_loads_defaults() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        assert_eq!(load_from(&dir.path().join(SETTINGS_FILE_NAME)), Settings::default());
+    }
+
+    #[test]
+    fn test_damaged_file_loads_defaults() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join(SETTINGS_FILE_NAME);
+
+        let settings = Settings { auto_lock_secs: Some(60), ..Settings::default() };
+        save_to(&path, &settings).expect("Save failed");
+
+        // Cut off in the middle of a value, as an interrupted write would leave it
+        let contents = fs::read_to_string(&path).expect("Failed to read");
+        let cut = contents.find("auto_lock_secs").expect("Missing field") + "auto_lock_secs = ".len();
+        fs::write(&path, &contents[..cut]).expect("Failed to write");
+        assert_eq!(load_from(&path), Settings::default());
+
+        fs::write(&path, [0xff, 0xfe, 0x00]).expect("Failed to write");
+        assert_eq!(load_from(&path), Settings::default());
+    }
+
+    #[test]
+    fn test_missing_fields_take_defaults() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join(SETTINGS_FILE_NAME);
+        fs::write(&path, "auto_lock_secs = 60\n").expect("Failed to write");
+
+        let settings = load_from(&path);
+        assert_eq!(settings.auto_lock_secs, Some(60));
+        assert_eq!(settings.clipboard_clear_secs, Settings::default().clipboard_clear_secs);
+        assert!(settings.compression_enabled);
+    }
+}
@@ -1,30 +1,233 @@
+use std::time::{Duration, Instant};
+
 use aes_gcm::{
-    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng as AesOsRng}, Aes256Gcm, Key as AesKey, Error as AesError, Nonce
+    aead::{rand_core::RngCore, AeadInPlace, KeyInit, OsRng as AesOsRng}, Aes256Gcm, Key as AesKey, Nonce, Tag
 };
-use argon2::{Argon2, Params};
+use aes_gcm::aes::Aes256;
+use aes_gcm::aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt};
+use argon2::{Algorithm, Argon2, Params, Version};
 use argon2::password_hash::{rand_core::OsRng as ArgonOsRng};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::errors::crypto_errors::CryptoError;
+use crate::errors::password_errors::PasswordError;
+use crate::utils::zero_byte::{ZeroByte, ZeroByteStack};
+
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// Shortest input `aes_gcm_decrypt` accepts, an empty plaintext
+pub(super) const MIN_ENCRYPTED_LEN: usize = NONCE_LEN + TAG_LEN;
+
+// Bounds for parameters read from a file, well beyond anything a NoPass vault uses
+const MAX_MEMORY_COST: u32 = 4 * 1024 * 1024;  // 4 GiB
+const MAX_TIME_COST: u32 = 1000;
+const MAX_PARALLELISM: u32 = 64;
+
+// Calibration only tunes memory, iterations stay at a fixed low count
+const CALIBRATION_TIME_COST: u32 = 3;
+const CALIBRATION_MAX_MEMORY_COST: u32 = 1024 * 1024;  // 1 GiB
+/// Most AES-KDF rounds accepted from a KeePass database, a few seconds of work
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+
+const AES_BLOCK_LEN: usize = 16;
+
+/// Bounds of `GeneratorOptions::length`
+pub(crate) const MIN_GENERATED_LEN: usize = 8;
+pub(crate) const MAX_GENERATED_LEN: usize = 128;
+
+const UPPERCASE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWERCASE: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const DIGITS: &[u8] = b"0123456789";
+/// Symbols most sites accept, without quotes or spaces that get mangled when pasted
+const SYMBOLS: &[u8] = b"!#$%&*+-./:;<=>?@^_~";
+
+/// Length and character classes of a password made by `Crypto::generate_password`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct GeneratorOptions {
+    pub length: usize,
+    pub uppercase: bool,
+    pub lowercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+}
+
+/// Argon2 variant used to derive the vault key. Recorded per vault in the file header.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub(crate) enum KdfAlgorithm {
+    #[default]
+    Argon2id,
+    Argon2i,
+    Argon2d,
+}
+
+impl KdfAlgorithm {
+    pub(crate) const ALL: [Self; 3] = [Self::Argon2id, Self::Argon2i, Self::Argon2d];
+
+    /// Stable identifier written to the vault file header
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Self::Argon2id => 0,
+            Self::Argon2i => 1,
+            Self::Argon2d => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|algorithm| algorithm.id() == id)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Argon2id => "Argon2id",
+            Self::Argon2i => "Argon2i",
+            Self::Argon2d => "Argon2d",
+        }
+    }
+
+    fn to_argon2(self) -> Algorithm {
+        match self {
+            Self::Argon2id => Algorithm::Argon2id,
+            Self::Argon2i => Algorithm::Argon2i,
+            Self::Argon2d => Algorithm::Argon2d,
+        }
+    }
+}
+
+/// Cipher used for the vault body. Recorded in the vault metadata.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub(crate) enum CipherAlgorithm {
+    #[default]
+    Aes256Gcm,
+}
+
+/// Key derivation parameters, stored in the vault header so unlock can reproduce the key
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub(crate) struct ArgonParams {
+    pub algorithm: KdfAlgorithm,
+    pub memory_cost: u32,   // KiB
+    pub time_cost: u32,     // Iterations
+    pub parallelism: u32,   // Lanes
+}
+
+impl Default for ArgonParams {
+    fn default() -> Self {
+        Self {
+            algorithm: KdfAlgorithm::default(),
+            memory_cost: 15000,  // 15 MB
+            time_cost: 50,       // 50 rounds
+            parallelism: 2,      // 2 threads
+        }
+    }
+}
+
+impl ArgonParams {
+    /// Checks the parameters are accepted by Argon2 and within plausible bounds,
+    /// so a crafted header can't stall key derivation for minutes
+    pub(crate) fn validate(&self) -> Result<(), CryptoError> {
+        Params::new(self.memory_cost, self.time_cost, self.parallelism, None)?;
+
+        if self.memory_cost > MAX_MEMORY_COST {
+            return Err(CryptoError::InvalidParams(format!("Memory cost of {} KiB is above the {} KiB limit", self.memory_cost, MAX_MEMORY_COST)));
+        }
+        if self.time_cost > MAX_TIME_COST {
+            return Err(CryptoError::InvalidParams(format!("Time cost of {} is above the {} limit", self.time_cost, MAX_TIME_COST)));
+        }
+        if self.parallelism > MAX_PARALLELISM {
+            return Err(CryptoError::InvalidParams(format!("Parallelism of {} is above the {} limit", self.parallelism, MAX_PARALLELISM)));
+        }
 
+        Ok(())
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub(crate) struct ArgonKey {
     pub(super) bytes: [u8; 32],
     pub(super) salt: [u8; 16],
+    pub(super) params: ArgonParams,
+}
+
+impl ArgonKey {
+    /// Copy of the key bytes for the cipher, wiped when dropped
+    pub(crate) fn to_zero_byte(&self) -> ZeroByte {
+        ZeroByte::from_fixed_array(self.bytes)
+    }
+
+    /// Overwrites the key material. The key is unusable afterwards.
+    pub(crate) fn wipe(&mut self) {
+        self.bytes.zeroize();
+    }
+
+    /// Whether `password` derives this key with its salt and parameters, e.g. to confirm the
+    /// master password again before a sensitive action. The keys are compared in constant time.
+    pub(crate) fn matches_password(&self, password: &[u8]) -> Result<bool, CryptoError> {
+        let mut derived = Crypto::derive_argon_key(password, Some(self.salt), self.params)?;
+
+        let mut expected = ZeroByte::default();
+        expected.extend_from_slice(&self.bytes);
+        let mut actual = ZeroByte::default();
+        actual.extend_from_slice(&derived.bytes);
+        derived.wipe();
+
+        Ok(expected == actual)
+    }
 }
 
 pub(crate) struct Crypto {}
 
 impl Crypto {
-    pub(crate) fn derive_argon_key(bytes: &[u8], salt: Option<[u8; 16]>) -> Result<ArgonKey, String> {
-        let memory = 15000;   // 15 MB
-        let transform = 50;   // 50 rounds
-        let parallel = 2;     // 2 threads
+    /// Random password drawn uniformly from the chosen character classes, with at least one
+    /// character of each. Candidates missing a class are thrown away rather than patched, so
+    /// every password meeting the options is equally likely.
+    pub(crate) fn generate_password(options: GeneratorOptions) -> Result<ZeroByte, PasswordError> {
+        if !(MIN_GENERATED_LEN..=MAX_GENERATED_LEN).contains(&options.length) {
+            return Err(PasswordError::LengthOutOfRange { min: MIN_GENERATED_LEN, max: MAX_GENERATED_LEN });
+        }
+
+        let classes: Vec<&[u8]> = [(options.uppercase, UPPERCASE), (options.lowercase, LOWERCASE), (options.digits, DIGITS), (options.symbols, SYMBOLS)]
+            .into_iter()
+            .filter_map(|(enabled, class)| enabled.then_some(class))
+            .collect();
+        if classes.is_empty() {
+            return Err(PasswordError::NoCharacterClasses);
+        }
+        let alphabet = classes.concat();
+
+        loop {
+            let mut password = ZeroByte::default();
+            password.reserve(options.length);
+            for _ in 0..options.length {
+                password.extend_from_slice(&[alphabet[Self::random_index(alphabet.len())]]);
+            }
+
+            if classes.iter().all(|class| password.as_ref().iter().any(|byte| class.contains(byte))) {
+                return Ok(password);
+            }
+        }
+    }
 
-        let params = 
-            Params::new(memory, transform, parallel, None)
-                .map_err(|e| e.to_string())?;
+    /// Uniform index below `len`, rejecting the values that would favor the low indices
+    fn random_index(len: usize) -> usize {
+        let len = len as u32;
+        let limit = u32::MAX - u32::MAX % len;
+        loop {
+            let value = AesOsRng.next_u32();
+            if value < limit {
+                return (value % len) as usize;
+            }
+        }
+    }
 
-        let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    /// Derives the vault key. `params` usually come from a file header, they are checked
+    /// against `ArgonParams::validate` before any memory is allocated for them.
+    pub(crate) fn derive_argon_key(bytes: &[u8], salt: Option<[u8; 16]>, params: ArgonParams) -> Result<ArgonKey, CryptoError> {
+        params.validate()?;
+        let argon_params = Params::new(params.memory_cost, params.time_cost, params.parallelism, None)?;
+
+        let argon2 = Argon2::new(params.algorithm.to_argon2(), Version::V0x13, argon_params);
     
         let mut salt_bytes = [0u8; 16];  // 128-bit salt
         if let Some(salt) = salt {
@@ -33,39 +236,188 @@ impl Crypto {
             ArgonOsRng.fill_bytes(&mut salt_bytes);
         }
 
-        let mut key = [0u8; 32];
-        argon2
-            .hash_password_into(bytes, &salt_bytes, &mut key)
-            .map_err(|e| e.to_string())?;
+        // Derived on the stack and wiped there, only the copy in the `ArgonKey` is left
+        let mut key = ZeroByteStack::<32>::with_len(32).expect("An Argon2 key fits in 32 bytes");
+        argon2.hash_password_into(bytes, &salt_bytes, key.as_mut())?;
 
         Ok(ArgonKey {
-            bytes: key,
+            bytes: key.to_fixed_array()?,
             salt: salt_bytes,
+            params,
         })
     }
 
-    pub(super) fn aes_gcm_encrypt(bytes: &[u8], key: Vec<u8>) -> Result<Vec<u8>, AesError> {
-        let key = AesKey::<Aes256Gcm>::from_slice(&key);
-        let cipher = Aes256Gcm::new(&key);
-        let nonce = Aes256Gcm::generate_nonce(&mut AesOsRng);
+    /// Picks Argon2 parameters for which one key derivation takes about `target_duration_ms` on
+    /// this machine: 3 iterations, with the memory cost searched until a derivation takes within
+    /// 20% of the target. Runs several derivations, so keep it off the UI thread.
+    pub(crate) fn calibrate_argon2_params(target_duration_ms: u64) -> ArgonParams {
+        let base = ArgonParams { time_cost: CALIBRATION_TIME_COST, ..ArgonParams::default() };
+        let min_memory_cost = Params::MIN_M_COST.max(8 * base.parallelism);
+
+        let memory_cost = Self::search_memory_cost(Duration::from_millis(target_duration_ms), min_memory_cost, |memory_cost| {
+            let start = Instant::now();
+            if let Err(e) = Self::derive_argon_key(b"calibration", Some([0u8; 16]), ArgonParams { memory_cost, ..base }) {
+                log::warn!("Key derivation failed during calibration: {}", e);
+            }
+            start.elapsed()
+        });
+
+        let params = ArgonParams { memory_cost, ..base };
+        log::info!("Calibrated Argon2 for {} ms: {} KiB, {} iterations, {} lanes", target_duration_ms, params.memory_cost, params.time_cost, params.parallelism);
+        params
+    }
 
-        let cipherbytes = cipher.encrypt(&nonce, bytes)?;
+    /// Memory cost between `min` and `CALIBRATION_MAX_MEMORY_COST` for which `measure` comes
+    /// within 20% of `target`. Doubles from `min` until a derivation is too slow, then bisects,
+    /// so slow machines never try the large sizes. Without an exact hit it settles for the
+    /// largest cost found to be faster than `target`.
+    fn search_memory_cost(target: Duration, min: u32, mut measure: impl FnMut(u32) -> Duration) -> u32 {
+        let tolerance = target / 5;
+        let mut fast_enough = None;
+
+        let mut memory_cost = min;
+        let mut high = loop {
+            let elapsed = measure(memory_cost);
+            if elapsed.abs_diff(target) <= tolerance {
+                return memory_cost;
+            }
+            if elapsed < target {
+                fast_enough = Some(memory_cost);
+                if memory_cost == CALIBRATION_MAX_MEMORY_COST {
+                    return memory_cost;
+                }
+                memory_cost = memory_cost.saturating_mul(2).min(CALIBRATION_MAX_MEMORY_COST);
+            } else {
+                break memory_cost;
+            }
+        };
+
+        let Some(mut low) = fast_enough else {
+            return min;  // Even the smallest cost is too slow
+        };
+        while high - low > 1 {
+            let memory_cost = low + (high - low) / 2;
+            let elapsed = measure(memory_cost);
+            if elapsed.abs_diff(target) <= tolerance {
+                return memory_cost;
+            }
+            if elapsed < target { low = memory_cost } else { high = memory_cost }
+        }
+        low
+    }
 
-        let mut encrypted_bytes = nonce.to_vec();
-        encrypted_bytes.extend_from_slice(&cipherbytes);
+    /// Argon2 with a salt of any length and an explicit version (0x10 or 0x13), as KeePass
+    /// databases record them. Vault keys use `derive_argon_key` instead.
+    pub(crate) fn derive_argon_key_raw(bytes: &[u8], salt: &[u8], params: ArgonParams, version: u32) -> Result<[u8; 32], CryptoError> {
+        params.validate()?;
+        let version = Version::try_from(version)?;
+        let argon_params = Params::new(params.memory_cost, params.time_cost, params.parallelism, None)?;
 
-        Ok(encrypted_bytes)
+        let mut key = [0u8; 32];
+        Argon2::new(params.algorithm.to_argon2(), version, argon_params).hash_password_into(bytes, salt, &mut key)?;
+        Ok(key)
     }
 
-    pub(super) fn aes_gcm_decrypt(bytes: &[u8], key: Vec<u8>) -> Result<Vec<u8>, AesError> {
-        let key = AesKey::<Aes256Gcm>::from_slice(&key);
-        let cipher = Aes256Gcm::new(&key);
+    /// KeePass' AES-KDF: both halves of `key` are encrypted `rounds` times with AES-256 under
+    /// `seed`, and the result hashed with SHA-256
+    pub(crate) fn aes_kdf(key: &[u8; 32], seed: &[u8; 32], rounds: u64) -> Result<[u8; 32], CryptoError> {
+        if rounds > MAX_AES_KDF_ROUNDS {
+            return Err(CryptoError::InvalidParams(format!("{} AES-KDF rounds are above the {} limit", rounds, MAX_AES_KDF_ROUNDS)));
+        }
 
-        let (nonce_bytes, cipherbytes) = bytes.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
+        let cipher = Aes256::new(GenericArray::from_slice(seed));
+        let mut blocks = *key;
+        for _ in 0..rounds {
+            for block in blocks.chunks_exact_mut(AES_BLOCK_LEN) {
+                cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            }
+        }
 
-        let decrypted_bytes = cipher.decrypt(nonce, cipherbytes)?;
-        Ok(decrypted_bytes)
+        let transformed = Sha256::digest(blocks).into();
+        blocks.zeroize();
+        Ok(transformed)
+    }
+
+    /// Decrypts AES-256-CBC in place and strips the PKCS#7 padding, leaving only the plaintext
+    /// in `buffer`. Nothing is authenticated, the caller has to check integrity separately.
+    pub(crate) fn aes_cbc_decrypt(buffer: &mut ZeroByte, key: &[u8; 32], iv: &[u8; 16]) -> Result<(), CryptoError> {
+        if buffer.len() == 0 || !buffer.len().is_multiple_of(AES_BLOCK_LEN) {
+            return Err(CryptoError::Padding);
+        }
+
+        let cipher = Aes256::new(GenericArray::from_slice(key));
+        let mut previous = *iv;
+        for block in buffer.as_mut().chunks_exact_mut(AES_BLOCK_LEN) {
+            let mut current = [0u8; AES_BLOCK_LEN];
+            current.copy_from_slice(block);
+            cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            block.iter_mut().zip(&previous).for_each(|(byte, chained)| *byte ^= chained);
+            previous = current;
+        }
+        previous.zeroize();
+
+        let plain = buffer.as_ref();
+        let pad = usize::from(plain[plain.len() - 1]);
+        if pad == 0 || pad > AES_BLOCK_LEN || plain[plain.len() - pad..].iter().any(|&byte| usize::from(byte) != pad) {
+            return Err(CryptoError::Padding);
+        }
+        buffer.truncate(buffer.len() - pad);
+
+        Ok(())
+    }
+
+    /// AES-256-GCM keyed with `key`, which must be 32 bytes. The stack copy of the key is wiped
+    /// once the cipher holds it.
+    fn aes_gcm_cipher(key: &ZeroByte) -> Result<Aes256Gcm, CryptoError> {
+        let mut key_bytes: [u8; 32] = key.to_fixed_array()?;
+        let cipher = Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(&key_bytes));
+        key_bytes.zeroize();
+        Ok(cipher)
+    }
+
+    /// Encrypts `bytes` and appends nonce + cipherbytes + tag to `out`.
+    /// Encryption happens in place inside `out`, so no intermediate plaintext copy is allocated.
+    pub(super) fn aes_gcm_encrypt(bytes: &[u8], key: &ZeroByte, out: &mut ZeroByte) -> Result<(), CryptoError> {
+        let cipher = Self::aes_gcm_cipher(key)?;
+        let mut nonce = ZeroByteStack::<32>::with_len(NONCE_LEN).expect("A nonce fits in 32 bytes");
+        nonce.fill_random();
+
+        let start = out.len();
+        out.reserve(NONCE_LEN + bytes.len() + TAG_LEN);
+        out.extend_from_slice(nonce.as_ref());
+        out.extend_from_slice(bytes);
+
+        match cipher.encrypt_in_place_detached(Nonce::from_slice(nonce.as_ref()), b"", &mut out.as_mut()[start + NONCE_LEN..]) {
+            Ok(tag) => {
+                out.extend_from_slice(&tag);
+                Ok(())
+            },
+            Err(e) => {
+                out.truncate(start);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Decrypts nonce + cipherbytes + tag in place, leaving only the plaintext in `buffer`
+    pub(super) fn aes_gcm_decrypt(buffer: &mut ZeroByte, key: &ZeroByte) -> Result<(), CryptoError> {
+        if buffer.len() < MIN_ENCRYPTED_LEN {
+            return Err(CryptoError::Nonce { min: MIN_ENCRYPTED_LEN, actual: buffer.len() });
+        }
+
+        let cipher = Self::aes_gcm_cipher(key)?;
+
+        let plain_len = buffer.len() - NONCE_LEN - TAG_LEN;
+        let bytes = buffer.as_mut();
+        let (nonce_bytes, rest) = bytes.split_at_mut(NONCE_LEN);
+        let (cipherbytes, tag) = rest.split_at_mut(plain_len);
+
+        cipher.decrypt_in_place_detached(Nonce::from_slice(nonce_bytes), b"", cipherbytes, Tag::from_slice(tag))?;
+
+        bytes.copy_within(NONCE_LEN..NONCE_LEN + plain_len, 0);
+        buffer.truncate(plain_len);
+
+        Ok(())
     }
 }
 
@@ -76,21 +428,82 @@ mod tests {
 
     const TEST_BYTES: &[u8] = b"Super secret message";
     const TEST_PASSWORD: &[u8] = b"correct-horse-battery-staple";
+    const TEST_PARAMS: ArgonParams = ArgonParams {
+        algorithm: KdfAlgorithm::Argon2id,
+        memory_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+    const ALL_CLASSES: GeneratorOptions = GeneratorOptions { length: 20, uppercase: true, lowercase: true, digits: true, symbols: true };
+
+    #[test]
+    fn test_generated_password_has_length_and_every_class() {
+        for length in [MIN_GENERATED_LEN, 20, MAX_GENERATED_LEN] {
+            let password = Crypto::generate_password(GeneratorOptions { length, ..ALL_CLASSES }).expect("Generation failed");
+            let bytes = password.as_ref();
+
+            assert_eq!(bytes.len(), length);
+            for class in [UPPERCASE, LOWERCASE, DIGITS, SYMBOLS] {
+                assert!(bytes.iter().any(|byte| class.contains(byte)), "Missing a character class");
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_password_only_uses_chosen_classes() {
+        let options = GeneratorOptions { uppercase: false, symbols: false, ..ALL_CLASSES };
+        for _ in 0..20 {
+            let password = Crypto::generate_password(options).expect("Generation failed");
+            assert!(password.as_ref().iter().all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit()));
+        }
+    }
+
+    #[test]
+    fn test_generated_passwords_differ() {
+        let first = Crypto::generate_password(ALL_CLASSES).expect("Generation failed");
+        let second = Crypto::generate_password(ALL_CLASSES).expect("Generation failed");
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_invalid_generator_options_are_rejected() {
+        let range = Err(PasswordError::LengthOutOfRange { min: MIN_GENERATED_LEN, max: MAX_GENERATED_LEN });
+        assert_eq!(Crypto::generate_password(GeneratorOptions { length: MIN_GENERATED_LEN - 1, ..ALL_CLASSES }), range);
+        assert_eq!(Crypto::generate_password(GeneratorOptions { length: MAX_GENERATED_LEN + 1, ..ALL_CLASSES }), range);
+
+        let none = GeneratorOptions { uppercase: false, lowercase: false, digits: false, symbols: false, ..ALL_CLASSES };
+        assert_eq!(Crypto::generate_password(none), Err(PasswordError::NoCharacterClasses));
+    }
+
+    fn encrypt(bytes: &[u8], key: &ArgonKey) -> ZeroByte {
+        let mut encrypted = ZeroByte::default();
+        Crypto::aes_gcm_encrypt(bytes, &key.to_zero_byte(), &mut encrypted).expect("Encryption failed");
+        encrypted
+    }
 
     #[test]
     fn test_derive_argon_key_is_deterministic_with_same_salt() {
         let salt = [42u8; 16];
-        let key1 = Crypto::derive_argon_key(TEST_PASSWORD, Some(salt)).expect("Key derivation failed");
-        let key2 = Crypto::derive_argon_key(TEST_PASSWORD, Some(salt)).expect("Key derivation failed");
+        let key1 = Crypto::derive_argon_key(TEST_PASSWORD, Some(salt), ArgonParams::default()).expect("Key derivation failed");
+        let key2 = Crypto::derive_argon_key(TEST_PASSWORD, Some(salt), ArgonParams::default()).expect("Key derivation failed");
 
         assert_eq!(key1.bytes, key2.bytes);
         assert_eq!(key1.salt, salt);
     }
 
+    #[test]
+    fn test_matches_password_only_for_the_deriving_password() {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD, None, TEST_PARAMS).expect("Key derivation failed");
+
+        assert_eq!(key.matches_password(TEST_PASSWORD), Ok(true));
+        assert_eq!(key.matches_password(b"correct-horse-battery-stapler"), Ok(false));
+        assert_eq!(key.matches_password(b""), Ok(false));
+    }
+
     #[test]
     fn test_derive_argon_key_generates_unique_salts() {
-        let key1 = Crypto::derive_argon_key(TEST_PASSWORD, None).expect("Key derivation failed");
-        let key2 = Crypto::derive_argon_key(TEST_PASSWORD, None).expect("Key derivation failed");
+        let key1 = Crypto::derive_argon_key(TEST_PASSWORD, None, ArgonParams::default()).expect("Key derivation failed");
+        let key2 = Crypto::derive_argon_key(TEST_PASSWORD, None, ArgonParams::default()).expect("Key derivation failed");
 
         assert_ne!(key1.salt, key2.salt);
         assert_ne!(key1.bytes, key2.bytes);
@@ -98,45 +511,223 @@ mod tests {
 
     #[test]
     fn test_encrypt_and_decrypt_returns_original_data() {
-        let key = Crypto::derive_argon_key(TEST_PASSWORD, None).expect("Key derivation failed");
-        let encrypted = Crypto::aes_gcm_encrypt(TEST_BYTES, key.bytes.to_vec()).expect("Encryption failed");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD, None, ArgonParams::default()).expect("Key derivation failed");
+        let mut buffer = encrypt(TEST_BYTES, &key);
 
-        let decrypted = Crypto::aes_gcm_decrypt(&encrypted, key.bytes.to_vec()).expect("Decryption failed");
+        Crypto::aes_gcm_decrypt(&mut buffer, &key.to_zero_byte()).expect("Decryption failed");
 
-        assert_eq!(decrypted, TEST_BYTES.to_vec());
+        assert_eq!(buffer.as_ref(), TEST_BYTES);
     }
 
     #[test]
     fn test_encrypt_produces_different_cipherbytes_each_time() {
-        let key = Crypto::derive_argon_key(TEST_PASSWORD, None).expect("Key derivation failed");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD, None, ArgonParams::default()).expect("Key derivation failed");
 
-        let cipherbytes1 = Crypto::aes_gcm_encrypt(TEST_BYTES, key.bytes.to_vec()).expect("Encryption failed");
-        let cipherbytes2 = Crypto::aes_gcm_encrypt(TEST_BYTES, key.bytes.to_vec()).expect("Encryption failed");
+        let cipherbytes1 = encrypt(TEST_BYTES, &key);
+        let cipherbytes2 = encrypt(TEST_BYTES, &key);
 
-        assert_ne!(cipherbytes1, cipherbytes2, "Cipherbytes should differ due to random nonces");
+        assert_ne!(cipherbytes1.as_ref(), cipherbytes2.as_ref(), "Cipherbytes should differ due to random nonces");
     }
 
     #[test]
     fn test_decrypt_fails_with_wrong_key() {
-        let correct_key = Crypto::derive_argon_key(TEST_PASSWORD, None).expect("Key derivation failed");
-        let wrong_key = Crypto::derive_argon_key(b"incorrect", None).expect("Key derivation failed");
+        let correct_key = Crypto::derive_argon_key(TEST_PASSWORD, None, ArgonParams::default()).expect("Key derivation failed");
+        let wrong_key = Crypto::derive_argon_key(b"incorrect", None, ArgonParams::default()).expect("Key derivation failed");
 
-        let cipherbytes = Crypto::aes_gcm_encrypt(TEST_BYTES, correct_key.bytes.to_vec()).expect("Encryption failed");
-        let result = Crypto::aes_gcm_decrypt(&cipherbytes, wrong_key.bytes.to_vec());
+        let mut cipherbytes = encrypt(TEST_BYTES, &correct_key);
+        let result = Crypto::aes_gcm_decrypt(&mut cipherbytes, &wrong_key.to_zero_byte());
 
         assert!(result.is_err(), "Decryption should fail with wrong key");
     }
 
+    #[test]
+    fn test_decrypt_rejects_short_input() {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD, None, TEST_PARAMS).expect("Key derivation failed");
+
+        for len in [0, 5, MIN_ENCRYPTED_LEN - 1] {
+            let mut buffer = ZeroByte::default();
+            buffer.extend_from_slice(&vec![1u8; len]);
+
+            let result = Crypto::aes_gcm_decrypt(&mut buffer, &key.to_zero_byte());
+            assert_eq!(result, Err(CryptoError::Nonce { min: MIN_ENCRYPTED_LEN, actual: len }));
+        }
+    }
+
     #[test]
     fn test_decrypt_fails_with_tampered_cipherbytes() {
-        let key = Crypto::derive_argon_key(TEST_PASSWORD, None).expect("Key derivation failed");
-        let mut cipherbytes = Crypto::aes_gcm_encrypt(TEST_BYTES, key.bytes.to_vec()).expect("Encryption failed");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD, None, ArgonParams::default()).expect("Key derivation failed");
+        let mut cipherbytes = encrypt(TEST_BYTES, &key);
 
         // Flip a byte in the cipherbytes
         let last_index = cipherbytes.len() - 1;
-        cipherbytes[last_index] ^= 0xFF;
+        cipherbytes.as_mut()[last_index] ^= 0xFF;
+
+        let result = Crypto::aes_gcm_decrypt(&mut cipherbytes, &key.to_zero_byte());
+        assert!(matches!(result, Err(CryptoError::AesGcm(_))), "Tampered cipherbytes should fail to decrypt");
+        assert!(result.unwrap_err().is_security_sensitive());
+    }
+
+    #[test]
+    fn test_derive_argon_key_differs_per_algorithm() {
+        let salt = [7u8; 16];
+        let keys: Vec<ArgonKey> = KdfAlgorithm::ALL
+            .into_iter()
+            .map(|algorithm| {
+                let params = ArgonParams { algorithm, ..TEST_PARAMS };
+                Crypto::derive_argon_key(TEST_PASSWORD, Some(salt), params).expect("Key derivation failed")
+            })
+            .collect();
+
+        assert_ne!(keys[0].bytes, keys[1].bytes);
+        assert_ne!(keys[0].bytes, keys[2].bytes);
+        assert_ne!(keys[1].bytes, keys[2].bytes);
+        assert_eq!(keys[1].params.algorithm, KdfAlgorithm::Argon2i);
+    }
+
+    #[test]
+    fn test_kdf_algorithm_id_round_trip() {
+        for algorithm in KdfAlgorithm::ALL {
+            assert_eq!(KdfAlgorithm::from_id(algorithm.id()), Some(algorithm));
+        }
+        assert_eq!(KdfAlgorithm::from_id(3), None);
+    }
+
+    #[test]
+    fn test_encrypt_appends_to_existing_buffer() {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD, None, TEST_PARAMS).expect("Key derivation failed");
+
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(b"header");
+        Crypto::aes_gcm_encrypt(TEST_BYTES, &key.to_zero_byte(), &mut buffer).expect("Encryption failed");
+
+        assert_eq!(&buffer.as_ref()[..6], b"header");
+        assert_eq!(buffer.len(), 6 + NONCE_LEN + TEST_BYTES.len() + TAG_LEN);
+    }
 
-        let result = Crypto::aes_gcm_decrypt(&cipherbytes, key.bytes.to_vec());
-        assert!(result.is_err(), "Tampered cipherbytes should fail to decrypt");
+    #[test]
+    fn test_decrypt_rejects_input_shorter_than_nonce_and_tag() {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD, None, TEST_PARAMS).expect("Key derivation failed");
+
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(&[0u8; NONCE_LEN + TAG_LEN - 1]);
+
+        assert!(Crypto::aes_gcm_decrypt(&mut buffer, &key.to_zero_byte()).is_err());
+    }
+
+    #[test]
+    fn test_validate_accepts_default_and_rejects_extreme_params() {
+        assert!(ArgonParams::default().validate().is_ok());
+        assert!(TEST_PARAMS.validate().is_ok());
+
+        assert!(matches!(ArgonParams { time_cost: 0, ..ArgonParams::default() }.validate(), Err(CryptoError::Argon2(_))));
+        assert!(matches!(ArgonParams { memory_cost: MAX_MEMORY_COST + 1, ..ArgonParams::default() }.validate(), Err(CryptoError::InvalidParams(_))));
+        assert!(matches!(ArgonParams { time_cost: MAX_TIME_COST + 1, ..ArgonParams::default() }.validate(), Err(CryptoError::InvalidParams(_))));
+        assert!(matches!(
+            ArgonParams { parallelism: MAX_PARALLELISM + 1, memory_cost: 1024 * 1024, ..ArgonParams::default() }.validate(),
+            Err(CryptoError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn test_aes_gcm_rejects_short_key() {
+        let key = ZeroByte::from_fixed_array([3u8; 16]);
+        let mut out = ZeroByte::default();
+
+        let result = Crypto::aes_gcm_encrypt(TEST_BYTES, &key, &mut out);
+        assert!(matches!(result, Err(CryptoError::KeyLength(_))));
+        assert!(!result.unwrap_err().is_security_sensitive());
+        assert_eq!(out.len(), 0);
+    }
+
+    #[test]
+    fn test_calibration_terminates_with_valid_params() {
+        let params = Crypto::calibrate_argon2_params(20);
+
+        assert!(Params::new(params.memory_cost, params.time_cost, params.parallelism, None).is_ok());
+        assert!(params.validate().is_ok());
+        assert_eq!(params.time_cost, CALIBRATION_TIME_COST);
+        assert!(params.memory_cost <= CALIBRATION_MAX_MEMORY_COST);
+    }
+
+    #[test]
+    fn test_memory_search_lands_within_tolerance() {
+        // A machine that needs 1 ms per 1000 KiB
+        let target = Duration::from_millis(500);
+        let mut steps = 0;
+        let memory_cost = Crypto::search_memory_cost(target, 16, |memory_cost| {
+            steps += 1;
+            Duration::from_micros(u64::from(memory_cost))
+        });
+
+        assert!(Duration::from_micros(u64::from(memory_cost)).abs_diff(target) <= target / 5, "Landed on {} KiB", memory_cost);
+        assert!(steps < 40, "Took {} derivations", steps);
+    }
+
+    #[test]
+    fn test_memory_search_stays_in_bounds() {
+        let slow = Crypto::search_memory_cost(Duration::from_millis(500), 16, |_| Duration::from_secs(10));
+        assert_eq!(slow, 16, "Hardware slower than the target gets the minimum");
+
+        let fast = Crypto::search_memory_cost(Duration::from_millis(500), 16, |_| Duration::from_millis(1));
+        assert_eq!(fast, CALIBRATION_MAX_MEMORY_COST, "Hardware faster than the target gets the maximum");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_only_authentication_failures_are_security_sensitive() {
+        let error = Crypto::derive_argon_key(TEST_PASSWORD, None, ArgonParams { parallelism: 0, ..TEST_PARAMS })
+            .expect_err("Key derivation should fail");
+
+        assert!(matches!(error, CryptoError::Argon2(_)));
+        assert!(!error.is_security_sensitive());
+        assert!(!CryptoError::InvalidParams("Too slow".into()).is_security_sensitive());
+        assert!(!CryptoError::Nonce { min: MIN_ENCRYPTED_LEN, actual: 0 }.is_security_sensitive());
+        assert!(CryptoError::Padding.is_security_sensitive());
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("Invalid hex")).collect()
+    }
+
+    #[test]
+    fn test_aes_cbc_decrypt_strips_padding() {
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+        let iv: [u8; 16] = std::array::from_fn(|i| i as u8);
+        let cipherbytes = from_hex("a6d36cca63e05d4110df96933c5773e375d7979b9d2d826fc449bd2f3a67e5ff9a9fa2d8c121e1521ef5a861f2299f1b");
+
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(&cipherbytes);
+        Crypto::aes_cbc_decrypt(&mut buffer, &key, &iv).expect("Decryption failed");
+        assert_eq!(buffer.as_ref(), b"KeePass payload, not block aligned");
+
+        let mut wrong_key = ZeroByte::default();
+        wrong_key.extend_from_slice(&cipherbytes);
+        assert_eq!(Crypto::aes_cbc_decrypt(&mut wrong_key, &[9; 32], &iv), Err(CryptoError::Padding));
+
+        let mut truncated = ZeroByte::default();
+        truncated.extend_from_slice(&cipherbytes[..40]);
+        assert_eq!(Crypto::aes_cbc_decrypt(&mut truncated, &key, &iv), Err(CryptoError::Padding));
+    }
+
+    #[test]
+    fn test_aes_kdf_matches_keepass() {
+        let composite = Sha256::digest(Sha256::digest(b"password")).into();
+
+        let transformed = Crypto::aes_kdf(&composite, &[7; 32], 100).expect("AES-KDF failed");
+        assert_eq!(hex(&transformed), "3ecb76e6effabacc73caf165ee7c37634d876b651067bfd027d25ae47d9efbb8");
+        assert!(matches!(Crypto::aes_kdf(&composite, &[7; 32], u64::MAX), Err(CryptoError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn test_derive_argon_key_raw_takes_any_salt_length() {
+        let salt: [u8; 32] = std::array::from_fn(|i| i as u8);
+        let params = ArgonParams { algorithm: KdfAlgorithm::Argon2id, memory_cost: 64, time_cost: 2, parallelism: 1 };
+
+        let key = Crypto::derive_argon_key_raw(b"password", &salt, params, 0x13).expect("Key derivation failed");
+        assert_eq!(hex(&key), "c69292eade4d82142df1f938d162cc8e1ede3e2a2c2e61fad8eb43a19b94760a");
+        assert!(Crypto::derive_argon_key_raw(b"password", &salt, params, 0x12).is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use serde::Serialize;
+
+use crate::errors::export_errors::ExportError;
+use crate::models::vault::{CustomField, Item};
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Identifies the layout of a NoPass JSON export, bumped whenever it changes
+const JSON_FORMAT: &str = "nopass-export";
+const JSON_VERSION: u32 = 1;
+
+/// Column layout browsers and most password managers import
+const CSV_HEADER: [&str; 5] = ["name", "url", "username", "password", "notes"];
+
+/// Header KeePassXC's CSV importer expects, every field quoted
+const KEEPASS_CSV_HEADER: [&str; 6] = ["Group", "Title", "Username", "Password", "URL", "Notes"];
+/// Group the items are exported into, importers' `Folder` fields become subgroups of it
+const KEEPASS_ROOT_GROUP: &str = "NoPass";
+/// Custom field importers record another manager's folder path in
+const FOLDER_FIELD: &str = "Folder";
+
+/// Written to the `Meta` block of a KeePass XML export
+const KEEPASS_GENERATOR: &str = "NoPass";
+
+/// Plaintext layouts the items can be exported in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PlaintextFormat {
+    Json,
+    Csv,
+    /// The layout KeePassXC imports, with a group column
+    KeePassCsv,
+    /// KeePass 2.x XML, for moving to KeePass or anything that reads its exports
+    KeePassXml,
+}
+
+impl PlaintextFormat {
+    /// Format for the ID the UI passes along, None for an unknown one
+    pub(crate) fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "keepass-csv" => Some(Self::KeePassCsv),
+            "keepass-xml" => Some(Self::KeePassXml),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Csv => "CSV",
+            Self::KeePassCsv => "KeePassXC CSV",
+            Self::KeePassXml => "KeePass XML",
+        }
+    }
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv | Self::KeePassCsv => "csv",
+            Self::KeePassXml => "xml",
+        }
+    }
+
+    pub(crate) fn encode(self, items: &[&Item], exported_at: u64) -> Result<ZeroByte, ExportError> {
+        match self {
+            Self::Json => encode_json(items, exported_at),
+            Self::Csv => Ok(encode_csv(items)),
+            Self::KeePassCsv => Ok(encode_keepass_csv(items)),
+            Self::KeePassXml => encode_keepass_xml(items),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    format: &'static str,
+    version: u32,
+    exported_at: u64,
+    items: Vec<JsonItem<'a>>,
+}
+
+/// An item as exported. Fields are `String`s and always valid UTF-8, so they are written as
+/// JSON strings as they are, with only JSON's own escapes.
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    name: &'a str,
+    username: &'a str,
+    password: &'a str,
+    url: &'a str,
+    notes: &'a str,
+    favorite: bool,
+    custom_fields: &'a [CustomField],
+    created_at: u64,
+    modified_at: u64,
+    password_changed_at: u64,
+}
+
+impl<'a> From<&'a Item> for JsonItem<'a> {
+    fn from(item: &'a Item) -> Self {
+        Self {
+            name: &item.name,
+            username: &item.username,
+            password: &item.password,
+            url: &item.url,
+            notes: &item.notes,
+            favorite: item.favorite,
+            custom_fields: &item.custom_fields,
+            created_at: item.created_at,
+            modified_at: item.modified_at,
+            password_changed_at: item.password_changed_at,
+        }
+    }
+}
+
+/// Serializes `items` as a NoPass JSON export. The JSON is written straight into a `ZeroByte`,
+/// so no copy of the plaintext is left behind in freed memory. Write it out with
+/// `file::write_private`.
+pub(crate) fn encode_json(items: &[&Item], exported_at: u64) -> Result<ZeroByte, ExportError> {
+    let export = JsonExport {
+        format: JSON_FORMAT,
+        version: JSON_VERSION,
+        exported_at,
+        items: items.iter().map(|&item| JsonItem::from(item)).collect(),
+    };
+
+    let mut json = ZeroByte::default();
+    serde_json::to_writer_pretty(&mut json, &export)?;
+    Ok(json)
+}
+
+/// Writes `items` as a `name,url,username,password,notes` CSV with a header row, quoted as in
+/// RFC 4180. Fields are copied straight from the items into a `ZeroByte`, so the plaintext is
+/// never held in an intermediate `String`.
+pub(crate) fn encode_csv(items: &[&Item]) -> ZeroByte {
+    let mut csv = ZeroByte::default();
+    write_csv_record(&mut csv, &CSV_HEADER);
+    for item in items {
+        write_csv_record(&mut csv, &[&item.name, &item.url, &item.username, &item.password, &item.notes]);
+    }
+    csv
+}
+
+/// Writes `items` as the `"Group","Title","Username","Password","URL","Notes"` CSV KeePassXC
+/// imports, every field quoted. Items are put in the `NoPass` group, or a subgroup of it for
+/// the folder an importer kept in a `Folder` custom field.
+pub(crate) fn encode_keepass_csv(items: &[&Item]) -> ZeroByte {
+    let mut csv = ZeroByte::default();
+    write_quoted_csv_record(&mut csv, &KEEPASS_CSV_HEADER);
+    for item in items {
+        let folder = item.custom_fields.iter().find(|field| field.name == FOLDER_FIELD && !field.value.is_empty());
+        let group = match folder {
+            Some(folder) => format!("{}/{}", KEEPASS_ROOT_GROUP, folder.value),
+            None => KEEPASS_ROOT_GROUP.to_string(),
+        };
+        write_quoted_csv_record(&mut csv, &[&group, &item.name, &item.username, &item.password, &item.url, &item.notes]);
+    }
+    csv
+}
+
+/// Writes `items` as a KeePass 2.x XML export: one `Entry` per item in a single `NoPass` group,
+/// with the `Title`, `UserName`, `Password`, `URL` and `Notes` strings and custom fields as extra
+/// strings. Like KeePass' own XML export, nothing is protected: every password is in the file
+/// as plain text, for anyone who can read the file. Hidden custom fields are only marked
+/// `ProtectInMemory` for the importing app.
+///
+/// Text is escaped piece by piece straight into the `ZeroByte`, so no escaped copy of a secret
+/// is left behind in a `String`. Fails if an item holds a control character XML 1.0 can't carry.
+pub(crate) fn encode_keepass_xml(items: &[&Item]) -> Result<ZeroByte, ExportError> {
+    let mut xml = ZeroByte::default();
+    let mut writer = Writer::new_with_indent(&mut xml, b'\t', 1);
+
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), Some("yes"))))?;
+    writer.write_event(Event::Start(BytesStart::new("KeePassFile")))?;
+
+    writer.write_event(Event::Start(BytesStart::new("Meta")))?;
+    write_xml_element(&mut writer, "GeneratorName", KEEPASS_GENERATOR)?;
+    writer.write_event(Event::End(BytesEnd::new("Meta")))?;
+
+    writer.write_event(Event::Start(BytesStart::new("Root")))?;
+    writer.write_event(Event::Start(BytesStart::new("Group")))?;
+    write_xml_element(&mut writer, "Name", KEEPASS_ROOT_GROUP)?;
+    for item in items {
+        writer.write_event(Event::Start(BytesStart::new("Entry")))?;
+        for (key, value) in [("Title", &item.name), ("UserName", &item.username), ("Password", &item.password), ("URL", &item.url), ("Notes", &item.notes)] {
+            write_keepass_string(&mut writer, key, value, false)?;
+        }
+        for field in &item.custom_fields {
+            write_keepass_string(&mut writer, &field.name, &field.value, field.hidden)?;
+        }
+        writer.write_event(Event::End(BytesEnd::new("Entry")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("Group")))?;
+    writer.write_event(Event::End(BytesEnd::new("Root")))?;
+
+    writer.write_event(Event::End(BytesEnd::new("KeePassFile")))?;
+    Ok(xml)
+}
+
+/// `<String><Key>key</Key><Value>value</Value></String>`
+fn write_keepass_string(writer: &mut Writer<&mut ZeroByte>, key: &str, value: &str, hidden: bool) -> Result<(), ExportError> {
+    writer.write_event(Event::Start(BytesStart::new("String")))?;
+    write_xml_element(writer, "Key", key)?;
+
+    let mut start = BytesStart::new("Value");
+    if hidden {
+        start.push_attribute(("ProtectInMemory", "True"));
+    }
+    writer.write_event(Event::Start(start))?;
+    write_xml_text(writer, value)?;
+    writer.write_event(Event::End(BytesEnd::new("Value")))?;
+
+    writer.write_event(Event::End(BytesEnd::new("String")))?;
+    Ok(())
+}
+
+fn write_xml_element(writer: &mut Writer<&mut ZeroByte>, name: &str, text: &str) -> Result<(), ExportError> {
+    writer.write_event(Event::Start(BytesStart::new(name)))?;
+    write_xml_text(writer, text)?;
+    writer.write_event(Event::End(BytesEnd::new(name)))?;
+    Ok(())
+}
+
+/// Writes `text` escaped. Runs without markup characters are borrowed as they are and the
+/// markup characters written as entities, so the text is never copied into an escaped `String`.
+fn write_xml_text(writer: &mut Writer<&mut ZeroByte>, text: &str) -> Result<(), ExportError> {
+    let mut run_start = 0;
+    for (index, character) in text.char_indices() {
+        let entity = match character {
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '&' => "&amp;",
+            '\t' | '\n' => continue,
+            '\r' => "&#13;",  // Would be read back as a line feed
+            control if control < ' ' => return Err(ExportError::EncodingFailed("An item holds a control character XML can't represent".into())),
+            _ => continue,
+        };
+        writer.write_event(Event::Text(BytesText::from_escaped(&text[run_start..index])))?;
+        writer.write_event(Event::Text(BytesText::from_escaped(entity)))?;
+        run_start = index + character.len_utf8();
+    }
+    writer.write_event(Event::Text(BytesText::from_escaped(&text[run_start..])))?;
+    Ok(())
+}
+
+fn write_csv_record(out: &mut ZeroByte, fields: &[&str]) {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            out.extend_from_slice(b",");
+        }
+        write_csv_field(out, field);
+    }
+    out.extend_from_slice(b"\r\n");
+}
+
+fn write_quoted_csv_record(out: &mut ZeroByte, fields: &[&str]) {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            out.extend_from_slice(b",");
+        }
+        write_quoted_csv_field(out, field);
+    }
+    out.extend_from_slice(b"\r\n");
+}
+
+/// A field holding a comma, quote or line break is enclosed in quotes, with its own quotes
+/// doubled. Leading or trailing spaces are quoted too, as some importers trim bare fields.
+fn write_csv_field(out: &mut ZeroByte, field: &str) {
+    let needs_quotes = field.contains([',', '"', '\r', '\n']) || field.starts_with(' ') || field.ends_with(' ');
+    if needs_quotes {
+        write_quoted_csv_field(out, field);
+    } else {
+        out.extend_from_slice(field.as_bytes());
+    }
+}
+
+/// Encloses the field in quotes, doubling its own quotes
+fn write_quoted_csv_field(out: &mut ZeroByte, field: &str) {
+    out.extend_from_slice(b"\"");
+    for (index, part) in field.split('"').enumerate() {
+        if index > 0 {
+            out.extend_from_slice(b"\"\"");
+        }
+        out.extend_from_slice(part.as_bytes());
+    }
+    out.extend_from_slice(b"\"");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::import;
+
+    fn item(name: &str, password: &str) -> Item {
+        Item {
+            id: 7,
+            name: name.into(),
+            username: "alice".into(),
+            password: password.into(),
+            url: "https://example.com".into(),
+            notes: "line one\nline \"two\"".into(),
+            favorite: true,
+            custom_fields: vec![CustomField { name: "PIN".into(), value: "1234".into(), hidden: true }],
+            modified_at: 1_700_000_000,
+            password_changed_at: 1_600_000_000,
+            created_at: 1_500_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_json_export_holds_every_field() {
+        let items = [item("Mail", "hunter2"), item("Bank", "pä$$wörd 🔑")];
+        let refs: Vec<&Item> = items.iter().collect();
+
+        let json = encode_json(&refs, 1_700_000_100).expect("Encoding failed");
+        let value: serde_json::Value = serde_json::from_slice(json.as_ref()).expect("Export is not valid JSON");
+
+        assert_eq!(value["format"], JSON_FORMAT);
+        assert_eq!(value["version"], JSON_VERSION);
+        assert_eq!(value["exported_at"], 1_700_000_100);
+
+        let exported = value["items"].as_array().expect("Missing items");
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[0]["name"], "Mail");
+        assert_eq!(exported[0]["notes"], "line one\nline \"two\"");
+        assert_eq!(exported[0]["custom_fields"][0]["value"], "1234");
+        assert_eq!(exported[0]["favorite"], true);
+        assert_eq!(exported[1]["password"], "pä$$wörd 🔑", "Non-ASCII text must survive unchanged");
+    }
+
+    #[test]
+    fn test_csv_export_quotes_special_fields() {
+        let mut plain = item("Mail", "hunter2");
+        plain.notes.clear();
+        let mut tricky = item("Bank, \"main\"", " padded ");
+        tricky.notes = "line one\r\nline two".into();
+
+        let csv = encode_csv(&[&plain, &tricky]);
+
+        assert_eq!(
+            std::str::from_utf8(csv.as_ref()).expect("Export is not UTF-8"),
+            "name,url,username,password,notes\r\n\
+            Mail,https://example.com,alice,hunter2,\r\n\
+            \"Bank, \"\"main\"\"\",https://example.com,alice,\" padded \",\"line one\r\nline two\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_keepass_csv_export_quotes_every_field() {
+        let mut plain = item("Mail", "hunter2");
+        plain.notes.clear();
+        plain.custom_fields.clear();
+        let mut filed = item("Bank", "x");
+        filed.notes.clear();
+        filed.custom_fields.push(CustomField { name: "Folder".into(), value: "Finance/Banks".into(), hidden: false });
+
+        let csv = encode_keepass_csv(&[&plain, &filed]);
+
+        assert_eq!(
+            std::str::from_utf8(csv.as_ref()).expect("Export is not UTF-8"),
+            "\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\"\r\n\
+            \"NoPass\",\"Mail\",\"alice\",\"hunter2\",\"https://example.com\",\"\"\r\n\
+            \"NoPass/Finance/Banks\",\"Bank\",\"alice\",\"x\",\"https://example.com\",\"\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_keepass_csv_export_parses_back() {
+        let items = [
+            item("Bank, \"main\"", "pä$$,wörd \"🔑\""),
+            item("Router", "\nstarts with a newline"),
+        ];
+        let refs: Vec<&Item> = items.iter().collect();
+        let csv = encode_keepass_csv(&refs);
+
+        let records = import::parse_csv(std::str::from_utf8(csv.as_ref()).expect("Export is not UTF-8")).expect("Export is not valid CSV");
+        assert_eq!(records[0], KEEPASS_CSV_HEADER);
+        assert_eq!(records.len(), items.len() + 1);
+        for (record, original) in records[1..].iter().zip(&items) {
+            assert_eq!(
+                record,
+                &["NoPass", &original.name, &original.username, &original.password, &original.url, &original.notes],
+            );
+        }
+    }
+
+    #[test]
+    fn test_csv_export_imports_back() {
+        let items = [
+            item("Mail", "hunter2"),
+            item("Bank, \"main\"", "pä$$,wörd \"🔑\""),
+            item("Router", "\nstarts with a newline"),
+        ];
+        let refs: Vec<&Item> = items.iter().collect();
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("export.csv");
+        std::fs::write(&path, encode_csv(&refs).as_ref()).expect("Failed to write");
+
+        let report = import::import_csv(&path).expect("Import failed");
+        assert!(report.warnings.is_empty(), "Unexpected warnings: {:?}", report.warnings);
+        assert_eq!(report.items.len(), items.len());
+        for (imported, original) in report.items.iter().zip(&items) {
+            assert_eq!(
+                (&imported.name, &imported.url, &imported.username, &imported.password, &imported.notes),
+                (&original.name, &original.url, &original.username, &original.password, &original.notes),
+            );
+        }
+    }
+
+    #[test]
+    fn test_keepass_xml_export_imports_back() {
+        let mut empty = item("Empty", "");
+        empty.username.clear();
+        empty.notes.clear();
+        empty.custom_fields.clear();
+        let items = [
+            item("Mail", "hunter2"),
+            item("<Bank> & \"Co\"", "pä$$<wörd> & 🔑\r\n'tab\t'"),
+            empty,
+        ];
+        let refs: Vec<&Item> = items.iter().collect();
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("export.xml");
+        std::fs::write(&path, encode_keepass_xml(&refs).expect("Encoding failed").as_ref()).expect("Failed to write");
+
+        let report = import::import_keepass_xml(&path, false).expect("Import failed");
+        assert!(report.warnings.is_empty(), "Unexpected warnings: {:?}", report.warnings);
+        assert_eq!(report.items.len(), items.len());
+        for (imported, original) in report.items.iter().zip(&items) {
+            assert_eq!(
+                (&imported.name, &imported.username, &imported.password, &imported.url, &imported.notes),
+                (&original.name, &original.username, &original.password, &original.url, &original.notes),
+            );
+            assert_eq!(imported.custom_fields.len(), original.custom_fields.len());
+        }
+        assert_eq!(report.items[0].custom_fields[0].value, "1234");
+    }
+
+    #[test]
+    fn test_keepass_xml_export_structure() {
+        let item = item("Mail", "hunter2");
+        let xml = encode_keepass_xml(&[&item]).expect("Encoding failed");
+        let text = std::str::from_utf8(xml.as_ref()).expect("Export is not UTF-8");
+        let document = roxmltree::Document::parse(text).expect("Export is not valid XML");
+
+        let file = document.root_element();
+        assert!(file.has_tag_name("KeePassFile"));
+        let generator = file.descendants().find(|node| node.has_tag_name("GeneratorName")).and_then(|node| node.text());
+        assert_eq!(generator, Some(KEEPASS_GENERATOR));
+
+        let entries: Vec<_> = file.descendants().filter(|node| node.has_tag_name("Entry")).collect();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].ancestors().any(|node| node.has_tag_name("Group")));
+        let hidden = entries[0].descendants().find(|node| node.has_tag_name("Value") && node.text() == Some("1234"));
+        assert_eq!(hidden.and_then(|node| node.attribute("ProtectInMemory")), Some("True"));
+    }
+
+    #[test]
+    fn test_keepass_xml_rejects_unrepresentable_characters() {
+        let item = item("Mail", "bell\u{7}");
+        assert!(matches!(encode_keepass_xml(&[&item]), Err(ExportError::EncodingFailed(_))));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::errors::fido2_errors::Fido2Error;
+use crate::utils::file::{VaultFileHeader, VaultMetadata};
+use crate::utils::zero_byte::ZeroByte;
+
+
+// Hardware security key unlock. A vault whose metadata names a FIDO2 credential can only
+// be opened with the master password and an assertion from that credential: the assertion
+// is appended to the password and the combination goes through Argon2 as usual, so the
+// file format and the cipher don't change.
+//
+// The challenge is the vault salt, which is stored next to the credential ID in the
+// unencrypted header. A new salt on rekey means a new assertion, just like a new key.
+
+/// A FIDO2 device, or anything standing in for one
+pub(crate) trait Fido2Authenticator {
+    /// Performs a `getAssertion` for `credential_id` over `challenge` and returns the
+    /// authenticator-signed response bytes.
+    ///
+    /// The response becomes part of the vault key, so it must be the same every time for
+    /// the same credential and challenge. Plain signatures aren't (they may be randomized and
+    /// cover the signature counter); the `hmac-secret` extension output is.
+    fn get_assertion(&self, credential_id: &[u8], challenge: &[u8]) -> Result<ZeroByte, Fido2Error>;
+}
+
+/// Key derivation input for unlocking the vault with `header`: the master password, followed
+/// by the hardware key's assertion when the metadata names a credential.
+/// Pass the result to `Crypto::derive_argon_key` in place of the password.
+#[allow(dead_code)]  // Entry point for the unlock flow once a device backend is linked in
+pub(crate) fn key_input(
+    authenticator: &dyn Fido2Authenticator,
+    header: &VaultFileHeader,
+    password: &ZeroByte,
+) -> Result<ZeroByte, Fido2Error> {
+    let mut input = password.clone();
+
+    if let Some(credential_id) = header.metadata.as_ref().and_then(|metadata| metadata.fido2_credential_id.as_deref()) {
+        let assertion = authenticator.get_assertion(credential_id, &header.salt)?;
+        if assertion.len() == 0 {
+            return Err(Fido2Error::AssertionFailed("Empty assertion response".into()));
+        }
+        input.extend_from_zero_byte(&assertion);
+    }
+
+    Ok(input)
+}
+
+/// Makes the vault key depend on `credential_id` from the next save on
+#[allow(dead_code)]  // Called when a hardware key is enrolled in the vault settings
+pub(crate) fn enroll(metadata: &mut VaultMetadata, credential_id: &[u8]) {
+    metadata.fido2_credential_id = Some(credential_id.to_vec());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use crate::utils::crypto::{ArgonParams, Crypto, KdfAlgorithm};
+    use crate::utils::file::PayloadCompression;
+
+    const CREDENTIAL_ID: &[u8] = b"credential-1";
+
+    /// Software authenticator: answers with an HMAC of the challenge under a per-credential
+    /// secret, like the `hmac-secret` extension, or fails the way a device would
+    struct MockAuthenticator {
+        secret: ZeroByte,
+        result: Option<Fido2Error>,
+        calls: Cell<usize>,
+    }
+
+    impl MockAuthenticator {
+        fn new(secret: &[u8]) -> Self {
+            let mut bytes = ZeroByte::default();
+            bytes.extend_from_slice(secret);
+            Self { secret: bytes, result: None, calls: Cell::new(0) }
+        }
+
+        fn failing(error: Fido2Error) -> Self {
+            Self { result: Some(error), ..Self::new(b"unused") }
+        }
+    }
+
+    impl Fido2Authenticator for MockAuthenticator {
+        fn get_assertion(&self, credential_id: &[u8], challenge: &[u8]) -> Result<ZeroByte, Fido2Error> {
+            self.calls.set(self.calls.get() + 1);
+            match &self.result {
+                Some(Fido2Error::DeviceNotFound) => return Err(Fido2Error::DeviceNotFound),
+                Some(Fido2Error::Cancelled) => return Err(Fido2Error::Cancelled),
+                Some(Fido2Error::AssertionFailed(e)) => return Err(Fido2Error::AssertionFailed(e.clone())),
+                None => {},
+            }
+            if credential_id != CREDENTIAL_ID {
+                return Err(Fido2Error::AssertionFailed("Unknown credential".into()));
+            }
+
+            let mut message = ZeroByte::default();
+            message.extend_from_slice(challenge);
+            Ok(message.hmac_sha256(&self.secret))
+        }
+    }
+
+    fn header(credential_id: Option<&[u8]>) -> VaultFileHeader {
+        let mut metadata = VaultMetadata::new("Work", 1_700_000_000);
+        if let Some(credential_id) = credential_id {
+            enroll(&mut metadata, credential_id);
+        }
+
+        VaultFileHeader {
+            version: 4,
+            salt: [7u8; 16],
+            params: ArgonParams { algorithm: KdfAlgorithm::Argon2id, memory_cost: 64, time_cost: 1, parallelism: 1 },
+            metadata: Some(metadata),
+            compression: PayloadCompression::None,
+            payload_len: Some(0),
+        }
+    }
+
+    fn password() -> ZeroByte {
+        let mut password = ZeroByte::default();
+        password.extend_from_slice(b"correct-horse-battery-staple");
+        password
+    }
+
+    fn derive(input: &ZeroByte, header: &VaultFileHeader) -> [u8; 32] {
+        Crypto::derive_argon_key(input.as_ref(), Some(header.salt), header.params).expect("Key derivation failed").bytes
+    }
+
+    #[test]
+    fn test_vault_without_credential_uses_only_the_password() {
+        let authenticator = MockAuthenticator::new(b"device secret");
+
+        let input = key_input(&authenticator, &header(None), &password()).expect("Key input failed");
+        assert_eq!(input, password());
+        assert_eq!(authenticator.calls.get(), 0, "The device must not be asked");
+    }
+
+    #[test]
+    fn test_assertion_is_appended_to_the_password() {
+        let authenticator = MockAuthenticator::new(b"device secret");
+        let header = header(Some(CREDENTIAL_ID));
+
+        let input = key_input(&authenticator, &header, &password()).expect("Key input failed");
+        let assertion = authenticator.get_assertion(CREDENTIAL_ID, &header.salt).expect("Assertion failed");
+        assert_eq!(&input.as_ref()[..password().len()], password().as_ref());
+        assert_eq!(&input.as_ref()[password().len()..], assertion.as_ref());
+    }
+
+    #[test]
+    fn test_key_depends_on_the_device() {
+        let header = header(Some(CREDENTIAL_ID));
+        let same = key_input(&MockAuthenticator::new(b"device secret"), &header, &password()).expect("Key input failed");
+        let again = key_input(&MockAuthenticator::new(b"device secret"), &header, &password()).expect("Key input failed");
+        let other = key_input(&MockAuthenticator::new(b"other device"), &header, &password()).expect("Key input failed");
+
+        assert_eq!(derive(&same, &header), derive(&again, &header));
+        assert_ne!(derive(&same, &header), derive(&other, &header));
+        assert_ne!(derive(&same, &header), derive(&password(), &header));
+    }
+
+    #[test]
+    fn test_device_errors_are_passed_on() {
+        let header = header(Some(CREDENTIAL_ID));
+
+        for error in [Fido2Error::DeviceNotFound, Fido2Error::Cancelled, Fido2Error::AssertionFailed("PIN blocked".into())] {
+            let expected = format!("{}", error);
+            let result = key_input(&MockAuthenticator::failing(error), &header, &password());
+            assert_eq!(result.map_err(|e| e.to_string()), Err(expected));
+        }
+    }
+
+    #[test]
+    fn test_unknown_credential_fails() {
+        let result = key_input(&MockAuthenticator::new(b"device secret"), &header(Some(b"someone else")), &password());
+        assert!(matches!(result, Err(Fido2Error::AssertionFailed(_))));
+    }
+}
@@ -1,125 +1,2513 @@
-use std::fs::File;
-use std::io::{BufReader, Read, Write};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
 
-use crate::utils::crypto::ArgonKey;
+use bincode::config::standard;
+use bincode::serde::{encode_to_vec, decode_from_slice};
+use blake2::{Blake2s256, Digest};
+use serde::{Serialize, Deserialize};
+use zeroize::Zeroize;
+
+use crate::errors::file_errors::{FileError, OpenVaultError};
+use crate::models::appearance;
+use crate::models::vault::Vault;
+use crate::utils::buffer_pool::BufferPool;
+use crate::utils::compression;
+use crate::utils::crypto::{ArgonKey, ArgonParams, CipherAlgorithm, KdfAlgorithm, MIN_ENCRYPTED_LEN};
+use crate::utils::zero_byte::ZeroByte;
 
 use super::crypto::Crypto;
 
 
-pub(crate) fn derive_file_key(path: &PathBuf, password: &String) -> Result<ArgonKey, String> {
-    let mut file = File::open(path).map_err(|e| e.to_string())?;
+// Vault file layout (integers are little-endian):
+//   [0..7]   magic "NPVAULT"
+//   [7..9]   format version (u16)
+//   [9..11]  metadata length (u16)
+//   [11..]   metadata, `VaultMetadata` as plain bincode (not encrypted)
+//   [..]     header length (u16), number of header bytes that follow
+//   [..]     header = salt (16) | kdf algorithm (u8) | memory cost (u32) | time cost (u32) | parallelism (u32)
+//            | payload compression (u8) | payload length (u64)
+//   [..]     payload = nonce + cipherbytes, the plaintext is compressed if the header says so
+//   [..]     attachment index = count (u32) | count * (attachment id (u32) | chunk length (u32))
+//   [..]     attachment chunks in index order, each nonce + cipherbytes of attachment id (u32) | data
+//
+// New header fields are appended after the existing ones; readers skip any header
+// bytes they don't know about, so the header length may exceed `HEADER_LEN`.
+//
+// Every attachment is its own AEAD chunk, so one damaged attachment doesn't keep the
+// vault or the other attachments from opening, and saves copy unchanged chunks as they are.
+//
+// Version 3 files have no payload length or attachments, the payload runs to the end of the file.
+// Version 2 files have no compression byte and are never compressed.
+// Version 1 files have no metadata section; the header length follows the version.
+//
+// Legacy files have no magic and start directly with the 16 byte salt, using the
+// default Argon2id parameters. They are still read for one release and reported
+// as `LEGACY_VERSION`.
+const MAGIC: &[u8; 7] = b"NPVAULT";
+const FORMAT_VERSION: u16 = 4;
+const NO_ATTACHMENTS_VERSION: u16 = 3;
+const NO_COMPRESSION_VERSION: u16 = 2;
+const NO_METADATA_VERSION: u16 = 1;
+const LEGACY_VERSION: u16 = 0;
+const HEADER_LEN: u16 = 16 + 1 + 4 * 3 + 1 + 8;
+/// Header length of files without the payload length
+const NO_ATTACHMENTS_HEADER_LEN: u16 = HEADER_LEN - 8;
+/// Header length of files without the compression byte
+const UNCOMPRESSED_HEADER_LEN: u16 = NO_ATTACHMENTS_HEADER_LEN - 1;
+
+/// Serialized vaults at least this large are compressed before encryption
+const COMPRESSION_THRESHOLD: usize = 4096;
+
+/// Whether large vaults are compressed when written, see `Settings::compression_enabled`.
+/// Compressed files are read either way.
+static COMPRESSION_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Vault details stored unencrypted so they can be shown before unlocking.
+/// Nothing in here may be secret.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct VaultMetadata {
+    pub vault_name: ZeroByte,
+    pub created_at: u64,        // Unix timestamp, 0 if unknown
+    pub modified_at: u64,       // Unix timestamp of the last save
+    pub item_count_hint: u32,   // Item count as of the last save
+    pub kdf_algorithm: KdfAlgorithm,
+    pub cipher: CipherAlgorithm,
+    #[serde(default = "default_accent")]
+    pub accent_color: String,   // One of `appearance::ACCENT_PALETTE`
+    #[serde(default)]
+    pub icon: Option<String>,   // `VaultIcon` id
+    #[serde(default)]
+    pub fido2_credential_id: Option<Vec<u8>>,  // Hardware key the vault key depends on, see utils::fido2
+}
+
+impl Default for VaultMetadata {
+    fn default() -> Self {
+        Self {
+            vault_name: ZeroByte::default(),
+            created_at: 0,
+            modified_at: 0,
+            item_count_hint: 0,
+            kdf_algorithm: KdfAlgorithm::default(),
+            cipher: CipherAlgorithm::default(),
+            accent_color: default_accent(),
+            icon: None,
+            fido2_credential_id: None,
+        }
+    }
+}
+
+fn default_accent() -> String {
+    appearance::DEFAULT_ACCENT.to_string()
+}
+
+/// Metadata as written before the appearance fields were added. Bincode has no field
+/// names, so `serde(default)` can't fill in trailing fields and these are decoded separately.
+#[derive(Deserialize)]
+struct MetadataWithoutAppearance {
+    vault_name: ZeroByte,
+    created_at: u64,
+    modified_at: u64,
+    item_count_hint: u32,
+    kdf_algorithm: KdfAlgorithm,
+    cipher: CipherAlgorithm,
+}
+
+impl From<MetadataWithoutAppearance> for VaultMetadata {
+    fn from(old: MetadataWithoutAppearance) -> Self {
+        Self {
+            vault_name: old.vault_name,
+            created_at: old.created_at,
+            modified_at: old.modified_at,
+            item_count_hint: old.item_count_hint,
+            kdf_algorithm: old.kdf_algorithm,
+            cipher: old.cipher,
+            ..Default::default()
+        }
+    }
+}
+
+/// Metadata as written before the hardware key credential was added
+#[derive(Deserialize)]
+struct MetadataWithoutCredential {
+    vault_name: ZeroByte,
+    created_at: u64,
+    modified_at: u64,
+    item_count_hint: u32,
+    kdf_algorithm: KdfAlgorithm,
+    cipher: CipherAlgorithm,
+    accent_color: String,
+    icon: Option<String>,
+}
+
+impl From<MetadataWithoutCredential> for VaultMetadata {
+    fn from(old: MetadataWithoutCredential) -> Self {
+        Self {
+            vault_name: old.vault_name,
+            created_at: old.created_at,
+            modified_at: old.modified_at,
+            item_count_hint: old.item_count_hint,
+            kdf_algorithm: old.kdf_algorithm,
+            cipher: old.cipher,
+            accent_color: old.accent_color,
+            icon: old.icon,
+            ..Default::default()
+        }
+    }
+}
+
+impl VaultMetadata {
+    pub(crate) fn new(vault_name: &str, created_at: u64) -> Self {
+        let mut name = ZeroByte::default();
+        name.extend_from_slice(vault_name.as_bytes());
+
+        Self {
+            vault_name: name,
+            created_at,
+            modified_at: created_at,
+            ..Default::default()
+        }
+    }
+
+    /// Metadata for a vault file that doesn't have any yet, named after the file
+    pub(crate) fn for_path(path: &Path) -> Self {
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Self::new(&name, 0)
+    }
+
+    pub(crate) fn name(&self) -> String {
+        String::from_utf8_lossy(self.vault_name.as_ref()).into_owned()
+    }
+
+    /// The vault key also needs an assertion from the hardware key in `fido2_credential_id`
+    pub(crate) fn requires_hardware_key(&self) -> bool {
+        self.fido2_credential_id.is_some()
+    }
+}
+
+/// How the vault plaintext was compressed before encryption
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum PayloadCompression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl PayloadCompression {
+    fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zstd => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::None),
+            1 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Unencrypted vault file header
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VaultFileHeader {
+    pub version: u16,
+    pub salt: [u8; 16],
+    pub params: ArgonParams,
+    pub metadata: Option<VaultMetadata>,  // None for files older than `FORMAT_VERSION` 2
+    pub compression: PayloadCompression,  // Always None for files older than `FORMAT_VERSION` 3
+    pub payload_len: Option<u64>,  // None for files older than `FORMAT_VERSION` 4, the payload runs to the end
+}
+
+impl VaultFileHeader {
+    /// Parses the header, leaving the reader positioned at the nonce.
+    /// Falls back to the legacy salt-first layout when the magic bytes are missing.
+    pub(crate) fn parse<R: Read>(reader: &mut R) -> Result<Self, FileError> {
+        let mut magic = [0u8; 7];
+        reader.read_exact(&mut magic)?;
+
+        let mut salt = [0u8; 16];
+
+        if &magic != MAGIC {
+            // Legacy layout, the bytes we just read are the start of the salt
+            salt[..magic.len()].copy_from_slice(&magic);
+            reader.read_exact(&mut salt[magic.len()..])?;
+
+            return Ok(Self {
+                version: LEGACY_VERSION,
+                salt,
+                params: ArgonParams::default(),
+                metadata: None,
+                compression: PayloadCompression::None,
+                payload_len: None,
+            });
+        }
+
+        let version = read_u16(reader)?;
+
+        if version > FORMAT_VERSION {
+            return Err(FileError::UnsupportedVersion(version));
+        }
+        if version == LEGACY_VERSION {
+            return Err(FileError::CorruptHeader("Corrupted vault file header"));
+        }
+
+        let metadata = if version > NO_METADATA_VERSION {
+            let mut metadata = vec![0u8; read_u16(reader)? as usize];
+            reader.read_exact(&mut metadata)?;
+
+            Some(decode_metadata(&metadata)?)
+        } else {
+            None
+        };
+
+        let min_header_len =
+            if version > NO_ATTACHMENTS_VERSION { HEADER_LEN }
+            else if version > NO_COMPRESSION_VERSION { NO_ATTACHMENTS_HEADER_LEN }
+            else { UNCOMPRESSED_HEADER_LEN };
+        let header_len = read_u16(reader)?;
+        if header_len < min_header_len {
+            return Err(FileError::CorruptHeader("Corrupted vault file header"));
+        }
+
+        // Read the whole header, including fields appended by newer minor revisions
+        let mut header = vec![0u8; header_len as usize];
+        reader.read_exact(&mut header)?;
+
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([header[offset], header[offset + 1], header[offset + 2], header[offset + 3]])
+        };
+
+        salt.copy_from_slice(&header[..16]);
+        let params = ArgonParams {
+            algorithm: KdfAlgorithm::from_id(header[16]).ok_or(FileError::CorruptHeader("Unknown key derivation algorithm"))?,
+            memory_cost: read_u32(17),
+            time_cost: read_u32(21),
+            parallelism: read_u32(25),
+        };
+
+        let compression =
+            if version > NO_COMPRESSION_VERSION { PayloadCompression::from_id(header[29]).ok_or(FileError::CorruptHeader("Unknown vault compression"))? }
+            else { PayloadCompression::None };
+
+        let payload_len = (version > NO_ATTACHMENTS_VERSION).then(|| {
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&header[30..38]);
+            u64::from_le_bytes(len)
+        });
+
+        Ok(Self { version, salt, params, metadata, compression, payload_len })
+    }
+
+    pub(crate) fn is_legacy(&self) -> bool {
+        self.version == LEGACY_VERSION
+    }
+}
+
+/// On-disk layout of a vault file, as far as it can be told from its first bytes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum VaultFormat {
+    /// Salt-first file without magic bytes
+    Legacy,
+    /// Versioned header this build writes
+    Current,
+    /// Newer than this build, or unreadable
+    Unsupported,
+}
+
+/// Rotating `.bak` copies kept next to the vault by default
+pub(crate) const DEFAULT_BACKUP_DEPTH: usize = 3;
+
+/// Renames over an existing file can fail on Windows while another process
+/// (e.g. a virus scanner) briefly holds the destination open
+#[cfg(windows)]
+const RENAME_RETRIES: u32 = 10;
+#[cfg(windows)]
+const RENAME_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Reusable buffers for the encrypt -> write and read -> decrypt pipelines.
+/// Saves are single-writer, so at most a couple of buffers are ever checked out.
+static BUFFER_POOL: Mutex<BufferPool> = Mutex::new(BufferPool::new());
+
+/// Reads and parses only the unencrypted header of the vault file at `path`
+pub(crate) fn read_header(path: &Path) -> Result<VaultFileHeader, FileError> {
+    VaultFileHeader::parse(&mut BufReader::new(open_file(path)?))
+}
+
+/// Reads the unencrypted metadata of a vault without decrypting it.
+/// Files older than `FORMAT_VERSION` 2 have none.
+pub(crate) fn read_vault_metadata(path: &Path) -> Result<Option<VaultMetadata>, FileError> {
+    Ok(read_header(path)?.metadata)
+}
+
+/// Whether the file at `path` starts with the vault magic bytes. Legacy vaults have none, so
+/// this only recognizes files in the versioned format.
+pub(crate) fn has_vault_magic(path: &Path) -> bool {
+    let mut magic = [0u8; MAGIC.len()];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == MAGIC
+}
+
+/// Tells legacy files apart from ones already using the versioned header
+pub(crate) fn detect_format(path: &Path) -> VaultFormat {
+    match read_header(path) {
+        Ok(header) if header.is_legacy() => VaultFormat::Legacy,
+        Ok(_) => VaultFormat::Current,
+        Err(_) => VaultFormat::Unsupported,
+    }
+}
+
+/// Path the original is kept at when a legacy vault is upgraded, e.g. `work.vault.legacy.bak`
+pub(crate) fn legacy_backup_path(path: &Path) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(".legacy.bak");
+    PathBuf::from(backup)
+}
+
+/// Rewrites a legacy vault file in the current format. The original is copied to
+/// `legacy_backup_path` first, and since writes are atomic a failed write leaves
+/// the legacy file untouched. Returns the backup path.
+pub(crate) fn migrate_legacy_file(bytes: &[u8], path: &Path, key: &ArgonKey, metadata: &VaultMetadata) -> Result<PathBuf, FileError> {
+    if detect_format(path) != VaultFormat::Legacy {
+        return Err(FileError::BadMagic);
+    }
+
+    let backup = legacy_backup_path(path);
+    fs::copy(path, &backup)?;
+
+    // The legacy backup above already keeps the original
+    write_encrypted_file(bytes, path, key, metadata, 0)?;
+    Ok(backup)
+}
+
+/// Removes temp files left next to the vault by saves that crashed midway.
+/// Temp files of this process are left alone.
+pub(crate) fn remove_stale_temp_files(path: &Path) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else { return; };
+    let Ok(entries) = fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir }) else { return; };
+
+    let prefix = format!("{}.tmp.", name.to_string_lossy());
+    let own_temp = temp_path(path);
+
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        let is_temp = file_name
+            .strip_prefix(&prefix)
+            .is_some_and(|pid| !pid.is_empty() && pid.chars().all(|c| c.is_ascii_digit()));
+
+        if is_temp && entry.path() != own_temp {
+            match fs::remove_file(entry.path()) {
+                Ok(()) => log::info!("Removed stale temp file {}", entry.path().display()),
+                Err(e) => log::warn!("Failed to remove stale temp file {}: {}", entry.path().display(), e),
+            }
+        }
+    }
+}
+
+/// Opens the vault file at `path` once: parses the header, derives the key from `password`
+/// and decrypts the payload. The plaintext buffer comes from the buffer pool, see `read_encrypted_file`.
+pub(crate) fn open_vault(path: &Path, password: &str) -> Result<(ZeroByte, ArgonKey), OpenVaultError> {
+    let mut reader = BufReader::new(open_file(path).map_err(OpenVaultError::Header)?);
+
+    let header = VaultFileHeader::parse(&mut reader).map_err(OpenVaultError::Header)?;
+    if header.is_legacy() {
+        log::warn!("Opening legacy vault file without header, it will be upgraded on the next save");
+    }
+
+    let key = Crypto::derive_argon_key(password.as_bytes(), Some(header.salt), header.params)
+        .map_err(|e| OpenVaultError::Header(e.into()))?;
+    let bytes = decrypt_payload(&mut reader, &header, &key).map_err(OpenVaultError::Payload)?;
+
+    Ok((bytes, key))
+}
+
+/// Encrypts and atomically writes the vault, keeping the attachments of the file it replaces.
+/// Right before the existing file is replaced it is copied to `<path>.bak1`, shifting older
+/// copies up to `backup_depth` (0 disables backups).
+pub(crate) fn write_encrypted_file(
+    bytes: &[u8], path: &Path, key: &ArgonKey, metadata: &VaultMetadata, backup_depth: usize
+) -> Result<(), FileError> {
+    write_vault_file(bytes, path, key, metadata, backup_depth, &AttachmentChanges::default())
+}
+
+/// Attachment edits applied by `write_vault_file`. Attachments that aren't changed are
+/// copied over from the source file still encrypted, without being re-encrypted.
+#[derive(Debug, Default)]
+pub(crate) struct AttachmentChanges {
+    /// Vault file the unchanged attachments are copied from, the file being replaced when None
+    pub source: Option<PathBuf>,
+    /// Plaintext to encrypt, replacing any attachment with the same id
+    pub put: Vec<(u32, ZeroByte)>,
+    pub remove: Vec<u32>,
+    /// Leave out every attachment of the source, only `put` is written
+    pub skip_source: bool,
+}
+
+/// Like `write_encrypted_file`, applying `changes` to the attachments carried over.
+/// Attachments of a source file encrypted under a different key can't be carried over
+/// and are left out.
+pub(crate) fn write_vault_file(
+    bytes: &[u8], path: &Path, key: &ArgonKey, metadata: &VaultMetadata, backup_depth: usize, changes: &AttachmentChanges
+) -> Result<(), FileError> {
+    let path = validate_target(path)?;
+
+    let mut combined = checkout_buffer();
+    let result = write_combined(&mut combined, bytes, &path, key, metadata, backup_depth, changes);
+    recycle_buffer(combined);
+
+    result
+}
+
+/// Writes a standalone copy of the vault to `path`, encrypted under a key derived from `passphrase`
+/// with a fresh salt and the parameters of `key`, the key of the vault file at `source`.
+/// The attachments of `source` can't be copied under another key, they are decrypted with `key`
+/// and encrypted again. No backups of `path` are kept.
+pub(crate) fn export_vault_file(
+    bytes: &[u8], source: &Path, key: &ArgonKey, path: &Path, passphrase: &[u8], metadata: &VaultMetadata
+) -> Result<(), FileError> {
+    let new_key = Crypto::derive_argon_key(passphrase, None, key.params)?;
+
+    let mut changes = AttachmentChanges { skip_source: true, ..Default::default() };
+    let result = attachment_ids(source)
+        .and_then(|ids| {
+            for id in ids {
+                changes.put.push((id, read_attachment(source, key, id)?));
+            }
+            write_vault_file(bytes, path, &new_key, metadata, 0, &changes)
+        });
+
+    for (_, data) in changes.put {
+        recycle_buffer(data);
+    }
+    result
+}
+
+/// What a vault file looked like when it was last read or written by this session,
+/// used to notice when another program (e.g. a sync client) has replaced it since.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FileFingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: [u8; 32],  // BLAKE2s of the whole file
+}
+
+impl FileFingerprint {
+    pub(crate) fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: hash_file(path)?,
+        })
+    }
+
+    /// Returns true if the file's contents differ from when the fingerprint was taken.
+    /// The file is only hashed when its modification time or size changed.
+    pub(crate) fn changed(&self, path: &Path) -> io::Result<bool> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(true),
+            Err(e) => return Err(e),
+        };
+
+        if metadata.modified().ok() == self.modified && metadata.len() == self.len {
+            return Ok(false);
+        }
+
+        Ok(hash_file(path)? != self.hash)
+    }
+}
+
+/// Outcome of `write_if_unchanged`
+#[derive(Debug, PartialEq)]
+pub(crate) enum GuardedWrite {
+    /// The file was written, with the fingerprint of the new contents
+    Written(FileFingerprint),
+    /// The file changed since `expected` was taken and was left alone
+    Conflict,
+}
+
+/// Writes the vault like `write_encrypted_file`, unless the file at `path` no longer matches
+/// `expected`. Pass `None` to write regardless, e.g. when the user chose to overwrite.
+pub(crate) fn write_if_unchanged(
+    bytes: &[u8], path: &Path, key: &ArgonKey, metadata: &VaultMetadata, backup_depth: usize,
+    expected: Option<&FileFingerprint>
+) -> Result<GuardedWrite, FileError> {
+    if let Some(expected) = expected
+        && expected.changed(path)? {
+        return Ok(GuardedWrite::Conflict);
+    }
+
+    write_encrypted_file(bytes, path, key, metadata, backup_depth)?;
+    Ok(GuardedWrite::Written(FileFingerprint::of(path)?))
+}
+
+fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut hasher = Blake2s256::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// Decrypts the vault file at `path`. The returned plaintext buffer comes from the
+/// buffer pool and should be handed back with `recycle_buffer` once decoded.
+pub(crate) fn read_encrypted_file(path: &Path, key: &ArgonKey) -> Result<ZeroByte, FileError> {
+    let mut reader = BufReader::new(open_file(path)?);
+
+    let header = VaultFileHeader::parse(&mut reader)?;
+    decrypt_payload(&mut reader, &header, key)
+}
+
+/// Decrypts and decompresses what follows the header, `reader` must be positioned at the nonce
+fn decrypt_payload<R: Read>(reader: &mut R, header: &VaultFileHeader, key: &ArgonKey) -> Result<ZeroByte, FileError> {
+    let mut buffer = checkout_buffer();
+    let read = match header.payload_len {
+        Some(len) => buffer.extend_from_reader(&mut reader.by_ref().take(len)),
+        None => buffer.extend_from_reader(reader),
+    };
+
+    let result = read
+        .map_err(FileError::from)
+        .and_then(|_| {
+            let cut_off = header.payload_len.is_some_and(|len| (buffer.len() as u64) < len);
+            if cut_off || buffer.len() < MIN_ENCRYPTED_LEN {
+                return Err(FileError::Truncated);
+            }
+            Ok(Crypto::aes_gcm_decrypt(&mut buffer, &key.to_zero_byte())?)
+        });
+
+    if let Err(e) = result {
+        recycle_buffer(buffer);
+        return Err(e);
+    }
+
+    match header.compression {
+        PayloadCompression::None => Ok(buffer),
+        PayloadCompression::Zstd => {
+            let decompressed = compression::decompress(&buffer, compression::DEFAULT_MAX_OUTPUT_LEN);
+            recycle_buffer(buffer);
+            Ok(decompressed?)
+        }
+    }
+}
+
+/// Outcome of a single `verify_vault` check
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum CheckStatus {
+    Passed,
+    Failed(String),
+    /// Not run because an earlier check failed
+    Skipped,
+}
+
+/// Result of `verify_vault`, one entry per check in the order they run
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct VerifyReport {
+    pub checks: Vec<(&'static str, CheckStatus)>,
+}
+
+impl VerifyReport {
+    const HEADER: &'static str = "Header parses";
+    const KDF_PARAMS: &'static str = "Key derivation parameters are sane";
+    const DECRYPTION: &'static str = "Password is correct and the data is authentic";
+    const CONTENTS: &'static str = "Vault contents decode";
+    const ATTACHMENTS: &'static str = "Attachments decrypt";
+
+    pub(crate) fn passed(&self) -> bool {
+        self.checks.iter().all(|(_, status)| *status == CheckStatus::Passed)
+    }
+
+    pub(crate) fn status(&self, check: &str) -> Option<&CheckStatus> {
+        self.checks.iter().find(|(name, _)| *name == check).map(|(_, status)| status)
+    }
+
+    /// Records the outcome of `check`, returning its value if it passed
+    fn record<T, E: fmt::Display>(&mut self, check: &'static str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.checks.push((check, CheckStatus::Passed));
+                Some(value)
+            },
+            Err(e) => {
+                self.checks.push((check, CheckStatus::Failed(e.to_string())));
+                None
+            }
+        }
+    }
+
+    /// Marks every check that hasn't run yet as skipped
+    fn skip_rest(mut self) -> Self {
+        for check in [Self::HEADER, Self::KDF_PARAMS, Self::DECRYPTION, Self::CONTENTS, Self::ATTACHMENTS] {
+            if self.status(check).is_none() {
+                self.checks.push((check, CheckStatus::Skipped));
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (check, status) in &self.checks {
+            match status {
+                CheckStatus::Passed => writeln!(f, "[ok] {}", check)?,
+                CheckStatus::Failed(e) => writeln!(f, "[failed] {}: {}", check, e)?,
+                CheckStatus::Skipped => writeln!(f, "[skipped] {}", check)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks the vault file at `path` can be opened with `password` without loading it anywhere:
+/// the header parses, the KDF parameters are sane, the AEAD tag verifies, the contents decode
+/// and every attachment decrypts. Decrypted data is zeroized before this returns.
+pub(crate) fn verify_vault(path: &Path, password: &str) -> VerifyReport {
+    let mut report = VerifyReport::default();
+
+    let header = open_file(path).and_then(|file| {
+        let mut reader = BufReader::new(file);
+        VaultFileHeader::parse(&mut reader).map(|header| (header, reader))
+    });
+    let Some((header, mut reader)) = report.record(VerifyReport::HEADER, header) else {
+        return report.skip_rest();
+    };
+
+    if report.record(VerifyReport::KDF_PARAMS, header.params.validate()).is_none() {
+        return report.skip_rest();
+    }
+
+    let opened = Crypto::derive_argon_key(password.as_bytes(), Some(header.salt), header.params)
+        .map_err(FileError::from)
+        .and_then(|key| decrypt_payload(&mut reader, &header, &key).map(|bytes| (key, bytes)));
+    let Some((mut key, bytes)) = report.record(VerifyReport::DECRYPTION, opened) else {
+        return report.skip_rest();
+    };
+
+    let decoded = Vault::decode(bytes.as_ref())
+        .map(|mut vault| vault.items.zeroize())
+        .map_err(|e| FileError::EncodingFailed(e.to_string()));
+    recycle_buffer(bytes);
+    report.record(VerifyReport::CONTENTS, decoded);
+
+    let attachments = verify_attachments(path, &header, &key);
+    report.record(VerifyReport::ATTACHMENTS, attachments);
+
+    key.wipe();
+    report
+}
+
+/// Decrypts every attachment of the vault file at `path` in turn, listing the ones that fail
+fn verify_attachments(path: &Path, header: &VaultFileHeader, key: &ArgonKey) -> Result<(), String> {
+    let Some(payload_len) = header.payload_len else {
+        return Ok(());  // Older files have no attachments
+    };
+
+    let mut reader = BufReader::new(open_file(path).map_err(|e| e.to_string())?);
+    VaultFileHeader::parse(&mut reader).map_err(|e| e.to_string())?;
+    let index = read_attachment_index(&mut reader, payload_len).map_err(|e| e.to_string())?;
+
+    let failures: Vec<String> = index.iter()
+        .filter_map(|&(id, _)| match read_attachment(path, key, id) {
+            Ok(data) => {
+                recycle_buffer(data);
+                None
+            },
+            Err(FileError::Truncated) => Some(format!("Attachment {} is cut off", id)),
+            Err(FileError::TamperingDetected) => Some(format!("Attachment {} is damaged", id)),
+            Err(e) => Some(e.to_string()),
+        })
+        .collect();
+
+    if failures.is_empty() { Ok(()) }
+    else { Err(failures.join(", ")) }
+}
+
+/// Turns compressing large vaults on write on or off for the rest of the session
+pub(crate) fn set_compression_enabled(enabled: bool) {
+    COMPRESSION_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Compresses a vault of at least `COMPRESSION_THRESHOLD` bytes if `enabled`. Returns None
+/// if it is written as it is, also when compressing doesn't make it smaller.
+fn compress_payload(bytes: &[u8], enabled: bool) -> Result<Option<ZeroByte>, FileError> {
+    if !enabled || bytes.len() < COMPRESSION_THRESHOLD {
+        return Ok(None);
+    }
+
+    let mut plaintext = checkout_buffer();
+    plaintext.extend_from_slice(bytes);
+    let compressed = compression::compress(&plaintext);
+    recycle_buffer(plaintext);
+
+    let compressed = compressed?;
+    Ok((compressed.len() < bytes.len()).then_some(compressed))
+}
+
+/// Runs blocking vault file work (key derivation, encryption, disk IO) on Tokio's blocking
+/// pool, so the UI thread stays responsive while e.g. a slow network share is written.
+/// Must be awaited from within the Tokio runtime.
+pub(crate) async fn run_blocking<T, F>(task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(task).await.map_err(|e| e.to_string())
+}
+
+/// Returns a buffer obtained from `read_encrypted_file` to the pool, zeroizing it
+pub(crate) fn recycle_buffer(buffer: ZeroByte) {
+    if let Ok(mut pool) = BUFFER_POOL.lock() {
+        pool.checkin(buffer);
+    }
+}
+
+/// Frees all pooled buffers, e.g. before the application exits
+pub(crate) fn release_buffers() {
+    if let Ok(mut pool) = BUFFER_POOL.lock() {
+        pool.scrub_hint();
+    }
+}
+
+fn checkout_buffer() -> ZeroByte {
+    match BUFFER_POOL.lock() {
+        Ok(mut pool) => pool.checkout(),
+        Err(_) => ZeroByte::default(),
+    }
+}
+
+/// Assembles header + nonce + cipherbytes + attachments into `combined` and writes it to `path`.
+/// Large vaults are compressed before encryption unless compression is turned off.
+fn write_combined(
+    combined: &mut ZeroByte, bytes: &[u8], path: &Path, key: &ArgonKey, metadata: &VaultMetadata, backup_depth: usize,
+    changes: &AttachmentChanges
+) -> Result<(), FileError> {
+    let compressed = compress_payload(bytes, COMPRESSION_ENABLED.load(Ordering::Relaxed))?;
+    let (payload, compression) = match &compressed {
+        Some(compressed) => (compressed.as_ref(), PayloadCompression::Zstd),
+        None => (bytes, PayloadCompression::None),
+    };
+
+    combined.extend_from_slice(&encode_header(key, metadata, compression)?);  // magic + version + metadata + header
+    let payload_start = combined.len();
+    let encrypted = Crypto::aes_gcm_encrypt(payload, &key.to_zero_byte(), combined)  // nonce + cipherbytes
+        .map_err(|e| FileError::EncodingFailed(e.to_string()));
+
+    if let Some(compressed) = compressed {
+        recycle_buffer(compressed);
+    }
+    encrypted?;
+
+    // The payload length closes the header, fill it in now that it is known
+    let payload_len = (combined.len() - payload_start) as u64;
+    combined.as_mut()[payload_start - 8..payload_start].copy_from_slice(&payload_len.to_le_bytes());
+
+    let attachments = merge_attachments(changes.source.as_deref().unwrap_or(path), key, changes)?;
+    encode_attachments(combined, &attachments);
+
+    write_via_temp(path, combined.as_ref(), backup_depth, create_private, |temp| verify_written(temp, key))
+        .map_err(|e| match e {
+            FileError::Io(source) => FileError::WriteFailed { path: path.to_path_buf(), source },
+            FileError::VerificationFailed { source, .. } => FileError::VerificationFailed { path: path.to_path_buf(), source },
+            e => e,
+        })
+}
+
+/// Reads a just written vault file back and decrypts it with the key it was written with, so
+/// a save that didn't reach the disk intact never replaces the previous file
+fn verify_written(path: &Path, key: &ArgonKey) -> Result<(), FileError> {
+    read_encrypted_file(path, key)
+        .map(recycle_buffer)
+        .map_err(|e| FileError::VerificationFailed { path: path.to_path_buf(), source: Box::new(e) })
+}
+
+/// Canonical path of a vault file about to be written. Its folder has to exist and be
+/// readable, so a missing folder is reported as such rather than as a failed temp file.
+fn validate_target(path: &Path) -> Result<PathBuf, FileError> {
+    let file_name = path.file_name().ok_or_else(|| FileError::InvalidTarget(path.to_path_buf()))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    match fs::metadata(parent) {
+        Ok(metadata) if metadata.is_dir() => { },
+        Ok(_) => return Err(FileError::InvalidTarget(path.to_path_buf())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(FileError::ParentNotFound(parent.to_path_buf())),
+        Err(e) => return Err(FileError::Io(e)),
+    }
+
+    let target = fs::canonicalize(parent)?.join(file_name);
+    if target.is_dir() {
+        return Err(FileError::InvalidTarget(target));
+    }
+    Ok(target)
+}
+
+/// Encrypted attachment chunks of `source` with `changes` applied, in file order
+fn merge_attachments(source: &Path, key: &ArgonKey, changes: &AttachmentChanges) -> Result<Vec<(u32, Vec<u8>)>, FileError> {
+    let mut chunks = if changes.skip_source { Vec::new() } else { read_attachment_chunks(source, key)? };
+    chunks.retain(|(id, _)| !changes.remove.contains(id) && !changes.put.iter().any(|(put_id, _)| put_id == id));
+
+    for (id, data) in &changes.put {
+        let mut plaintext = checkout_buffer();
+        plaintext.extend_from_slice(&id.to_le_bytes());
+        plaintext.extend_from_slice(data.as_ref());
+
+        let mut chunk = ZeroByte::default();
+        let encrypted = Crypto::aes_gcm_encrypt(plaintext.as_ref(), &key.to_zero_byte(), &mut chunk);
+        recycle_buffer(plaintext);
+        encrypted.map_err(|e| FileError::EncodingFailed(e.to_string()))?;
+
+        chunks.push((*id, chunk.as_ref().to_vec()));
+    }
+
+    Ok(chunks)
+}
+
+fn encode_attachments(combined: &mut ZeroByte, chunks: &[(u32, Vec<u8>)]) {
+    combined.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    for (id, chunk) in chunks {
+        combined.extend_from_slice(&id.to_le_bytes());
+        combined.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+    }
+    for (_, chunk) in chunks {
+        combined.extend_from_slice(chunk);
+    }
+}
+
+/// Encrypted attachment chunks of the vault file at `path`, without decrypting them.
+/// Missing, empty or unreadable files, files older than attachments and files encrypted
+/// under a different key have none to carry over, and neither do files whose attachments are cut off.
+fn read_attachment_chunks(path: &Path, key: &ArgonKey) -> Result<Vec<(u32, Vec<u8>)>, FileError> {
+    let file = match open_file(path) {
+        Ok(file) => file,
+        Err(FileError::PathNotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    if file.metadata()?.len() == 0 {
+        return Ok(Vec::new());
+    }
+    let mut reader = BufReader::new(file);
+
+    let index = VaultFileHeader::parse(&mut reader).and_then(|header| {
+        let Some(payload_len) = header.payload_len else {
+            return Ok((header, Vec::new()));
+        };
+        read_attachment_index(&mut reader, payload_len).map(|index| (header, index))
+    });
+    let (header, index) = match index {
+        Ok(index) => index,
+        Err(e) => {
+            log::warn!("No attachments carried over from {}: {}", path.display(), e);
+            return Ok(Vec::new());
+        }
+    };
+    if !index.is_empty() && (header.salt != key.salt || header.params != key.params) {
+        log::warn!("Attachments of {} were encrypted under a different key, not carrying them over", path.display());
+        return Ok(Vec::new());
+    }
+
+    let mut chunks = Vec::with_capacity(index.len());
+    for (id, len) in index {
+        let mut chunk = vec![0u8; len as usize];
+        if let Err(e) = reader.read_exact(&mut chunk) {
+            log::warn!("Attachment {} of {} could not be read: {}", id, path.display(), FileError::from(e));
+            break;
+        }
+        chunks.push((id, chunk));
+    }
+
+    Ok(chunks)
+}
+
+/// Skips the payload and reads the attachment index as (id, chunk length) pairs.
+/// An index that lists more entries or chunk data than the rest of the file holds is
+/// `FileError::CorruptAttachments`, so nothing is allocated for sizes read from a damaged file.
+fn read_attachment_index(reader: &mut BufReader<File>, payload_len: u64) -> Result<Vec<(u32, u32)>, FileError> {
+    let payload_len = i64::try_from(payload_len).map_err(|_| FileError::CorruptHeader("Corrupted vault file header"))?;
+    reader.seek_relative(payload_len)?;
+
+    let count = read_u32(reader)?;
+    let remaining = reader.get_ref().metadata()?.len().saturating_sub(reader.stream_position()?);
+    let index_len = u64::from(count) * 8;
+    if index_len > remaining {
+        return Err(FileError::CorruptAttachments);
+    }
+
+    let mut index = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        index.push((read_u32(reader)?, read_u32(reader)?));
+    }
+
+    let chunks_len: u64 = index.iter().map(|&(_, len)| u64::from(len)).sum();
+    if chunks_len > remaining - index_len {
+        return Err(FileError::CorruptAttachments);
+    }
+
+    Ok(index)
+}
+
+/// Ids of the attachments in the vault file at `path`, in file order
+pub(crate) fn attachment_ids(path: &Path) -> Result<Vec<u32>, FileError> {
+    let mut reader = BufReader::new(open_file(path)?);
+
+    let header = VaultFileHeader::parse(&mut reader)?;
+    let Some(payload_len) = header.payload_len else {
+        return Ok(Vec::new());  // Older files have none
+    };
+
+    Ok(read_attachment_index(&mut reader, payload_len)?.into_iter().map(|(id, _)| id).collect())
+}
+
+/// Decrypts a single attachment of the vault file at `path` without decrypting the rest.
+/// The returned buffer comes from the buffer pool, see `read_encrypted_file`.
+pub(crate) fn read_attachment(path: &Path, key: &ArgonKey, id: u32) -> Result<ZeroByte, FileError> {
+    let mut reader = BufReader::new(open_file(path)?);
+
+    let header = VaultFileHeader::parse(&mut reader)?;
+    let payload_len = header.payload_len.ok_or(FileError::AttachmentNotFound(id))?;  // Older files have none
+    let index = read_attachment_index(&mut reader, payload_len)?;
+
+    let position = index.iter().position(|&(entry_id, _)| entry_id == id)
+        .ok_or(FileError::AttachmentNotFound(id))?;
+    let skip: i64 = index[..position].iter().map(|&(_, len)| i64::from(len)).sum();
+    reader.seek_relative(skip)?;
+
+    let len = index[position].1 as usize;
+    let mut buffer = checkout_buffer();
+    let result = buffer.extend_from_reader(&mut reader.by_ref().take(len as u64))
+        .map_err(FileError::from)
+        .and_then(|read| {
+            if read < len {
+                return Err(FileError::Truncated);
+            }
+            // The vault's own key opened the payload, so a chunk that fails to authenticate was modified
+            Crypto::aes_gcm_decrypt(&mut buffer, &key.to_zero_byte())
+                .map_err(|_| FileError::TamperingDetected)
+        })
+        .and_then(|_| {
+            // The id inside the chunk stops chunks from being swapped between attachments
+            if buffer.len() < 4 || buffer.as_ref()[..4] != id.to_le_bytes() {
+                return Err(FileError::TamperingDetected);
+            }
+            Ok(())
+        });
+
+    if let Err(e) = result {
+        recycle_buffer(buffer);
+        return Err(e);
+    }
+
+    let mut data = checkout_buffer();
+    data.extend_from_slice(&buffer.as_ref()[4..]);
+    recycle_buffer(buffer);
+    Ok(data)
+}
+
+/// Path of the nth rotating backup, e.g. `work.vault.bak1` for the most recent one
+pub(crate) fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(format!(".bak{}", n));
+    PathBuf::from(backup)
+}
+
+/// Most recent rotating backup of the vault at `path`, if there is one
+pub(crate) fn latest_backup(path: &Path) -> Option<PathBuf> {
+    Some(backup_path(path, 1)).filter(|backup| backup.is_file())
+}
+
+/// Opens the latest rotating backup of the vault at `path`, for when the vault file itself
+/// fails to decrypt. Returns the backup's path, plaintext and key, or None if there is no
+/// backup or it doesn't open with `password` either.
+pub(crate) fn open_latest_backup(path: &Path, password: &str) -> Option<(PathBuf, ZeroByte, ArgonKey)> {
+    let backup = latest_backup(path)?;
+
+    match open_vault(&backup, password) {
+        Ok((bytes, key)) => Some((backup, bytes, key)),
+        Err(e) => {
+            log::info!("Latest backup {} did not open either: {}", backup.display(), e);
+            None
+        }
+    }
+}
+
+/// Path a damaged vault file is kept at once a session restored from backup saves over it,
+/// e.g. `work.vault.corrupt`
+pub(crate) fn corrupt_path(path: &Path) -> PathBuf {
+    let mut corrupt = OsString::from(path.as_os_str());
+    corrupt.push(".corrupt");
+    PathBuf::from(corrupt)
+}
+
+/// Moves the damaged vault file at `path` to `corrupt_path`, replacing an older one, so the
+/// next save neither overwrites it nor rotates it into the backups
+pub(crate) fn set_aside_corrupt(path: &Path) -> io::Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    fs::rename(path, corrupt_path(path))
+}
+
+/// Shifts `.bak1..` up by one, dropping the oldest beyond `depth`, and copies the current
+/// file to `.bak1` with its modification time preserved
+fn rotate_backups(path: &Path, depth: usize) -> io::Result<()> {
+    if depth == 0 || !path.is_file() {
+        return Ok(());
+    }
+
+    let oldest = backup_path(path, depth);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+
+    for n in (1..depth).rev() {
+        let backup = backup_path(path, n);
+        if backup.exists() {
+            fs::rename(&backup, backup_path(path, n + 1))?;  // Renames keep timestamps
+        }
+    }
+
+    let latest = backup_path(path, 1);
+    fs::copy(path, &latest)?;
+
+    let modified = fs::metadata(path)?.modified()?;
+    File::options().write(true).open(&latest)?.set_modified(modified)?;
+
+    Ok(())
+}
+
+/// Temp file a save writes to before it replaces the vault, e.g. `work.vault.tmp.1234`
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(format!(".tmp.{}", std::process::id()));
+    PathBuf::from(temp)
+}
+
+/// Writes `contents` to a temp file in the same directory, syncs it and renames it over
+/// `path`. If anything fails the existing file at `path` is left untouched.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8], backup_depth: usize) -> io::Result<()> {
+    write_via_temp(path, contents, backup_depth, |path| File::create(path), |_| Ok(())).map_err(into_io_error)
+}
+
+/// Like `write_atomically` without backups, for plaintext that others must not be able to read
+/// even while it is being written. On Unix the file is only readable by the user.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_via_temp(path, contents, 0, create_private, |_| Ok(())).map_err(into_io_error)
+}
+
+/// The OS error behind a failed write, for the writers that report plain IO errors
+fn into_io_error(e: FileError) -> io::Error {
+    match e {
+        FileError::Io(e) | FileError::WriteFailed { source: e, .. } | FileError::FlushFailed(e) | FileError::SyncFailed(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let file = File::options().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    // The mode only applies to new files, a stale temp file keeps its own
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(windows)]
+fn create_private(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    // Keeps the file out of the Windows Search index. Access is left to the ACL inherited
+    // from the folder, which for the user profile is already private.
+    const FILE_ATTRIBUTE_NOT_CONTENT_INDEXED: u32 = 0x2000;
+    File::options().write(true).create(true).truncate(true).attributes(FILE_ATTRIBUTE_NOT_CONTENT_INDEXED).open(path)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn create_private(path: &Path) -> io::Result<File> {
+    File::create(path)
+}
+
+/// Shared by the writers above. `verify` checks the synced temp file before it replaces `path`.
+fn write_via_temp(
+    path: &Path, contents: &[u8], backup_depth: usize, create: fn(&Path) -> io::Result<File>,
+    verify: impl FnOnce(&Path) -> Result<(), FileError>
+) -> Result<(), FileError> {
+    let temp = temp_path(path);
+
+    let result = create(&temp)
+        .map_err(FileError::from)
+        .and_then(|file| write_synced(file, contents))
+        .and_then(|_| verify(&temp))
+        .and_then(|_| {
+            // A failed backup must not block the save
+            if let Err(e) = rotate_backups(path, backup_depth) {
+                log::warn!("Failed to back up {}: {}", path.display(), e);
+            }
+            rename_replace(&temp, path)?;
+            Ok(sync_parent(path)?)
+        });
+
+    if result.is_err() && temp.is_file() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    result
+}
+
+/// Writes `contents` to `file` and syncs it to the disk. A buffered write only reports some
+/// errors, like a full disk, when flushed, so each step is checked and reported on its own.
+fn write_synced(file: File, contents: &[u8]) -> Result<(), FileError> {
+    let mut writer = BufWriter::new(file);
+    writer.write_all(contents)?;
+    writer.flush().map_err(FileError::FlushFailed)?;
+
+    let mut file = writer.into_inner().map_err(|e| FileError::FlushFailed(e.into_error()))?;
+    file.sync_all().map_err(FileError::SyncFailed)?;
+
+    let expected = contents.len() as u64;
+    let written = file.seek(SeekFrom::End(0))?;
+    if written != expected {
+        return Err(FileError::IncompleteWrite { expected, written });
+    }
+    Ok(())
+}
+
+/// Syncs the folder of `path`, so the rename that put the file there survives a crash
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+/// Windows can't open folders as files; NTFS journals the rename itself
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn rename_replace(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)
+}
+
+#[cfg(windows)]
+fn rename_replace(from: &Path, to: &Path) -> io::Result<()> {
+    let mut attempts = 0;
+
+    loop {
+        match fs::rename(from, to) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied && attempts < RENAME_RETRIES => {
+                attempts += 1;
+                std::thread::sleep(RENAME_RETRY_DELAY);
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Serializes the magic, version, metadata and header for the given key
+fn encode_header(key: &ArgonKey, metadata: &VaultMetadata, compression: PayloadCompression) -> Result<Vec<u8>, FileError> {
+    // The key decides the algorithm, keep the metadata in line with it
+    let mut metadata = metadata.clone();
+    metadata.kdf_algorithm = key.params.algorithm;
+
+    let metadata = encode_to_vec(&metadata, standard()).map_err(|e| FileError::EncodingFailed(e.to_string()))?;
+    let metadata_len = u16::try_from(metadata.len())
+        .map_err(|_| FileError::EncodingFailed("Vault metadata is too large".into()))?;
+
+    let mut header = Vec::with_capacity(MAGIC.len() + 6 + metadata.len() + HEADER_LEN as usize);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&metadata_len.to_le_bytes());
+    header.extend_from_slice(&metadata);
+    header.extend_from_slice(&HEADER_LEN.to_le_bytes());
+    header.extend_from_slice(&key.salt);
+    header.push(key.params.algorithm.id());
+    header.extend_from_slice(&key.params.memory_cost.to_le_bytes());
+    header.extend_from_slice(&key.params.time_cost.to_le_bytes());
+    header.extend_from_slice(&key.params.parallelism.to_le_bytes());
+    header.push(compression.id());
+    header.extend_from_slice(&0u64.to_le_bytes());  // Payload length, filled in once encrypted
+
+    Ok(header)
+}
+
+/// Decodes the metadata section, accepting sections written before the appearance fields
+/// or the hardware key credential
+fn decode_metadata(bytes: &[u8]) -> Result<VaultMetadata, FileError> {
+    decode_from_slice::<VaultMetadata, _>(bytes, standard())
+        .or_else(|_| decode_from_slice::<MetadataWithoutCredential, _>(bytes, standard())
+            .map(|(old, read)| (old.into(), read)))
+        .or_else(|_| decode_from_slice::<MetadataWithoutAppearance, _>(bytes, standard())
+            .map(|(old, read)| (old.into(), read)))
+        .map(|(metadata, _)| metadata)
+        .map_err(|_| FileError::CorruptHeader("Corrupted vault metadata"))
+}
+
+/// Opens `path` for reading, reporting a missing file with its path
+fn open_file(path: &Path) -> Result<File, FileError> {
+    File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => FileError::PathNotFound(path.to_path_buf()),
+        _ => FileError::Io(e),
+    })
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16, FileError> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, FileError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::NamedTempFile;
+
+    const TEST_BYTES: &[u8] = b"Super secret message";
+    const TEST_PASSWORD: &str = "correct-horse-battery-staple";
+
+    fn test_params(algorithm: KdfAlgorithm) -> ArgonParams {
+        ArgonParams { algorithm, memory_cost: 64, time_cost: 1, parallelism: 1 }
+    }
+
+    /// Offset of the header length field, which follows the variable length metadata
+    fn header_len_offset(contents: &[u8]) -> usize {
+        11 + u16::from_le_bytes([contents[9], contents[10]]) as usize
+    }
+
+    #[test]
+    fn test_write_encrypted_file_create_files() {
+        let bytes = TEST_BYTES.to_vec();
+        let password = TEST_PASSWORD.to_string();
+        let key = Crypto::derive_argon_key(password.as_bytes(), None, ArgonParams::default()).expect("Key derivation failed");
+
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        write_encrypted_file(&bytes, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        // Assert the file exists and has some size
+        let metadata = std::fs::metadata(&path).expect("File not found");
+        assert!(metadata.len() > 16, "File too small to contain salt and data");
+
+        // Check the header for magic and salt
+        let mut contents = Vec::new();
+        std::fs::File::open(&path).unwrap().read_to_end(&mut contents).unwrap();
+        assert_eq!(contents.len() as u64, metadata.len());
+
+        // Salt follows magic, version, metadata and header length
+        let salt_start = header_len_offset(&contents) + 2;
+        assert_eq!(&contents[..7], MAGIC);
+        assert_eq!(contents[salt_start..salt_start + 16], key.salt);
+    }
+
+    #[test]
+    fn test_read_encrypted_file_returns_data() {
+        let bytes = TEST_BYTES.to_vec();
+        let password = TEST_PASSWORD.to_string();
+
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(password.as_bytes(), None, ArgonParams::default()).expect("Key derivation failed");
+        write_encrypted_file(&bytes, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        // TODO: Test if returned key is the same
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), bytes);
+    }
+
+    #[test]
+    fn test_decrypt_with_wrong_password_fails() {
+        let bytes = TEST_BYTES.to_vec();
+        
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+
+        let correct_password = TEST_PASSWORD.to_string();
+        let correct_key = Crypto::derive_argon_key(correct_password.as_bytes(), None, ArgonParams::default()).expect("Key derivation failed");
+        write_encrypted_file(&bytes, &path, &correct_key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let wrong_password = "incorrect".to_string();
+        let wrong_key = Crypto::derive_argon_key(wrong_password.as_bytes(), None, ArgonParams::default()).expect("Key derivation failed");
+        let result = read_encrypted_file(&path, &wrong_key);
+        assert!(result.is_err(), "Decryption should fail with wrong password");
+    }
+
+    #[test]
+    fn test_invalid_file_format_fails() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        // Write invalid content (too short for salt)
+        fs::write(&path, b"short").expect("Failed to write");
+
+        let wrong_key = Crypto::derive_argon_key(b"incorrect", None, ArgonParams::default()).expect("Key derivation failed");
+        let result = read_encrypted_file(&path, &wrong_key);
+        assert!(result.is_err(), "Should fail on invalid input");
+    }
+
+    #[test]
+    fn test_short_files_are_reported_as_truncated() {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+
+        for len in [0, 5, 27] {
+            let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+            let path = temp_file.path().to_path_buf();
+            fs::write(&path, vec![3u8; len]).expect("Failed to write");
+
+            let error = read_encrypted_file(&path, &key).expect_err("Short file must not open");
+            assert!(matches!(error, FileError::Truncated), "{} byte file: {:?}", len, error);
+        }
+    }
+
+    #[test]
+    fn test_file_cut_after_header_is_reported_as_truncated() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let contents = fs::read(&path).expect("Failed to read");
+        let header_end = header_len_offset(&contents) + 2 + HEADER_LEN as usize;
+
+        fs::write(&path, &contents[..header_end - 3]).expect("Failed to write");
+        assert!(matches!(open_vault(&path, TEST_PASSWORD), Err(OpenVaultError::Header(FileError::Truncated))));
+
+        fs::write(&path, &contents[..header_end + 5]).expect("Failed to write");
+        assert!(matches!(read_encrypted_file(&path, &key), Err(FileError::Truncated)));
+    }
+
+    #[test]
+    fn test_create_and_unlock_with_each_algorithm() {
+        for algorithm in KdfAlgorithm::ALL {
+            let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+            let path = temp_file.path().to_path_buf();
+            let password = TEST_PASSWORD.to_string();
+
+            let key = Crypto::derive_argon_key(password.as_bytes(), None, test_params(algorithm)).expect("Key derivation failed");
+            write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+            // Unlock only knows the password, the header provides the rest
+            let (decrypted, unlock_key) = open_vault(&path, &password).expect("Open failed");
+            assert_eq!(unlock_key.params, test_params(algorithm));
+            assert_eq!(unlock_key.bytes, key.bytes);
+            assert_eq!(decrypted.as_ref(), TEST_BYTES);
+        }
+    }
+
+    #[test]
+    fn test_open_vault_with_wrong_password_is_a_payload_error() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        assert!(matches!(open_vault(&path, "wrong password"), Err(OpenVaultError::Payload(_))));
+    }
+
+    #[test]
+    fn test_unlock_with_wrong_algorithm_fails() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2i)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let wrong_key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), Some(key.salt), test_params(KdfAlgorithm::Argon2d)).expect("Key derivation failed");
+        assert!(read_encrypted_file(&path, &wrong_key).is_err(), "Key from a different variant must not decrypt");
+    }
+
+    #[test]
+    fn test_legacy_salt_first_file_still_opens() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, ArgonParams::default()).expect("Key derivation failed");
+
+        // Legacy layout: salt followed directly by nonce + cipherbytes
+        let mut contents = ZeroByte::default();
+        contents.extend_from_slice(&key.salt);
+        Crypto::aes_gcm_encrypt(TEST_BYTES, &key.to_zero_byte(), &mut contents).expect("Encryption failed");
+        fs::write(&path, contents.as_ref()).expect("Failed to write");
+
+        let (decrypted, unlock_key) = open_vault(&path, TEST_PASSWORD).expect("Open failed");
+        assert_eq!(unlock_key.params, ArgonParams::default());
+        assert_eq!(decrypted.as_ref(), TEST_BYTES);
+    }
+
+    #[test]
+    fn test_read_header_returns_version_and_params() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2d)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let header = read_header(temp_file.path()).expect("Header parse failed");
+
+        assert_eq!(header.version, FORMAT_VERSION);
+        assert!(!header.is_legacy());
+        assert_eq!(header.salt, key.salt);
+        assert_eq!(header.params, key.params);
+    }
+
+    #[test]
+    fn test_read_header_reports_legacy_files() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        fs::write(temp_file.path(), [9u8; 64]).expect("Failed to write");
+
+        let header = read_header(temp_file.path()).expect("Header parse failed");
+
+        assert!(header.is_legacy());
+        assert_eq!(header.salt, [9u8; 16]);
+        assert_eq!(header.params, ArgonParams::default());
+    }
+
+    #[test]
+    fn test_missing_file_is_reported_with_its_path() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("missing.vault");
+
+        match read_header(&path) {
+            Err(FileError::PathNotFound(missing)) => assert_eq!(missing, path),
+            other => panic!("Expected PathNotFound, got {:?}", other),
+        }
+        assert!(matches!(open_vault(&path, TEST_PASSWORD), Err(OpenVaultError::Header(FileError::PathNotFound(_)))));
+    }
+
+    #[test]
+    fn test_unreadable_file_is_an_io_error() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+        // Opening a directory works on Linux, reading it doesn't
+        let result = read_header(dir.path());
+        assert!(matches!(result, Err(FileError::Io(_))), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_wrong_password_is_a_decryption_failure() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let result = open_vault(temp_file.path(), "incorrect");
+        assert!(matches!(result, Err(OpenVaultError::Payload(FileError::DecryptionFailed(_)))), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_unknown_kdf_algorithm_is_a_corrupt_header() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let mut contents = fs::read(temp_file.path()).expect("Failed to read");
+        let algorithm = header_len_offset(&contents) + 2 + 16;
+        contents[algorithm] = 99;
+        fs::write(temp_file.path(), contents).expect("Failed to write");
+
+        let result = read_header(temp_file.path());
+        assert!(matches!(result, Err(FileError::CorruptHeader(_))), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_invalid_kdf_params_fail_key_derivation() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let mut contents = fs::read(temp_file.path()).expect("Failed to read");
+        let parallelism = header_len_offset(&contents) + 2 + 16 + 1 + 8;
+        contents[parallelism..parallelism + 4].copy_from_slice(&0u32.to_le_bytes());
+        fs::write(temp_file.path(), contents).expect("Failed to write");
+
+        let result = open_vault(temp_file.path(), TEST_PASSWORD);
+        assert!(matches!(result, Err(OpenVaultError::Header(FileError::KeyDerivation(_)))), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_out_of_range_kdf_params_are_rejected_before_deriving() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        // A 4 TiB memory cost, deriving with it would abort the process
+        let mut contents = fs::read(temp_file.path()).expect("Failed to read");
+        let memory_cost = header_len_offset(&contents) + 2 + 16 + 1;
+        contents[memory_cost..memory_cost + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(temp_file.path(), contents).expect("Failed to write");
+        assert_eq!(read_header(temp_file.path()).expect("Header parse failed").params.memory_cost, u32::MAX);
+
+        let result = open_vault(temp_file.path(), TEST_PASSWORD);
+        assert!(matches!(result, Err(OpenVaultError::Header(FileError::KeyDerivation(_)))), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_migrating_a_current_file_is_rejected() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let result = migrate_legacy_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default());
+        assert!(matches!(result, Err(FileError::BadMagic)), "Got {:?}", result);
+        assert!(!legacy_backup_path(temp_file.path()).exists());
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected_with_message() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let mut contents = MAGIC.to_vec();
+        contents.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        contents.extend_from_slice(&HEADER_LEN.to_le_bytes());
+        contents.extend_from_slice(&[0u8; 64]);
+        fs::write(temp_file.path(), contents).expect("Failed to write");
+
+        let result = read_header(temp_file.path());
+        assert!(matches!(result, Err(FileError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1));
+    }
+
+    #[test]
+    fn test_unknown_trailing_header_fields_are_skipped() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        // Splice four extra header bytes in after the known fields
+        let mut contents = fs::read(&path).expect("Failed to read");
+        let offset = header_len_offset(&contents);
+        let header_end = offset + 2 + HEADER_LEN as usize;
+        contents[offset..offset + 2].copy_from_slice(&(HEADER_LEN + 4).to_le_bytes());
+        contents.splice(header_end..header_end, [0xEE; 4]);
+        fs::write(&path, contents).expect("Failed to write");
+
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), TEST_BYTES);
+    }
+
+    /// Writes a salt-first legacy file and returns its contents
+    fn write_legacy_file(path: &Path, key: &ArgonKey) -> Vec<u8> {
+        let mut contents = ZeroByte::default();
+        contents.extend_from_slice(&key.salt);
+        Crypto::aes_gcm_encrypt(TEST_BYTES, &key.to_zero_byte(), &mut contents).expect("Encryption failed");
+        fs::write(path, contents.as_ref()).expect("Failed to write");
+
+        contents.as_ref().to_vec()
+    }
+
+    #[test]
+    fn test_migrate_legacy_file_keeps_backup() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        let original = write_legacy_file(&path, &key);
+        assert_eq!(detect_format(&path), VaultFormat::Legacy);
+
+        let backup = migrate_legacy_file(TEST_BYTES, &path, &key, &VaultMetadata::default()).expect("Migration failed");
+
+        assert_eq!(backup, dir.path().join("test.vault.legacy.bak"));
+        assert_eq!(fs::read(&backup).expect("Backup missing"), original);
+        assert_eq!(detect_format(&path), VaultFormat::Current);
+
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), TEST_BYTES);
+    }
+
+    #[test]
+    fn test_migrate_refuses_when_write_fails() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        let original = write_legacy_file(&path, &key);
+
+        // A directory in place of the temp file makes the write fail
+        fs::create_dir(temp_path(&path)).expect("Failed to create dir");
+
+        assert!(migrate_legacy_file(TEST_BYTES, &path, &key, &VaultMetadata::default()).is_err());
+        assert_eq!(fs::read(&path).expect("Original missing"), original);
+        assert_eq!(detect_format(&path), VaultFormat::Legacy);
+    }
+
+    #[test]
+    fn test_migrated_file_is_not_migrated_again() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_legacy_file(&path, &key);
+        migrate_legacy_file(TEST_BYTES, &path, &key, &VaultMetadata::default()).expect("Migration failed");
+        fs::remove_file(legacy_backup_path(&path)).expect("Backup missing");
+
+        assert_eq!(detect_format(&path), VaultFormat::Current);
+        assert!(migrate_legacy_file(TEST_BYTES, &path, &key, &VaultMetadata::default()).is_err());
+        assert!(!legacy_backup_path(&path).exists(), "No backup should be made for current files");
+    }
+
+    #[test]
+    fn test_failed_write_leaves_original_untouched() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        let original = fs::read(&path).expect("Failed to read");
+
+        // A directory in place of the temp file makes the temp write fail
+        fs::create_dir(temp_path(&path)).expect("Failed to create dir");
+
+        assert!(write_encrypted_file(b"replacement", &path, &key, &VaultMetadata::default(), 0).is_err());
+        assert_eq!(fs::read(&path).expect("Failed to read"), original);
+    }
+
+    #[test]
+    fn test_failed_write_reports_path_and_io_error() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = fs::canonicalize(dir.path()).expect("Failed to canonicalize").join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        fs::create_dir(temp_path(&path)).expect("Failed to create dir");
+
+        match write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0) {
+            Err(FileError::WriteFailed { path: failed, source }) => {
+                assert_eq!(failed, path);
+                assert_ne!(source.kind(), io::ErrorKind::UnexpectedEof);
+            },
+            other => panic!("Expected WriteFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_verify_written_rejects_damaged_file() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+        verify_written(temp_file.path(), &key).expect("Intact file failed verification");
+
+        let mut contents = fs::read(temp_file.path()).expect("Failed to read");
+        let last = contents.len() - 5;
+        contents[last] ^= 0x01;
+        fs::write(temp_file.path(), contents).expect("Failed to write");
+
+        let result = verify_written(temp_file.path(), &key);
+        assert!(matches!(&result, Err(FileError::VerificationFailed { path, .. }) if path == temp_file.path()), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_failed_verification_keeps_previous_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        fs::write(&path, b"previous").expect("Failed to write");
+
+        let result = write_via_temp(&path, b"replacement", 0, create_private, |temp| Err(FileError::VerificationFailed {
+            path: temp.to_path_buf(),
+            source: Box::new(FileError::Truncated),
+        }));
+
+        assert!(matches!(result, Err(FileError::VerificationFailed { .. })));
+        assert_eq!(fs::read(&path).expect("Failed to read"), b"previous");
+        assert!(!temp_path(&path).exists(), "The unverified temp file must be removed");
+    }
+
+    #[test]
+    fn test_large_vault_is_written_and_synced_whole() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        // Random bytes don't compress, so the whole MiB goes through the buffered writer
+        let vault = ZeroByte::with_random_bytes(1024 * 1024);
+
+        write_encrypted_file(vault.as_ref(), temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let header = read_header(temp_file.path()).expect("Header failed");
+        assert_eq!(header.compression, PayloadCompression::None);
+        assert!(fs::metadata(temp_file.path()).expect("No file").len() > vault.len() as u64);
+        assert_eq!(read_encrypted_file(temp_file.path(), &key).expect("Read failed"), vault);
+    }
+
+    #[test]
+    fn test_write_synced_checks_the_written_size() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let contents = vec![0x5A; 3 * 8192 + 17];  // Spans several buffer flushes
+
+        write_synced(File::create(temp_file.path()).expect("Failed to create"), &contents).expect("Write failed");
+        assert_eq!(fs::read(temp_file.path()).expect("Failed to read"), contents);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_buffered_write_error_surfaces_on_flush() {
+        // Writes to /dev/full fail with ENOSPC. A small write only fills the buffer, so the
+        // error turns up when it is flushed.
+        let Ok(full) = File::options().write(true).open("/dev/full") else { return; };
+
+        let result = write_synced(full, b"vault bytes");
+        assert!(matches!(&result, Err(FileError::FlushFailed(e)) if e.kind() == io::ErrorKind::StorageFull), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_successful_write_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Overwrite failed");
+
+        let names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, vec![OsString::from("test.vault")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_write_is_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("export.json");
+        fs::write(&path, b"older export").expect("Failed to write");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).expect("chmod failed");
+
+        write_private(&path, b"plaintext").expect("Write failed");
+
+        assert_eq!(fs::read(&path).expect("Failed to read"), b"plaintext");
+        assert_eq!(fs::metadata(&path).expect("Missing file").permissions().mode() & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_new_vault_is_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        assert_eq!(fs::metadata(&path).expect("Missing file").permissions().mode() & 0o777, 0o600);
+
+        // A vault created before files were private is tightened on its next save
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).expect("chmod failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        assert_eq!(fs::metadata(&path).expect("Missing file").permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_write_into_missing_folder_names_the_folder() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let missing = dir.path().join("missing");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+
+        match write_encrypted_file(TEST_BYTES, &missing.join("test.vault"), &key, &VaultMetadata::default(), 0) {
+            Err(FileError::ParentNotFound(folder)) => assert_eq!(folder, missing),
+            other => panic!("Expected a missing folder, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_write_to_invalid_target_is_refused() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        let not_a_folder = dir.path().join("notes.txt");
+        fs::write(&not_a_folder, b"x").expect("Failed to write");
+
+        for target in [dir.path().to_path_buf(), dir.path().join(".."), not_a_folder.join("test.vault")] {
+            let result = write_encrypted_file(TEST_BYTES, &target, &key, &VaultMetadata::default(), 0);
+            assert!(matches!(result, Err(FileError::InvalidTarget(_))), "{}: {:?}", target.display(), result);
+        }
+    }
+
+    #[test]
+    fn test_write_resolves_relative_components() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        fs::create_dir(dir.path().join("sub")).expect("Failed to create dir");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
 
-    let mut salt = [0u8; 16];
-    file.read_exact(&mut salt).map_err(|e| e.to_string())?;
+        write_encrypted_file(TEST_BYTES, &dir.path().join("sub/../test.vault"), &key, &VaultMetadata::default(), 0).expect("Write failed");
+        assert!(dir.path().join("test.vault").is_file());
+    }
 
-    Ok(Crypto::derive_argon_key(password.as_bytes(), Some(salt))?)
-}
+    #[test]
+    fn test_remove_stale_temp_files() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        for name in ["test.vault", "test.vault.tmp.1", "test.vault.tmp.99999", "test.vault.tmp.notes", "other.vault.tmp.1"] {
+            fs::write(dir.path().join(name), b"x").expect("Failed to write");
+        }
+        fs::write(temp_path(&path), b"x").expect("Failed to write");
 
-pub(crate) fn write_encrypted_file(bytes: &Vec<u8>, path: &PathBuf, key: &ArgonKey) -> Result<(), String> {    
-    let encrypted_bytes = Crypto::aes_gcm_encrypt(bytes, key.bytes.to_vec())
-        .map_err(|e| e.to_string())?;
+        remove_stale_temp_files(&path);
 
-    let mut combined = Vec::with_capacity(key.salt.len() + encrypted_bytes.len());
-    combined.extend_from_slice(&key.salt);          // [0..16] = salt
-    combined.extend_from_slice(&encrypted_bytes);   // [16..] = none + cipherbytes
+        assert!(path.exists());
+        assert!(temp_path(&path).exists(), "Temp files of this process must be kept");
+        assert!(!dir.path().join("test.vault.tmp.1").exists());
+        assert!(!dir.path().join("test.vault.tmp.99999").exists());
+        assert!(dir.path().join("test.vault.tmp.notes").exists());
+        assert!(dir.path().join("other.vault.tmp.1").exists());
+    }
 
-    let mut file = File::create(path).map_err(|e| e.to_string())?;
-    file.write_all(&combined).map_err(|e| e.to_string())?;
+    #[test]
+    fn test_vault_metadata_round_trips() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("work.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2i)).expect("Key derivation failed");
 
-    Ok(())
-}
+        let mut metadata = VaultMetadata::new("Work", 1_700_000_000);
+        metadata.modified_at = 1_700_000_500;
+        metadata.item_count_hint = 12;
+        write_encrypted_file(TEST_BYTES, &path, &key, &metadata, 0).expect("Write failed");
 
-pub(crate) fn read_encrypted_file(path: &PathBuf, key: &ArgonKey) -> Result<Vec<u8>, String> {
-    let file = File::open(path).map_err(|e| e.to_string())?;
-    let mut reader = BufReader::new(file);
+        let read = read_vault_metadata(&path).expect("Metadata read failed").expect("No metadata");
 
-    let mut salt = [0u8; 16];
-    reader.read_exact(&mut salt).map_err(|e| e.to_string())?;
+        assert_eq!(read.name(), "Work");
+        assert_eq!(read.created_at, 1_700_000_000);
+        assert_eq!(read.modified_at, 1_700_000_500);
+        assert_eq!(read.item_count_hint, 12);
+        assert_eq!(read.kdf_algorithm, KdfAlgorithm::Argon2i, "Algorithm must follow the key");
+        assert_eq!(read.cipher, CipherAlgorithm::Aes256Gcm);
 
-    let mut encrypted_data = Vec::new();
-    reader.read_to_end(&mut encrypted_data).map_err(|e| e.to_string())?;
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), TEST_BYTES);
+    }
 
-    let decrypted_bytes = Crypto::aes_gcm_decrypt(&encrypted_data, key.bytes.to_vec())
-        .map_err(|e| e.to_string())?;
+    #[test]
+    fn test_version_one_file_without_metadata_still_opens() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        downgrade_to_version_three(&path);
 
-    Ok(decrypted_bytes)
-}
+        // Strip the metadata section and mark the file as version 1
+        let contents = fs::read(&path).expect("Failed to read");
+        let mut version_one = MAGIC.to_vec();
+        version_one.extend_from_slice(&NO_METADATA_VERSION.to_le_bytes());
+        version_one.extend_from_slice(&contents[header_len_offset(&contents)..]);
+        fs::write(&path, version_one).expect("Failed to write");
 
+        let header = read_header(&path).expect("Header parse failed");
+        assert_eq!(header.version, NO_METADATA_VERSION);
+        assert_eq!(header.metadata, None);
+        assert!(read_vault_metadata(&path).expect("Header parse failed").is_none());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::NamedTempFile;
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), TEST_BYTES);
+    }
 
-    const TEST_BYTES: &[u8] = b"Super secret message";
-    const TEST_PASSWORD: &str = "correct-horse-battery-staple";
+    /// Rewrites a current file as version 3, which has no payload length or attachments
+    fn downgrade_to_version_three(path: &Path) {
+        let mut contents = fs::read(path).expect("Failed to read");
+        let offset = header_len_offset(&contents);
+        let header_start = offset + 2;
+
+        contents[7..9].copy_from_slice(&NO_ATTACHMENTS_VERSION.to_le_bytes());
+        contents[offset..offset + 2].copy_from_slice(&NO_ATTACHMENTS_HEADER_LEN.to_le_bytes());
+        contents.drain(header_start + NO_ATTACHMENTS_HEADER_LEN as usize..header_start + HEADER_LEN as usize);
+
+        // Drop the (empty) attachment index that follows the payload
+        contents.truncate(contents.len() - 4);
+        fs::write(path, contents).expect("Failed to write");
+    }
+
+    /// Rewrites a current file as version 2, which has no compression byte
+    fn downgrade_to_version_two(path: &Path) {
+        downgrade_to_version_three(path);
+
+        let mut contents = fs::read(path).expect("Failed to read");
+        let offset = header_len_offset(&contents);
+        assert_eq!(contents[offset + 1 + NO_ATTACHMENTS_HEADER_LEN as usize], PayloadCompression::None.id());
+
+        contents[7..9].copy_from_slice(&NO_COMPRESSION_VERSION.to_le_bytes());
+        contents[offset..offset + 2].copy_from_slice(&UNCOMPRESSED_HEADER_LEN.to_le_bytes());
+        contents.remove(offset + 1 + NO_ATTACHMENTS_HEADER_LEN as usize);
+        fs::write(path, contents).expect("Failed to write");
+    }
 
     #[test]
-    fn test_write_encrypted_file_create_files() {
-        let bytes = TEST_BYTES.to_vec();
-        let password = TEST_PASSWORD.to_string();
-        let key = Crypto::derive_argon_key(password.as_bytes(), None).expect("Key derivation failed");
+    fn test_version_two_file_without_compression_still_opens() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        downgrade_to_version_two(&path);
+
+        let header = read_header(&path).expect("Header parse failed");
+        assert_eq!(header.version, NO_COMPRESSION_VERSION);
+        assert_eq!(header.compression, PayloadCompression::None);
+
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), TEST_BYTES);
+    }
 
+    #[test]
+    fn test_large_vault_is_compressed() {
         let temp_file = NamedTempFile::new().expect("Failed to create temp file");
         let path = temp_file.path().to_path_buf();
-        write_encrypted_file(&bytes, &path, &key).expect("Write failed");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        let notes = TEST_BYTES.repeat(1000);
 
-        // Assert the file exists and has some size
-        let metadata = std::fs::metadata(&path).expect("File not found");
-        assert!(metadata.len() > 16, "File too small to contain salt and data");
+        write_encrypted_file(&notes, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
 
-        // Check the first 16 bytes for salt
-        let mut contents = Vec::new();
-        std::fs::File::open(&path).unwrap().read_to_end(&mut contents).unwrap();
-        assert_eq!(contents.len() as u64, metadata.len());
+        assert_eq!(read_header(&path).expect("Header parse failed").compression, PayloadCompression::Zstd);
+        assert!(fs::metadata(&path).expect("File not found").len() < notes.len() as u64 / 4);
 
-        // Salt is first 16 bytes
-        assert_eq!(contents[..16].len(), 16);
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), notes.as_slice());
     }
 
     #[test]
-    fn test_read_encrypted_file_returns_data() {
-        let bytes = TEST_BYTES.to_vec();
-        let password = TEST_PASSWORD.to_string();
+    fn test_small_or_incompressible_vault_is_not_compressed() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        assert_eq!(read_header(&path).expect("Header parse failed").compression, PayloadCompression::None);
+
+        let random = ZeroByte::with_random_bytes(COMPRESSION_THRESHOLD * 2);
+        write_encrypted_file(random.as_ref(), &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        assert_eq!(read_header(&path).expect("Header parse failed").compression, PayloadCompression::None);
+
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted, random);
+    }
+
+    #[test]
+    fn test_compression_can_be_turned_off() {
+        let notes = TEST_BYTES.repeat(1000);
+
+        assert!(compress_payload(&notes, true).expect("Compression failed").is_some());
+        assert!(compress_payload(&notes, false).expect("Compression failed").is_none());
+    }
+
+    #[test]
+    fn test_metadata_for_path_uses_file_stem() {
+        let metadata = VaultMetadata::for_path(Path::new("/home/user/Personal.vault"));
+        assert_eq!(metadata.name(), "Personal");
+        assert_eq!(metadata.created_at, 0);
+    }
+
+    #[test]
+    fn test_saves_rotate_backups_up_to_depth() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+
+        let mut saved = Vec::new();
+        for i in 0..5u8 {
+            write_encrypted_file(&[i; 8], &path, &key, &VaultMetadata::default(), 3).expect("Write failed");
+            saved.push(fs::read(&path).expect("Failed to read"));
+        }
+
+        // bak1 is the save before the current one, and so on
+        assert_eq!(fs::read(backup_path(&path, 1)).unwrap(), saved[3]);
+        assert_eq!(fs::read(backup_path(&path, 2)).unwrap(), saved[2]);
+        assert_eq!(fs::read(backup_path(&path, 3)).unwrap(), saved[1]);
+        assert!(!backup_path(&path, 4).exists());
+        assert_eq!(latest_backup(&path), Some(backup_path(&path, 1)));
+
+        let decrypted = read_encrypted_file(&backup_path(&path, 1), &key).expect("Backup read failed");
+        assert_eq!(decrypted.as_ref(), [3; 8]);
+    }
+
+    /// Saves twice so there is a `.bak1`, then cuts the vault file short
+    fn truncated_vault_with_backup(path: &Path) -> ArgonKey {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(b"first save", path, &key, &VaultMetadata::default(), 3).expect("Write failed");
+        write_encrypted_file(b"second save", path, &key, &VaultMetadata::default(), 3).expect("Write failed");
+
+        let contents = fs::read(path).expect("Failed to read");
+        fs::write(path, &contents[..contents.len() - 20]).expect("Failed to write");
+        key
+    }
+
+    #[test]
+    fn test_truncated_vault_falls_back_to_latest_backup() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        truncated_vault_with_backup(&path);
+
+        assert!(matches!(open_vault(&path, TEST_PASSWORD), Err(OpenVaultError::Payload(_))));
+
+        let (backup, bytes, _key) = open_latest_backup(&path, TEST_PASSWORD).expect("Backup did not open");
+        assert_eq!(backup, backup_path(&path, 1));
+        assert_eq!(bytes.as_ref(), b"first save");
+    }
+
+    #[test]
+    fn test_backup_is_not_offered_for_wrong_password() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        truncated_vault_with_backup(&path);
+
+        assert!(open_latest_backup(&path, "incorrect").is_none());
+        assert!(open_latest_backup(&dir.path().join("missing.vault"), TEST_PASSWORD).is_none());
+    }
+
+    #[test]
+    fn test_save_after_restore_keeps_corrupt_file_and_backups() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = truncated_vault_with_backup(&path);
+        let corrupt = fs::read(&path).expect("Failed to read");
+        let backup = fs::read(backup_path(&path, 1)).expect("Failed to read");
+
+        set_aside_corrupt(&path).expect("Set aside failed");
+        write_encrypted_file(b"restored", &path, &key, &VaultMetadata::default(), 3).expect("Write failed");
+
+        assert_eq!(read_encrypted_file(&path, &key).expect("Read failed").as_ref(), b"restored");
+        assert_eq!(fs::read(corrupt_path(&path)).unwrap(), corrupt);
+        assert_eq!(fs::read(backup_path(&path, 1)).unwrap(), backup, "The damaged file must not be rotated into the backups");
+        assert!(!backup_path(&path, 2).exists());
+    }
+
+    #[test]
+    fn test_backup_preserves_modification_time() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        fs::write(&path, b"previous save").expect("Failed to write");
+
+        let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+
+        rotate_backups(&path, 3).expect("Rotation failed");
+
+        let backup = fs::metadata(backup_path(&path, 1)).expect("Backup missing");
+        assert_eq!(backup.modified().unwrap(), modified);
+    }
+
+    #[test]
+    fn test_failed_backup_does_not_block_save() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 3).expect("Write failed");
+
+        // A directory in place of the oldest backup makes rotation fail
+        fs::create_dir(backup_path(&path, 3)).expect("Failed to create dir");
+
+        write_encrypted_file(b"new contents", &path, &key, &VaultMetadata::default(), 3).expect("Save must succeed");
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), b"new contents");
+    }
+
+    #[test]
+    fn test_zero_depth_keeps_no_backups() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        assert_eq!(latest_backup(&path), None);
+    }
+
+    #[test]
+    fn test_metadata_without_appearance_fields_still_decodes() {
+        #[derive(Serialize)]
+        struct OldMetadata {
+            vault_name: ZeroByte,
+            created_at: u64,
+            modified_at: u64,
+            item_count_hint: u32,
+            kdf_algorithm: KdfAlgorithm,
+            cipher: CipherAlgorithm,
+        }
+
+        let current = VaultMetadata::new("Work", 1_700_000_000);
+        let old = OldMetadata {
+            vault_name: current.vault_name.clone(),
+            created_at: current.created_at,
+            modified_at: current.modified_at,
+            item_count_hint: 3,
+            kdf_algorithm: KdfAlgorithm::Argon2d,
+            cipher: CipherAlgorithm::Aes256Gcm,
+        };
+
+        let decoded = decode_metadata(&encode_to_vec(&old, standard()).unwrap()).expect("Decode failed");
+
+        assert_eq!(decoded.name(), "Work");
+        assert_eq!(decoded.item_count_hint, 3);
+        assert_eq!(decoded.kdf_algorithm, KdfAlgorithm::Argon2d);
+        assert_eq!(decoded.accent_color, appearance::DEFAULT_ACCENT);
+        assert_eq!(decoded.icon, None);
+    }
+
+    #[test]
+    fn test_metadata_without_credential_still_decodes() {
+        let mut current = VaultMetadata::new("Work", 1_700_000_000);
+        current.icon = Some("key".into());
+
+        // Bincode writes `None` as a single zero byte at the end
+        let mut encoded = encode_to_vec(&current, standard()).unwrap();
+        assert_eq!(encoded.pop(), Some(0));
+
+        let decoded = decode_metadata(&encoded).expect("Decode failed");
+        assert_eq!(decoded, current);
+        assert!(!decoded.requires_hardware_key());
+    }
+
+    #[test]
+    fn test_credential_id_round_trips_through_header() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        let mut metadata = VaultMetadata::new("Work", 1_700_000_000);
+        metadata.fido2_credential_id = Some(vec![0xC0, 0xFF, 0xEE]);
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &metadata, 0).expect("Write failed");
+
+        let read = read_vault_metadata(temp_file.path()).expect("Read failed").expect("Metadata missing");
+        assert_eq!(read.fido2_credential_id, Some(vec![0xC0, 0xFF, 0xEE]));
+        assert!(read.requires_hardware_key());
+    }
+
+    #[test]
+    fn test_appearance_round_trips_through_header() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+
+        let mut metadata = VaultMetadata::new("Home", 0);
+        metadata.accent_color = "#e0568f".into();
+        metadata.icon = Some("home".into());
+        write_encrypted_file(TEST_BYTES, temp_file.path(), &key, &metadata, 0).expect("Write failed");
+
+        let read = read_vault_metadata(temp_file.path()).expect("Metadata read failed").expect("No metadata");
+        assert_eq!(read.accent_color, "#e0568f");
+        assert_eq!(read.icon.as_deref(), Some("home"));
+    }
+
+    #[test]
+    fn test_write_if_unchanged_detects_external_modification() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        // Unlock
+        let unlocked = FileFingerprint::of(&path).expect("Fingerprint failed");
+
+        // Another machine syncs a new version in
+        write_encrypted_file(b"synced from elsewhere", &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let result = write_if_unchanged(b"local edit", &path, &key, &VaultMetadata::default(), 0, Some(&unlocked));
+        assert!(matches!(result, Ok(GuardedWrite::Conflict)));
+
+        let contents = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(contents.as_ref(), b"synced from elsewhere", "Conflicting save must not overwrite");
+
+        let result = write_if_unchanged(b"local edit", &path, &key, &VaultMetadata::default(), 0, None);
+        assert!(matches!(result, Ok(GuardedWrite::Written(_))), "Overwrite without a fingerprint");
+        assert_eq!(read_encrypted_file(&path, &key).unwrap().as_ref(), b"local edit");
+    }
+
+    #[test]
+    fn test_write_if_unchanged_updates_fingerprint_for_next_save() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let mut fingerprint = FileFingerprint::of(&path).expect("Fingerprint failed");
+        for contents in [b"first save".as_slice(), b"second save"] {
+            match write_if_unchanged(contents, &path, &key, &VaultMetadata::default(), 0, Some(&fingerprint)) {
+                Ok(GuardedWrite::Written(next)) => fingerprint = next,
+                other => panic!("Own saves must not conflict, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_touched_file_with_same_contents_is_not_changed() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        fs::write(&path, b"ciphertext").expect("Failed to write");
+        let fingerprint = FileFingerprint::of(&path).expect("Fingerprint failed");
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert!(!fingerprint.changed(&path).unwrap(), "Only the contents matter once mtime differs");
 
+        fs::remove_file(&path).unwrap();
+        assert!(fingerprint.changed(&path).unwrap(), "A deleted file counts as changed");
+    }
+
+    fn attachment(data: &[u8]) -> ZeroByte {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(data);
+        buffer
+    }
+
+    /// Writes TEST_BYTES with attachments 1 and 2 and returns the key
+    fn write_with_attachments(path: &Path) -> ArgonKey {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        let changes = AttachmentChanges {
+            put: vec![(1, attachment(b"first attachment")), (2, attachment(b"second attachment"))],
+            ..Default::default()
+        };
+        write_vault_file(TEST_BYTES, path, &key, &VaultMetadata::default(), 0, &changes).expect("Write failed");
+        key
+    }
+
+    #[test]
+    fn test_version_three_file_without_attachments_still_opens() {
         let temp_file = NamedTempFile::new().expect("Failed to create temp file");
         let path = temp_file.path().to_path_buf();
-        let key = Crypto::derive_argon_key(password.as_bytes(), None).expect("Key derivation failed");
-        write_encrypted_file(&bytes, &path, &key).expect("Write failed");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        downgrade_to_version_three(&path);
+
+        let header = read_header(&path).expect("Header parse failed");
+        assert_eq!(header.version, NO_ATTACHMENTS_VERSION);
+        assert_eq!(header.payload_len, None);
 
-        // TODO: Test if returned key is the same
         let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
-        assert_eq!(decrypted, bytes);
+        assert_eq!(decrypted.as_ref(), TEST_BYTES);
+        assert!(read_attachment(&path, &key, 1).is_err());
     }
 
     #[test]
-    fn test_decrypt_with_wrong_password_fails() {
-        let bytes = TEST_BYTES.to_vec();
-        
+    fn test_attachments_are_read_one_at_a_time() {
         let temp_file = NamedTempFile::new().expect("Failed to create temp file");
         let path = temp_file.path().to_path_buf();
+        let key = write_with_attachments(&path);
 
-        let correct_password = TEST_PASSWORD.to_string();
-        let correct_key = Crypto::derive_argon_key(correct_password.as_bytes(), None).expect("Key derivation failed");
-        write_encrypted_file(&bytes, &path, &correct_key).expect("Write failed");
+        let decrypted = read_encrypted_file(&path, &key).expect("Read failed");
+        assert_eq!(decrypted.as_ref(), TEST_BYTES, "Attachments must not leak into the vault payload");
 
-        let wrong_password = "incorrect".to_string();
-        let wrong_key = Crypto::derive_argon_key(wrong_password.as_bytes(), None).expect("Key derivation failed");
-        let result = read_encrypted_file(&path, &wrong_key);
-        assert!(result.is_err(), "Decryption should fail with wrong password");
+        assert_eq!(read_attachment(&path, &key, 2).expect("Read failed").as_ref(), b"second attachment");
+        assert_eq!(read_attachment(&path, &key, 1).expect("Read failed").as_ref(), b"first attachment");
+        assert!(read_attachment(&path, &key, 3).is_err());
     }
 
     #[test]
-    fn test_invalid_file_format_fails() {
+    fn test_unchanged_attachments_are_copied_without_reencrypting() {
         let temp_file = NamedTempFile::new().expect("Failed to create temp file");
         let path = temp_file.path().to_path_buf();
-        // Write invalid content (too short for salt)
-        fs::write(&path, b"short").expect("Failed to write");
+        let key = write_with_attachments(&path);
+        let before = read_attachment_chunks(&path, &key).expect("Read failed");
 
-        let wrong_key = Crypto::derive_argon_key(b"incorrect", None).expect("Key derivation failed");
-        let result = read_encrypted_file(&path, &wrong_key);
-        assert!(result.is_err(), "Should fail on invalid input");
+        // A plain vault save keeps the attachments
+        write_encrypted_file(b"edited vault", &path, &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        assert_eq!(read_attachment_chunks(&path, &key).expect("Read failed"), before, "Chunks must be copied as they are");
+        assert_eq!(read_encrypted_file(&path, &key).expect("Read failed").as_ref(), b"edited vault");
+    }
+
+    #[test]
+    fn test_attachment_changes_replace_and_remove() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = write_with_attachments(&path);
+
+        let changes = AttachmentChanges {
+            put: vec![(2, attachment(b"new second")), (3, attachment(b"third"))],
+            remove: vec![1],
+            ..Default::default()
+        };
+        write_vault_file(TEST_BYTES, &path, &key, &VaultMetadata::default(), 0, &changes).expect("Write failed");
+
+        assert!(read_attachment(&path, &key, 1).is_err());
+        assert_eq!(read_attachment(&path, &key, 2).expect("Read failed").as_ref(), b"new second");
+        assert_eq!(read_attachment(&path, &key, 3).expect("Read failed").as_ref(), b"third");
+    }
+
+    #[test]
+    fn test_damaged_attachment_leaves_the_rest_readable() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = write_with_attachments(&path);
+
+        // Attachment 2 is the last chunk, flip a byte inside it
+        let mut contents = fs::read(&path).expect("Failed to read");
+        let last = contents.len() - 5;
+        contents[last] ^= 0xFF;
+        fs::write(&path, contents).expect("Failed to write");
+
+        assert_eq!(read_encrypted_file(&path, &key).expect("Read failed").as_ref(), TEST_BYTES);
+        assert_eq!(read_attachment(&path, &key, 1).expect("Read failed").as_ref(), b"first attachment");
+        assert!(matches!(read_attachment(&path, &key, 2), Err(FileError::TamperingDetected)));
+    }
+
+    #[test]
+    fn test_unknown_attachment_is_not_found() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = write_with_attachments(temp_file.path());
+
+        let result = read_attachment(temp_file.path(), &key, 99);
+        assert!(matches!(result, Err(FileError::AttachmentNotFound(99))), "Got {:?}", result);
+    }
+
+    #[test]
+    fn test_truncated_attachment_leaves_the_vault_readable() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = write_with_attachments(&path);
+
+        let contents = fs::read(&path).expect("Failed to read");
+        fs::write(&path, &contents[..contents.len() - 10]).expect("Failed to write");
+
+        assert_eq!(read_encrypted_file(&path, &key).expect("Read failed").as_ref(), TEST_BYTES);
+        assert!(matches!(read_attachment(&path, &key, 1), Err(FileError::CorruptAttachments)));
+        assert!(read_attachment_chunks(&path, &key).expect("Read failed").is_empty());
+    }
+
+    #[test]
+    fn test_oversized_attachment_index_is_corrupt() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        let key = write_with_attachments(&path);
+        let chunks_len: usize = read_attachment_chunks(&path, &key).expect("Read failed").iter().map(|(_, chunk)| chunk.len()).sum();
+
+        // The count sits in front of two 8 byte index entries and the chunks
+        let mut contents = fs::read(&path).expect("Failed to read");
+        let count_at = contents.len() - chunks_len - 16 - 4;
+        assert_eq!(contents[count_at..count_at + 4], 2u32.to_le_bytes());
+        contents[count_at..count_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &contents).expect("Failed to write");
+        assert!(matches!(read_attachment(&path, &key, 1), Err(FileError::CorruptAttachments)));
+
+        // A chunk length past the end of the file
+        contents[count_at..count_at + 4].copy_from_slice(&2u32.to_le_bytes());
+        contents[count_at + 8..count_at + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &contents).expect("Failed to write");
+        assert!(matches!(read_attachment(&path, &key, 2), Err(FileError::CorruptAttachments)));
+
+        assert!(read_attachment_chunks(&path, &key).expect("Read failed").is_empty());
+        assert_eq!(read_encrypted_file(&path, &key).expect("Read failed").as_ref(), TEST_BYTES);
+    }
+
+    #[test]
+    fn test_attachments_under_another_key_are_not_carried_over() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = temp_file.path().to_path_buf();
+        write_with_attachments(&path);
+
+        // E.g. a new vault created over an existing file
+        let other_key = Crypto::derive_argon_key(b"other password", None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(TEST_BYTES, &path, &other_key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        assert!(read_attachment(&path, &other_key, 1).is_err());
+        assert_eq!(read_encrypted_file(&path, &other_key).expect("Read failed").as_ref(), TEST_BYTES);
+    }
+
+    #[test]
+    fn test_export_opens_with_new_passphrase_only() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let source = dir.path().join("work.vault");
+        let export = dir.path().join("export.vault");
+        let key = write_with_attachments(&source);
+
+        export_vault_file(TEST_BYTES, &source, &key, &export, b"executor passphrase", &VaultMetadata::default()).expect("Export failed");
+        assert_ne!(read_header(&export).expect("Header parse failed").salt, key.salt, "The export needs a fresh salt");
+
+        let (bytes, opened_key) = open_vault(&export, "executor passphrase").expect("Export did not open");
+        assert_eq!(bytes.as_ref(), TEST_BYTES);
+        assert!(matches!(open_vault(&export, TEST_PASSWORD), Err(OpenVaultError::Payload(_))));
+
+        assert_eq!(attachment_ids(&export).expect("Read failed"), vec![1, 2]);
+        assert_eq!(read_attachment(&export, &opened_key, 2).expect("Read failed").as_ref(), b"second attachment");
+        assert_eq!(read_encrypted_file(&source, &key).expect("Read failed").as_ref(), TEST_BYTES, "Source must be left as is");
+    }
+
+    /// Writes an encoded empty vault with one attachment and returns the key
+    fn write_verifiable_vault(path: &Path) -> ArgonKey {
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        let encoded = encode_to_vec(Vault::new(), standard()).expect("Encode failed");
+        let changes = AttachmentChanges { put: vec![(1, attachment(b"receipt"))], ..Default::default() };
+        write_vault_file(&encoded, path, &key, &VaultMetadata::default(), 0, &changes).expect("Write failed");
+        key
+    }
+
+    #[test]
+    fn test_verify_healthy_vault_passes_every_check() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_verifiable_vault(temp_file.path());
+
+        let report = verify_vault(temp_file.path(), TEST_PASSWORD);
+
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.checks.len(), 5);
+    }
+
+    #[test]
+    fn test_verify_with_wrong_password_skips_later_checks() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_verifiable_vault(temp_file.path());
+
+        let report = verify_vault(temp_file.path(), "wrong password");
+
+        assert!(!report.passed());
+        assert_eq!(report.status(VerifyReport::HEADER), Some(&CheckStatus::Passed));
+        assert_eq!(report.status(VerifyReport::KDF_PARAMS), Some(&CheckStatus::Passed));
+        assert!(matches!(report.status(VerifyReport::DECRYPTION), Some(CheckStatus::Failed(_))));
+        assert_eq!(report.status(VerifyReport::CONTENTS), Some(&CheckStatus::Skipped));
+        assert_eq!(report.status(VerifyReport::ATTACHMENTS), Some(&CheckStatus::Skipped));
+    }
+
+    #[test]
+    fn test_verify_reports_unparseable_header() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        fs::write(temp_file.path(), b"NPV").expect("Failed to write");
+
+        let report = verify_vault(temp_file.path(), TEST_PASSWORD);
+
+        assert_eq!(report.status(VerifyReport::HEADER), Some(&CheckStatus::Failed(FileError::Truncated.to_string())));
+        assert_eq!(report.status(VerifyReport::KDF_PARAMS), Some(&CheckStatus::Skipped));
+    }
+
+    #[test]
+    fn test_verify_rejects_extreme_kdf_params_before_deriving() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_verifiable_vault(temp_file.path());
+
+        // Patch the time cost in the header to something that would take hours
+        let mut contents = fs::read(temp_file.path()).expect("Failed to read");
+        let time_cost = header_len_offset(&contents) + 2 + 21;
+        contents[time_cost..time_cost + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(temp_file.path(), contents).expect("Failed to write");
+
+        let report = verify_vault(temp_file.path(), TEST_PASSWORD);
+
+        assert!(matches!(report.status(VerifyReport::KDF_PARAMS), Some(CheckStatus::Failed(_))));
+        assert_eq!(report.status(VerifyReport::DECRYPTION), Some(&CheckStatus::Skipped));
+    }
+
+    #[test]
+    fn test_verify_reports_data_that_is_not_a_vault() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let key = Crypto::derive_argon_key(TEST_PASSWORD.as_bytes(), None, test_params(KdfAlgorithm::Argon2id)).expect("Key derivation failed");
+        write_encrypted_file(&[0xFF; 8], temp_file.path(), &key, &VaultMetadata::default(), 0).expect("Write failed");
+
+        let report = verify_vault(temp_file.path(), TEST_PASSWORD);
+
+        assert_eq!(report.status(VerifyReport::DECRYPTION), Some(&CheckStatus::Passed));
+        assert!(matches!(report.status(VerifyReport::CONTENTS), Some(CheckStatus::Failed(_))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_verify_reports_damaged_attachments() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_verifiable_vault(temp_file.path());
+
+        let mut contents = fs::read(temp_file.path()).expect("Failed to read");
+        let last = contents.len() - 1;
+        contents[last] ^= 0xFF;
+        fs::write(temp_file.path(), contents).expect("Failed to write");
+
+        let report = verify_vault(temp_file.path(), TEST_PASSWORD);
+
+        assert_eq!(report.status(VerifyReport::CONTENTS), Some(&CheckStatus::Passed));
+        assert_eq!(report.status(VerifyReport::ATTACHMENTS), Some(&CheckStatus::Failed("Attachment 1 is damaged".into())));
+    }
+}
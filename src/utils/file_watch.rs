@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+
+/// How often the watched file is checked
+pub(crate) const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watches a single file for changes by polling its modification time and size on a
+/// background thread. A stat call every couple of seconds is cheap, and works the same on
+/// every platform and on network shares where change notifications are unreliable.
+///
+/// Changes are reported as they are seen; the callback should check whether a change
+/// matters (e.g. it was this process's own save). Stops when dropped.
+pub(crate) struct FileWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FileWatcher {
+    /// Starts watching `path`, calling `on_change` from the watcher thread after each change
+    pub(crate) fn start(path: &Path, interval: Duration, on_change: impl Fn() + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let path = path.to_path_buf();
+
+        let thread = thread::spawn(move || {
+            let mut last_seen = stat(&path);
+
+            loop {
+                thread::park_timeout(interval);
+                if thread_stop.load(Ordering::Acquire) {
+                    break;
+                }
+
+                let current = stat(&path);
+                if current != last_seen {
+                    last_seen = current;
+                    on_change();
+                }
+            }
+        });
+
+        Self { stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Modification time and size, or None if the file can't be read (e.g. while it is replaced)
+fn stat(path: &Path) -> Option<(Option<SystemTime>, u64)> {
+    fs::metadata(path).ok().map(|metadata| (metadata.modified().ok(), metadata.len()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    const TEST_INTERVAL: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn test_change_is_reported() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        fs::write(&path, b"first").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let _watcher = FileWatcher::start(&path, TEST_INTERVAL, move || sender.send(()).unwrap());
+
+        assert!(receiver.recv_timeout(TEST_INTERVAL * 5).is_err(), "Nothing changed yet");
+
+        fs::write(&path, b"second, longer").unwrap();
+        receiver.recv_timeout(Duration::from_secs(5)).expect("Change was not reported");
+    }
+
+    #[test]
+    fn test_drop_stops_watching() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.vault");
+        fs::write(&path, b"first").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let watcher = FileWatcher::start(&path, Duration::from_secs(60), move || sender.send(()).unwrap());
+
+        // Must return promptly even though the interval is long
+        drop(watcher);
+        fs::write(&path, b"second, longer").unwrap();
+        assert!(receiver.recv_timeout(TEST_INTERVAL * 5).is_err());
+    }
+}
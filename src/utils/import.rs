@@ -0,0 +1,1203 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use roxmltree::Node;
+use serde::Deserialize;
+
+use crate::errors::import_errors::ImportError;
+use crate::models::vault::{CustomField, Item, Vault};
+use crate::utils;
+use crate::utils::zero_byte::ZeroByte;
+use crate::utils::zip;
+
+
+/// Items read from another password manager's export, along with what couldn't be imported
+#[derive(Debug, Default)]
+pub(crate) struct ImportReport {
+    pub items: Vec<Item>,
+    pub skipped: usize,        // Export entries that aren't logins
+    pub warnings: Vec<String>, // One line per skipped entry or dropped detail, for showing to the user
+}
+
+/// What happens to one incoming item, chosen per row in the import preview
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]  // Overwrite is only chosen in the import preview
+pub(crate) enum ImportChoice {
+    Import,
+    Skip,
+    Overwrite,  // Replace the contents of the existing item it duplicates
+}
+
+/// One incoming item in the import preview
+#[derive(Debug)]
+pub(crate) struct PlannedImport {
+    pub item: Item,
+    pub duplicate_of: Option<i32>,  // Existing item with the same URL host and username
+    pub choice: ImportChoice,
+}
+
+/// Incoming items matched against the vault, see `plan_import`
+#[derive(Debug, Default)]
+pub(crate) struct ImportPlan {
+    pub rows: Vec<PlannedImport>,
+}
+
+/// Items an applied `ImportPlan` changed
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ImportOutcome {
+    pub added: usize,
+    pub overwritten: usize,
+}
+
+/// Columns of the generic CSV layout, see `import_csv`
+const CSV_COLUMNS: [&str; 5] = ["name", "url", "username", "password", "notes"];
+
+/// LastPass CSV columns, in the order LastPass exports them
+const LASTPASS_COLUMNS: [&str; 8] = ["url", "username", "password", "totp", "extra", "name", "grouping", "fav"];
+/// URL LastPass gives secure notes, which aren't logins
+const LASTPASS_SECURE_NOTE_URL: &str = "http://sn";
+
+/// Largest KeePass XML export read, the whole document is held in memory while it's parsed
+const MAX_KEEPASS_XML_LEN: u64 = 64 * 1024 * 1024;
+/// Largest 1Password export read, and the most its `export.data` may inflate to
+const MAX_1PUX_LEN: u64 = 64 * 1024 * 1024;
+const ONEPUX_DATA_FILE: &str = "export.data";
+
+/// 1Password categories imported as logins
+const ONEPASSWORD_LOGIN: &str = "001";
+const ONEPASSWORD_PASSWORD: &str = "005";
+
+/// Seconds from 0001-01-01, where KeePass database times count from, to the Unix epoch
+const KEEPASS_EPOCH_OFFSET: i64 = 62_135_596_800;
+
+/// Bitwarden item types, see `type` in the export format
+const BITWARDEN_LOGIN: u8 = 1;
+const BITWARDEN_SECURE_NOTE: u8 = 2;
+const BITWARDEN_CARD: u8 = 3;
+const BITWARDEN_IDENTITY: u8 = 4;
+
+/// Bitwarden custom field types
+const BITWARDEN_FIELD_HIDDEN: u8 = 1;
+const BITWARDEN_FIELD_LINKED: u8 = 3;
+
+#[derive(Deserialize)]
+struct BitwardenExport {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenItem {
+    #[serde(rename = "type")]
+    item_type: u8,
+    name: String,
+    notes: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    login: Option<BitwardenLogin>,
+    fields: Option<Vec<BitwardenField>>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    uris: Option<Vec<BitwardenUri>>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenField {
+    name: Option<String>,
+    value: Option<String>,
+    #[serde(rename = "type")]
+    field_type: u8,
+}
+
+#[derive(Deserialize)]
+struct OnePuxExport {
+    #[serde(default)]
+    accounts: Vec<OnePuxAccount>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxAccount {
+    #[serde(default)]
+    vaults: Vec<OnePuxVault>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxVault {
+    #[serde(default)]
+    attrs: OnePuxVaultAttrs,
+    #[serde(default)]
+    items: Vec<OnePuxItem>,
+}
+
+#[derive(Default, Deserialize)]
+struct OnePuxVaultAttrs {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OnePuxItem {
+    #[serde(default)]
+    category_uuid: String,
+    #[serde(default)]
+    fav_index: u64,
+    #[serde(default)]
+    created_at: u64,
+    #[serde(default)]
+    updated_at: u64,
+    #[serde(default)]
+    overview: OnePuxOverview,
+    #[serde(default)]
+    details: OnePuxDetails,
+}
+
+#[derive(Default, Deserialize)]
+struct OnePuxOverview {
+    #[serde(default)]
+    title: String,
+    url: Option<String>,
+    #[serde(default)]
+    urls: Vec<OnePuxUrl>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxUrl {
+    #[serde(default)]
+    url: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OnePuxDetails {
+    #[serde(default)]
+    login_fields: Vec<OnePuxLoginField>,
+    notes_plain: Option<String>,
+    /// The password of a Password item, which has no login fields
+    password: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxLoginField {
+    #[serde(default)]
+    value: String,
+    designation: Option<String>,
+}
+
+/// Reads an unencrypted Bitwarden JSON export. Only login items are imported; secure notes,
+/// cards and identities are skipped with a warning. Imported items are numbered from 0, so
+/// they need new IDs when added to an existing vault.
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+pub(crate) fn import_bitwarden_json(path: &Path) -> Result<ImportReport, ImportError> {
+    let export: BitwardenExport = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    if export.encrypted {
+        return Err(ImportError::Encrypted);
+    }
+
+    let mut report = ImportReport::default();
+    let mut next_id = 0;
+
+    for entry in export.items {
+        if entry.item_type != BITWARDEN_LOGIN {
+            let kind = match entry.item_type {
+                BITWARDEN_SECURE_NOTE => "secure note",
+                BITWARDEN_CARD => "card",
+                BITWARDEN_IDENTITY => "identity",
+                _ => "unknown item",
+            };
+            warn(&mut report, format!("Skipped {} '{}'", kind, entry.name));
+            report.skipped += 1;
+            continue;
+        }
+
+        let item = bitwarden_login_item(entry, next_id, &mut report);
+        report.items.push(item);
+        next_id += 1;
+    }
+
+    Ok(report)
+}
+
+fn bitwarden_login_item(entry: BitwardenItem, id: i32, report: &mut ImportReport) -> Item {
+    let login = entry.login.unwrap_or(BitwardenLogin { username: None, password: None, uris: None });
+    let mut uris = login.uris.unwrap_or_default().into_iter().filter_map(|uri| uri.uri);
+
+    let url = uris.next().unwrap_or_default();
+    let extra_uris = uris.count();
+    if extra_uris > 0 {
+        warn(report, format!("'{}': only the first of {} URLs was imported", entry.name, extra_uris + 1));
+    }
+
+    let mut custom_fields = Vec::new();
+    for field in entry.fields.unwrap_or_default() {
+        let name = field.name.unwrap_or_default();
+
+        // Linked fields only point at another field of the item and have no value of their own
+        if field.field_type == BITWARDEN_FIELD_LINKED {
+            warn(report, format!("'{}': skipped linked field '{}'", entry.name, name));
+            continue;
+        }
+
+        custom_fields.push(CustomField {
+            name,
+            value: field.value.unwrap_or_default(),
+            hidden: field.field_type == BITWARDEN_FIELD_HIDDEN,
+        });
+    }
+
+    Item {
+        id,
+        name: entry.name,
+        username: login.username.unwrap_or_default(),
+        password: login.password.unwrap_or_default(),
+        url,
+        notes: entry.notes.unwrap_or_default(),
+        favorite: entry.favorite,
+        custom_fields,
+        ..Default::default()
+    }
+}
+
+/// Reads a 1Password `.1pux` export, a zip archive holding the items as JSON in
+/// `export.data`. Login and Password items of every account and vault are imported; their
+/// tags are added to the notes, as items have no tags of their own. Other categories are
+/// skipped with a warning. When the export holds more than one vault, they are merged and
+/// each item's name is prefixed with its vault's, e.g. `Personal / GitHub`. The JSON is
+/// inflated into a `ZeroByte` and parsed from there, and archives or `export.data` over
+/// 64 MiB are refused. Imported items are numbered from 0.
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+pub(crate) fn import_1pux(path: &Path) -> Result<ImportReport, ImportError> {
+    if fs::metadata(path)?.len() > MAX_1PUX_LEN {
+        return Err(ImportError::TooLarge { max_len: MAX_1PUX_LEN });
+    }
+
+    let archive = fs::read(path)?;
+    let data = zip::extract_file(&archive, ONEPUX_DATA_FILE, MAX_1PUX_LEN as usize)?;
+    let export: OnePuxExport = serde_json::from_slice(data.as_ref())?;
+    drop(data);
+
+    let mut report = ImportReport::default();
+    let vaults: Vec<OnePuxVault> = export.accounts.into_iter().flat_map(|account| account.vaults).collect();
+    let prefix_vault_names = vaults.len() > 1;
+    let items = vaults.into_iter()
+        .flat_map(|vault| vault.items.into_iter().map(move |item| (vault.attrs.name.clone(), item)));
+
+    for (vault_name, mut entry) in items {
+        if entry.category_uuid != ONEPASSWORD_LOGIN && entry.category_uuid != ONEPASSWORD_PASSWORD {
+            warn(&mut report, format!("Skipped '{}', not a login", entry.overview.title));
+            report.skipped += 1;
+            continue;
+        }
+
+        if prefix_vault_names && !vault_name.is_empty() {
+            entry.overview.title = format!("{} / {}", vault_name, entry.overview.title);
+        }
+        let item = onepassword_login_item(entry, report.items.len() as i32, &mut report);
+        report.items.push(item);
+    }
+
+    Ok(report)
+}
+
+fn onepassword_login_item(entry: OnePuxItem, id: i32, report: &mut ImportReport) -> Item {
+    let OnePuxItem { fav_index, created_at, updated_at, overview, details, .. } = entry;
+
+    let mut urls = overview.url.into_iter().chain(overview.urls.into_iter().map(|url| url.url)).filter(|url| !url.is_empty());
+    let url = urls.next().unwrap_or_default();
+    let extra_urls = urls.filter(|extra| *extra != url).count();
+    if extra_urls > 0 {
+        warn(report, format!("'{}': only the first of {} URLs was imported", overview.title, extra_urls + 1));
+    }
+
+    let designated = |designation: &str| details.login_fields.iter()
+        .find(|field| field.designation.as_deref() == Some(designation))
+        .map(|field| field.value.clone());
+    let username = designated("username").unwrap_or_default();
+    let password = designated("password").or(details.password).unwrap_or_default();
+
+    let mut notes = details.notes_plain.unwrap_or_default();
+    if !overview.tags.is_empty() {
+        if !notes.is_empty() {
+            notes.push_str("\n\n");
+        }
+        notes.push_str(&format!("Tags: {}", overview.tags.join(", ")));
+    }
+
+    Item {
+        id,
+        name: overview.title,
+        username,
+        password,
+        url,
+        notes,
+        favorite: fav_index > 0,
+        modified_at: updated_at,
+        created_at,
+        ..Default::default()
+    }
+}
+
+/// Reads a `name,url,username,password,notes` CSV, the layout browsers and most password
+/// managers export. Columns are matched by their header, so their order doesn't matter and
+/// unknown ones are ignored. Imported items are numbered from 0.
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+pub(crate) fn import_csv(path: &Path) -> Result<ImportReport, ImportError> {
+    let contents = fs::read_to_string(path)?;
+    let mut records = parse_csv(&contents)?.into_iter();
+
+    let header = records.next().ok_or_else(|| ImportError::Parse("the file is empty".into()))?;
+    let columns = CSV_COLUMNS.map(|name| column_index(&header, name));
+    if columns.iter().all(Option::is_none) {
+        return Err(ImportError::Parse("the header names none of the name, url, username, password and notes columns".into()));
+    }
+
+    let mut report = ImportReport::default();
+    for (line, mut record) in records.enumerate() {
+        let [name, url, username, password, notes] = columns.map(|index| take_field(&mut record, index));
+
+        if [&name, &url, &username, &password, &notes].iter().all(|field| field.is_empty()) {
+            warn(&mut report, format!("Skipped empty record {}", line + 1));
+            report.skipped += 1;
+            continue;
+        }
+
+        report.items.push(Item {
+            id: report.items.len() as i32,
+            name,
+            username,
+            password,
+            url,
+            notes,
+            ..Default::default()
+        });
+    }
+
+    Ok(report)
+}
+
+/// Reads a LastPass CSV export. Secure notes are skipped with a warning. Items have no TOTP
+/// secret or folder of their own, so a TOTP secret is kept as a hidden `TOTP` custom field and
+/// the LastPass grouping as a `Folder` one. Imported items are numbered from 0.
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+pub(crate) fn import_lastpass_csv(path: &Path) -> Result<ImportReport, ImportError> {
+    let contents = fs::read_to_string(path)?;
+    let records = parse_csv(&contents)?;
+
+    // Some LastPass exports leave out the header row, the first row then starts with a URL
+    let has_header = records.first()
+        .is_some_and(|first| !first.first().is_some_and(|url| url.starts_with("http")));
+    let columns = match records.first() {
+        Some(header) if has_header => LASTPASS_COLUMNS.map(|name| column_index(header, name)),
+        _ => std::array::from_fn(Some),
+    };
+    if columns[0].is_none() || columns[2].is_none() {
+        return Err(ImportError::Parse("not a LastPass export, the url or password column is missing".into()));
+    }
+
+    let mut report = ImportReport::default();
+    for mut record in records.into_iter().skip(usize::from(has_header)) {
+        let [url, username, password, totp, extra, name, grouping, fav] = columns.map(|index| take_field(&mut record, index));
+
+        if url == LASTPASS_SECURE_NOTE_URL {
+            warn(&mut report, format!("Skipped secure note '{}'", name));
+            report.skipped += 1;
+            continue;
+        }
+
+        let mut custom_fields = Vec::new();
+        if !totp.is_empty() {
+            custom_fields.push(CustomField { name: "TOTP".into(), value: totp, hidden: true });
+        }
+        if !grouping.is_empty() {
+            custom_fields.push(CustomField { name: "Folder".into(), value: grouping, hidden: false });
+        }
+
+        report.items.push(Item {
+            id: report.items.len() as i32,
+            name,
+            username,
+            password,
+            url,
+            notes: extra,
+            favorite: fav == "1",
+            custom_fields,
+            ..Default::default()
+        });
+    }
+
+    Ok(report)
+}
+
+/// Reads a KeePass 2.x XML export. Entries of every group are imported; the group path below
+/// the database's own root group is kept as a `Folder` custom field, as items have no folders of
+/// their own. Strings other than the standard fields become custom fields, hidden if KeePass
+/// protects them. Entries in the recycle bin are skipped. Past versions of an entry are only
+/// imported, as separate items, if `include_history` is set. Files over 64 MiB are refused.
+/// Imported items are numbered from 0.
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+pub(crate) fn import_keepass_xml(path: &Path, include_history: bool) -> Result<ImportReport, ImportError> {
+    if fs::metadata(path)?.len() > MAX_KEEPASS_XML_LEN {
+        return Err(ImportError::TooLarge { max_len: MAX_KEEPASS_XML_LEN });
+    }
+
+    let contents = fs::read_to_string(path)?;
+    let document = roxmltree::Document::parse(&contents).map_err(|e| ImportError::Parse(e.to_string()))?;
+
+    import_keepass_document(&document, include_history, &|value| unprotect_keepass_value(value.text().unwrap_or_default()))
+}
+
+/// Imports the entries of a KeePass XML document, an export or the inner XML of a database.
+/// `unprotect` reads the text of a protected `Value` element, None if it can't be read.
+pub(super) fn import_keepass_document(document: &roxmltree::Document, include_history: bool, unprotect: &dyn Fn(Node) -> Option<String>) -> Result<ImportReport, ImportError> {
+    let file = document.root_element();
+    let root = Some(file)
+        .filter(|file| file.has_tag_name("KeePassFile"))
+        .and_then(|file| child(file, "Root"))
+        .ok_or_else(|| ImportError::Parse("not a KeePass XML export".into()))?;
+
+    let recycle_bin = child(file, "Meta")
+        .filter(|meta| child_text(*meta, "RecycleBinEnabled") != Some("False"))
+        .and_then(|meta| child_text(meta, "RecycleBinUUID"));
+
+    let context = KeePassContext { recycle_bin, include_history, unprotect };
+    let mut report = ImportReport::default();
+    for group in children(root, "Group") {
+        keepass_group(group, None, &context, &mut report);
+    }
+
+    Ok(report)
+}
+
+/// What every group and entry of a KeePass document is imported with
+struct KeePassContext<'a> {
+    recycle_bin: Option<&'a str>,
+    include_history: bool,
+    unprotect: &'a dyn Fn(Node) -> Option<String>,
+}
+
+/// Imports the entries of `group` and its subgroups. `folder` is the path of `group`, None for
+/// the root group.
+fn keepass_group(group: Node, folder: Option<&str>, context: &KeePassContext, report: &mut ImportReport) {
+    if context.recycle_bin.is_some() && child_text(group, "UUID") == context.recycle_bin {
+        let deleted = group.descendants()
+            .filter(|node| node.has_tag_name("Entry") && !node.ancestors().any(|ancestor| ancestor.has_tag_name("History")))
+            .count();
+        if deleted > 0 {
+            warn(report, format!("Skipped {} entries in the recycle bin", deleted));
+            report.skipped += deleted;
+        }
+        return;
+    }
+
+    for entry in children(group, "Entry") {
+        let item = keepass_entry(entry, folder, context, report);
+        let name = item.name.clone();
+        report.items.push(item);
+
+        if context.include_history {
+            let history = child(entry, "History").into_iter().flat_map(|history| children(history, "Entry"));
+            for (version, old) in history.enumerate() {
+                let mut item = keepass_entry(old, folder, context, report);
+                item.name = format!("{} (version {})", name, version + 1);
+                report.items.push(item);
+            }
+        }
+    }
+
+    for subgroup in children(group, "Group") {
+        let name = child_text(subgroup, "Name").unwrap_or_default();
+        let path = match folder {
+            Some(folder) => format!("{}/{}", folder, name),
+            None => name.to_string(),
+        };
+        keepass_group(subgroup, Some(&path), context, report);
+    }
+}
+
+fn keepass_entry(entry: Node, folder: Option<&str>, context: &KeePassContext, report: &mut ImportReport) -> Item {
+    let mut item = Item {
+        id: report.items.len() as i32,
+        ..Default::default()
+    };
+    let mut unreadable = Vec::new();
+
+    for string in children(entry, "String") {
+        let key = child_text(string, "Key").unwrap_or_default();
+        let Some(value) = child(string, "Value") else { continue };
+
+        let protected = value.attribute("Protected") == Some("True");
+        let text = if protected { (context.unprotect)(value) } else { Some(value.text().unwrap_or_default().to_string()) };
+        let Some(text) = text else {
+            unreadable.push(key);
+            continue;
+        };
+
+        match key {
+            "Title" => item.name = text,
+            "UserName" => item.username = text,
+            "Password" => item.password = text,
+            "URL" => item.url = text,
+            "Notes" => item.notes = text,
+            _ => item.custom_fields.push(CustomField {
+                name: key.to_string(),
+                value: text,
+                hidden: protected || value.attribute("ProtectInMemory") == Some("True"),
+            }),
+        }
+    }
+
+    for key in unreadable {
+        warn(report, format!("'{}': could not read protected field '{}'", item.name, key));
+    }
+
+    if let Some(folder) = folder {
+        item.custom_fields.push(CustomField { name: "Folder".into(), value: folder.to_string(), hidden: false });
+    }
+
+    if let Some(times) = child(entry, "Times") {
+        item.created_at = child_text(times, "CreationTime").and_then(parse_keepass_time).unwrap_or(0);
+        item.modified_at = child_text(times, "LastModificationTime").and_then(parse_keepass_time).unwrap_or(0);
+    }
+
+    item
+}
+
+/// Protected values are base64. In a database they are also encrypted with the database's
+/// inner stream, which a plain XML export doesn't have, so only values that decode to text are
+/// readable.
+fn unprotect_keepass_value(text: &str) -> Option<String> {
+    let bytes = BASE64.decode(text.trim()).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Unix timestamp of a KeePass time, `YYYY-MM-DDTHH:MM:SSZ` in exports and base64 seconds
+/// since 0001-01-01 in databases
+fn parse_keepass_time(text: &str) -> Option<u64> {
+    if !text.ends_with('Z') {
+        let seconds: [u8; 8] = BASE64.decode(text.trim()).ok()?.try_into().ok()?;
+        return u64::try_from(i64::from_le_bytes(seconds).checked_sub(KEEPASS_EPOCH_OFFSET)?).ok();
+    }
+
+    let (date, time) = text.strip_suffix('Z')?.split_once('T')?;
+    let number = |part: Option<&str>| part.and_then(|part| part.parse::<i64>().ok());
+
+    let mut date = date.splitn(3, '-');
+    let (year, month, day) = (number(date.next())?, number(date.next())?, number(date.next())?);
+    let mut time = time.splitn(3, ':');
+    let (hour, minute, second) = (number(time.next())?, number(time.next())?, number(time.next())?);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let seconds = utils::days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    u64::try_from(seconds).ok()
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn children<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).map(|child| child.text().unwrap_or_default())
+}
+
+/// Position of the column titled `name` in a CSV header, ignoring case and surrounding spaces
+fn column_index(header: &[String], name: &str) -> Option<usize> {
+    header.iter().position(|title| title.trim().eq_ignore_ascii_case(name))
+}
+
+/// Moves the field at `index` out of `record`, empty if the column or the field is missing
+fn take_field(record: &mut [String], index: Option<usize>) -> String {
+    index.and_then(|index| record.get_mut(index)).map(std::mem::take).unwrap_or_default()
+}
+
+/// Splits CSV text into records of fields, as in RFC 4180. Quoted fields may hold commas,
+/// doubled quotes and line breaks; records end in CRLF or a bare LF. A byte order mark and a
+/// final line break are ignored.
+pub(super) fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, ImportError> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;       // Inside a quoted field
+    let mut after_quote = false;  // A quoted field just closed, only a separator may follow
+
+    let mut chars = text.strip_prefix('\u{feff}').unwrap_or(text).chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => {
+                    quoted = false;
+                    after_quote = true;
+                },
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            ',' => {
+                record.push(std::mem::take(&mut field));
+                after_quote = false;
+            },
+            '\r' if chars.peek() == Some(&'\n') => { },
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                after_quote = false;
+            },
+            _ if after_quote => {
+                return Err(ImportError::CsvParse(format!("unexpected '{}' after a quoted field on record {}", c, records.len() + 1)));
+            },
+            '"' if field.is_empty() => quoted = true,
+            _ => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(ImportError::CsvParse("a quoted field is never closed".into()));
+    }
+    if !field.is_empty() || !record.is_empty() || after_quote {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Matches incoming items against the active items of a vault. An item is a probable duplicate
+/// of an existing one with the same URL host and username; it starts out skipped, every other
+/// item imported.
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+pub(crate) fn plan_import(existing: &[Item], incoming: Vec<Item>) -> ImportPlan {
+    let existing: Vec<(i32, ZeroByte, ZeroByte)> = existing.iter()
+        .filter(|item| item.deleted_at.is_none())
+        .filter_map(|item| Some((item.id, url_host(&item.url)?, secret_copy(&item.username))))
+        .collect();
+
+    let rows = incoming.into_iter().map(|item| {
+        let duplicate_of = url_host(&item.url).and_then(|host| {
+            let username = secret_copy(&item.username);
+            existing.iter()
+                .find(|(_, existing_host, existing_username)| {
+                    existing_host.constant_time_eq(&host) && existing_username.constant_time_eq(&username)
+                })
+                .map(|(id, _, _)| *id)
+        });
+        let choice = if duplicate_of.is_some() { ImportChoice::Skip } else { ImportChoice::Import };
+
+        PlannedImport { item, duplicate_of, choice }
+    }).collect();
+
+    ImportPlan { rows }
+}
+
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+impl ImportPlan {
+    /// Applies every row's choice to `vault`. The changes are made to a copy of the items that
+    /// replaces the vault's only once every row went through, so an error leaves the vault as
+    /// it was.
+    pub(crate) fn apply(self, vault: &mut Vault, now: u64) -> Result<ImportOutcome, ImportError> {
+        let mut items = vault.items.clone();
+        let nonce = vault.nonce;
+
+        match Self::apply_rows(self.rows, vault, &mut items, now) {
+            Ok(outcome) => {
+                if outcome != ImportOutcome::default() {
+                    vault.items = items;
+                    vault.mark_changed();
+                }
+                Ok(outcome)
+            },
+            Err(e) => {
+                // IDs handed out for the discarded copy can be handed out again
+                vault.nonce = nonce;
+                Err(e)
+            },
+        }
+    }
+
+    /// Applies `rows` to `items`, a copy of the vault's items, taking new IDs from `vault`
+    fn apply_rows(rows: Vec<PlannedImport>, vault: &mut Vault, items: &mut Vec<Item>, now: u64) -> Result<ImportOutcome, ImportError> {
+        let mut outcome = ImportOutcome::default();
+
+        for row in rows {
+            match row.choice {
+                ImportChoice::Skip => { },
+                ImportChoice::Import => {
+                    let id = vault.next_id().map_err(|e| ImportError::InvalidPlan(e.to_string()))?;
+                    items.push(Item { id, modified_at: now, created_at: now, password_changed_at: now, ..row.item });
+                    outcome.added += 1;
+                },
+                ImportChoice::Overwrite => {
+                    let target = row.duplicate_of
+                        .and_then(|id| items.iter_mut().find(|item| item.id == id && item.deleted_at.is_none()))
+                        .ok_or_else(|| ImportError::InvalidPlan(format!("'{}' has no existing item to overwrite", row.item.name)))?;
+                    overwrite_item(target, row.item, now);
+                    outcome.overwritten += 1;
+                },
+            }
+        }
+
+        Ok(outcome)
+    }
+}
+
+/// Replaces the contents of `target` with those of `incoming`, keeping its id and history
+fn overwrite_item(target: &mut Item, incoming: Item, now: u64) {
+    if target.password != incoming.password {
+        target.password_changed_at = now;
+    }
+    target.name = incoming.name;
+    target.username = incoming.username;
+    target.password = incoming.password;
+    target.url = incoming.url;
+    target.notes = incoming.notes;
+    target.custom_fields = incoming.custom_fields;
+    target.favorite |= incoming.favorite;
+    target.modified_at = now;
+}
+
+/// Lowercase host of `url` without a `www.` prefix, or `None` if it has none. The scheme is
+/// optional since exports often store bare domains.
+fn url_host(url: &str) -> Option<ZeroByte> {
+    let rest = url.trim();
+    let rest = rest.split_once("://").map_or(rest, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+
+    // Drop the port, but not the colons of a bracketed IPv6 address
+    let host = match host.rfind(':') {
+        Some(colon) if !host[colon..].contains(']') => &host[..colon],
+        _ => host,
+    };
+    let host = host.trim_end_matches('.');
+    let host = match host.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("www.") => &host[4..],
+        _ => host,
+    };
+
+    (!host.is_empty()).then(|| secret_copy(host).to_ascii_lowercase_copy())
+}
+
+fn secret_copy(text: &str) -> ZeroByte {
+    let mut copy = ZeroByte::default();
+    copy.extend_from_slice(text.as_bytes());
+    copy
+}
+
+fn warn(report: &mut ImportReport, warning: String) {
+    log::warn!("{}", warning);
+    report.warnings.push(warning);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    #[test]
+    fn test_import_bitwarden_export_fixture() {
+        let report = import_bitwarden_json(&fixture("bitwarden_export.json")).expect("Import failed");
+
+        assert_eq!(report.items.len(), 2);
+        assert_eq!(report.skipped, 2, "The secure note and the card are not logins");
+
+        let github = &report.items[0];
+        assert_eq!(github.id, 0);
+        assert_eq!(github.name, "GitHub");
+        assert_eq!(github.username, "octocat");
+        assert_eq!(github.password, "Tr0ub4dor&3");
+        assert_eq!(github.url, "https://github.com/login");
+        assert_eq!(github.notes, "Work account");
+        assert!(github.favorite);
+        assert_eq!(github.custom_fields, vec![
+            CustomField { name: "Recovery code".into(), value: "abcd-efgh-ijkl".into(), hidden: true },
+            CustomField { name: "Team".into(), value: "platform".into(), hidden: false },
+        ]);
+
+        // Null username, uris and notes become empty strings
+        let router = &report.items[1];
+        assert_eq!(router.id, 1);
+        assert_eq!((router.username.as_str(), router.url.as_str(), router.notes.as_str()), ("", "", ""));
+        assert_eq!(router.password, "admin");
+
+        assert_eq!(report.warnings, vec![
+            "'GitHub': only the first of 2 URLs was imported",
+            "'GitHub': skipped linked field 'Username link'",
+            "Skipped secure note 'Wifi password'",
+            "Skipped card 'Visa'",
+        ]);
+    }
+
+    #[test]
+    fn test_encrypted_export_is_rejected() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("encrypted.json");
+        fs::write(&path, r#"{ "encrypted": true, "passwordProtected": true, "data": "2.abc|def" }"#).unwrap();
+
+        assert!(matches!(import_bitwarden_json(&path), Err(ImportError::Encrypted)));
+    }
+
+    #[test]
+    fn test_malformed_export_is_a_parse_error() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("broken.json");
+        fs::write(&path, r#"{ "items": [ { "type": 1 } ] }"#).unwrap();
+
+        assert!(matches!(import_bitwarden_json(&path), Err(ImportError::Parse(_))));
+        assert!(matches!(import_bitwarden_json(&dir.path().join("missing.json")), Err(ImportError::Io(_))));
+    }
+
+    #[test]
+    fn test_parse_csv_handles_quoting() {
+        let records = parse_csv("\u{feff}a,\"b,c\",\"say \"\"hi\"\"\"\r\n,\"multi\nline\",\r\nlast,row\n").expect("Parse failed");
+
+        assert_eq!(records, vec![
+            vec!["a", "b,c", "say \"hi\""],
+            vec!["", "multi\nline", ""],
+            vec!["last", "row"],
+        ]);
+        assert_eq!(parse_csv("no,final,newline").expect("Parse failed"), vec![vec!["no", "final", "newline"]]);
+    }
+
+    #[test]
+    fn test_parse_csv_rejects_broken_quotes() {
+        assert!(matches!(parse_csv("\"never closed,a\n"), Err(ImportError::CsvParse(_))));
+        assert!(matches!(parse_csv("\"closed\"early,a\n"), Err(ImportError::CsvParse(_))));
+    }
+
+    #[test]
+    fn test_import_csv_matches_columns_by_header() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("passwords.csv");
+        fs::write(&path, "URL,Name,Password,Extra,Username\nhttps://example.com,Example,s3cret,x,alice\n,,,,\n").unwrap();
+
+        let report = import_csv(&path).expect("Import failed");
+
+        assert_eq!(report.items.len(), 1);
+        let item = &report.items[0];
+        assert_eq!(
+            (item.name.as_str(), item.url.as_str(), item.username.as_str(), item.password.as_str(), item.notes.as_str()),
+            ("Example", "https://example.com", "alice", "s3cret", ""),
+        );
+        assert_eq!(report.skipped, 1);
+
+        fs::write(&path, "title,secret\nExample,s3cret\n").unwrap();
+        assert!(matches!(import_csv(&path), Err(ImportError::Parse(_))));
+    }
+
+    #[test]
+    fn test_import_lastpass_export_fixture() {
+        let report = import_lastpass_csv(&fixture("lastpass_export.csv")).expect("Import failed");
+
+        assert_eq!(report.items.len(), 3);
+        assert_eq!(report.skipped, 0);
+
+        let github = &report.items[0];
+        assert_eq!(github.name, "GitHub");
+        assert_eq!(github.url, "https://github.com/login");
+        assert_eq!(github.username, "octocat");
+        assert_eq!(github.notes, "Work account\nRecovery codes in the safe");
+        assert!(github.favorite);
+        assert_eq!(github.custom_fields, vec![
+            CustomField { name: "TOTP".into(), value: "JBSWY3DPEHPK3PXP".into(), hidden: true },
+            CustomField { name: "Folder".into(), value: "Work".into(), hidden: false },
+        ]);
+
+        let router = &report.items[1];
+        assert_eq!((router.name.as_str(), router.url.as_str()), ("Home router", ""));
+        assert_eq!(router.password, "admin");
+        assert!(!router.favorite);
+        assert!(router.custom_fields.is_empty());
+
+        let bank = &report.items[2];
+        assert_eq!(bank.password, "p,a\"ss;w\\ö'rd 🔑");
+        assert_eq!(bank.custom_fields, vec![CustomField { name: "Folder".into(), value: "Finance\\Banks".into(), hidden: false }]);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_lastpass_export_without_header() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("lastpass.csv");
+        let with_header = fs::read_to_string(fixture("lastpass_export.csv")).unwrap();
+        fs::write(&path, with_header.split_once('\n').expect("Missing header").1).unwrap();
+
+        let report = import_lastpass_csv(&path).expect("Import failed");
+
+        assert_eq!(report.items.len(), 3);
+        assert_eq!(report.items[0].name, "GitHub");
+        assert_eq!(report.items[1].password, "admin");
+    }
+
+    #[test]
+    fn test_lastpass_secure_notes_are_skipped() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("lastpass.csv");
+        fs::write(&path, "url,username,password,totp,extra,name,grouping,fav\nhttp://sn,,,,Wifi is hunter2,Wifi,,0\n").unwrap();
+
+        let report = import_lastpass_csv(&path).expect("Import failed");
+
+        assert!(report.items.is_empty());
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.warnings, vec!["Skipped secure note 'Wifi'"]);
+    }
+
+    #[test]
+    fn test_malformed_lastpass_export_is_a_csv_error() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("lastpass.csv");
+        fs::write(&path, "url,username,password,totp,extra,name,grouping,fav\nhttps://a.com,\"unterminated,x,,,A,,0\n").unwrap();
+
+        assert!(matches!(import_lastpass_csv(&path), Err(ImportError::CsvParse(_))));
+    }
+
+    #[test]
+    fn test_import_1password_export_fixture() {
+        let report = import_1pux(&fixture("onepassword_export.1pux")).expect("Import failed");
+
+        assert_eq!(report.items.len(), 2);
+        assert_eq!(report.skipped, 1, "The secure note is skipped");
+
+        let mail = &report.items[0];
+        assert_eq!(mail.id, 0);
+        assert_eq!((mail.username.as_str(), mail.password.as_str()), ("alice@example.com", "hunter2"));
+        assert_eq!(mail.url, "https://mail.example.com");
+        assert_eq!(mail.notes, "Personal mail\n\nTags: email, personal");
+        assert!(mail.favorite);
+        assert_eq!((mail.created_at, mail.modified_at), (1_600_000_000, 1_700_000_000));
+
+        let wifi = &report.items[1];
+        assert_eq!(wifi.id, 1);
+        assert_eq!(wifi.password, "pä$$wörd 🔑", "Password items keep their password in the details");
+        assert_eq!(wifi.notes, "Tags: home");
+
+        assert!(report.warnings.iter().any(|warning| warning.contains("only the first of 2 URLs")));
+        assert!(report.warnings.iter().any(|warning| warning.contains("Door codes")));
+    }
+
+    #[test]
+    fn test_1password_vaults_are_merged_with_prefixed_names() {
+        let report = import_1pux(&fixture("onepassword_two_vaults.1pux")).expect("Import failed");
+
+        let names: Vec<&str> = report.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["Personal / GitHub", "Work / GitHub", "Work / VPN"]);
+        assert_eq!(report.items.iter().map(|item| item.id).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(report.items[1].password, "work-secret");
+    }
+
+    #[test]
+    fn test_1password_import_rejects_other_files() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("export.1pux");
+
+        fs::write(&path, "{\"accounts\": []}").unwrap();
+        assert!(matches!(import_1pux(&path), Err(ImportError::Archive(_))));
+
+        fs::write(&path, []).unwrap();
+        assert!(matches!(import_1pux(&path), Err(ImportError::Archive(_))));
+    }
+
+    #[test]
+    fn test_import_keepass_export_fixture() {
+        let report = import_keepass_xml(&fixture("keepass_export.xml"), false).expect("Import failed");
+
+        assert_eq!(report.items.len(), 3);
+        assert_eq!(report.skipped, 1, "The recycled entry is not imported");
+
+        let router = &report.items[0];
+        assert_eq!((router.name.as_str(), router.username.as_str(), router.password.as_str()), ("Router", "admin", "admin"));
+        assert!(router.custom_fields.is_empty(), "Entries of the root group have no folder");
+
+        let github = &report.items[1];
+        assert_eq!(github.name, "GitHub");
+        assert_eq!(github.username, "octocat");
+        assert_eq!(github.password, "Tr0ub4dor&3", "Protected base64 value");
+        assert_eq!(github.url, "https://github.com/login");
+        assert_eq!(github.notes, "Work account\nSecond line");
+        assert_eq!(github.created_at, 1_700_000_000);
+        assert_eq!(github.modified_at, 1_700_086_400);
+        assert_eq!(github.custom_fields, vec![
+            CustomField { name: "Recovery code".into(), value: "abcd-efgh".into(), hidden: true },
+            CustomField { name: "Team".into(), value: "platform".into(), hidden: false },
+            CustomField { name: "Folder".into(), value: "Internet/Work".into(), hidden: false },
+        ]);
+
+        let bank = &report.items[2];
+        assert_eq!(bank.password, "<p&ss>\"ö\"");
+        assert_eq!(bank.custom_fields, vec![CustomField { name: "Folder".into(), value: "Finance".into(), hidden: false }]);
+
+        assert_eq!(report.warnings, vec![
+            "'Bank': could not read protected field 'PIN'",
+            "Skipped 1 entries in the recycle bin",
+        ]);
+    }
+
+    #[test]
+    fn test_keepass_history_is_imported_when_asked() {
+        let report = import_keepass_xml(&fixture("keepass_export.xml"), true).expect("Import failed");
+
+        let names: Vec<&str> = report.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["Router", "GitHub", "GitHub (version 1)", "Bank"]);
+        assert_eq!(report.items[2].password, "hunter2");
+        assert_eq!(report.items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_keepass_import_rejects_other_files() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("export.xml");
+
+        fs::write(&path, "<Other><Root/></Other>").unwrap();
+        assert!(matches!(import_keepass_xml(&path, false), Err(ImportError::Parse(_))));
+
+        fs::write(&path, "<KeePassFile><Root><Group>").unwrap();
+        assert!(matches!(import_keepass_xml(&path, false), Err(ImportError::Parse(_))));
+    }
+
+    #[test]
+    fn test_oversized_keepass_export_is_refused() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("export.xml");
+        File::create(&path).and_then(|file| file.set_len(MAX_KEEPASS_XML_LEN + 1)).unwrap();
+
+        assert!(matches!(import_keepass_xml(&path, false), Err(ImportError::TooLarge { .. })));
+    }
+
+    #[test]
+    fn test_parse_keepass_time() {
+        assert_eq!(parse_keepass_time("2023-11-14T22:13:20Z"), Some(1_700_000_000));
+        assert_eq!(parse_keepass_time("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_keepass_time("AOjl3A4AAAA="), Some(1_700_000_000), "Database times are base64");
+        assert_eq!(parse_keepass_time("AAAAAAAAAAA="), None, "Before the Unix epoch");
+        assert_eq!(parse_keepass_time("2023-13-14T22:13:20Z"), None);
+    }
+
+    fn login(id: i32, url: &str, username: &str, password: &str) -> Item {
+        Item {
+            id,
+            name: format!("Login {}", id),
+            username: username.into(),
+            password: password.into(),
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_url_host_normalization() {
+        let host = |url: &str| url_host(url).map(|host| String::from_utf8(host.as_ref().to_vec()).expect("Host is not UTF-8"));
+
+        assert_eq!(host("https://www.GitHub.com/login?next=/"), Some("github.com".into()));
+        assert_eq!(host("github.com"), Some("github.com".into()));
+        assert_eq!(host("http://user:pw@example.org:8080/path#top"), Some("example.org".into()));
+        assert_eq!(host("https://[::1]:8443/admin"), Some("[::1]".into()));
+        assert_eq!(host("https://[::1]/admin"), Some("[::1]".into()));
+        assert_eq!(host("  "), None);
+        assert_eq!(host("https:///path"), None);
+    }
+
+    #[test]
+    fn test_plan_flags_same_host_and_username() {
+        let existing = vec![login(3, "https://github.com/login", "octocat", "old"), login(4, "https://gitlab.com", "octocat", "pw")];
+        let incoming = vec![
+            login(0, "http://www.github.com/session", "octocat", "new"),  // Same host, different scheme and path
+            login(1, "https://github.com", "Octocat", "pw"),  // Usernames are compared exactly
+            login(2, "https://gist.github.com", "octocat", "pw"),  // Subdomains are different sites
+            login(3, "", "octocat", "pw"),  // Without a URL nothing is a duplicate
+        ];
+
+        let plan = plan_import(&existing, incoming);
+        let flagged: Vec<(Option<i32>, ImportChoice)> = plan.rows.iter().map(|row| (row.duplicate_of, row.choice)).collect();
+        assert_eq!(flagged, vec![
+            (Some(3), ImportChoice::Skip),
+            (None, ImportChoice::Import),
+            (None, ImportChoice::Import),
+            (None, ImportChoice::Import),
+        ]);
+    }
+
+    #[test]
+    fn test_plan_ignores_trashed_items() {
+        let mut trashed = login(3, "https://github.com", "octocat", "pw");
+        trashed.deleted_at = Some(1);
+
+        let plan = plan_import(&[trashed], vec![login(0, "https://github.com", "octocat", "pw")]);
+        assert_eq!(plan.rows[0].duplicate_of, None);
+    }
+
+    #[test]
+    fn test_apply_imports_and_overwrites() {
+        let mut vault = Vault::new();
+        vault.items = vec![login(0, "https://github.com", "octocat", "old")];
+        vault.items[0].created_at = 100;
+
+        let mut plan = plan_import(&vault.items, vec![
+            login(0, "https://github.com", "octocat", "new"),
+            login(1, "https://example.org", "alice", "pw"),
+            login(2, "https://example.net", "bob", "pw"),
+        ]);
+        plan.rows[0].choice = ImportChoice::Overwrite;
+        plan.rows[2].choice = ImportChoice::Skip;
+
+        let outcome = plan.apply(&mut vault, 500).expect("Apply failed");
+        assert_eq!(outcome, ImportOutcome { added: 1, overwritten: 1 });
+
+        let overwritten = vault.item_by_id(0).expect("Item missing");
+        assert_eq!((overwritten.password.as_str(), overwritten.created_at, overwritten.password_changed_at), ("new", 100, 500));
+
+        // Imported items get fresh ids rather than their position in the export
+        let added = vault.item_by_id(1).expect("Item missing");
+        assert_eq!((added.username.as_str(), added.created_at), ("alice", 500));
+        assert_eq!((vault.items.len(), vault.nonce), (2, 2));
+    }
+
+    #[test]
+    fn test_failed_apply_leaves_vault_unchanged() {
+        let mut vault = Vault::new();
+        vault.items = vec![login(0, "https://github.com", "octocat", "old")];
+
+        let mut plan = plan_import(&vault.items, vec![
+            login(0, "https://example.org", "alice", "pw"),
+            login(1, "https://github.com", "octocat", "new"),
+        ]);
+        plan.rows[1].choice = ImportChoice::Overwrite;
+        vault.items.clear();  // Deleted while the preview was open
+        let version = vault.version;
+
+        assert!(matches!(plan.apply(&mut vault, 500), Err(ImportError::InvalidPlan(_))));
+        assert!(vault.items.is_empty(), "The item imported before the failure must not be kept");
+        assert_eq!((vault.nonce, vault.version), (1, version));
+    }
+}
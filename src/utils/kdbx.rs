@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20::ChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use flate2::read::GzDecoder;
+use roxmltree::NodeId;
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::errors::import_errors::ImportError;
+use crate::utils::compression::DEFAULT_MAX_OUTPUT_LEN;
+use crate::utils::crypto::{ArgonParams, Crypto, KdfAlgorithm};
+use crate::utils::import::{self, ImportReport};
+use crate::utils::zero_byte::ZeroByte;
+
+
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+const SUPPORTED_MAJOR_VERSION: u16 = 4;
+/// Largest database read, it is decrypted and parsed in memory
+const MAX_KDBX_LEN: u64 = 64 * 1024 * 1024;
+
+// Outer header field IDs
+const HEADER_END: u8 = 0;
+const HEADER_CIPHER_ID: u8 = 2;
+const HEADER_COMPRESSION: u8 = 3;
+const HEADER_MASTER_SEED: u8 = 4;
+const HEADER_ENCRYPTION_IV: u8 = 7;
+const HEADER_KDF_PARAMETERS: u8 = 11;
+
+// Inner header field IDs
+const INNER_END: u8 = 0;
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+/// The only inner stream KDBX 4 databases use for protected values
+const INNER_STREAM_CHACHA20: u32 = 3;
+
+const CIPHER_AES256: [u8; 16] = uuid(0x31c1f2e6_bf71_4350_be58_05216afc5aff);
+const CIPHER_CHACHA20: [u8; 16] = uuid(0xd6038a2b_8b6f_4cb5_a524_339a31dbb59a);
+const CIPHER_TWOFISH: [u8; 16] = uuid(0xad68f29f_576f_4bb9_a36a_d47af965346c);
+
+const KDF_AES_KDBX3: [u8; 16] = uuid(0xc9d9f39a_628a_4460_bf74_0d08c18a4fea);
+const KDF_AES_KDBX4: [u8; 16] = uuid(0x7c02bb82_79a7_4ac0_927d_114a00648238);
+const KDF_ARGON2D: [u8; 16] = uuid(0xef636ddf_8c29_444b_91f7_a9a403e30a0c);
+const KDF_ARGON2ID: [u8; 16] = uuid(0x9e298b19_56db_4773_b23d_fc3ec6f0a1e6);
+
+/// Index the header's HMAC key is derived with, blocks count up from 0
+const HEADER_HMAC_INDEX: u64 = u64::MAX;
+const HASH_LEN: usize = 32;
+
+/// Value type that ends a KDBX variant dictionary
+const VARIANT_END: u8 = 0x00;
+const VARIANT_DICTIONARY_VERSION: u16 = 0x0100;
+
+const fn uuid(value: u128) -> [u8; 16] {
+    value.to_be_bytes()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PayloadCipher {
+    Aes256,
+    ChaCha20,
+}
+
+/// Key derivation recorded in the header
+enum Kdf {
+    Aes { seed: [u8; 32], rounds: u64 },
+    Argon2 { salt: Vec<u8>, params: ArgonParams, version: u32 },
+}
+
+/// The outer header fields needed to decrypt the payload
+struct Header {
+    cipher: PayloadCipher,
+    gzip: bool,
+    master_seed: Vec<u8>,
+    iv: Vec<u8>,
+    kdf: Kdf,
+    /// Bytes up to and including the end field, what the checksum and HMAC cover
+    len: usize,
+}
+
+/// Reads the entries of a KeePass 2 database (KDBX 4) opened with `password`, the same way
+/// `import::import_keepass_xml` reads an XML export. Databases with AES-KDF or Argon2 and an
+/// AES-256 or ChaCha20 payload are supported; KDBX 3 files, Twofish and key files are not.
+/// The database is only read, never written back. Files over 64 MiB are refused.
+#[allow(dead_code)]  // Entry point for the import flow in the UI
+pub(crate) fn import_kdbx(path: &Path, password: &str, include_history: bool) -> Result<ImportReport, ImportError> {
+    if fs::metadata(path)?.len() > MAX_KDBX_LEN {
+        return Err(ImportError::TooLarge { max_len: MAX_KDBX_LEN });
+    }
+    let bytes = fs::read(path)?;
+
+    let header = parse_header(&bytes)?;
+    let stored_hash = bytes.get(header.len..header.len + HASH_LEN).ok_or_else(|| corrupt_header("it is cut off"))?;
+    if Sha256::digest(&bytes[..header.len]).as_slice() != stored_hash {
+        return Err(corrupt_header("its checksum doesn't match"));
+    }
+
+    let transformed_key = transform_key(password, &header.kdf)?;
+    let hmac_base: Zeroizing<[u8; 64]> = Zeroizing::new(
+        Sha512::new().chain_update(&header.master_seed).chain_update(transformed_key.as_slice()).chain_update([1]).finalize().into()
+    );
+
+    let stored_hmac = bytes.get(header.len + HASH_LEN..header.len + 2 * HASH_LEN).ok_or_else(|| corrupt_header("it is cut off"))?;
+    if !zero_byte(&bytes[..header.len]).hmac_sha256_verify(&block_hmac_key(&hmac_base, HEADER_HMAC_INDEX), &zero_byte(stored_hmac)) {
+        return Err(ImportError::WrongPassword);
+    }
+
+    let mut payload = read_blocks(&bytes[header.len + 2 * HASH_LEN..], &hmac_base)?;
+    let key: Zeroizing<[u8; 32]> = Zeroizing::new(Sha256::new().chain_update(&header.master_seed).chain_update(transformed_key.as_slice()).finalize().into());
+    match header.cipher {
+        PayloadCipher::Aes256 => {
+            let iv: &[u8; 16] = header.iv.as_slice().try_into().map_err(|_| corrupt_header("the AES IV isn't 16 bytes"))?;
+            Crypto::aes_cbc_decrypt(&mut payload, &key, iv).map_err(|_| damaged("the payload doesn't decrypt"))?;
+        },
+        PayloadCipher::ChaCha20 => {
+            let nonce: &[u8; 12] = header.iv.as_slice().try_into().map_err(|_| corrupt_header("the ChaCha20 nonce isn't 12 bytes"))?;
+            ChaCha20::new(key.as_ref().into(), nonce.into()).apply_keystream(payload.as_mut());
+        },
+    }
+
+    if header.gzip {
+        payload = gunzip(&payload)?;
+    }
+
+    let (stream_key, xml_start) = parse_inner_header(payload.as_ref())?;
+    let xml = std::str::from_utf8(&payload.as_ref()[xml_start..]).map_err(|_| damaged("the XML isn't UTF-8"))?;
+    let document = roxmltree::Document::parse(xml).map_err(|e| ImportError::Parse(e.to_string()))?;
+
+    let protected = unprotect_values(&document, &stream_key);
+    import::import_keepass_document(&document, include_history, &|value| protected.get(&value.id()).cloned().flatten())
+}
+
+fn parse_header(bytes: &[u8]) -> Result<Header, ImportError> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    let signatures = (reader.u32(), reader.u32());
+    if signatures != (Some(SIGNATURE_1), Some(SIGNATURE_2)) {
+        return Err(ImportError::Parse("not a KeePass database".into()));
+    }
+    let _minor = reader.u16();
+    match reader.u16() {
+        Some(SUPPORTED_MAJOR_VERSION) => {},
+        Some(major) => return Err(ImportError::Unsupported(format!("KDBX {} databases can't be read, save it as KDBX 4 in KeePass first", major))),
+        None => return Err(corrupt_header("it is cut off")),
+    }
+
+    let (mut cipher, mut gzip, mut master_seed, mut iv, mut kdf) = (None, false, None, None, None);
+    loop {
+        let id = reader.u8().ok_or_else(|| corrupt_header("it is cut off"))?;
+        let len = reader.u32().ok_or_else(|| corrupt_header("it is cut off"))?;
+        let data = reader.take(len as usize).ok_or_else(|| corrupt_header("a field runs past the end"))?;
+
+        match id {
+            HEADER_END => break,
+            HEADER_CIPHER_ID => cipher = Some(parse_cipher(data)?),
+            HEADER_COMPRESSION => gzip = match data {
+                [0, 0, 0, 0] => false,
+                [1, 0, 0, 0] => true,
+                _ => return Err(corrupt_header("unknown compression")),
+            },
+            HEADER_MASTER_SEED if data.len() == 32 => master_seed = Some(data.to_vec()),
+            HEADER_MASTER_SEED => return Err(corrupt_header("the master seed isn't 32 bytes")),
+            HEADER_ENCRYPTION_IV => iv = Some(data.to_vec()),
+            HEADER_KDF_PARAMETERS => kdf = Some(parse_kdf(data)?),
+            _ => {},  // Public custom data and fields of newer versions
+        }
+    }
+
+    Ok(Header {
+        cipher: cipher.ok_or_else(|| corrupt_header("the cipher is missing"))?,
+        gzip,
+        master_seed: master_seed.ok_or_else(|| corrupt_header("the master seed is missing"))?,
+        iv: iv.ok_or_else(|| corrupt_header("the encryption IV is missing"))?,
+        kdf: kdf.ok_or_else(|| corrupt_header("the key derivation parameters are missing"))?,
+        len: reader.pos,
+    })
+}
+
+fn parse_cipher(id: &[u8]) -> Result<PayloadCipher, ImportError> {
+    match id {
+        id if id == CIPHER_AES256 => Ok(PayloadCipher::Aes256),
+        id if id == CIPHER_CHACHA20 => Ok(PayloadCipher::ChaCha20),
+        id if id == CIPHER_TWOFISH => Err(ImportError::UnsupportedCipher("Twofish".into())),
+        id => Err(ImportError::UnsupportedCipher(id.iter().map(|byte| format!("{:02x}", byte)).collect())),
+    }
+}
+
+/// Reads the key derivation out of the header's variant dictionary
+fn parse_kdf(bytes: &[u8]) -> Result<Kdf, ImportError> {
+    let params = parse_variant_dictionary(bytes)?;
+    let field = |name: &str| params.get(name).copied().ok_or_else(|| corrupt_header("a key derivation parameter is missing"));
+    let number = |name: &str| -> Result<u64, ImportError> {
+        match field(name)? {
+            &[a, b, c, d] => Ok(u64::from(u32::from_le_bytes([a, b, c, d]))),
+            bytes => bytes.try_into().map(u64::from_le_bytes).map_err(|_| corrupt_header("a key derivation parameter isn't a number")),
+        }
+    };
+    let small_number = |name: &str| u32::try_from(number(name)?).map_err(|_| ImportError::Unsupported(format!("the key derivation parameter {} is too large", name)));
+
+    let algorithm = match field("$UUID")? {
+        id if id == KDF_AES_KDBX3 || id == KDF_AES_KDBX4 => {
+            let seed = field("S")?.try_into().map_err(|_| corrupt_header("the AES-KDF seed isn't 32 bytes"))?;
+            return Ok(Kdf::Aes { seed, rounds: number("R")? });
+        },
+        id if id == KDF_ARGON2D => KdfAlgorithm::Argon2d,
+        id if id == KDF_ARGON2ID => KdfAlgorithm::Argon2id,
+        _ => return Err(ImportError::Unsupported("unknown key derivation".into())),
+    };
+
+    let params = ArgonParams {
+        algorithm,
+        memory_cost: u32::try_from(number("M")? / 1024).map_err(|_| ImportError::Unsupported("the Argon2 memory cost is too large".into()))?,
+        time_cost: small_number("I")?,
+        parallelism: small_number("P")?,
+    };
+    Ok(Kdf::Argon2 { salt: field("S")?.to_vec(), params, version: small_number("V")? })
+}
+
+/// Names and raw values of a KDBX variant dictionary. Value types aren't checked, the length
+/// of each value is checked where it's used.
+fn parse_variant_dictionary(bytes: &[u8]) -> Result<HashMap<&str, &[u8]>, ImportError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let cut_off = || corrupt_header("the key derivation parameters are cut off");
+
+    let version = reader.u16().ok_or_else(cut_off)?;
+    if version & 0xff00 != VARIANT_DICTIONARY_VERSION {
+        return Err(ImportError::Unsupported(format!("key derivation parameters version {:#06x}", version)));
+    }
+
+    let mut entries = HashMap::new();
+    loop {
+        let kind = reader.u8().ok_or_else(cut_off)?;
+        if kind == VARIANT_END {
+            return Ok(entries);
+        }
+        let name_len = reader.u32().ok_or_else(cut_off)?;
+        let name = reader.take(name_len as usize).ok_or_else(cut_off)?;
+        let value_len = reader.u32().ok_or_else(cut_off)?;
+        let value = reader.take(value_len as usize).ok_or_else(cut_off)?;
+
+        let name = std::str::from_utf8(name).map_err(|_| corrupt_header("a key derivation parameter name isn't UTF-8"))?;
+        entries.insert(name, value);
+    }
+}
+
+/// Composite key of a password-only database, SHA-256 of the password's SHA-256, put through
+/// the database's key derivation
+fn transform_key(password: &str, kdf: &Kdf) -> Result<Zeroizing<[u8; 32]>, ImportError> {
+    let password_hash: Zeroizing<[u8; 32]> = Zeroizing::new(Sha256::digest(password.as_bytes()).into());
+    let composite: Zeroizing<[u8; 32]> = Zeroizing::new(Sha256::digest(password_hash.as_slice()).into());
+
+    let transformed = match kdf {
+        Kdf::Aes { seed, rounds } => Crypto::aes_kdf(&composite, seed, *rounds),
+        Kdf::Argon2 { salt, params, version } => Crypto::derive_argon_key_raw(&*composite, salt, *params, *version),
+    };
+    transformed
+        .map(Zeroizing::new)
+        .map_err(|e| ImportError::Unsupported(format!("key derivation: {}", e)))
+}
+
+fn block_hmac_key(hmac_base: &[u8; 64], index: u64) -> ZeroByte {
+    let mut key = ZeroByte::default();
+    let mut digest = Sha512::new().chain_update(index.to_le_bytes()).chain_update(hmac_base).finalize();
+    key.extend_from_slice(&digest);
+    digest.as_mut_slice().zeroize();
+    key
+}
+
+/// Joins the payload's HMAC-protected blocks, each `hmac, length, data`, checking every
+/// block's HMAC on the way. A block of length 0 ends the payload.
+fn read_blocks(bytes: &[u8], hmac_base: &[u8; 64]) -> Result<ZeroByte, ImportError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let mut payload = ZeroByte::default();
+
+    for index in 0u64.. {
+        let cut_off = || damaged("the payload is cut off");
+        let stored_hmac = reader.take(HASH_LEN).ok_or_else(cut_off)?;
+        let len_bytes = reader.take(4).ok_or_else(cut_off)?;
+        let len = u32::from_le_bytes(len_bytes.try_into().expect("Slice is 4 bytes long"));
+        let data = reader.take(len as usize).ok_or_else(cut_off)?;
+
+        let mut block = ZeroByte::default();
+        block.extend_from_slice(&index.to_le_bytes());
+        block.extend_from_slice(len_bytes);
+        block.extend_from_slice(data);
+        if !block.hmac_sha256_verify(&block_hmac_key(hmac_base, index), &zero_byte(stored_hmac)) {
+            return Err(damaged(&format!("block {} failed its integrity check", index)));
+        }
+
+        if len == 0 {
+            break;
+        }
+        payload.extend_from_slice(data);
+    }
+
+    Ok(payload)
+}
+
+fn gunzip(bytes: &ZeroByte) -> Result<ZeroByte, ImportError> {
+    let mut output = ZeroByte::default();
+    let mut decoder = std::io::Read::take(GzDecoder::new(bytes.as_ref()), DEFAULT_MAX_OUTPUT_LEN as u64 + 1);
+
+    match output.extend_from_reader(&mut decoder) {
+        Ok(len) if len > DEFAULT_MAX_OUTPUT_LEN => Err(ImportError::TooLarge { max_len: DEFAULT_MAX_OUTPUT_LEN as u64 }),
+        Ok(_) => Ok(output),
+        Err(_) => Err(damaged("the payload doesn't decompress")),
+    }
+}
+
+/// Key of the inner stream protected values are encrypted with, and where the XML starts
+fn parse_inner_header(bytes: &[u8]) -> Result<(Zeroizing<Vec<u8>>, usize), ImportError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let cut_off = || damaged("the inner header is cut off");
+    let (mut stream_id, mut stream_key) = (None, None);
+
+    loop {
+        let id = reader.u8().ok_or_else(cut_off)?;
+        let len = reader.u32().ok_or_else(cut_off)?;
+        let data = reader.take(len as usize).ok_or_else(cut_off)?;
+
+        match id {
+            INNER_END => break,
+            INNER_STREAM_ID => stream_id = data.try_into().ok().map(u32::from_le_bytes),
+            INNER_STREAM_KEY => stream_key = Some(Zeroizing::new(data.to_vec())),
+            _ => {},  // Attachments aren't imported
+        }
+    }
+
+    if stream_id != Some(INNER_STREAM_CHACHA20) {
+        return Err(ImportError::Unsupported("protected values use an unknown stream cipher".into()));
+    }
+    let stream_key = stream_key.ok_or_else(|| damaged("the inner stream key is missing"))?;
+    Ok((stream_key, reader.pos))
+}
+
+/// Decrypts every protected value of the document. They share one ChaCha20 keystream, used in
+/// document order, so they are all decrypted up front, history and recycle bin included.
+fn unprotect_values(document: &roxmltree::Document, stream_key: &[u8]) -> HashMap<NodeId, Option<String>> {
+    let hash: Zeroizing<[u8; 64]> = Zeroizing::new(Sha512::digest(stream_key).into());
+    let mut stream = ChaCha20::new(hash[..32].into(), hash[32..44].into());
+
+    document.descendants()
+        .filter(|node| node.has_tag_name("Value") && node.attribute("Protected") == Some("True"))
+        .map(|value| {
+            let text = BASE64.decode(value.text().unwrap_or_default().trim()).ok().map(|mut bytes| {
+                stream.apply_keystream(&mut bytes);
+                String::from_utf8(bytes).ok()
+            });
+            (value.id(), text.flatten())
+        })
+        .collect()
+}
+
+fn zero_byte(bytes: &[u8]) -> ZeroByte {
+    let mut zero_byte = ZeroByte::default();
+    zero_byte.extend_from_slice(bytes);
+    zero_byte
+}
+
+fn corrupt_header(reason: &str) -> ImportError {
+    ImportError::CorruptHeader(reason.to_string())
+}
+
+fn damaged(reason: &str) -> ImportError {
+    ImportError::Damaged(reason.to_string())
+}
+
+/// Little-endian reads over a byte slice, None past the end
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let data = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(data)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const PASSWORD: &str = "correct horse";
+    /// Offsets into the fixtures' outer header
+    const MAJOR_VERSION_OFFSET: usize = 10;
+    const CIPHER_ID_OFFSET: usize = 17;
+    const MASTER_SEED_OFFSET: usize = 47;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    /// Copy of the AES fixture with `patch` applied
+    fn patched_fixture(dir: &tempfile::TempDir, patch: impl FnOnce(&mut Vec<u8>)) -> PathBuf {
+        let mut bytes = fs::read(fixture("keepass_aes.kdbx")).expect("Failed to read fixture");
+        patch(&mut bytes);
+        let path = dir.path().join("patched.kdbx");
+        fs::write(&path, bytes).expect("Failed to write");
+        path
+    }
+
+    #[test]
+    fn test_import_aes_kdf_database() {
+        let report = import_kdbx(&fixture("keepass_aes.kdbx"), PASSWORD, false).expect("Import failed");
+
+        assert_eq!(report.items.len(), 2);
+        assert_eq!(report.skipped, 1, "The recycle bin entry is skipped");
+
+        let mail = &report.items[0];
+        assert_eq!((mail.name.as_str(), mail.username.as_str()), ("Mail", "alice@example.com"));
+        assert_eq!(mail.password, "hunter2");
+        assert_eq!(mail.modified_at, 1_700_000_000);
+        assert_eq!(mail.created_at, 1_699_999_000);
+
+        let bank = &report.items[1];
+        assert_eq!(bank.password, "pä$$wörd 🔑");
+        let pin = bank.custom_fields.iter().find(|field| field.name == "PIN").expect("Missing PIN");
+        assert_eq!(pin.value, "1234");
+        assert!(pin.hidden);
+        assert!(bank.custom_fields.iter().any(|field| field.name == "Folder" && field.value == "Banking"));
+    }
+
+    #[test]
+    fn test_history_values_keep_the_stream_in_step() {
+        let report = import_kdbx(&fixture("keepass_aes.kdbx"), PASSWORD, true).expect("Import failed");
+
+        let names: Vec<&str> = report.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["Mail", "Bank", "Bank (version 1)"]);
+        assert_eq!(report.items[2].password, "old bank password");
+        assert!(report.warnings.iter().all(|warning| !warning.contains("protected")), "{:?}", report.warnings);
+    }
+
+    #[test]
+    fn test_import_argon2_chacha20_database() {
+        let report = import_kdbx(&fixture("keepass_chacha20.kdbx"), PASSWORD, false).expect("Import failed");
+
+        assert_eq!(report.items.len(), 1);
+        assert_eq!(report.items[0].name, "Server");
+        assert_eq!(report.items[0].password, "s3rv3r!");
+    }
+
+    #[test]
+    fn test_wrong_password_is_reported() {
+        for name in ["keepass_aes.kdbx", "keepass_chacha20.kdbx"] {
+            let result = import_kdbx(&fixture(name), "wrong horse", false);
+            assert!(matches!(result, Err(ImportError::WrongPassword)), "{}: {:?}", name, result.map(|r| r.items.len()));
+        }
+    }
+
+    #[test]
+    fn test_unsupported_cipher_is_reported() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = patched_fixture(&dir, |bytes| bytes[CIPHER_ID_OFFSET..CIPHER_ID_OFFSET + 16].copy_from_slice(&CIPHER_TWOFISH));
+
+        match import_kdbx(&path, PASSWORD, false) {
+            Err(ImportError::UnsupportedCipher(cipher)) => assert_eq!(cipher, "Twofish"),
+            other => panic!("Expected an unsupported cipher, got {:?}", other.map(|r| r.items.len())),
+        }
+    }
+
+    #[test]
+    fn test_corrupted_header_is_reported() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+        let path = patched_fixture(&dir, |bytes| bytes[MASTER_SEED_OFFSET + 3] ^= 0x01);
+        assert!(matches!(import_kdbx(&path, PASSWORD, false), Err(ImportError::CorruptHeader(_))));
+
+        let path = patched_fixture(&dir, |bytes| bytes.truncate(MASTER_SEED_OFFSET + 10));
+        assert!(matches!(import_kdbx(&path, PASSWORD, false), Err(ImportError::CorruptHeader(_))));
+    }
+
+    #[test]
+    fn test_other_versions_and_files_are_refused() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+        let path = patched_fixture(&dir, |bytes| bytes[MAJOR_VERSION_OFFSET] = 3);
+        assert!(matches!(import_kdbx(&path, PASSWORD, false), Err(ImportError::Unsupported(_))));
+
+        let path = dir.path().join("export.xml");
+        fs::write(&path, "<KeePassFile/>").expect("Failed to write");
+        assert!(matches!(import_kdbx(&path, PASSWORD, false), Err(ImportError::Parse(_))));
+    }
+
+    #[test]
+    fn test_damaged_payload_is_reported() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = patched_fixture(&dir, |bytes| {
+            let last = bytes.len() - 40;
+            bytes[last] ^= 0x01;
+        });
+
+        assert!(matches!(import_kdbx(&path, PASSWORD, false), Err(ImportError::Damaged(_))));
+    }
+}
@@ -1,10 +1,101 @@
+pub(super) mod auto_lock;
+pub(super) mod buffer_pool;
+pub(super) mod clipboard;
+pub(super) mod compression;
+pub(super) mod config;
 pub(super) mod crypto;
+pub(super) mod export;
+#[cfg(feature = "fido2")]
+pub(super) mod fido2;
 pub(super) mod file;
+pub(super) mod file_watch;
+pub(super) mod import;
+pub(super) mod kdbx;
+pub(super) mod password_strength;
+pub(super) mod query;
+#[cfg(debug_assertions)]
+pub(super) mod scrub_check;
+pub(super) mod search;
+pub(super) mod sensitive_clipboard;
+pub(super) mod snapshot;
+pub(super) mod tempsec;
+pub(super) mod vault_lock;
+pub(super) mod zero_byte;
+pub(super) mod zip;
 
-use copypasta::{ClipboardContext, ClipboardProvider};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub(super) fn copy_text_to_clipboard(text: String) {
-    let mut ctx = ClipboardContext::new().unwrap();
-    ctx.set_contents(text).unwrap();
-    ctx.get_contents().unwrap();  // Not sure why I have to get_contents for this to work on KDE
-}
\ No newline at end of file
+/// Current time as seconds since the Unix epoch
+pub(super) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Year, month and day of a Unix timestamp, in UTC
+pub(super) fn civil_date(timestamp: u64) -> (i64, i64, i64) {
+    // Civil from days, see https://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Days since the Unix epoch of a UTC date, the inverse of `civil_date`
+pub(super) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Formats a Unix timestamp as a UTC `YYYY-MM-DD` date
+pub(super) fn format_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_date(timestamp);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats a Unix timestamp as a UTC `YYYY-MM-DD HH:MM` date and time
+pub(super) fn format_date_time(timestamp: u64) -> String {
+    let seconds_of_day = timestamp % 86_400;
+    format!("{} {:02}:{:02} UTC", format_date(timestamp), seconds_of_day / 3600, seconds_of_day % 3600 / 60)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14");
+    }
+
+    #[test]
+    fn test_format_date_time() {
+        assert_eq!(format_date_time(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_date_time(1_700_000_000), "2023-11-14 22:13 UTC");
+    }
+
+    #[test]
+    fn test_days_from_civil_inverts_civil_date() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        for timestamp in [0, 951_782_400, 1_700_000_000, 4_102_444_800] {
+            let (year, month, day) = civil_date(timestamp);
+            assert_eq!(days_from_civil(year, month, day) as u64, timestamp / 86_400);
+        }
+    }
+}
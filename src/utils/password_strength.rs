@@ -0,0 +1,151 @@
+use crate::errors::password_errors::PasswordError;
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Passwords that are guessed first no matter how they are built.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "password1", "password123", "passw0rd", "12345678", "123456789",
+    "1234567890", "qwerty", "qwertyui", "qwertyuiop", "asdfghjk", "asdfghjkl",
+    "zxcvbnm", "iloveyou", "letmein", "welcome", "admin", "abc123", "football",
+    "baseball", "monkey", "dragon", "sunshine", "princess", "trustno1", "master",
+];
+
+/// Lowest score not considered weak
+pub(crate) const MIN_SCORE: u8 = 2;
+/// Shortest password accepted for a new vault or an encrypted export
+pub(crate) const MIN_PASSWORD_LEN: usize = 8;
+
+/// Strength score from 0 (too guessable) to 4 (very unguessable), on the same
+/// scale as zxcvbn: under 10^3, 10^6, 10^8 and 10^10 estimated guesses.
+pub(crate) fn score(password: &str) -> u8 {
+    match estimate_guesses_log10(password) {
+        guesses if guesses < 3.0 => 0,
+        guesses if guesses < 6.0 => 1,
+        guesses if guesses < 8.0 => 2,
+        guesses if guesses < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/// Check a new vault password against its confirmation, the minimum length and the
+/// minimum strength score. The two entries are compared in constant time.
+pub(crate) fn validate_new_password(password: &str, confirm: &str) -> Result<(), PasswordError> {
+    validate_strength(password)?;
+    validate_confirmation(password, confirm)
+}
+
+/// Check a new password against the minimum length and the minimum strength score
+pub(crate) fn validate_strength(password: &str) -> Result<(), PasswordError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(PasswordError::TooShort { min: MIN_PASSWORD_LEN });
+    }
+    if score(password) < MIN_SCORE {
+        return Err(PasswordError::TooWeak);
+    }
+    Ok(())
+}
+
+/// Check that a new password was entered the same way twice, in constant time
+pub(crate) fn validate_confirmation(password: &str, confirm: &str) -> Result<(), PasswordError> {
+    let mut password_bytes = ZeroByte::default();
+    password_bytes.extend_from_slice(password.as_bytes());
+    let mut confirm_bytes = ZeroByte::default();
+    confirm_bytes.extend_from_slice(confirm.as_bytes());
+
+    if password_bytes != confirm_bytes {
+        return Err(PasswordError::Mismatch);
+    }
+
+    Ok(())
+}
+
+/// Order of magnitude of the guesses needed to brute force `password` over the
+/// character classes it uses. A character repeating or continuing a run from the
+/// previous one (`aaa`, `abc`, `321`) adds next to nothing.
+fn estimate_guesses_log10(password: &str) -> f64 {
+    if password.is_empty() || COMMON_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+        return 0.0;
+    }
+
+    let pool: u32 = [
+        (password.chars().any(|c| c.is_ascii_lowercase()), 26),
+        (password.chars().any(|c| c.is_ascii_uppercase()), 26),
+        (password.chars().any(|c| c.is_ascii_digit()), 10),
+        (password.chars().any(|c| c.is_ascii_punctuation() || c == ' '), 33),
+        (!password.is_ascii(), 100),
+    ].iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, size)| size)
+        .sum();
+
+    let mut effective_len = 0.0;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let continues_run = previous.is_some_and(|p| (c as i64 - p as i64).abs() <= 1);
+        effective_len += if continues_run { 0.1 } else { 1.0 };
+        previous = Some(c);
+    }
+
+    effective_len * f64::from(pool).log10()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_common_passwords_score_zero() {
+        assert_eq!(score("password"), 0);
+        assert_eq!(score("QWERTYUIOP"), 0);
+        assert_eq!(score(""), 0);
+    }
+
+    #[test]
+    fn test_repeats_and_sequences_score_low() {
+        assert!(score("aaaaaaaaaa") < 2);
+        assert!(score("abcdefghij") < 2);
+        assert!(score("98765432") < 2);
+    }
+
+    #[test]
+    fn test_mixed_passwords_score_high() {
+        assert!(score("kitten12") >= 2);
+        assert_eq!(score("Tr0ub4dor&3"), 4);
+        assert_eq!(score("correct horse battery staple"), 4);
+    }
+
+    #[test]
+    fn test_matching_passwords_are_accepted() {
+        assert_eq!(validate_new_password("kitten12&Co", "kitten12&Co"), Ok(()));
+    }
+
+    #[test]
+    fn test_mismatched_passwords_are_rejected() {
+        assert_eq!(validate_new_password("kitten12&Co", "kitten12&Cp"), Err(PasswordError::Mismatch));
+    }
+
+    #[test]
+    fn test_short_password_is_rejected() {
+        assert_eq!(validate_new_password("k1t&Co", "k1t&Co"), Err(PasswordError::TooShort { min: MIN_PASSWORD_LEN }));
+    }
+
+    #[test]
+    fn test_weak_password_is_rejected() {
+        assert_eq!(validate_new_password("password", "password"), Err(PasswordError::TooWeak));
+    }
+
+    #[test]
+    fn test_strength_ignores_confirmation() {
+        assert_eq!(validate_strength("kitten12&Co"), Ok(()));
+        assert_eq!(validate_strength(""), Err(PasswordError::TooShort { min: MIN_PASSWORD_LEN }));
+        assert_eq!(validate_strength("12345678"), Err(PasswordError::TooWeak));
+    }
+
+    #[test]
+    fn test_confirmation_ignores_strength() {
+        assert_eq!(validate_confirmation("abc", "abc"), Ok(()));
+        assert_eq!(validate_confirmation("abc", "abd"), Err(PasswordError::Mismatch));
+        assert_eq!(validate_confirmation("abc", ""), Err(PasswordError::Mismatch));
+    }
+}
@@ -0,0 +1,391 @@
+use std::fmt;
+
+use crate::models::vault::Item;
+use crate::utils::search;
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Item fields that can be targeted with a `field:value` qualifier
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum QueryField {
+    Name,
+    Username,
+    Url,
+    Notes,
+}
+
+/// Item properties that can be filtered with an `is:flag` qualifier
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum QueryFlag {
+    Weak,
+    Favorite,
+}
+
+/// A single search term. All terms of a query must match (conjunctive).
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum QueryTerm {
+    /// Unqualified text, matched against name, username and url
+    Text(String),
+    Field(QueryField, String),
+    Flag(QueryFlag),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum QueryError {
+    UnterminatedQuote,
+    MissingValue(String),
+}
+
+impl std::error::Error for QueryError { }
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnterminatedQuote => write!(f, "Missing closing quote"),
+            Self::MissingValue(key) => write!(f, "Missing value after '{}:'", key),
+        }
+    }
+}
+
+/// Structured search query parsed from the search box text, e.g.
+/// `user:admin url:github "two words"`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Query {
+    terms: Vec<QueryTerm>,
+}
+
+/// Raw token produced by the tokenizer before qualifiers are resolved
+struct Token {
+    text: String,
+    colon: Option<usize>,  // Byte index of the first colon outside of quotes
+}
+
+impl Query {
+    /// Parses search text into a query. Values are lowercased so matching is case-insensitive.
+    /// Unknown qualifiers (`foo:bar`) are kept as literal text.
+    pub(crate) fn parse(input: &str) -> Result<Self, QueryError> {
+        let mut terms = Vec::new();
+
+        for token in Self::tokenize(input)? {
+            terms.push(Self::parse_term(token)?);
+        }
+
+        Ok(Self { terms })
+    }
+
+    /// Parses search text, falling back to plain whitespace separated text terms if the
+    /// input is malformed. The parse error is returned alongside so it can be shown as a hint.
+    pub(crate) fn parse_lenient(input: &str) -> (Self, Option<QueryError>) {
+        match Self::parse(input) {
+            Ok(query) => (query, None),
+            Err(e) => {
+                let terms = input
+                    .split(|c: char| c.is_whitespace() || c == '"')
+                    .filter(|word| !word.is_empty())
+                    .map(|word| QueryTerm::Text(word.to_lowercase()))
+                    .collect();
+
+                (Self { terms }, Some(e))
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Fuzzy search mode: how well the item matches, higher first, None if it doesn't.
+    /// Unqualified text is matched with `search::item_score` against name, username, URL and
+    /// notes, so `ghub` finds `GitHub`. Qualifiers and flags still have to match as in `matches`.
+    pub(crate) fn rank(&self, item: &Item) -> Option<u32> {
+        self.terms.iter().try_fold(0u32, |total, term| match term {
+            QueryTerm::Text(value) => search::item_score(item, value).map(|score| total.saturating_add(score)),
+            _ => Self::term_matches(term, item).then_some(total),
+        })
+    }
+
+    /// Returns true if the item satisfies every term of the query
+    pub(crate) fn matches(&self, item: &Item) -> bool {
+        self.terms.iter().all(|term| Self::term_matches(term, item))
+    }
+
+    fn term_matches(term: &QueryTerm, item: &Item) -> bool {
+        match term {
+            QueryTerm::Text(value) => {
+                contains_lowercase(&item.name, value)
+                    || contains_lowercase(&item.username, value)
+                    || contains_lowercase(&item.url, value)
+            },
+            QueryTerm::Field(field, value) => {
+                let haystack = match field {
+                    QueryField::Name => &item.name,
+                    QueryField::Username => &item.username,
+                    QueryField::Url => &item.url,
+                    QueryField::Notes => &item.notes,
+                };
+                contains_lowercase(haystack, value)
+            },
+            QueryTerm::Flag(QueryFlag::Weak) => is_weak_password(&item.password),
+            QueryTerm::Flag(QueryFlag::Favorite) => item.favorite,
+        }
+    }
+
+    /// Splits input on whitespace, keeping quoted sections (which may contain spaces) together
+    fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+        let mut tokens = Vec::new();
+        let mut chars = input.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+
+            let mut text = String::new();
+            let mut colon = None;
+
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                chars.next();
+
+                match c {
+                    '"' => {
+                        let mut closed = false;
+                        for quoted in chars.by_ref() {
+                            if quoted == '"' {
+                                closed = true;
+                                break;
+                            }
+                            text.push(quoted);
+                        }
+
+                        if !closed {
+                            return Err(QueryError::UnterminatedQuote);
+                        }
+                    },
+                    ':' if colon.is_none() => {
+                        colon = Some(text.len());
+                        text.push(c);
+                    },
+                    _ => text.push(c),
+                }
+            }
+
+            tokens.push(Token { text, colon });
+        }
+
+        Ok(tokens)
+    }
+
+    fn parse_term(token: Token) -> Result<QueryTerm, QueryError> {
+        let literal = |text: &str| QueryTerm::Text(text.to_lowercase());
+
+        let Some(colon) = token.colon else {
+            return Ok(literal(&token.text));
+        };
+
+        let key = token.text[..colon].to_lowercase();
+        let value = token.text[colon + 1..].to_lowercase();
+
+        let field = match key.as_str() {
+            "name" => QueryField::Name,
+            "user" | "username" => QueryField::Username,
+            "url" => QueryField::Url,
+            "notes" | "note" => QueryField::Notes,
+            "is" => {
+                return match value.as_str() {
+                    "" => Err(QueryError::MissingValue(key)),
+                    "weak" => Ok(QueryTerm::Flag(QueryFlag::Weak)),
+                    "fav" | "favorite" => Ok(QueryTerm::Flag(QueryFlag::Favorite)),
+                    _ => Ok(literal(&token.text)),
+                };
+            },
+            _ => return Ok(literal(&token.text)),
+        };
+
+        if value.is_empty() {
+            return Err(QueryError::MissingValue(key));
+        }
+
+        Ok(QueryTerm::Field(field, value))
+    }
+}
+
+/// Whether the lowercased `haystack` contains `needle`, which is lowercase already. The
+/// lowercased copy only lives in a `ZeroByte` for the duration of the match.
+fn contains_lowercase(haystack: &str, needle: &str) -> bool {
+    ZeroByte::with_bytes(haystack.as_bytes(), |haystack| haystack.to_lowercase_copy().secure_contains(needle.as_bytes()))
+}
+
+/// Rough weakness heuristic: short passwords or ones drawing from fewer than three
+/// character classes. Items without a password are not considered weak.
+fn is_weak_password(password: &str) -> bool {
+    if password.is_empty() {
+        return false;
+    }
+
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ];
+
+    password.chars().count() < 8 || classes.iter().filter(|&&class| class).count() < 3
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, username: &str, password: &str, url: &str, notes: &str) -> Item {
+        Item {
+            name: name.into(),
+            username: username.into(),
+            password: password.into(),
+            url: url.into(),
+            notes: notes.into(),
+            ..Default::default()
+        }
+    }
+
+    fn github() -> Item {
+        item("GitHub", "Admin", "Tr0ub4dor&3-horse", "https://github.com", "work account")
+    }
+
+    #[test]
+    fn test_parse_free_text_and_qualifiers() {
+        let query = Query::parse("hub user:Admin url:github").expect("Parse failed");
+
+        assert_eq!(query.terms, vec![
+            QueryTerm::Text("hub".into()),
+            QueryTerm::Field(QueryField::Username, "admin".into()),
+            QueryTerm::Field(QueryField::Url, "github".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_quoted_values_with_spaces() {
+        let query = Query::parse("notes:\"work account\" \"free text\"").expect("Parse failed");
+
+        assert_eq!(query.terms, vec![
+            QueryTerm::Field(QueryField::Notes, "work account".into()),
+            QueryTerm::Text("free text".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_unknown_qualifier_falls_back_to_text() {
+        let query = Query::parse("foo:bar is:shiny").expect("Parse failed");
+
+        assert_eq!(query.terms, vec![
+            QueryTerm::Text("foo:bar".into()),
+            QueryTerm::Text("is:shiny".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_is_weak_flag() {
+        let query = Query::parse("IS:Weak").expect("Parse failed");
+        assert_eq!(query.terms, vec![QueryTerm::Flag(QueryFlag::Weak)]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Query::parse("user:\"john"), Err(QueryError::UnterminatedQuote));
+        assert_eq!(Query::parse("url:"), Err(QueryError::MissingValue("url".into())));
+        assert_eq!(Query::parse("is:"), Err(QueryError::MissingValue("is".into())));
+    }
+
+    #[test]
+    fn test_parse_lenient_returns_text_terms_and_error() {
+        let (query, error) = Query::parse_lenient("user:\"john doe");
+
+        assert_eq!(error, Some(QueryError::UnterminatedQuote));
+        assert_eq!(query.terms, vec![
+            QueryTerm::Text("user:".into()),
+            QueryTerm::Text("john".into()),
+            QueryTerm::Text("doe".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_empty_query_matches_everything() {
+        let query = Query::parse("   ").expect("Parse failed");
+
+        assert!(query.is_empty());
+        assert!(query.matches(&github()));
+    }
+
+    #[test]
+    fn test_text_matches_name_username_and_url_only() {
+        let item = github();
+
+        assert!(Query::parse("GITHUB").unwrap().matches(&item));
+        assert!(Query::parse("admin").unwrap().matches(&item));
+        assert!(Query::parse("https").unwrap().matches(&item));
+        assert!(!Query::parse("account").unwrap().matches(&item), "Notes are only matched with notes:");
+    }
+
+    #[test]
+    fn test_text_matches_ignoring_unicode_case() {
+        let cafe = item("Café Ölmühle", "", "", "", "");
+
+        assert!(Query::parse("ÖLMÜHLE").expect("Parse failed").matches(&cafe));
+        assert!(Query::parse("café").expect("Parse failed").matches(&cafe));
+        assert!(!Query::parse("cafe").expect("Parse failed").matches(&cafe));
+    }
+
+    #[test]
+    fn test_rank_matches_text_fuzzily_and_qualifiers_exactly() {
+        let github = github();
+
+        assert!(Query::parse("ghub").expect("Parse failed").rank(&github).is_some());
+        assert!(Query::parse("work").expect("Parse failed").rank(&github).is_some(), "Notes are searched too");
+        assert!(Query::parse("ghub user:admin").expect("Parse failed").rank(&github).is_some());
+        assert_eq!(Query::parse("ghub user:adm1n").expect("Parse failed").rank(&github), None);
+        assert_eq!(Query::parse("tr0ub").expect("Parse failed").rank(&github), None, "Passwords are never searched");
+    }
+
+    #[test]
+    fn test_field_terms_are_conjunctive() {
+        let item = github();
+
+        assert!(Query::parse("user:admin url:github").unwrap().matches(&item));
+        assert!(!Query::parse("user:admin url:gitlab").unwrap().matches(&item));
+        assert!(Query::parse("notes:\"work account\"").unwrap().matches(&item));
+        assert!(!Query::parse("name:admin").unwrap().matches(&item));
+    }
+
+    #[test]
+    fn test_unknown_qualifier_matches_literally() {
+        let item = item("Key foo:bar", "", "", "", "");
+
+        assert!(Query::parse("foo:bar").unwrap().matches(&item));
+        assert!(!Query::parse("foo:baz").unwrap().matches(&item));
+    }
+
+    #[test]
+    fn test_is_weak_flag_matches_weak_passwords() {
+        let weak = Query::parse("is:weak").unwrap();
+
+        assert!(!weak.matches(&github()));
+        assert!(weak.matches(&item("Short", "", "abc12", "", "")));
+        assert!(weak.matches(&item("Letters", "", "onlylowercaseletters", "", "")));
+        assert!(!weak.matches(&item("Empty", "", "", "", "")));
+    }
+
+    #[test]
+    fn test_is_favorite_flag_matches_favorites() {
+        let favorite = Query::parse("is:favorite").unwrap();
+        let mut item = github();
+
+        assert_eq!(Query::parse("is:fav").unwrap(), favorite);
+        assert!(!favorite.matches(&item));
+        item.favorite = true;
+        assert!(favorite.matches(&item));
+    }
+}
@@ -0,0 +1,151 @@
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+
+use slint::SharedString;
+
+
+/// Setting this to 1 in a debug build enables the scrub check: a canary secret is planted
+/// at unlock, and after the exit pass the process scans its own writable memory for
+/// leftover copies and logs what it finds.
+const ENV_VAR: &str = "NOPASS_SCRUB_CHECK";
+
+/// The canary is only ever kept XORed with this, so the scan doesn't find the needle itself
+const MASK: u8 = 0xA5;
+
+static MASKED_CANARY: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+pub(crate) fn enabled() -> bool {
+    std::env::var_os(ENV_VAR).is_some_and(|value| value == "1")
+}
+
+/// Creates a random canary and returns it as a string for the caller to push through the
+/// same paths secrets take (window properties, item models). Returns None if the check is off.
+pub(crate) fn plant_canary() -> Option<SharedString> {
+    if !enabled() {
+        return None;
+    }
+
+    let random = RandomState::new().hash_one(std::process::id());
+    let canary = format!("nopass-canary-{:016x}", random);
+
+    *MASKED_CANARY.lock().unwrap_or_else(|e| e.into_inner()) = Some(mask(canary.as_bytes()));
+    Some(canary.into())
+}
+
+/// Scans the process's writable memory for the planted canary and logs every hit
+pub(crate) fn report() {
+    let Some(masked) = MASKED_CANARY.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return;
+    };
+
+    match scan_self(&masked) {
+        Ok(hits) if hits.is_empty() => log::info!("Scrub check: no copies of the canary left in memory"),
+        Ok(hits) => {
+            log::warn!("Scrub check: {} copies of the canary left in memory", hits.len());
+            for address in hits {
+                log::warn!("Scrub check: canary at {:#x}", address);
+            }
+        },
+        Err(e) => log::warn!("Scrub check: failed to scan memory: {}", e),
+    }
+}
+
+fn mask(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().map(|byte| byte ^ MASK).collect()
+}
+
+/// Offsets in `haystack` where the unmasked needle starts
+fn find_masked(haystack: &[u8], masked: &[u8]) -> Vec<usize> {
+    if masked.is_empty() || haystack.len() < masked.len() {
+        return Vec::new();
+    }
+
+    haystack.windows(masked.len())
+        .enumerate()
+        .filter(|(_, window)| window.iter().zip(masked).all(|(byte, masked)| byte ^ MASK == *masked))
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+/// Reads the heap and anonymous writable mappings through `/proc/self/mem`, which fails
+/// cleanly on unreadable pages instead of faulting. Returns the addresses of all hits.
+#[cfg(target_os = "linux")]
+fn scan_self(masked: &[u8]) -> std::io::Result<Vec<usize>> {
+    use std::fs::{self, File};
+    use std::io::{Read, Seek, SeekFrom};
+
+    const CHUNK: usize = 64 * 1024;
+
+    let maps = fs::read_to_string("/proc/self/maps")?;
+    let mut mem = File::open("/proc/self/mem")?;
+    let mut hits = Vec::new();
+
+    // On the stack, which isn't scanned, so the copy being searched isn't found in itself
+    let mut chunk = [0u8; CHUNK];
+    let overlap = masked.len().saturating_sub(1);
+
+    for line in maps.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(range), Some(perms)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let name = fields.nth(3).unwrap_or("");
+
+        if !perms.starts_with("rw") || !(name.is_empty() || name == "[heap]") {
+            continue;
+        }
+
+        let Some((start, end)) = range.split_once('-') else {
+            continue;
+        };
+        let (Ok(start), Ok(end)) = (usize::from_str_radix(start, 16), usize::from_str_radix(end, 16)) else {
+            continue;
+        };
+
+        let mut address = start;
+        while address < end {
+            let len = CHUNK.min(end - address);
+            if mem.seek(SeekFrom::Start(address as u64)).is_err() || mem.read_exact(&mut chunk[..len]).is_err() {
+                break;
+            }
+
+            hits.extend(find_masked(&chunk[..len], masked).into_iter().map(|offset| address + offset));
+
+            // Step back a little so matches spanning two chunks are still found
+            address += if len > overlap && address + len < end { len - overlap } else { len };
+        }
+    }
+
+    hits.dedup();
+    Ok(hits)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn scan_self(_masked: &[u8]) -> std::io::Result<Vec<usize>> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "memory scan is only implemented on Linux"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_masked_locates_unmasked_needle() {
+        let masked = mask(b"canary");
+
+        assert_eq!(find_masked(b"xxcanaryyycanary", &masked), vec![2, 10]);
+        assert!(find_masked(b"canar", &masked).is_empty());
+        assert!(find_masked(&masked, &masked).is_empty(), "The masked copy must not match");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_scan_finds_planted_heap_copy() {
+        let masked = mask(b"scrub-check-test-needle-5c1e");
+        let planted = String::from_utf8(mask(&masked)).unwrap();
+
+        let hits = scan_self(&masked).expect("Scan failed");
+        assert!(hits.contains(&(planted.as_ptr() as usize)), "Planted copy not found in {:x?}", hits);
+    }
+}
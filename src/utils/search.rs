@@ -0,0 +1,171 @@
+use crate::models::vault::Item;
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Score of a field starting with the needle
+const PREFIX_SCORE: u32 = 3000;
+/// Score of a field containing the needle elsewhere
+const SUBSTRING_SCORE: u32 = 2000;
+/// Score of a field containing the needle's characters in order with nothing between them;
+/// every skipped character takes one off
+const SUBSEQUENCE_SCORE: u32 = 1000;
+
+/// How well `needle` matches `haystack` ignoring case, higher is better, None when it
+/// doesn't. A prefix beats a substring anywhere, which beats the needle's characters in
+/// order with gaps between them (`ghub` in `GitHub`), the fewer skipped the better.
+/// `needle` must be lowercase already. The lowercased field only lives in a `ZeroByte` for
+/// the duration of the call.
+pub(crate) fn match_score(haystack: &str, needle: &str) -> Option<u32> {
+    if needle.is_empty() {
+        return Some(PREFIX_SCORE);
+    }
+
+    ZeroByte::with_bytes(haystack.as_bytes(), |haystack| {
+        let lowered = haystack.to_lowercase_copy();
+        let lowered = std::str::from_utf8(lowered.as_ref()).ok()?;
+
+        if lowered.starts_with(needle) {
+            Some(PREFIX_SCORE)
+        } else if lowered.contains(needle) {
+            Some(SUBSTRING_SCORE)
+        } else {
+            let skipped = subsequence_gap(lowered, needle)?;
+            Some(SUBSEQUENCE_SCORE - skipped.min(SUBSEQUENCE_SCORE - 1))
+        }
+    })
+}
+
+/// Best `match_score` of `needle` over the item's name, username, URL and notes. A match in
+/// the name ranks above the same kind of match in another field.
+pub(crate) fn item_score(item: &Item, needle: &str) -> Option<u32> {
+    let name = match_score(&item.name, needle).map(|score| score * 2 + 1);
+    [&item.username, &item.url, &item.notes]
+        .into_iter()
+        .filter_map(|field| match_score(field, needle).map(|score| score * 2))
+        .chain(name)
+        .max()
+}
+
+/// Fewest characters skipped between the first and last of `needle`'s characters found in
+/// order in `haystack`, None if they aren't all there
+fn subsequence_gap(haystack: &str, needle: &str) -> Option<u32> {
+    let first = needle.chars().next()?;
+
+    let mut best: Option<usize> = None;
+    for (start, _) in haystack.char_indices().filter(|&(_, c)| c == first) {
+        // Greedy from each start finds the tightest match ending as early as possible
+        let mut remaining = needle.chars().skip(1).peekable();
+        let mut skipped = 0;
+        let mut pending = 0;
+        for c in haystack[start..].chars().skip(1) {
+            let Some(&wanted) = remaining.peek() else { break };
+            if c == wanted {
+                remaining.next();
+                skipped += pending;
+                pending = 0;
+            } else {
+                pending += 1;
+            }
+        }
+        if remaining.peek().is_some() {
+            break;  // Later starts have even less haystack left
+        }
+
+        best = Some(best.map_or(skipped, |best| best.min(skipped)));
+        if skipped == 0 {
+            break;
+        }
+    }
+
+    best.map(|skipped| u32::try_from(skipped).unwrap_or(u32::MAX))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn item(name: &str, username: &str, url: &str, notes: &str) -> Item {
+        Item {
+            name: name.into(),
+            username: username.into(),
+            password: "hunter2".into(),
+            url: url.into(),
+            notes: notes.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_subsequence_finds_abbreviations() {
+        assert!(match_score("GitHub", "ghub").is_some());
+        assert!(match_score("GitHub", "gthb").is_some());
+        assert_eq!(match_score("GitHub", "hg"), None, "Characters have to be in order");
+        assert_eq!(match_score("GitHub", "gitlab"), None);
+    }
+
+    #[test]
+    fn test_prefix_beats_substring_beats_subsequence() {
+        let prefix = match_score("GitHub", "git").expect("No match");
+        let substring = match_score("My GitHub", "git").expect("No match");
+        let subsequence = match_score("Great Internet Thing", "git").expect("No match");
+
+        assert!(prefix > substring);
+        assert!(substring > subsequence);
+    }
+
+    #[test]
+    fn test_tighter_subsequence_ranks_higher() {
+        let tight = match_score("gxhub", "ghub").expect("No match");
+        let loose = match_score("gxxxxhub", "ghub").expect("No match");
+        assert!(tight > loose);
+
+        assert_eq!(subsequence_gap("g-x-ghub", "ghub"), Some(0), "The tightest occurrence counts");
+    }
+
+    #[test]
+    fn test_matching_ignores_case() {
+        assert_eq!(match_score("GITHUB", "github"), Some(PREFIX_SCORE));
+        assert_eq!(match_score("Ölmühle", "ölm"), Some(PREFIX_SCORE));
+        assert_eq!(match_score("anything", ""), Some(PREFIX_SCORE));
+    }
+
+    #[test]
+    fn test_item_score_scans_every_text_field() {
+        let entry = item("Work", "octocat", "https://github.com", "recovery codes in the safe");
+
+        assert!(item_score(&entry, "octo").is_some());
+        assert!(item_score(&entry, "github").is_some());
+        assert!(item_score(&entry, "recovery").is_some());
+        assert_eq!(item_score(&entry, "hunter2"), None, "Passwords are never searched");
+    }
+
+    #[test]
+    fn test_name_match_outranks_same_match_elsewhere() {
+        let by_name = item("GitHub", "", "", "");
+        let by_url = item("Work", "", "github.com", "");
+
+        assert!(item_score(&by_name, "git") > item_score(&by_url, "git"));
+    }
+
+    /// Benchmark: ranking a 5k item vault has to keep up with typing
+    #[test]
+    fn test_ranking_5k_items_is_fast() {
+        let items: Vec<Item> = (0..5000)
+            .map(|i| item(
+                &format!("Account {} on some service", i),
+                &format!("user{}@example.com", i),
+                &format!("https://service{}.example.com/login", i),
+                "Security questions: first pet, street grew up on, mother's maiden name",
+            ))
+            .collect();
+
+        let start = Instant::now();
+        let matches = items.iter().filter_map(|entry| item_score(entry, "svc")).count();
+        let elapsed = start.elapsed();
+
+        assert_eq!(matches, 5000);
+        assert!(elapsed < Duration::from_secs(1), "Ranking 5k items took {:?}", elapsed);
+    }
+}
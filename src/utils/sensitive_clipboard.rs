@@ -0,0 +1,311 @@
+use crate::errors::clipboard_errors::ClipboardError;
+
+
+/// Clipboard system the hints are written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Platform {
+    /// X11 selections, which includes apps running on XWayland
+    X11,
+    Windows,
+    MacOs,
+}
+
+impl Platform {
+    pub(crate) const fn current() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::X11
+        }
+    }
+}
+
+/// Extra clipboard format offered next to the text, by name as the platform registers it
+#[derive(Debug, PartialEq)]
+pub(crate) struct Format {
+    pub name: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// Formats that tell clipboard managers and history the copy is a secret not to keep:
+/// - X11: Klipper and the GNOME history extensions skip a copy offering
+///   `x-kde-passwordManagerHint` with the value `secret`
+/// - Windows: Clipboard monitors skip `ExcludeClipboardContentFromMonitorProcessing`,
+///   whatever its value. Clipboard history and cloud sync skip a copy whose
+///   `CanIncludeInClipboardHistory` and `CanUploadToCloudClipboard` are a DWORD 0.
+/// - macOS: Pasteboard managers that follow nspasteboard.org skip a copy offering
+///   `org.nspasteboard.ConcealedType`, whatever its value
+pub(crate) fn hint_formats(platform: Platform) -> Vec<Format> {
+    match platform {
+        Platform::X11 => vec![Format { name: "x-kde-passwordManagerHint", data: b"secret".to_vec() }],
+        Platform::Windows => vec![
+            Format { name: "ExcludeClipboardContentFromMonitorProcessing", data: 0u32.to_le_bytes().to_vec() },
+            Format { name: "CanIncludeInClipboardHistory", data: 0u32.to_le_bytes().to_vec() },
+            Format { name: "CanUploadToCloudClipboard", data: 0u32.to_le_bytes().to_vec() },
+        ],
+        Platform::MacOs => vec![Format { name: "org.nspasteboard.ConcealedType", data: Vec::new() }],
+    }
+}
+
+/// Puts `text` on the clipboard along with the `hint_formats` of this platform. Fails where
+/// the hints can't be written, so the caller can copy plain text instead. That includes
+/// macOS for now, there are no pasteboard bindings to write them with.
+pub(crate) fn copy(text: &str) -> Result<(), ClipboardError> {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    return x11::copy(text, hint_formats(Platform::current()));
+
+    #[cfg(windows)]
+    return win32::copy(text, &hint_formats(Platform::current()));
+
+    #[cfg(not(any(all(unix, not(target_os = "macos")), windows)))]
+    {
+        let _ = text;
+        Err(ClipboardError::Unavailable("clipboard hints aren't supported here".to_string()))
+    }
+}
+
+/// UTF-16 with a terminating nul, as `CF_UNICODETEXT` holds it
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn to_wide_nul(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod x11 {
+    use std::thread;
+
+    use x11rb::connection::Connection;
+    use x11rb::errors::ConnectionError;
+    use x11rb::protocol::Event;
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, SelectionNotifyEvent,
+        SelectionRequestEvent, WindowClass, SELECTION_NOTIFY_EVENT,
+    };
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+    use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+    use super::Format;
+    use crate::errors::clipboard_errors::ClipboardError;
+    use crate::utils::zero_byte::ZeroByte;
+
+    /// Targets the text is offered as
+    const TEXT_TARGETS: [&str; 2] = ["UTF8_STRING", "text/plain;charset=utf-8"];
+
+    /// What the selection owner hands out, by target atom
+    pub(super) struct Offer {
+        pub targets: Atom,
+        pub text_targets: Vec<Atom>,
+        pub text: ZeroByte,
+        pub hints: Vec<(Atom, Vec<u8>)>,
+    }
+
+    impl Offer {
+        /// Answer to a `TARGETS` request, every target this offer can be converted to
+        pub(super) fn target_list(&self) -> Vec<Atom> {
+            std::iter::once(self.targets)
+                .chain(self.text_targets.iter().copied())
+                .chain(self.hints.iter().map(|(target, _)| *target))
+                .collect()
+        }
+
+        pub(super) fn data(&self, target: Atom) -> Option<&[u8]> {
+            if self.text_targets.contains(&target) {
+                return Some(self.text.as_ref());
+            }
+            self.hints.iter().find(|(hint, _)| *hint == target).map(|(_, data)| data.as_slice())
+        }
+    }
+
+    /// Takes the CLIPBOARD selection on a connection of its own and serves it from a thread
+    /// until another client, or the next copy, takes it over. The text is wiped then.
+    pub(super) fn copy(text: &str, hints: Vec<Format>) -> Result<(), ClipboardError> {
+        let (conn, screen) = x11rb::connect(None).map_err(|e| ClipboardError::Unavailable(e.to_string()))?;
+        let root = conn.setup().roots[screen].root;
+        let window = conn.generate_id().map_err(|e| ClipboardError::Unavailable(e.to_string()))?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT, window, root, 0, 0, 1, 1, 0,
+            WindowClass::INPUT_ONLY, COPY_FROM_PARENT, &CreateWindowAux::new(),
+        ).map_err(write_failed)?;
+
+        let atom = |name: &str| -> Result<Atom, ClipboardError> {
+            Ok(conn.intern_atom(false, name.as_bytes()).map_err(write_failed)?.reply().map_err(write_failed)?.atom)
+        };
+        let clipboard = atom("CLIPBOARD")?;
+        let mut text_bytes = ZeroByte::default();
+        text_bytes.extend_from_slice(text.as_bytes());
+        let offer = Offer {
+            targets: atom("TARGETS")?,
+            text_targets: TEXT_TARGETS.iter().map(|name| atom(name)).collect::<Result<_, _>>()?,
+            text: text_bytes,
+            hints: hints.into_iter().map(|hint| Ok((atom(hint.name)?, hint.data))).collect::<Result<_, ClipboardError>>()?,
+        };
+
+        conn.set_selection_owner(window, clipboard, CURRENT_TIME).map_err(write_failed)?;
+        let owner = conn.get_selection_owner(clipboard).map_err(write_failed)?.reply().map_err(write_failed)?.owner;
+        if owner != window {
+            return Err(ClipboardError::WriteFailed("another client kept the clipboard".to_string()));
+        }
+
+        thread::spawn(move || {
+            while let Ok(event) = conn.wait_for_event() {
+                match event {
+                    Event::SelectionRequest(request) => {
+                        if let Err(e) = answer(&conn, &request, &offer) {
+                            log::warn!("Clipboard request failed: {}", e);
+                            break;
+                        }
+                    }
+                    Event::SelectionClear(_) => break,
+                    _ => {}
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn answer(conn: &RustConnection, request: &SelectionRequestEvent, offer: &Offer) -> Result<(), ConnectionError> {
+        // Obsolete clients leave the property unset and expect the target to be used
+        let property = if request.property == NONE { request.target } else { request.property };
+        let converted = if request.target == offer.targets {
+            conn.change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::ATOM, &offer.target_list())?;
+            true
+        } else if let Some(data) = offer.data(request.target) {
+            conn.change_property8(PropMode::REPLACE, request.requestor, property, request.target, data)?;
+            true
+        } else {
+            false
+        };
+
+        conn.send_event(false, request.requestor, EventMask::NO_EVENT, SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if converted { property } else { NONE },
+        })?;
+        conn.flush()
+    }
+
+    fn write_failed(e: impl std::fmt::Display) -> ClipboardError {
+        ClipboardError::WriteFailed(e.to_string())
+    }
+}
+
+#[cfg(windows)]
+mod win32 {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{GlobalFree, HANDLE};
+    use windows::Win32::System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard, RegisterClipboardFormatW, SetClipboardData};
+    use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+    use zeroize::Zeroizing;
+
+    use super::{to_wide_nul, Format};
+    use crate::errors::clipboard_errors::ClipboardError;
+
+    const CF_UNICODETEXT: u32 = 13;
+
+    pub(super) fn copy(text: &str, hints: &[Format]) -> Result<(), ClipboardError> {
+        unsafe { OpenClipboard(None) }.map_err(|e| ClipboardError::Unavailable(e.to_string()))?;
+        let result = fill(text, hints);
+        // SAFETY: opened above
+        let _ = unsafe { CloseClipboard() };
+        result
+    }
+
+    fn fill(text: &str, hints: &[Format]) -> Result<(), ClipboardError> {
+        // SAFETY: the clipboard is open on this thread
+        unsafe { EmptyClipboard() }.map_err(write_failed)?;
+
+        let wide = Zeroizing::new(to_wide_nul(text));
+        let bytes: Zeroizing<Vec<u8>> = Zeroizing::new(wide.iter().flat_map(|unit| unit.to_ne_bytes()).collect());
+        set_data(CF_UNICODETEXT, &bytes)?;
+
+        for hint in hints {
+            let name = to_wide_nul(hint.name);
+            // SAFETY: `name` is nul terminated and outlives the call
+            let format = unsafe { RegisterClipboardFormatW(PCWSTR(name.as_ptr())) };
+            if format == 0 {
+                return Err(ClipboardError::WriteFailed(format!("Can't register the {} format", hint.name)));
+            }
+            set_data(format, &hint.data)?;
+        }
+        Ok(())
+    }
+
+    /// Hands a copy of `data` to the open clipboard, which owns it from then on
+    fn set_data(format: u32, data: &[u8]) -> Result<(), ClipboardError> {
+        // SAFETY: the block is at least `data.len()` bytes and only written while locked
+        unsafe {
+            let memory = GlobalAlloc(GMEM_MOVEABLE, data.len().max(1)).map_err(write_failed)?;
+            let target = GlobalLock(memory).cast::<u8>();
+            if target.is_null() {
+                let _ = GlobalFree(Some(memory));
+                return Err(ClipboardError::WriteFailed("Can't lock clipboard memory".to_string()));
+            }
+            std::ptr::copy_nonoverlapping(data.as_ptr(), target, data.len());
+            let _ = GlobalUnlock(memory);
+
+            if let Err(e) = SetClipboardData(format, Some(HANDLE(memory.0))) {
+                let _ = GlobalFree(Some(memory));
+                return Err(write_failed(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_failed(e: impl std::fmt::Display) -> ClipboardError {
+        ClipboardError::WriteFailed(e.to_string())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_x11_hint_is_kde_password_manager_hint() {
+        assert_eq!(hint_formats(Platform::X11), vec![Format { name: "x-kde-passwordManagerHint", data: b"secret".to_vec() }]);
+    }
+
+    #[test]
+    fn test_windows_hints_opt_out_of_history_and_monitors() {
+        let formats = hint_formats(Platform::Windows);
+        let names: Vec<&str> = formats.iter().map(|format| format.name).collect();
+
+        assert_eq!(names, ["ExcludeClipboardContentFromMonitorProcessing", "CanIncludeInClipboardHistory", "CanUploadToCloudClipboard"]);
+        assert!(formats.iter().all(|format| format.data == [0, 0, 0, 0]), "Windows reads these as a DWORD 0");
+    }
+
+    #[test]
+    fn test_macos_hint_is_concealed_type() {
+        let formats = hint_formats(Platform::MacOs);
+        assert_eq!(formats.len(), 1);
+        assert_eq!(formats[0].name, "org.nspasteboard.ConcealedType");
+    }
+
+    #[test]
+    fn test_windows_text_is_nul_terminated_utf16() {
+        assert_eq!(to_wide_nul("pä"), vec![u16::from(b'p'), 0xE4, 0]);
+        assert_eq!(to_wide_nul(""), vec![0]);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_x11_offer_lists_and_converts_targets() {
+        let mut text = crate::utils::zero_byte::ZeroByte::default();
+        text.extend_from_slice(b"hunter2");
+        let offer = x11::Offer { targets: 1, text_targets: vec![2, 3], text, hints: vec![(4, b"secret".to_vec())] };
+
+        assert_eq!(offer.target_list(), [1, 2, 3, 4]);
+        assert_eq!(offer.data(2), Some(&b"hunter2"[..]));
+        assert_eq!(offer.data(3), Some(&b"hunter2"[..]));
+        assert_eq!(offer.data(4), Some(&b"secret"[..]));
+        assert_eq!(offer.data(5), None);
+    }
+}
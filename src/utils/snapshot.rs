@@ -0,0 +1,240 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::utils;
+
+
+const SNAPSHOT_EXTENSION: &str = "vault";
+const SECS_PER_DAY: u64 = 86_400;
+/// Length of the `-YYYYMMDD-HHMMSS` suffix after the vault name
+const STAMP_LEN: usize = 16;
+
+/// How many daily snapshots of a vault are kept
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SnapshotRetention {
+    /// Most snapshots kept per vault, 0 for no limit
+    pub(crate) max_count: usize,
+    /// Snapshots older than this are removed, None to keep them regardless of age
+    pub(crate) max_age_secs: Option<u64>,
+}
+
+/// Copy of a vault file in the backups directory, taken on the first save of a day
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Snapshot {
+    pub(crate) path: PathBuf,
+    /// File name of the vault it was taken from, without extension
+    pub(crate) vault_name: String,
+    pub(crate) created_at: u64,
+}
+
+/// `<dir>/<vault name>-YYYYMMDD-HHMMSS.vault` for a snapshot taken at `timestamp`.
+/// Snapshots are told apart by the vault's file name only, so vaults with the same name in
+/// different directories share their snapshots and retention.
+pub(crate) fn snapshot_path(dir: &Path, vault_path: &Path, timestamp: u64) -> PathBuf {
+    let (year, month, day) = utils::civil_date(timestamp);
+    let seconds_of_day = timestamp % SECS_PER_DAY;
+
+    dir.join(format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}.{}",
+        vault_name(vault_path), year, month, day,
+        seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60,
+        SNAPSHOT_EXTENSION,
+    ))
+}
+
+/// Whether `path` is a snapshot in the backups directory `dir`
+pub(crate) fn is_snapshot(path: &Path, dir: &Path) -> bool {
+    path.parent() == Some(dir)
+        && path.file_name().and_then(|name| name.to_str()).and_then(parse_snapshot_name).is_some()
+}
+
+/// Every snapshot in `dir`, newest first. A missing directory has none.
+pub(crate) fn list_snapshots(dir: &Path) -> io::Result<Vec<Snapshot>> {
+    let entries = match fs::read_dir(dir) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        entries => entries?,
+    };
+
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let (vault_name, created_at) = parse_snapshot_name(path.file_name()?.to_str()?)?;
+            path.is_file().then_some(Snapshot { path, vault_name, created_at })
+        })
+        .collect();
+
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.created_at));
+    Ok(snapshots)
+}
+
+/// Copies the vault at `vault_path` into `dir` unless it already has a snapshot from the same
+/// UTC calendar day, then prunes that vault's snapshots to `retention`.
+/// Returns the path of the new snapshot, if one was taken.
+pub(crate) fn take_daily_snapshot(vault_path: &Path, dir: &Path, retention: SnapshotRetention, now: u64) -> io::Result<Option<PathBuf>> {
+    let name = vault_name(vault_path);
+    let today = now / SECS_PER_DAY;
+
+    let taken_today = list_snapshots(dir)?
+        .iter()
+        .any(|snapshot| snapshot.vault_name == name && snapshot.created_at / SECS_PER_DAY == today);
+    if taken_today {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(dir)?;
+    let path = snapshot_path(dir, vault_path, now);
+
+    // Copy under a name that isn't listed first, so an interrupted copy never shows up as a snapshot
+    let temp = path.with_extension("tmp");
+    let copied = fs::copy(vault_path, &temp).and_then(|_| fs::rename(&temp, &path));
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+
+    prune_snapshots(dir, &name, retention, now)?;
+    Ok(Some(path))
+}
+
+/// Removes the snapshots of `vault_name` beyond the retention count or age.
+/// Returns how many were removed.
+fn prune_snapshots(dir: &Path, vault_name: &str, retention: SnapshotRetention, now: u64) -> io::Result<usize> {
+    let snapshots = list_snapshots(dir)?
+        .into_iter()
+        .filter(|snapshot| snapshot.vault_name == vault_name);
+
+    let mut removed = 0;
+    for (index, snapshot) in snapshots.enumerate() {
+        let too_many = retention.max_count > 0 && index >= retention.max_count;
+        let too_old = retention.max_age_secs.is_some_and(|max_age| now.saturating_sub(snapshot.created_at) > max_age);
+
+        if too_many || too_old {
+            fs::remove_file(&snapshot.path)?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// File name of the vault without its extension
+fn vault_name(vault_path: &Path) -> String {
+    vault_path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "vault".to_string())
+}
+
+/// Vault name and timestamp of a snapshot file name, None for any other file
+fn parse_snapshot_name(file_name: &str) -> Option<(String, u64)> {
+    let stem = file_name.strip_suffix(SNAPSHOT_EXTENSION)?.strip_suffix('.')?;
+    let (name, stamp) = stem.split_at_checked(stem.len().checked_sub(STAMP_LEN)?)?;
+    let stamp = stamp.strip_prefix('-')?;
+
+    if name.is_empty() || !stamp.is_ascii() || stamp.len() != STAMP_LEN - 1 || stamp.as_bytes()[8] != b'-' {
+        return None;
+    }
+
+    let field = |start: usize, len: usize| -> Option<i64> {
+        let digits = &stamp[start..start + len];
+        if digits.bytes().all(|b| b.is_ascii_digit()) { digits.parse().ok() } else { None }
+    };
+    let (year, month, day) = (field(0, 4)?, field(4, 2)?, field(6, 2)?);
+    let (hour, minute, second) = (field(9, 2)?, field(11, 2)?, field(13, 2)?);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let days = u64::try_from(utils::days_from_civil(year, month, day)).ok()?;
+    let seconds = u64::try_from(hour * 3600 + minute * 60 + second).ok()?;
+    Some((name.to_string(), days * SECS_PER_DAY + seconds))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;  // 2023-11-14 22:13:20 UTC
+    const KEEP_ALL: SnapshotRetention = SnapshotRetention { max_count: 0, max_age_secs: None };
+
+    fn vault_in(dir: &Path) -> PathBuf {
+        let path = dir.join("work.vault");
+        fs::write(&path, b"encrypted vault").expect("Failed to write");
+        path
+    }
+
+    #[test]
+    fn test_snapshot_name_round_trip() {
+        let path = snapshot_path(Path::new("/backups"), Path::new("/home/user/my-work.vault"), NOW);
+
+        assert_eq!(path, PathBuf::from("/backups/my-work-20231114-221320.vault"));
+        assert_eq!(parse_snapshot_name("my-work-20231114-221320.vault"), Some(("my-work".to_string(), NOW)));
+        assert!(is_snapshot(&path, Path::new("/backups")));
+    }
+
+    #[test]
+    fn test_other_files_are_not_snapshots() {
+        for name in [
+            "work.vault", "work-20231114.vault", "-20231114-221320.vault", "work-20231314-221320.vault",
+            "work-2023111x-221320.vault", "work-2023111é-22132.vault", "work-20231114-221320.vault.bak1",
+            "work-20231114-221320.tmp",
+        ] {
+            assert_eq!(parse_snapshot_name(name), None, "{} is not a snapshot", name);
+        }
+        assert!(!is_snapshot(Path::new("/elsewhere/work-20231114-221320.vault"), Path::new("/backups")));
+    }
+
+    #[test]
+    fn test_first_save_of_the_day_takes_a_snapshot() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let vault = vault_in(dir.path());
+        let backups = dir.path().join("backups");
+
+        let first = take_daily_snapshot(&vault, &backups, KEEP_ALL, NOW).expect("Snapshot failed");
+        let later_that_day = take_daily_snapshot(&vault, &backups, KEEP_ALL, NOW + 60).expect("Snapshot failed");
+        let next_day = take_daily_snapshot(&vault, &backups, KEEP_ALL, NOW + SECS_PER_DAY).expect("Snapshot failed");
+
+        let first = first.expect("First save takes a snapshot");
+        assert_eq!(fs::read(&first).expect("Failed to read"), b"encrypted vault");
+        assert_eq!(later_that_day, None);
+        assert!(next_day.is_some());
+
+        let snapshots = list_snapshots(&backups).expect("Listing failed");
+        assert_eq!(snapshots.iter().map(|s| s.created_at).collect::<Vec<_>>(), vec![NOW + SECS_PER_DAY, NOW]);
+    }
+
+    #[test]
+    fn test_snapshots_beyond_retention_are_pruned() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let vault = vault_in(dir.path());
+        let backups = dir.path().join("backups");
+
+        // Another vault's snapshots don't count towards this one's retention
+        fs::create_dir_all(&backups).expect("Failed to create dir");
+        let other = snapshot_path(&backups, Path::new("home.vault"), NOW - 100 * SECS_PER_DAY);
+        fs::write(&other, b"other").expect("Failed to write");
+
+        let retention = SnapshotRetention { max_count: 3, max_age_secs: Some(30 * SECS_PER_DAY) };
+        for day in [40, 20, 3, 2, 1, 0] {
+            take_daily_snapshot(&vault, &backups, retention, NOW - day * SECS_PER_DAY).expect("Snapshot failed");
+        }
+
+        let kept: Vec<u64> = list_snapshots(&backups).expect("Listing failed")
+            .iter()
+            .filter(|s| s.vault_name == "work")
+            .map(|s| (NOW - s.created_at) / SECS_PER_DAY)
+            .collect();
+        assert_eq!(kept, vec![0, 1, 2]);
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn test_missing_backups_dir_has_no_snapshots() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        assert_eq!(list_snapshots(&dir.path().join("missing")).expect("Listing failed"), Vec::new());
+    }
+}
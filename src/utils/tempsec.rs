@@ -0,0 +1,253 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use zeroize::Zeroize;
+
+use crate::errors::tempsec_errors::TempSecError;
+
+
+/// Name of the directory created inside the per-user temp location
+const DIR_NAME: &str = "nopass";
+
+/// Process wide private temp directory, created on first use
+static SECURE_TEMP: Mutex<Option<SecureTempDir>> = Mutex::new(None);
+
+/// Private per-user directory for decrypted files that have to be handed to an
+/// external program (attachment opens, export previews). Every file created is
+/// tracked and overwritten before removal.
+pub(crate) struct SecureTempDir {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    counter: u32,
+}
+
+impl SecureTempDir {
+    /// Creates (or reuses) `DIR_NAME` inside `base`, restricted to the current user.
+    /// Fails if `base` is writable by other users.
+    pub(crate) fn open(base: &Path) -> Result<Self, TempSecError> {
+        if is_world_writable(base)? {
+            return Err(TempSecError::WorldWritable(base.to_path_buf()));
+        }
+
+        let root = base.join(DIR_NAME);
+        create_private_dir(&root)?;
+
+        Ok(Self { root, files: Vec::new(), counter: 0 })
+    }
+
+    /// Writes `contents` to a new user-only file named after `name` and tracks it
+    pub(crate) fn create_file(&mut self, name: &str, contents: &[u8]) -> Result<PathBuf, TempSecError> {
+        // Only keep the final component so a crafted name can't escape the directory
+        let name = Path::new(name).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+        self.counter += 1;
+        let path = self.root.join(format!("{}-{}-{}", std::process::id(), self.counter, name));
+
+        let mut file = create_private_file(&path)?;
+        self.files.push(path.clone());
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        Ok(path)
+    }
+
+    /// Overwrites and removes a tracked file
+    pub(crate) fn remove_file(&mut self, path: &Path) -> Result<(), TempSecError> {
+        self.files.retain(|file| file != path);
+        secure_delete(path)
+    }
+
+    /// Overwrites and removes every tracked file, returning how many were removed
+    pub(crate) fn cleanup(&mut self) -> usize {
+        let mut removed = 0;
+
+        for path in self.files.drain(..) {
+            match secure_delete(&path) {
+                Ok(()) => removed += 1,
+                Err(e) => log::warn!("Failed to remove temp file {}: {}", path.display(), e),
+            }
+        }
+
+        removed
+    }
+}
+
+/// Writes a decrypted file to the private temp directory for an external viewer.
+/// Entry point for attachment opens and export previews.
+#[allow(dead_code)]
+pub(crate) fn create_file(name: &str, contents: &[u8]) -> Result<PathBuf, TempSecError> {
+    let mut guard = SECURE_TEMP.lock().unwrap();
+
+    if guard.is_none() {
+        *guard = Some(SecureTempDir::open(&platform_base()?)?);
+    }
+
+    guard.as_mut().unwrap().create_file(name, contents)
+}
+
+/// Removes a file handed out by `create_file`, e.g. once its viewer has exited
+#[allow(dead_code)]
+pub(crate) fn remove_file(path: &Path) -> Result<(), TempSecError> {
+    match SECURE_TEMP.lock().unwrap().as_mut() {
+        Some(temp) => temp.remove_file(path),
+        None => Ok(()),
+    }
+}
+
+/// Removes every file handed out by `create_file`. Called on lock and exit.
+pub(crate) fn cleanup() {
+    if let Ok(mut guard) = SECURE_TEMP.lock()
+        && let Some(temp) = guard.as_mut() {
+        temp.cleanup();
+    }
+}
+
+/// Per-user temp location: XDG_RUNTIME_DIR on Linux, the per-user temp directory elsewhere
+#[cfg(target_os = "linux")]
+fn platform_base() -> Result<PathBuf, TempSecError> {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => Err(TempSecError::NoPrivateLocation),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn platform_base() -> Result<PathBuf, TempSecError> {
+    // %LOCALAPPDATA%\Temp on Windows and the per-user $TMPDIR on macOS,
+    // both only accessible to the current user
+    Ok(std::env::temp_dir())
+}
+
+#[cfg(unix)]
+fn is_world_writable(path: &Path) -> Result<bool, TempSecError> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(fs::metadata(path)?.permissions().mode() & 0o002 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_world_writable(path: &Path) -> Result<bool, TempSecError> {
+    // Access is governed by the per-user profile ACLs
+    fs::metadata(path)?;
+    Ok(false)
+}
+
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> Result<(), TempSecError> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if !path.is_dir() {
+        fs::DirBuilder::new().mode(0o700).create(path)?;
+    }
+    // Tighten permissions of a directory left over from an older session
+    fs::set_permissions(path, fs::Permissions::from_mode(0o700))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path) -> Result<(), TempSecError> {
+    fs::create_dir_all(path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> Result<File, TempSecError> {
+    use std::os::unix::fs::OpenOptionsExt;
+    Ok(OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> Result<File, TempSecError> {
+    Ok(OpenOptions::new().write(true).create_new(true).open(path)?)
+}
+
+/// Overwrites the file contents with zeros before removing it
+fn secure_delete(path: &Path) -> Result<(), TempSecError> {
+    let len = fs::metadata(path)?.len() as usize;
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut zeros = vec![0u8; len];
+    file.write_all(&zeros)?;
+    file.sync_all()?;
+    zeros.zeroize();
+    drop(file);
+
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).expect("Missing path").permissions().mode() & 0o777
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_and_files_are_user_only() {
+        let base = tempfile::tempdir().expect("Failed to create temp dir");
+        let mut temp = SecureTempDir::open(base.path()).expect("Open failed");
+
+        let path = temp.create_file("preview.txt", b"secret").expect("Create failed");
+
+        assert_eq!(mode(&base.path().join(DIR_NAME)), 0o700);
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(fs::read(&path).expect("Read failed"), b"secret");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_world_writable_base_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = tempfile::tempdir().expect("Failed to create temp dir");
+        fs::set_permissions(base.path(), fs::Permissions::from_mode(0o777)).expect("chmod failed");
+
+        let result = SecureTempDir::open(base.path());
+
+        assert!(matches!(result, Err(TempSecError::WorldWritable(_))));
+        assert!(!base.path().join(DIR_NAME).exists());
+    }
+
+    #[test]
+    fn test_names_cannot_escape_directory() {
+        let base = tempfile::tempdir().expect("Failed to create temp dir");
+        let mut temp = SecureTempDir::open(base.path()).expect("Open failed");
+
+        let path = temp.create_file("../../escape.txt", b"x").expect("Create failed");
+
+        assert_eq!(path.parent(), Some(base.path().join(DIR_NAME).as_path()));
+    }
+
+    #[test]
+    fn test_cleanup_removes_tracked_files() {
+        let base = tempfile::tempdir().expect("Failed to create temp dir");
+        let mut temp = SecureTempDir::open(base.path()).expect("Open failed");
+        let first = temp.create_file("a.txt", b"first").expect("Create failed");
+        let second = temp.create_file("a.txt", b"second").expect("Create failed");
+        assert_ne!(first, second);
+
+        assert_eq!(temp.cleanup(), 2);
+        assert!(!first.exists());
+        assert!(!second.exists());
+        assert_eq!(temp.cleanup(), 0);
+    }
+
+    #[test]
+    fn test_remove_file_stops_tracking() {
+        let base = tempfile::tempdir().expect("Failed to create temp dir");
+        let mut temp = SecureTempDir::open(base.path()).expect("Open failed");
+        let path = temp.create_file("a.txt", b"secret").expect("Create failed");
+
+        temp.remove_file(&path).expect("Remove failed");
+
+        assert!(!path.exists());
+        assert_eq!(temp.cleanup(), 0);
+    }
+}
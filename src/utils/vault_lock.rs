@@ -0,0 +1,140 @@
+use std::ffi::OsString;
+use std::fs::{File, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::errors::vault_lock_errors::VaultLockError;
+
+
+/// Exclusive advisory lock on a `<vault>.lock` sidecar, held while a vault is unlocked
+/// so a second NoPass instance can't overwrite the first one's changes on save.
+///
+/// The lock itself is an OS file lock (flock / LockFileEx), so it is released by the OS
+/// if the process crashes. The file only records the holder's pid for the user message.
+/// It is never removed: another instance may already have it open and be waiting to lock
+/// it, and unlinking it would let that instance and a third one lock different inodes.
+#[derive(Debug)]
+pub(crate) struct VaultLock {
+    file: File,
+}
+
+impl VaultLock {
+    /// Takes the lock for the vault at `vault_path` without blocking.
+    /// Returns `VaultLockError::Held` if another window or process already has it.
+    pub(crate) fn acquire(vault_path: &Path) -> Result<Self, VaultLockError> {
+        let path = lock_path(vault_path);
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {},
+            Err(TryLockError::WouldBlock) => return Err(VaultLockError::Held(read_pid(&path))),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        // Overwrites the pid of a crashed holder, whose OS lock is already gone
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        // Clear the pid while still holding the lock so nobody reads it after the release
+        if let Err(e) = self.file.set_len(0) {
+            log::warn!("Failed to clear lock file: {}", e);
+        }
+        let _ = self.file.unlock();
+    }
+}
+
+/// Sidecar lock file for a vault, e.g. `work.vault.lock`
+pub(crate) fn lock_path(vault_path: &Path) -> PathBuf {
+    let mut path = OsString::from(vault_path.as_os_str());
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    let mut contents = String::new();
+    File::open(path).ok()?.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::file_errors::FileError;
+
+    #[test]
+    fn test_second_acquire_is_refused_with_holder_pid() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let vault = dir.path().join("test.vault");
+
+        let _lock = VaultLock::acquire(&vault).expect("First acquire failed");
+
+        match VaultLock::acquire(&vault) {
+            Err(VaultLockError::Held(pid)) => assert_eq!(pid, Some(std::process::id())),
+            other => panic!("Expected the lock to be held, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_held_lock_is_a_file_error() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let vault = dir.path().join("test.vault");
+
+        let _lock = VaultLock::acquire(&vault).expect("First acquire failed");
+        let error = VaultLock::acquire(&vault).map(|_| ()).map_err(FileError::from);
+
+        assert!(matches!(error, Err(FileError::AlreadyLocked)), "Got {:?}", error);
+    }
+
+    #[test]
+    fn test_drop_releases_lock_and_keeps_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let vault = dir.path().join("test.vault");
+
+        let lock = VaultLock::acquire(&vault).expect("Acquire failed");
+        drop(lock);
+
+        assert!(lock_path(&vault).exists(), "Lock file should stay so every instance locks the same inode");
+        assert_eq!(read_pid(&lock_path(&vault)), None);
+        VaultLock::acquire(&vault).expect("Lock should be free after drop");
+    }
+
+    #[test]
+    fn test_leftover_lock_file_does_not_block() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let vault = dir.path().join("test.vault");
+
+        // File left by a crashed process, whose OS lock went away with it
+        std::fs::write(lock_path(&vault), u32::MAX.to_string()).expect("Failed to write");
+
+        let _lock = VaultLock::acquire(&vault).expect("Leftover lock file should not block");
+        assert_eq!(read_pid(&lock_path(&vault)), Some(std::process::id()));
+    }
+
+    #[test]
+    fn test_lock_is_refused_while_held_through_an_earlier_handle() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let vault = dir.path().join("test.vault");
+
+        // Another instance opened the file before the first holder released it
+        let first = VaultLock::acquire(&vault).expect("First acquire failed");
+        let waiting = File::options().read(true).write(true).open(lock_path(&vault)).expect("Failed to open");
+        drop(first);
+
+        waiting.try_lock().expect("Waiting handle should get the lock");
+        assert!(matches!(VaultLock::acquire(&vault), Err(VaultLockError::Held(_))));
+    }
+}
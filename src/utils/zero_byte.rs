@@ -0,0 +1,1363 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use bincode::config::standard;
+use bincode::error::EncodeError;
+use bincode::serde::encode_into_std_write;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::{Serialize, Serializer};
+use sha2::Sha256;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::errors::zero_byte_errors::ZeroByteError;
+
+
+/// Growable byte buffer for secret data.
+/// Contents are zeroized when truncated, cleared, reallocated or dropped, so no
+/// stale copy of the secret is left behind in freed memory.
+#[derive(Default)]
+pub(crate) struct ZeroByte {
+    bytes: Vec<u8>,
+}
+
+/// View of a `ZeroByte` that serializes as `null`, so a secret inside a struct that gets
+/// logged or dumped as JSON is never written out. See `ZeroByte::as_secure`.
+#[repr(transparent)]
+pub(crate) struct ZeroByteSecure(ZeroByte);
+
+/// View of a `ZeroByte` that serializes as a base64 string, for text formats that have to
+/// carry the bytes, e.g. exports. See `ZeroByte::as_encoded`.
+pub(crate) struct ZeroByteEncoded<'a>(&'a ZeroByte);
+
+impl ZeroByte {
+    pub(crate) fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Serializes `value` with bincode's standard config straight into a new buffer. Growing
+    /// it wipes the old allocation, and a failed encoding drops the partial output wiped too,
+    /// so no plain copy of the serialized value is left behind.
+    pub(crate) fn from_encoder<T: Serialize + ?Sized>(value: &T) -> Result<ZeroByte, EncodeError> {
+        let mut encoded = ZeroByte::default();
+        encode_into_std_write(value, &mut encoded, standard())?;
+        Ok(encoded)
+    }
+
+    /// Ensures room for `additional` more bytes. Unlike `Vec::reserve`, the old
+    /// allocation is zeroized before it is freed when the buffer has to grow.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let required = self.bytes.len() + additional;
+        if required <= self.bytes.capacity() {
+            return;
+        }
+
+        let mut bytes = Vec::with_capacity(required.max(self.bytes.capacity() * 2));
+        bytes.extend_from_slice(&self.bytes);
+
+        self.bytes.zeroize();
+        self.bytes = bytes;
+    }
+
+    pub(crate) fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.reserve(bytes.len());
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Reads the remainder of `reader` into the buffer through a zeroized stack chunk
+    pub(crate) fn extend_from_reader<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let mut chunk = [0u8; 8192];
+        let mut total = 0;
+
+        let result = loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break Ok(total),
+                Ok(n) => {
+                    self.extend_from_slice(&chunk[..n]);
+                    total += n;
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            }
+        };
+
+        chunk.zeroize();
+        result
+    }
+
+    /// Shortens the buffer, zeroizing the removed bytes
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len < self.bytes.len() {
+            self.bytes[len..].zeroize();
+            self.bytes.truncate(len);
+        }
+    }
+
+    /// Zeroizes and empties the buffer while keeping its allocation for reuse
+    pub(crate) fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Copies the bytes into an array, e.g. for a cipher key or nonce.
+    /// The array lives on the stack and is not zeroized for you; wipe it once it's used.
+    pub(crate) fn to_fixed_array<const N: usize>(&self) -> Result<[u8; N], ZeroByteError> {
+        if self.bytes.len() != N {
+            return Err(ZeroByteError::LengthMismatch { left: self.bytes.len(), right: N });
+        }
+
+        let mut array = [0u8; N];
+        array.copy_from_slice(&self.bytes);
+        Ok(array)
+    }
+
+    /// Copies `array` into a new buffer and wipes the copy that was passed in.
+    /// The caller's own array, if it kept one, still has to be wiped by the caller.
+    pub(crate) fn from_fixed_array<const N: usize>(mut array: [u8; N]) -> Self {
+        let mut bytes = ZeroByte::default();
+        bytes.extend_from_slice(&array);
+        array.zeroize();
+        bytes
+    }
+}
+
+/// Byte-level primitives for crypto code built on `ZeroByte`.
+/// Not all of them have callers yet.
+#[allow(dead_code)]
+impl ZeroByte {
+    /// Returns `self XOR other` as a new buffer.
+    ///
+    /// Not guaranteed to be constant-time with respect to the byte values, only the length.
+    /// For security sensitive XOR (e.g. one-time pads) the caller must make sure the
+    /// inputs are not attacker controlled.
+    pub(crate) fn xor_with(&self, other: &ZeroByte) -> Result<ZeroByte, ZeroByteError> {
+        let mut result = ZeroByte::default();
+        result.extend_from_slice(&self.bytes);
+        result.xor_in_place(other)?;
+
+        Ok(result)
+    }
+
+    /// XORs `other` into this buffer without allocating.
+    /// Same timing caveats as `xor_with`.
+    pub(crate) fn xor_in_place(&mut self, other: &ZeroByte) -> Result<(), ZeroByteError> {
+        if self.bytes.len() != other.bytes.len() {
+            return Err(ZeroByteError::LengthMismatch { left: self.bytes.len(), right: other.bytes.len() });
+        }
+
+        for (byte, other) in self.bytes.iter_mut().zip(&other.bytes) {
+            *byte ^= other;
+        }
+
+        Ok(())
+    }
+
+    /// Allocates a buffer of `len` bytes from `OsRng`
+    pub(crate) fn with_random_bytes(len: usize) -> ZeroByte {
+        let mut buffer = ZeroByte::default();
+        buffer.bytes.resize(len, 0);
+        buffer.fill_random();
+        buffer
+    }
+
+    /// Returns true if `needle` occurs anywhere in the buffer. Every alignment is compared in
+    /// full and the results are OR-ed together without branching, so the time taken depends
+    /// only on the two lengths, not on whether or where `needle` occurs. An empty needle is
+    /// always found.
+    pub(crate) fn secure_contains(&self, needle: &[u8]) -> bool {
+        if needle.is_empty() {
+            return true;
+        }
+        if needle.len() > self.bytes.len() {
+            return false;
+        }
+
+        let found = self.bytes
+            .windows(needle.len())
+            .fold(0u8, |found, window| found | is_zero(difference(window, needle)));
+
+        std::hint::black_box(found) == 1
+    }
+
+    /// `secure_contains` for a needle that is itself a secret
+    pub(crate) fn secure_contains_zero_byte(&self, needle: &ZeroByte) -> bool {
+        self.secure_contains(&needle.bytes)
+    }
+
+    /// Copy with ASCII `A-Z` mapped to `a-z`. Every other byte, including those of non-ASCII
+    /// UTF-8 characters, is copied unchanged. The mapping has no branches, so the time taken
+    /// doesn't depend on which bytes are letters.
+    pub(crate) fn to_ascii_lowercase_copy(&self) -> ZeroByte {
+        let mut lowered = ZeroByte::default();
+        lowered.bytes.extend(self.bytes.iter().map(|&byte| fold_ascii_case(byte)));
+        lowered
+    }
+
+    /// Copy with every character lowercased like `str::to_lowercase`, for matching text
+    /// regardless of case. Bytes that aren't valid UTF-8 are copied unchanged.
+    pub(crate) fn to_lowercase_copy(&self) -> ZeroByte {
+        let mut lowered = ZeroByte::default();
+        lowered.reserve(self.bytes.len());
+        let mut encoded = [0u8; 4];
+        for chunk in self.bytes.utf8_chunks() {
+            for c in chunk.valid().chars().flat_map(char::to_lowercase) {
+                lowered.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+            }
+            lowered.extend_from_slice(chunk.invalid());
+        }
+        encoded.zeroize();
+        lowered
+    }
+
+    /// Runs `f` on a copy of `bytes` that is wiped as soon as `f` returns, for plaintext
+    /// that is only needed for a moment, e.g. a field lowercased to search it
+    pub(crate) fn with_bytes<T>(bytes: &[u8], f: impl FnOnce(&ZeroByte) -> T) -> T {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(bytes);
+        f(&buffer)
+    }
+
+    /// Whether both buffers are equal ignoring ASCII case, compared without exiting early on
+    /// the first differing byte. Only the lengths leak through the time taken: buffers of
+    /// different lengths are rejected straight away.
+    pub(crate) fn eq_ascii_case_insensitive(&self, other: &ZeroByte) -> bool {
+        if self.bytes.len() != other.bytes.len() {
+            return false;
+        }
+
+        let diff = self.bytes.iter()
+            .zip(&other.bytes)
+            .fold(0u8, |diff, (&a, &b)| diff | (fold_ascii_case(a) ^ fold_ascii_case(b)));
+        std::hint::black_box(diff) == 0
+    }
+
+    /// Whether both buffers hold the same bytes, compared without exiting early on the first
+    /// differing byte. Only the lengths leak through the time taken.
+    pub(crate) fn constant_time_eq(&self, other: &ZeroByte) -> bool {
+        self.bytes.len() == other.bytes.len() && std::hint::black_box(difference(&self.bytes, &other.bytes)) == 0
+    }
+
+    /// This buffer as a `ZeroByteSecure`, for serializing where the contents must not appear
+    pub(crate) fn as_secure(&self) -> &ZeroByteSecure {
+        // SAFETY: `ZeroByteSecure` is a `repr(transparent)` wrapper of `ZeroByte`, so both
+        // have the same layout and the reference keeps the borrow of `self`
+        unsafe { &*(self as *const ZeroByte as *const ZeroByteSecure) }
+    }
+
+    /// This buffer as a `ZeroByteEncoded`, for serializing as base64 text
+    pub(crate) fn as_encoded(&self) -> ZeroByteEncoded<'_> {
+        ZeroByteEncoded(self)
+    }
+
+    /// Copies an OS string, e.g. an environment variable, without going through `String`.
+    /// On Unix the raw bytes are kept even if they aren't UTF-8; elsewhere a string that
+    /// isn't valid Unicode gives None.
+    pub(crate) fn from_os_str(s: &OsStr) -> Option<ZeroByte> {
+        let bytes = s.as_encoded_bytes();
+        if cfg!(not(unix)) && std::str::from_utf8(bytes).is_err() {
+            return None;
+        }
+
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(bytes);
+        Some(buffer)
+    }
+
+    /// Like `from_os_str`, taking over the string's allocation instead of copying it. A
+    /// rejected string is zeroized before it is dropped.
+    pub(crate) fn from_os_string(s: OsString) -> Option<ZeroByte> {
+        let mut bytes = s.into_encoded_bytes();
+        if cfg!(not(unix)) && std::str::from_utf8(&bytes).is_err() {
+            bytes.zeroize();
+            return None;
+        }
+
+        Some(ZeroByte { bytes })
+    }
+
+    /// Appends the contents of another buffer, e.g. to combine key derivation inputs
+    pub(crate) fn extend_from_zero_byte(&mut self, other: &ZeroByte) {
+        self.extend_from_slice(other.as_ref());
+    }
+
+    /// Appends a single byte, e.g. a tag or separator in a protocol message
+    pub(crate) fn append_byte(&mut self, byte: u8) {
+        self.extend_from_slice(&[byte]);
+    }
+
+    /// New buffer holding `prefix` followed by this buffer, e.g. a file header in front of
+    /// the salt. Both are copied once into an allocation of the final size, so building a
+    /// message front to back doesn't reallocate over and over.
+    pub(crate) fn prepend(&self, prefix: &ZeroByte) -> ZeroByte {
+        self.prepend_bytes(prefix.as_ref())
+    }
+
+    /// `prepend` for a prefix that isn't secret, e.g. magic bytes
+    pub(crate) fn prepend_bytes(&self, prefix: &[u8]) -> ZeroByte {
+        let mut joined = ZeroByte::default();
+        joined.reserve(prefix.len() + self.len());
+        joined.extend_from_slice(prefix);
+        joined.extend_from_slice(self.as_ref());
+        joined
+    }
+
+    /// Inserts `byte` before the byte at `index`, shifting the rest up. An `index` equal to
+    /// the length appends; anything past it is `ZeroByteError::IndexOutOfBounds`.
+    pub(crate) fn insert_byte(&mut self, index: usize, byte: u8) -> Result<(), ZeroByteError> {
+        if index > self.len() {
+            return Err(ZeroByteError::IndexOutOfBounds { index, len: self.len() });
+        }
+
+        // Room is made first so the shift can't reallocate without wiping
+        self.reserve(1);
+        self.bytes.insert(index, byte);
+        Ok(())
+    }
+
+    /// Splits the buffer on every `delimiter` byte into new buffers, like `str::split`. The
+    /// delimiter isn't included; leading, trailing and consecutive delimiters give empty
+    /// segments, and an empty buffer gives one empty segment.
+    ///
+    /// Not constant time: where the delimiters are shows in the time taken. Use it where the
+    /// field layout isn't secret, only the field contents.
+    pub(crate) fn split_on_byte(&self, delimiter: u8) -> Vec<ZeroByte> {
+        self.split_on_byte_limited(delimiter, usize::MAX)
+    }
+
+    /// Like `split_on_byte`, but splits at no more than `max_parts - 1` delimiters, leaving the
+    /// rest of the buffer, delimiters and all, in the last segment like `str::splitn`.
+    /// `max_parts` of 0 gives no segments. Not constant time either.
+    pub(crate) fn split_on_byte_limited(&self, delimiter: u8, max_parts: usize) -> Vec<ZeroByte> {
+        self.bytes.splitn(max_parts, |&byte| byte == delimiter)
+            .map(|segment| {
+                let mut part = ZeroByte::default();
+                part.extend_from_slice(segment);
+                part
+            })
+            .collect()
+    }
+
+    /// Sets every byte to `value`, e.g. for padding
+    pub(crate) fn fill(&mut self, value: u8) {
+        self.bytes.fill(value);
+    }
+
+    /// Overwrites every byte with output from `OsRng`. Empty buffers don't touch the RNG.
+    pub(crate) fn fill_random(&mut self) {
+        if !self.bytes.is_empty() {
+            OsRng.fill_bytes(&mut self.bytes);
+        }
+    }
+
+    /// Copy padded to the next multiple of `block_size` with PKCS#7: n bytes of value n are
+    /// added, a whole block of them when the length is already a multiple. The work done only
+    /// depends on the length. Panics if `block_size` isn't between 1 and 255.
+    pub(crate) fn secure_pad_to(&self, block_size: usize) -> ZeroByte {
+        assert!((1..=255).contains(&block_size), "PKCS#7 block size must be between 1 and 255");
+
+        let pad = block_size - self.bytes.len() % block_size;
+        let mut padded = ZeroByte::default();
+        padded.reserve(self.bytes.len() + pad);
+        padded.extend_from_slice(&self.bytes);
+        padded.bytes.resize(self.bytes.len() + pad, pad as u8);
+        padded
+    }
+
+    /// Copy without its PKCS#7 padding. Every byte of the last 255 (or the whole buffer, if
+    /// shorter) is checked whatever the padding length turns out to be, so the time taken
+    /// doesn't tell how far the padding was valid. Returns `ZeroByteError::InvalidPadding`
+    /// for an empty buffer, a padding length of 0 or longer than the buffer, or a padding byte
+    /// of another value.
+    pub(crate) fn remove_pkcs7_padding(&self) -> Result<ZeroByte, ZeroByteError> {
+        let Some(&last) = self.bytes.last() else {
+            return Err(ZeroByteError::InvalidPadding);
+        };
+        let pad = usize::from(last);
+        let window = self.bytes.len().min(255);
+
+        // 0xFF for every check that failed, masks instead of branches on the secret bytes
+        let mut invalid = ct_mask(pad == 0) | ct_mask(pad > self.bytes.len());
+        for (offset, &byte) in self.bytes[self.bytes.len() - window..].iter().rev().enumerate() {
+            invalid |= ct_mask(offset < pad) & (byte ^ last);
+        }
+
+        if std::hint::black_box(invalid) != 0 {
+            return Err(ZeroByteError::InvalidPadding);
+        }
+
+        let mut unpadded = ZeroByte::default();
+        unpadded.extend_from_slice(&self.bytes[..self.bytes.len() - pad]);
+        Ok(unpadded)
+    }
+
+    /// HMAC-SHA256 (RFC 2104) of this buffer under `key`, as a 32 byte tag. The `hmac` state
+    /// is dropped before returning and the tag's copy on the stack is wiped.
+    pub(crate) fn hmac_sha256(&self, key: &ZeroByte) -> ZeroByte {
+        let mut tag = ZeroByte::default();
+        let mut output = self.hmac(key).finalize().into_bytes();
+        tag.extend_from_slice(&output);
+        output.as_mut_slice().zeroize();
+        tag
+    }
+
+    /// Whether `expected_tag` is the HMAC-SHA256 of this buffer under `key`. The tags are
+    /// compared in constant time.
+    pub(crate) fn hmac_sha256_verify(&self, key: &ZeroByte, expected_tag: &ZeroByte) -> bool {
+        self.hmac(key).verify_slice(&expected_tag.bytes).is_ok()
+    }
+
+    /// HMAC-SHA256 state keyed with `key`, fed with this buffer
+    fn hmac(&self, key: &ZeroByte) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key.bytes).expect("HMAC takes keys of any length");
+        mac.update(&self.bytes);
+        mac
+    }
+
+    /// Percent-encodes every byte outside the URL unreserved set (`A-Z a-z 0-9 - _ . ~`) as
+    /// `%XX` with uppercase hex, e.g. for a URL field put into a link or a query
+    pub(crate) fn to_percent_encoded(&self) -> ZeroByte {
+        let mut encoded = ZeroByte::default();
+        encoded.reserve(self.bytes.len());
+
+        for &byte in &self.bytes {
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+                encoded.extend_from_slice(&[byte]);
+            } else {
+                encoded.extend_from_slice(&[b'%', HEX_DIGITS[usize::from(byte >> 4)], HEX_DIGITS[usize::from(byte & 0x0F)]]);
+            }
+        }
+
+        encoded
+    }
+
+    /// Decodes `%XX` sequences in either case, other bytes are copied as they are
+    pub(crate) fn from_percent_encoded(input: &ZeroByte) -> Result<ZeroByte, ZeroByteError> {
+        let mut decoded = ZeroByte::default();
+        decoded.reserve(input.bytes.len());
+
+        let mut position = 0;
+        while position < input.bytes.len() {
+            let byte = input.bytes[position];
+            if byte != b'%' {
+                decoded.extend_from_slice(&[byte]);
+                position += 1;
+                continue;
+            }
+
+            let digits = input.bytes.get(position + 1..position + 3)
+                .and_then(|digits| Some((hex_value(digits[0])?, hex_value(digits[1])?)))
+                .ok_or(ZeroByteError::InvalidPercentEncoding { position })?;
+            decoded.extend_from_slice(&[digits.0 << 4 | digits.1]);
+            position += 3;
+        }
+
+        Ok(decoded)
+    }
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+fn hex_value(digit: u8) -> Option<u8> {
+    char::from(digit).to_digit(16).map(|value| value as u8)
+}
+
+impl Drop for ZeroByte {
+    fn drop(&mut self) {
+        // Zeroizes the full capacity, not just the initialized length
+        self.bytes.zeroize();
+    }
+}
+
+impl AsRef<[u8]> for ZeroByte {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl AsMut<[u8]> for ZeroByte {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+/// Lets secrets be assembled with `write!` or any `Write`-accepting API. Growing the buffer
+/// zeroizes the old allocation as with `extend_from_slice`. There's deliberately no `Read`,
+/// which would make copying the secret into unprotected buffers too easy.
+impl Write for ZeroByte {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// For paths where the string isn't a secret worth handling failures for.
+/// Panics if `s` isn't valid UTF-8.
+impl From<&OsStr> for ZeroByte {
+    fn from(s: &OsStr) -> Self {
+        let s = s.to_str().expect("OS string isn't valid UTF-8");
+
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(s.as_bytes());
+        buffer
+    }
+}
+
+impl Clone for ZeroByte {
+    fn clone(&self) -> Self {
+        let mut clone = Self::default();
+        clone.extend_from_slice(&self.bytes);
+        clone
+    }
+}
+
+impl PartialEq for ZeroByte {
+    /// Compares without exiting early on the first differing byte
+    fn eq(&self, other: &Self) -> bool {
+        self.constant_time_eq(other)
+    }
+}
+
+/// 0xFF if `condition` holds, 0 otherwise, without a branch
+fn ct_mask(condition: bool) -> u8 {
+    0u8.wrapping_sub(u8::from(std::hint::black_box(condition)))
+}
+
+/// OR of the XOR of each byte pair, zero only if the slices are equal over their common length.
+/// Looks at every byte regardless of where the first difference is.
+fn difference(a: &[u8], b: &[u8]) -> u8 {
+    a.iter().zip(b).fold(0u8, |diff, (a, b)| diff | (a ^ b))
+}
+
+/// 1 if `byte` is zero, 0 otherwise, computed without a branch
+fn is_zero(byte: u8) -> u8 {
+    (u16::from(byte).wrapping_sub(1) >> 15) as u8
+}
+
+/// `byte` with ASCII `A-Z` mapped to `a-z`, computed without a branch
+fn fold_ascii_case(byte: u8) -> u8 {
+    // Both differences are negative, setting bit 15, only for bytes from b'A' to b'Z'
+    let byte = u16::from(byte);
+    let is_upper = ((u16::from(b'A') - 1).wrapping_sub(byte) & byte.wrapping_sub(u16::from(b'Z') + 1)) >> 15;
+    (byte | (is_upper << 5)) as u8
+}
+
+impl Serialize for ZeroByte {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.bytes)
+    }
+}
+
+impl Serialize for ZeroByteSecure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_none()
+    }
+}
+
+impl fmt::Debug for ZeroByteSecure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for ZeroByteEncoded<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // The encoded copy is as secret as the bytes, wipe it once the serializer has it
+        let encoded = Zeroizing::new(BASE64.encode(&self.0.bytes));
+        serializer.serialize_str(&encoded)
+    }
+}
+
+impl<'de> Deserialize<'de> for ZeroByte {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ZeroByteVisitor;
+
+        impl<'de> Visitor<'de> for ZeroByteVisitor {
+            type Value = ZeroByte;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a byte array")
+            }
+
+            fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<ZeroByte, E> {
+                let mut buffer = ZeroByte::default();
+                buffer.extend_from_slice(bytes);
+                Ok(buffer)
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, mut bytes: Vec<u8>) -> Result<ZeroByte, E> {
+                let buffer = self.visit_bytes(&bytes);
+                bytes.zeroize();
+                buffer
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ZeroByte, A::Error> {
+                let mut buffer = ZeroByte::default();
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    buffer.extend_from_slice(&[byte]);
+                }
+                Ok(buffer)
+            }
+        }
+
+        deserializer.deserialize_bytes(ZeroByteVisitor)
+    }
+}
+
+impl fmt::Debug for ZeroByte {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ZeroByte([REDACTED; {}])", self.bytes.len())
+    }
+}
+
+/// Fixed-capacity secret buffer on the stack, for short-lived secrets like a nonce or a key
+/// on its way into a cipher, without a heap allocation. Holds up to `N` bytes and wipes all
+/// `N` when dropped. There's no growing or extending; `as_zerobyte_ref` copies the bytes into
+/// a `ZeroByte` for APIs that need one.
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub(crate) struct ZeroByteStack<const N: usize> {
+    data: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ZeroByteStack<N> {
+    /// `len` zero bytes, None if that's over the capacity `N`
+    pub(crate) fn with_len(len: usize) -> Option<Self> {
+        (len <= N).then_some(Self { data: [0u8; N], len })
+    }
+
+    pub(crate) fn fill_random(&mut self) {
+        if self.len > 0 {
+            OsRng.fill_bytes(&mut self.data[..self.len]);
+        }
+    }
+
+    /// Copies the bytes into an array, see `ZeroByte::to_fixed_array`
+    pub(crate) fn to_fixed_array<const M: usize>(&self) -> Result<[u8; M], ZeroByteError> {
+        if self.len != M {
+            return Err(ZeroByteError::LengthMismatch { left: self.len, right: M });
+        }
+
+        let mut array = [0u8; M];
+        array.copy_from_slice(self.as_ref());
+        Ok(array)
+    }
+}
+
+/// The rest of the `ZeroByte`-like API. Not all of it has callers yet.
+#[allow(dead_code)]
+impl<const N: usize> ZeroByteStack<N> {
+    /// Copy of `bytes`, None if they don't fit in `N`
+    pub(crate) fn from_slice(bytes: &[u8]) -> Option<Self> {
+        let mut buffer = Self::with_len(bytes.len())?;
+        buffer.data[..bytes.len()].copy_from_slice(bytes);
+        Some(buffer)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy on the heap, for APIs that take a `ZeroByte`
+    pub(crate) fn as_zerobyte_ref(&self) -> ZeroByte {
+        let mut bytes = ZeroByte::default();
+        bytes.extend_from_slice(self.as_ref());
+        bytes
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for ZeroByteStack<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl<const N: usize> AsMut<[u8]> for ZeroByteStack<N> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.data[..self.len]
+    }
+}
+
+impl<const N: usize> fmt::Debug for ZeroByteStack<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ZeroByteStack<{}>([REDACTED; {}])", N, self.len)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::mem::ManuallyDrop;
+
+    /// Reads `len` bytes starting at the buffer's allocation, including spare capacity
+    fn raw_bytes(buffer: &ZeroByte, len: usize) -> Vec<u8> {
+        assert!(len <= buffer.bytes.capacity());
+        // SAFETY: the range is within the allocation and every byte was written at some point
+        unsafe { std::slice::from_raw_parts(buffer.bytes.as_ptr(), len).to_vec() }
+    }
+
+    #[test]
+    fn test_stack_buffer_is_zeroed_after_drop() {
+        let mut buffer = ManuallyDrop::new(ZeroByteStack::<16>::from_slice(b"secret").expect("Doesn't fit"));
+        let ptr = buffer.data.as_ptr();
+
+        // SAFETY: dropped once, the storage stays in place inside the `ManuallyDrop`
+        unsafe { ManuallyDrop::drop(&mut buffer) };
+        // SAFETY: `ptr` points at the 16 bytes of the dropped array, which are still there
+        let raw = unsafe { std::slice::from_raw_parts(ptr, 16) };
+        assert_eq!(raw, [0u8; 16]);
+    }
+
+    #[test]
+    fn test_stack_buffer_holds_at_most_its_capacity() {
+        let buffer = ZeroByteStack::<8>::from_slice(b"12345678").expect("Doesn't fit");
+        assert_eq!(buffer.as_ref(), b"12345678");
+        assert_eq!(buffer.len(), 8);
+
+        assert!(ZeroByteStack::<8>::from_slice(b"123456789").is_none());
+        assert!(ZeroByteStack::<8>::with_len(9).is_none());
+        assert!(ZeroByteStack::<8>::from_slice(b"").expect("Doesn't fit").is_empty());
+    }
+
+    #[test]
+    fn test_stack_buffer_conversions() {
+        let buffer = ZeroByteStack::<32>::from_slice(&[7u8; 12]).expect("Doesn't fit");
+
+        assert_eq!(buffer.as_zerobyte_ref(), zero_byte(&[7u8; 12]));
+        assert_eq!(buffer.to_fixed_array::<12>(), Ok([7u8; 12]));
+        assert_eq!(buffer.to_fixed_array::<32>(), Err(ZeroByteError::LengthMismatch { left: 12, right: 32 }));
+    }
+
+    #[test]
+    fn test_stack_buffer_debug_is_redacted() {
+        let buffer = ZeroByteStack::<8>::from_slice(b"hunter2").expect("Doesn't fit");
+        assert_eq!(format!("{:?}", buffer), "ZeroByteStack<8>([REDACTED; 7])");
+    }
+
+    #[test]
+    fn test_stack_buffer_fill_random_stays_within_len() {
+        let mut buffer = ZeroByteStack::<32>::with_len(12).expect("Doesn't fit");
+        buffer.fill_random();
+
+        assert!(buffer.data[12..].iter().all(|&byte| byte == 0));
+        assert_ne!(buffer.as_ref(), [0u8; 12], "12 random bytes are practically never all zero");
+    }
+
+    #[test]
+    fn test_extend_from_slice_preserves_contents_across_growth() {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(b"abcd");
+        buffer.extend_from_slice(b"efghijkl");
+
+        assert_eq!(buffer.as_ref(), b"abcdefghijkl");
+        assert!(buffer.bytes.capacity() >= 12);
+    }
+
+    #[test]
+    fn test_truncate_zeroizes_removed_bytes() {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(b"secret!!");
+        buffer.truncate(2);
+
+        assert_eq!(buffer.as_ref(), b"se");
+        assert_eq!(raw_bytes(&buffer, 8), b"se\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn test_clear_keeps_allocation_and_zeroizes() {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(b"secret");
+        let ptr = buffer.bytes.as_ptr();
+
+        buffer.clear();
+
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.bytes.as_ptr(), ptr);
+        assert_eq!(raw_bytes(&buffer, 6), [0u8; 6]);
+    }
+
+    #[test]
+    fn test_extend_from_reader_reads_everything() {
+        let source: Vec<u8> = (0..20_000u32).map(|i| i as u8).collect();
+        let mut buffer = ZeroByte::default();
+
+        let read = buffer.extend_from_reader(&mut Cursor::new(&source)).expect("Read failed");
+
+        assert_eq!(read, source.len());
+        assert_eq!(buffer.as_ref(), source.as_slice());
+    }
+
+    #[test]
+    fn test_io_copy_streams_into_buffer() {
+        let source: Vec<u8> = (0..20_000u32).map(|i| (i * 7) as u8).collect();
+        let mut buffer = ZeroByte::default();
+
+        let copied = io::copy(&mut Cursor::new(source.as_slice()), &mut buffer).expect("Copy failed");
+
+        assert_eq!(copied, source.len() as u64);
+        assert_eq!(buffer.as_ref(), source.as_slice());
+    }
+
+    #[test]
+    fn test_write_macro_appends() {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(b"password");
+
+        write!(buffer, ":{}", 42).expect("Write failed");
+        buffer.flush().expect("Flush failed");
+
+        assert_eq!(buffer.as_ref(), b"password:42");
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(b"hunter2");
+
+        assert_eq!(format!("{:?}", buffer), "ZeroByte([REDACTED; 7])");
+    }
+
+    fn zero_byte(bytes: &[u8]) -> ZeroByte {
+        let mut buffer = ZeroByte::default();
+        buffer.extend_from_slice(bytes);
+        buffer
+    }
+
+    #[test]
+    fn test_pad_block_aligned_adds_full_block() {
+        let padded = zero_byte(&[1u8; 16]).secure_pad_to(16);
+
+        assert_eq!(padded.len(), 32);
+        assert_eq!(&padded.as_ref()[16..], &[16u8; 16]);
+        assert_eq!(padded.remove_pkcs7_padding(), Ok(zero_byte(&[1u8; 16])));
+    }
+
+    #[test]
+    fn test_pad_unaligned_input() {
+        let padded = zero_byte(b"YELLOW SUBMARINE!").secure_pad_to(8);
+
+        assert_eq!(padded.len(), 24);
+        assert_eq!(&padded.as_ref()[17..], &[7u8; 7]);
+        assert_eq!(padded.remove_pkcs7_padding(), Ok(zero_byte(b"YELLOW SUBMARINE!")));
+    }
+
+    #[test]
+    fn test_pad_zero_length_input() {
+        let padded = ZeroByte::default().secure_pad_to(16);
+
+        assert_eq!(padded.as_ref(), &[16u8; 16]);
+        assert_eq!(padded.remove_pkcs7_padding().map(|unpadded| unpadded.len()), Ok(0));
+        assert_eq!(ZeroByte::default().remove_pkcs7_padding(), Err(ZeroByteError::InvalidPadding));
+    }
+
+    #[test]
+    fn test_pad_to_largest_block() {
+        let padded = zero_byte(b"x").secure_pad_to(255);
+        assert_eq!(padded.len(), 255);
+        assert_eq!(padded.remove_pkcs7_padding(), Ok(zero_byte(b"x")));
+    }
+
+    #[test]
+    fn test_invalid_padding_is_rejected() {
+        // Padding byte of 0
+        assert_eq!(zero_byte(&[1, 2, 3, 0]).remove_pkcs7_padding(), Err(ZeroByteError::InvalidPadding));
+        // Longer than the buffer
+        assert_eq!(zero_byte(&[5, 5, 5]).remove_pkcs7_padding(), Err(ZeroByteError::InvalidPadding));
+        // One padding byte of the wrong value, first or last of the run
+        assert_eq!(zero_byte(&[9, 2, 4, 4, 4]).remove_pkcs7_padding(), Err(ZeroByteError::InvalidPadding));
+        assert_eq!(zero_byte(&[9, 4, 4, 4, 3, 4]).remove_pkcs7_padding(), Err(ZeroByteError::InvalidPadding));
+        // Bytes before the padding may hold anything
+        assert_eq!(zero_byte(&[3, 3, 1]).remove_pkcs7_padding(), Ok(zero_byte(&[3, 3])));
+    }
+
+    #[test]
+    #[should_panic(expected = "block size")]
+    fn test_pad_rejects_zero_block_size() {
+        let _ = zero_byte(b"x").secure_pad_to(0);
+    }
+
+    #[test]
+    fn test_fixed_array_round_trip_16() {
+        let nonce = ZeroByte::from_fixed_array([7u8; 16]);
+        assert_eq!(nonce.len(), 16);
+        assert_eq!(nonce.to_fixed_array::<16>(), Ok([7u8; 16]));
+    }
+
+    #[test]
+    fn test_fixed_array_round_trip_32() {
+        let mut key = [0u8; 32];
+        key.iter_mut().enumerate().for_each(|(i, byte)| *byte = i as u8);
+
+        let bytes = ZeroByte::from_fixed_array(key);
+        assert_eq!(bytes.as_ref(), &key);
+        assert_eq!(bytes.to_fixed_array::<32>(), Ok(key));
+    }
+
+    #[test]
+    fn test_fixed_array_rejects_length_mismatch() {
+        let key = ZeroByte::from_fixed_array([1u8; 32]);
+
+        assert_eq!(key.to_fixed_array::<16>(), Err(ZeroByteError::LengthMismatch { left: 32, right: 16 }));
+        assert_eq!(ZeroByte::default().to_fixed_array::<32>(), Err(ZeroByteError::LengthMismatch { left: 0, right: 32 }));
+        assert_eq!(ZeroByte::default().to_fixed_array::<0>(), Ok([]));
+    }
+
+    #[test]
+    fn test_xor_with_combines_bytes() {
+        let a = zero_byte(&[0b1100, 0xFF, 0x00]);
+        let b = zero_byte(&[0b1010, 0x0F, 0xAB]);
+
+        let result = a.xor_with(&b).expect("XOR failed");
+        assert_eq!(result.as_ref(), [0b0110, 0xF0, 0xAB]);
+    }
+
+    #[test]
+    fn test_xor_rejects_length_mismatch() {
+        let mut a = zero_byte(b"four");
+        let b = zero_byte(b"three");
+
+        assert_eq!(a.xor_with(&b).unwrap_err(), ZeroByteError::LengthMismatch { left: 4, right: 5 });
+        assert_eq!(a.xor_in_place(&b), Err(ZeroByteError::LengthMismatch { left: 4, right: 5 }));
+        assert_eq!(a.as_ref(), b"four", "Failed XOR must leave the buffer untouched");
+    }
+
+    #[test]
+    fn test_xor_twice_round_trips() {
+        let a = zero_byte(b"attack at dawn");
+        let b = zero_byte(b"0123456789abcd");
+
+        let mut result = a.xor_with(&b).expect("XOR failed");
+        assert_ne!(result.as_ref(), a.as_ref());
+
+        result.xor_in_place(&b).expect("XOR failed");
+        assert_eq!(result.as_ref(), a.as_ref());
+    }
+
+    #[test]
+    fn test_fill_sets_every_byte() {
+        let mut buffer = zero_byte(b"secret");
+        buffer.fill(0xFF);
+
+        assert_eq!(buffer.as_ref(), [0xFF; 6]);
+    }
+
+    #[test]
+    fn test_fill_random_overwrites_contents() {
+        let mut buffer = zero_byte(&[0; 32]);
+        buffer.fill_random();
+
+        // 32 zero bytes from a working RNG happen with probability 2^-256
+        assert_eq!(buffer.len(), 32);
+        assert!(buffer.as_ref().iter().any(|&byte| byte != 0));
+
+        // At most a handful of bytes should be zero, each has a 1/256 chance
+        let zeros = buffer.as_ref().iter().filter(|&&byte| byte == 0).count();
+        assert!(zeros < 8, "Unexpectedly many zero bytes: {}", zeros);
+    }
+
+    #[test]
+    fn test_with_random_bytes() {
+        let a = ZeroByte::with_random_bytes(32);
+        let b = ZeroByte::with_random_bytes(32);
+
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, b);
+        assert_eq!(ZeroByte::with_random_bytes(0).len(), 0);
+    }
+
+    #[test]
+    fn test_eq_compares_contents() {
+        assert_eq!(zero_byte(b"same"), zero_byte(b"same"));
+        assert_ne!(zero_byte(b"same"), zero_byte(b"diff"));
+        assert_ne!(zero_byte(b"same"), zero_byte(b"same!"));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(zero_byte(b"octocat").constant_time_eq(&zero_byte(b"octocat")));
+        assert!(!zero_byte(b"octocat").constant_time_eq(&zero_byte(b"octocaT")));
+        assert!(!zero_byte(b"octocat").constant_time_eq(&zero_byte(b"octo")));
+        assert!(ZeroByte::default().constant_time_eq(&ZeroByte::default()));
+    }
+
+    #[test]
+    fn test_secure_view_never_serializes_the_bytes() {
+        let secret = zero_byte(b"hunter2");
+        assert_eq!(serde_json::to_string(secret.as_secure()).expect("Serialize failed"), "null");
+
+        #[derive(Serialize)]
+        struct Login<'a> {
+            username: &'a str,
+            password: &'a ZeroByteSecure,
+        }
+        let json = serde_json::to_string(&Login { username: "octocat", password: secret.as_secure() }).expect("Serialize failed");
+        assert_eq!(json, r#"{"username":"octocat","password":null}"#);
+        assert!(!json.contains("hunter2"));
+        assert_eq!(format!("{:?}", secret.as_secure()), "ZeroByte([REDACTED; 7])");
+    }
+
+    #[test]
+    fn test_encoded_view_serializes_as_base64() {
+        let secret = zero_byte(&[0xFF, 0x00, b'a']);
+        assert_eq!(serde_json::to_string(&secret.as_encoded()).expect("Serialize failed"), r#""/wBh""#);
+        assert_eq!(serde_json::to_string(&ZeroByte::default().as_encoded()).expect("Serialize failed"), r#""""#);
+    }
+
+    #[test]
+    fn test_from_os_str_keeps_utf8() {
+        let expected = zero_byte("pä$$wörd".as_bytes());
+
+        assert_eq!(ZeroByte::from_os_str(OsStr::new("pä$$wörd")), Some(expected.clone()));
+        assert_eq!(ZeroByte::from_os_string(OsString::from("pä$$wörd")), Some(expected.clone()));
+        assert_eq!(ZeroByte::from(OsStr::new("pä$$wörd")), expected);
+        assert_eq!(ZeroByte::from_os_str(OsStr::new("")).map(|buffer| buffer.len()), Some(0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_os_str_keeps_raw_bytes_on_unix() {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+        let invalid = [b'p', 0xFF, b'w', 0xC3];  // Stray byte and a cut off sequence
+
+        assert_eq!(ZeroByte::from_os_str(OsStr::from_bytes(&invalid)).expect("Rejected").as_ref(), invalid);
+        assert_eq!(ZeroByte::from_os_string(OsString::from_vec(invalid.to_vec())).expect("Rejected").as_ref(), invalid);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[should_panic(expected = "isn't valid UTF-8")]
+    fn test_from_os_str_impl_panics_on_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let _ = ZeroByte::from(OsStr::from_bytes(&[0xFF, 0xFE]));
+    }
+
+    #[test]
+    fn test_extend_from_zero_byte_appends() {
+        let mut buffer = zero_byte(b"pass");
+        buffer.extend_from_zero_byte(&zero_byte(b"word"));
+        assert_eq!(buffer.as_ref(), b"password");
+    }
+
+    #[test]
+    fn test_append_byte() {
+        let mut buffer = zero_byte(b"pass");
+        buffer.append_byte(b'!');
+        assert_eq!(buffer.as_ref(), b"pass!");
+    }
+
+    #[test]
+    fn test_prepend_empty_prefix_copies() {
+        let salt = zero_byte(b"salt");
+        assert_eq!(salt.prepend(&ZeroByte::default()).as_ref(), b"salt");
+        assert_eq!(salt.prepend_bytes(&[]).as_ref(), b"salt");
+        assert_eq!(ZeroByte::default().prepend_bytes(&[]).len(), 0);
+    }
+
+    #[test]
+    fn test_prepend_puts_prefix_first() {
+        let salt = zero_byte(b"salt");
+        assert_eq!(salt.prepend_bytes(b"NPV\x01").as_ref(), b"NPV\x01salt");
+        assert_eq!(salt.prepend(&zero_byte(b"magic")).as_ref(), b"magicsalt");
+        assert_eq!(salt.as_ref(), b"salt", "The original must be left alone");
+    }
+
+    #[test]
+    fn test_insert_byte_at_start_middle_and_end() {
+        let mut buffer = zero_byte(b"bd");
+        buffer.insert_byte(0, b'a').expect("Insert at 0 failed");
+        buffer.insert_byte(2, b'c').expect("Insert in the middle failed");
+        buffer.insert_byte(4, b'e').expect("Insert at the end failed");
+        assert_eq!(buffer.as_ref(), b"abcde");
+
+        let mut empty = ZeroByte::default();
+        empty.insert_byte(0, b'x').expect("Insert into empty buffer failed");
+        assert_eq!(empty.as_ref(), b"x");
+    }
+
+    #[test]
+    fn test_insert_byte_past_end_fails() {
+        let mut buffer = zero_byte(b"abc");
+        assert_eq!(buffer.insert_byte(4, b'x'), Err(ZeroByteError::IndexOutOfBounds { index: 4, len: 3 }));
+        assert_eq!(buffer.as_ref(), b"abc");
+    }
+
+    fn segments(parts: Vec<ZeroByte>) -> Vec<Vec<u8>> {
+        parts.iter().map(|part| part.as_ref().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_split_on_byte() {
+        assert_eq!(segments(zero_byte(b"user:pa55:host").split_on_byte(b':')), vec![b"user".to_vec(), b"pa55".to_vec(), b"host".to_vec()]);
+        assert_eq!(segments(zero_byte(b"no delimiter").split_on_byte(b':')), vec![b"no delimiter".to_vec()]);
+    }
+
+    #[test]
+    fn test_split_on_byte_keeps_empty_segments() {
+        assert_eq!(segments(zero_byte(b":a::b:").split_on_byte(b':')), vec![vec![], b"a".to_vec(), vec![], b"b".to_vec(), vec![]]);
+        assert_eq!(segments(zero_byte(b":").split_on_byte(b':')), vec![Vec::<u8>::new(), vec![]]);
+        assert_eq!(segments(ZeroByte::default().split_on_byte(b':')), vec![Vec::<u8>::new()]);
+    }
+
+    #[test]
+    fn test_split_on_byte_limited() {
+        let line = zero_byte(b"user:pa:55:");
+        assert_eq!(segments(line.split_on_byte_limited(b':', 2)), vec![b"user".to_vec(), b"pa:55:".to_vec()]);
+        assert_eq!(segments(line.split_on_byte_limited(b':', 1)), vec![b"user:pa:55:".to_vec()]);
+        assert_eq!(segments(line.split_on_byte_limited(b':', 10)), segments(line.split_on_byte(b':')));
+        assert!(line.split_on_byte_limited(b':', 0).is_empty());
+        assert_eq!(segments(zero_byte(b"::x").split_on_byte_limited(b':', 2)), vec![vec![], b":x".to_vec()]);
+    }
+
+    #[test]
+    fn test_secure_contains_finds_needles_at_any_position() {
+        let haystack = zero_byte(b"correct horse battery staple");
+
+        assert!(haystack.secure_contains(b"correct"));
+        assert!(haystack.secure_contains(b"battery"));
+        assert!(haystack.secure_contains(b"staple"));
+        assert!(haystack.secure_contains(b"correct horse battery staple"));
+        assert!(haystack.secure_contains(b""));
+        assert!(haystack.secure_contains_zero_byte(&zero_byte(b"horse")));
+    }
+
+    #[test]
+    fn test_secure_contains_rejects_absent_needles() {
+        let haystack = zero_byte(b"correct horse battery staple");
+
+        assert!(!haystack.secure_contains(b"donkey"));
+        assert!(!haystack.secure_contains(b"Horse"));
+        assert!(!haystack.secure_contains(b"staples"));
+        assert!(!haystack.secure_contains(b"correct horse battery staple!"));
+        assert!(!zero_byte(b"").secure_contains(b"a"));
+        assert!(!haystack.secure_contains_zero_byte(&zero_byte(b"pony")));
+    }
+
+    #[test]
+    fn test_to_ascii_lowercase_copy() {
+        assert_eq!(zero_byte(b"Hello").to_ascii_lowercase_copy(), zero_byte(b"hello"));
+        assert_eq!(zero_byte(b"@AZ[`az{").to_ascii_lowercase_copy(), zero_byte(b"@az[`az{"));
+    }
+
+    #[test]
+    fn test_ascii_case_fold_passes_non_ascii_through() {
+        let cafe = zero_byte("CAFÉ café".as_bytes());
+        assert_eq!(cafe.to_ascii_lowercase_copy().as_ref(), "cafÉ café".as_bytes());
+
+        // Only ASCII is folded, every byte from 0x80 up is left alone
+        let high: Vec<u8> = (0x80..=0xff).collect();
+        assert_eq!(zero_byte(&high).to_ascii_lowercase_copy().as_ref(), high.as_slice());
+    }
+
+    #[test]
+    fn test_to_lowercase_copy_folds_unicode() {
+        assert_eq!(zero_byte("GitHub CAFÉ Σ".as_bytes()).to_lowercase_copy().as_ref(), "github café σ".as_bytes());
+        assert_eq!(zero_byte(b"AB\xffC").to_lowercase_copy().as_ref(), b"ab\xffc", "Invalid UTF-8 is kept as is");
+        assert_eq!(zero_byte(b"").to_lowercase_copy().len(), 0);
+    }
+
+    #[test]
+    fn test_with_bytes_hands_over_a_copy() {
+        let found = ZeroByte::with_bytes(b"GitHub", |bytes| {
+            assert_eq!(bytes.as_ref(), b"GitHub");
+            bytes.to_lowercase_copy().secure_contains(b"hub")
+        });
+        assert!(found);
+    }
+
+    #[test]
+    fn test_fold_ascii_case_matches_std_for_every_byte() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(fold_ascii_case(byte), byte.to_ascii_lowercase(), "byte {:#04x}", byte);
+        }
+    }
+
+    #[test]
+    fn test_eq_ascii_case_insensitive() {
+        assert!(zero_byte(b"GitHub Login").eq_ascii_case_insensitive(&zero_byte(b"github LOGIN")));
+        assert!(zero_byte(b"").eq_ascii_case_insensitive(&zero_byte(b"")));
+
+        assert!(!zero_byte(b"GitHub").eq_ascii_case_insensitive(&zero_byte(b"GitLab")));
+        assert!(!zero_byte(b"GitHub").eq_ascii_case_insensitive(&zero_byte(b"GitHub ")));
+        assert!(!zero_byte(b"@").eq_ascii_case_insensitive(&zero_byte(b"`")), "Only letters are folded");
+        assert!(!zero_byte("É".as_bytes()).eq_ascii_case_insensitive(&zero_byte("é".as_bytes())));
+    }
+
+    #[test]
+    fn test_is_zero_for_every_byte() {
+        for byte in 0..=255u8 {
+            assert_eq!(is_zero(byte), u8::from(byte == 0), "byte {}", byte);
+        }
+    }
+
+    /// Rough check only, a real timing analysis needs many more samples on a quiet machine.
+    /// Catches an early exit on the first match, which would make the match at the start
+    /// many times faster than no match at all.
+    #[test]
+    fn test_secure_contains_timing_does_not_depend_on_match() {
+        use std::time::{Duration, Instant};
+
+        let mut bytes = vec![b'a'; 4096];
+        bytes[..6].copy_from_slice(b"needle");
+        let haystack = zero_byte(&bytes);
+
+        let time = |needle: &[u8]| -> Duration {
+            (0..5).map(|_| {
+                let start = Instant::now();
+                for _ in 0..20 {
+                    std::hint::black_box(haystack.secure_contains(std::hint::black_box(needle)));
+                }
+                start.elapsed()
+            }).min().unwrap()
+        };
+
+        let at_start = time(b"needle");
+        let absent = time(b"nestle");
+        let ratio = at_start.as_secs_f64() / absent.as_secs_f64();
+        assert!((0.33..3.0).contains(&ratio), "Match took {:?}, no match took {:?}", at_start, absent);
+    }
+
+    #[test]
+    fn test_percent_encoding_escapes_reserved_bytes() {
+        assert_eq!(zero_byte(b"my vault").to_percent_encoded().as_ref(), b"my%20vault");
+        assert_eq!(zero_byte(b"a/b?c=d&e#f").to_percent_encoded().as_ref(), b"a%2Fb%3Fc%3Dd%26e%23f");
+        assert_eq!(zero_byte("café".as_bytes()).to_percent_encoded().as_ref(), b"caf%C3%A9");
+        assert_eq!(zero_byte(&[0x00, 0xFF]).to_percent_encoded().as_ref(), b"%00%FF");
+    }
+
+    #[test]
+    fn test_percent_encoding_keeps_unreserved_bytes() {
+        let unreserved = b"ABCXYZabcxyz0189-_.~";
+        assert_eq!(zero_byte(unreserved).to_percent_encoded().as_ref(), unreserved);
+        assert_eq!(zero_byte(b"").to_percent_encoded().len(), 0);
+    }
+
+    #[test]
+    fn test_percent_decoding_round_trips() {
+        let every_byte: Vec<u8> = (0..=255).collect();
+        let mut inputs = vec![zero_byte(&every_byte), zero_byte(b""), zero_byte(b"%%41")];
+        inputs.extend((0..32).map(ZeroByte::with_random_bytes));
+
+        for input in inputs {
+            let decoded = ZeroByte::from_percent_encoded(&input.to_percent_encoded()).expect("Decode failed");
+            assert_eq!(decoded, input);
+        }
+
+        assert_eq!(ZeroByte::from_percent_encoded(&zero_byte(b"caf%c3%a9+x")).expect("Decode failed").as_ref(), "café+x".as_bytes());
+    }
+
+    #[test]
+    fn test_percent_decoding_rejects_incomplete_escapes() {
+        for (input, position) in [(&b"%"[..], 0), (b"ab%4", 2), (b"%4G", 0), (b"%20%%20", 3), (b"%+1", 0)] {
+            assert_eq!(
+                ZeroByte::from_percent_encoded(&zero_byte(input)),
+                Err(ZeroByteError::InvalidPercentEncoding { position }),
+                "{:?}", String::from_utf8_lossy(input)
+            );
+        }
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        use bincode::config::standard;
+        use bincode::serde::{encode_to_vec, decode_from_slice};
+
+        let buffer = zero_byte(b"vault name");
+        let encoded = encode_to_vec(&buffer, standard()).expect("Encode failed");
+        let (decoded, _): (ZeroByte, usize) = decode_from_slice(&encoded, standard()).expect("Decode failed");
+
+        assert_eq!(decoded, buffer);
+    }
+
+    fn from_hex(hex: &str) -> ZeroByte {
+        let mut bytes = ZeroByte::default();
+        for pair in hex.as_bytes().chunks(2) {
+            let pair = std::str::from_utf8(pair).expect("Hex is ASCII");
+            bytes.extend_from_slice(&[u8::from_str_radix(pair, 16).expect("Invalid hex")]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_hmac_sha256_rfc_4231_vectors() {
+        // (key, data, tag) of test cases 1 to 4, 6 and 7; case 5 checks a truncated tag
+        let cases = [
+            (
+                from_hex(&"0b".repeat(20)),
+                zero_byte(b"Hi There"),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                zero_byte(b"Jefe"),
+                zero_byte(b"what do ya want for nothing?"),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                from_hex(&"aa".repeat(20)),
+                from_hex(&"dd".repeat(50)),
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                from_hex("0102030405060708090a0b0c0d0e0f10111213141516171819"),
+                from_hex(&"cd".repeat(50)),
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                from_hex(&"aa".repeat(131)),
+                zero_byte(b"Test Using Larger Than Block-Size Key - Hash Key First"),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                from_hex(&"aa".repeat(131)),
+                zero_byte(b"This is a test using a larger than block-size key and a larger than block-size data. \
+                    The key needs to be hashed before being used by the HMAC algorithm."),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+
+        for (index, (key, data, tag)) in cases.iter().enumerate() {
+            assert_eq!(data.hmac_sha256(key), from_hex(tag), "Test vector {}", index + 1);
+        }
+    }
+
+    #[test]
+    fn test_hmac_sha256_verify_accepts_matching_tag() {
+        let key = zero_byte(b"Jefe");
+        let data = zero_byte(b"what do ya want for nothing?");
+        let tag = data.hmac_sha256(&key);
+
+        assert_eq!(tag.len(), 32);
+        assert!(data.hmac_sha256_verify(&key, &tag));
+    }
+
+    #[test]
+    fn test_hmac_sha256_verify_rejects_flipped_byte() {
+        let key = zero_byte(b"Jefe");
+        let data = zero_byte(b"what do ya want for nothing?");
+        let tag = data.hmac_sha256(&key);
+
+        for index in [0, 17, 31] {
+            let mut flipped = tag.clone();
+            flipped.bytes[index] ^= 0x01;
+            assert!(!data.hmac_sha256_verify(&key, &flipped), "Flip at byte {}", index);
+        }
+
+        let mut flipped_data = data.clone();
+        flipped_data.bytes[0] ^= 0x01;
+        assert!(!flipped_data.hmac_sha256_verify(&key, &tag));
+        assert!(!data.hmac_sha256_verify(&key, &zero_byte(&tag.as_ref()[..31])), "Truncated tag");
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::{Cursor, Read};
+
+use ::zip::ZipArchive;
+use ::zip::result::ZipError;
+
+use crate::errors::import_errors::ImportError;
+use crate::utils::zero_byte::ZeroByte;
+
+
+/// Extracts the file `name` from the zip archive in `bytes` into a `ZeroByte`, for export
+/// formats that are zip archives. Only stored and deflated entries are read, and the whole
+/// archive is refused if any entry has an absolute path or one leaving its directory. An entry
+/// larger than `max_len` fails before more than `max_len` bytes are inflated, and the CRC of
+/// what was read is checked. Encrypted archives aren't supported.
+pub(crate) fn extract_file(bytes: &[u8], name: &str, max_len: usize) -> Result<ZeroByte, ImportError> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(archive_error)?;
+    if let Some(unsafe_name) = archive.file_names().find(|entry_name| !is_safe_path(entry_name)) {
+        return Err(invalid(&format!("entry '{}' has an unsafe path", unsafe_name)));
+    }
+
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Err(invalid(&format!("'{}' is missing", name))),
+        Err(e) => return Err(archive_error(e)),
+    };
+    if entry.size() > max_len as u64 {
+        return Err(ImportError::TooLarge { max_len: max_len as u64 });
+    }
+
+    // The size in the archive can't be trusted, so inflating stops one byte past the limit.
+    // Reading to the end has the zip crate check the CRC.
+    let mut contents = ZeroByte::default();
+    match contents.extend_from_reader(&mut (&mut entry).take(max_len as u64 + 1)) {
+        Ok(len) if len > max_len => Err(ImportError::TooLarge { max_len: max_len as u64 }),
+        Ok(len) if len as u64 != entry.size() => Err(invalid(&format!("'{}' is damaged", name))),
+        Ok(_) => Ok(contents),
+        Err(e) => Err(invalid(&format!("'{}' is damaged or doesn't decompress: {}", name, e))),
+    }
+}
+
+/// Whether an entry name stays inside the directory it's extracted to: relative, without a
+/// drive letter and without `..` components
+fn is_safe_path(name: &str) -> bool {
+    let has_drive = name.as_bytes().get(1) == Some(&b':');
+    !name.is_empty()
+        && !name.starts_with(['/', '\\'])
+        && !has_drive
+        && name.split(['/', '\\']).all(|component| component != "..")
+}
+
+fn archive_error(e: ZipError) -> ImportError {
+    match e {
+        ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) | ZipError::InvalidPassword => ImportError::Encrypted,
+        ZipError::Io(e) => invalid(&format!("it can't be read: {}", e)),
+        ZipError::InvalidArchive(reason) | ZipError::UnsupportedArchive(reason) => invalid(reason),
+        e => invalid(&e.to_string()),
+    }
+}
+
+fn invalid(reason: &str) -> ImportError {
+    ImportError::Archive(reason.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use ::zip::write::{SimpleFileOptions, ZipWriter};
+    use ::zip::CompressionMethod;
+
+    /// Zip archive of `files`, stored without compression
+    fn stored_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+        for (name, contents) in files {
+            writer.start_file(*name, options).expect("Failed to start entry");
+            writer.write_all(contents).expect("Failed to write entry");
+        }
+        writer.finish().expect("Failed to finish archive").into_inner()
+    }
+
+    #[test]
+    fn test_extract_stored_file() {
+        let archive = stored_archive(&[("export.attributes", b"{}"), ("export.data", b"{\"accounts\":[]}")]);
+
+        let contents = extract_file(&archive, "export.data", 1024).expect("Extraction failed");
+        assert_eq!(contents.as_ref(), b"{\"accounts\":[]}");
+        assert!(matches!(extract_file(&archive, "missing.data", 1024), Err(ImportError::Archive(_))));
+    }
+
+    #[test]
+    fn test_unsafe_paths_are_refused() {
+        for name in ["/etc/passwd", "files/../../escape", "C:\\Windows\\evil", "\\\\server\\share"] {
+            let archive = stored_archive(&[("export.data", b"{}"), (name, b"x")]);
+            assert!(matches!(extract_file(&archive, "export.data", 1024), Err(ImportError::Archive(_))), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_oversized_entry_is_refused() {
+        let archive = stored_archive(&[("export.data", &[b'a'; 100])]);
+        assert!(matches!(extract_file(&archive, "export.data", 99), Err(ImportError::TooLarge { max_len: 99 })));
+    }
+
+    #[test]
+    fn test_understated_size_is_refused() {
+        let mut archive = stored_archive(&[("export.data", &[b'a'; 100])]);
+
+        // Claim 10 bytes in both headers, the data is still 100 bytes long
+        let size = 100u32.to_le_bytes();
+        let positions: Vec<usize> = archive.windows(8).enumerate()
+            .filter(|(_, window)| window[..4] == size && window[4..] == size)
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(positions.len(), 2, "Sizes in the local header and the central directory");
+        for pos in positions {
+            archive[pos..pos + 8].copy_from_slice(&[10, 0, 0, 0, 10, 0, 0, 0]);
+        }
+
+        assert!(extract_file(&archive, "export.data", 50).is_err());
+    }
+
+    #[test]
+    fn test_damaged_archive_is_refused() {
+        let contents = b"{\"accounts\":[]}";
+        let mut archive = stored_archive(&[("export.data", contents)]);
+        let data = archive.windows(contents.len()).position(|window| window == contents).expect("Contents not found");
+        archive[data + 3] ^= 0x01;
+        assert!(matches!(extract_file(&archive, "export.data", 1024), Err(ImportError::Archive(_))), "CRC mismatch");
+
+        assert!(matches!(extract_file(b"not a zip archive", "export.data", 1024), Err(ImportError::Archive(_))));
+        archive.truncate(archive.len() - 30);
+        assert!(matches!(extract_file(&archive, "export.data", 1024), Err(ImportError::Archive(_))));
+    }
+}